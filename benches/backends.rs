@@ -0,0 +1,92 @@
+//! compares walking the syntax tree against compiling to bytecode & running that.
+//! `cargo bench -- fib` only runs the benchmarks whose names contain `fib`.
+
+use sdw::driver;
+use sdw::prelude::*;
+use sdw::{compile, interp, vm};
+use std::time::{Duration, Instant};
+
+const PROGRAMS: [(&str, &str); 3] = [
+    (
+        "fib",
+        "fn int fib(int n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+         fn int main() { fib(22) };",
+    ),
+    (
+        "loop",
+        "fn int main() {
+            let total = 0;
+            let n = 0;
+            loop {
+                if n == 200000 { return total; };
+                total = total + n % 7 * 3;
+                n = n + 1;
+            };
+         };",
+    ),
+    (
+        "structs",
+        "type Vec2 struct { int x, int y };
+         type Vec2Ptr &Vec2;
+         fn Vec2 add(Vec2Ptr a, Vec2 b) { Vec2 { a.x + b.x, a.y + b.y } };
+         fn int main() {
+            let at = Vec2 { 0, 0 };
+            let n = 0;
+            loop {
+                if n == 50000 { goto @done; };
+                at = add(&at, Vec2 { 1, 2 });
+                n = n + 1;
+            };
+            @done;
+            at.x + at.y
+         };",
+    ),
+];
+
+/// the fastest of a few runs, which is least disturbed by anything else going on
+fn time(mut run: impl FnMut()) -> Duration {
+    run();
+    (0..5)
+        .map(|_| {
+            let before = Instant::now();
+            run();
+            before.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    for (name, source) in PROGRAMS {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+        let mut state = State::new();
+        let Ok(checked) = driver::check(&mut state, source, &mut ()) else {
+            panic!("`{name}` failed to check: {:#?}", state.errors);
+        };
+        let program = compile::compile(&checked);
+        assert_eq!(
+            interp::run(&checked).ok(),
+            vm::run(&program).ok(),
+            "`{name}` gives different results"
+        );
+
+        let tree = time(|| {
+            interp::run(&checked).unwrap();
+        });
+        let bytecode = time(|| {
+            vm::run(&program).unwrap();
+        });
+        println!(
+            "{name:<10} tree-walking {:>9.2?}   bytecode {:>9.2?}   ({:.1}x)",
+            tree,
+            bytecode,
+            tree.as_secs_f64() / bytecode.as_secs_f64()
+        );
+    }
+}
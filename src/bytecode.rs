@@ -0,0 +1,1014 @@
+use crate::interp::Value;
+use crate::intrinsics::Intrinsic;
+use crate::prelude::*;
+use std::fmt;
+use thiserror::Error;
+
+/// the type of a runtime value, as far as choosing an implementation or union variant goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtTy {
+    Void,
+    Int,
+    Unt,
+    Bool,
+    Str,
+    Named(DefId),
+    Pointer,
+    Fn,
+    /// a generic, which accepts anything
+    Any,
+}
+
+impl RtTy {
+    pub fn of(value: &Value) -> RtTy {
+        match value {
+            Value::Void => RtTy::Void,
+            Value::Int(_) => RtTy::Int,
+            Value::Unt(_) => RtTy::Unt,
+            Value::Bool(_) => RtTy::Bool,
+            Value::Str(_) => RtTy::Str,
+            Value::Struct { ty, .. } | Value::Union { ty, .. } => RtTy::Named(*ty),
+            Value::Pointer(_) => RtTy::Pointer,
+            Value::Fn(_) => RtTy::Fn,
+        }
+    }
+}
+
+/// a `goto` which can't be followed at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    /// jumps into the middle of an expression, whose operands were never evaluated
+    JumpIntoExpr,
+    /// a union literal without a member
+    EmptyUnion,
+}
+
+/// every instruction pops its operands from the stack, & pushes its result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Int(i64),
+    Unt(u64),
+    Bool(bool),
+    Str(String),
+    Void,
+    Fn(DefId),
+    /// push the value of a local
+    Load(u32),
+    /// pop into a local, as a new variable (so pointers to the last one dangle)
+    Declare(u32),
+    /// pop into a local
+    Store(u32),
+    /// push a pointer to a local
+    Ref(u32),
+    /// pop a value into a fresh slot, pushing a pointer to it
+    Temp,
+    /// pop a pointer, pushing a pointer to one of its members
+    RefMember(u32),
+    /// pop a pointer, pushing the value it points to
+    Deref,
+    /// pop a struct or union, pushing one of its members
+    Member(u32),
+    Pop,
+    /// pop values left behind by expressions a `goto` jumps out of
+    Drop(u32),
+    Neg,
+    Not,
+    BiOp(BiOps),
+    /// pop a value for each member, in the order given (by member index)
+    Struct {
+        ty: DefId,
+        order: Vec<u32>,
+    },
+    /// pop a value, wrapping it as the union's member
+    Union {
+        ty: DefId,
+        discriminant: u32,
+    },
+    /// convert a variant to the union, unless it's one already
+    Coerce(DefId),
+    Jump(u32),
+    /// pop a bool, jumping if it's false
+    JumpUnless(u32),
+    /// jump if the bool on top of the stack is `when`, leaving it there either way
+    JumpKeep {
+        when: bool,
+        to: u32,
+    },
+    /// pop the arguments, pushing the function's result
+    Call {
+        function: u32,
+        args: u32,
+    },
+    /// like `Call`, but for an interface stub
+    CallInterface {
+        interface: u32,
+        args: u32,
+    },
+    /// pop the arguments, then a function pointer
+    CallPtr {
+        args: u32,
+    },
+    /// pop the arguments, pushing what the intrinsic gives back (as a `ret`)
+    Intrinsic {
+        intrinsic: Intrinsic,
+        args: u32,
+        ret: RtTy,
+    },
+    /// call the overload for the variant held by the union argument `arg`
+    Dispatch {
+        table: u32,
+        arg: u32,
+        args: u32,
+    },
+    /// pop the result, & leave the function
+    Return,
+    Trap(Trap),
+}
+
+/// a function's code. `spans` gives the source of each instruction, for errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    /// `None` for the module's top level
+    pub id: Option<DefId>,
+    pub name: String,
+    /// where it's declared
+    pub span: Span,
+    pub params: u32,
+    /// parameters included
+    pub locals: u32,
+    pub code: Vec<Op>,
+    pub spans: Vec<Span>,
+}
+
+/// the implementations of an interface stub - chosen between at runtime, by argument type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub stub: DefId,
+    pub name: String,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub function: u32,
+    pub params: Vec<RtTy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Callee {
+    Function(u32),
+    Interface(u32),
+}
+
+/// what a dispatch table does for one of its union's variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arm {
+    Call(Callee),
+    /// match on the argument `arg` too, with a later table
+    Dispatch {
+        table: u32,
+        arg: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Union {
+    pub id: DefId,
+    pub members: Vec<(String, RtTy)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// the module's top level is run first, then `main`
+    pub init: u32,
+    pub main: Option<u32>,
+    pub functions: Vec<Function>,
+    pub interfaces: Vec<Interface>,
+    pub unions: Vec<Union>,
+    /// what's done for each variant (by discriminant), for each `Op::Dispatch`
+    pub dispatches: Vec<Vec<Arm>>,
+}
+
+impl Program {
+    pub fn union(&self, id: DefId) -> Option<&Union> {
+        self.unions.iter().find(|union| union.id == id)
+    }
+}
+
+/*
+ * disassembly
+ */
+
+impl fmt::Display for RtTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtTy::Void => write!(f, "void"),
+            RtTy::Int => write!(f, "int"),
+            RtTy::Unt => write!(f, "unt"),
+            RtTy::Bool => write!(f, "bool"),
+            RtTy::Str => write!(f, "string"),
+            RtTy::Named(id) => write!(f, "#{}", id.0),
+            RtTy::Pointer => write!(f, "&"),
+            RtTy::Fn => write!(f, "fn"),
+            RtTy::Any => write!(f, "*"),
+        }
+    }
+}
+
+impl fmt::Display for Callee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Callee::Function(function) => write!(f, "fn {function}"),
+            Callee::Interface(interface) => write!(f, "interface {interface}"),
+        }
+    }
+}
+
+impl fmt::Display for Arm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arm::Call(callee) => write!(f, "{callee}"),
+            Arm::Dispatch { table, arg } => write!(f, "dispatch {table} on {arg}"),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Int(int) => write!(f, "int {int}"),
+            Op::Unt(unt) => write!(f, "unt {unt}"),
+            Op::Bool(bool) => write!(f, "bool {bool}"),
+            Op::Str(text) => write!(f, "str {text:?}"),
+            Op::Void => write!(f, "void"),
+            Op::Fn(id) => write!(f, "fn #{}", id.0),
+            Op::Load(local) => write!(f, "load {local}"),
+            Op::Declare(local) => write!(f, "declare {local}"),
+            Op::Store(local) => write!(f, "store {local}"),
+            Op::Ref(local) => write!(f, "ref {local}"),
+            Op::Temp => write!(f, "temp"),
+            Op::RefMember(member) => write!(f, "ref.member {member}"),
+            Op::Deref => write!(f, "deref"),
+            Op::Member(member) => write!(f, "member {member}"),
+            Op::Pop => write!(f, "pop"),
+            Op::Drop(count) => write!(f, "drop {count}"),
+            Op::Neg => write!(f, "neg"),
+            Op::Not => write!(f, "not"),
+            Op::BiOp(op) => write!(f, "op {}", op.symbol()),
+            Op::Struct { ty, order } => {
+                let order = order.iter().map(u32::to_string).collect::<Vec<_>>();
+                write!(f, "struct #{} [{}]", ty.0, order.join(", "))
+            }
+            Op::Union { ty, discriminant } => write!(f, "union #{} {discriminant}", ty.0),
+            Op::Coerce(ty) => write!(f, "coerce #{}", ty.0),
+            Op::Jump(to) => write!(f, "jump {to:04}"),
+            Op::JumpUnless(to) => write!(f, "jump.unless {to:04}"),
+            Op::JumpKeep { when, to } => write!(f, "jump.keep {when} {to:04}"),
+            Op::Call { function, args } => write!(f, "call fn {function} ({args})"),
+            Op::CallInterface { interface, args } => {
+                write!(f, "call interface {interface} ({args})")
+            }
+            Op::CallPtr { args } => write!(f, "call.ptr ({args})"),
+            Op::Intrinsic {
+                intrinsic,
+                args,
+                ret,
+            } => write!(f, "intrinsic {} ({args}) -> {ret}", intrinsic.name()),
+            Op::Dispatch { table, arg, args } => {
+                write!(f, "dispatch {table} on {arg} ({args})")
+            }
+            Op::Return => write!(f, "return"),
+            Op::Trap(trap) => write!(f, "trap {trap:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            let role = if index as u32 == self.init {
+                " (top level)"
+            } else if Some(index as u32) == self.main {
+                " (entry point)"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "fn {index} `{}`{role} - {} params, {} locals",
+                function.name, function.params, function.locals
+            )?;
+            let mut line = None;
+            for (at, (op, span)) in function.code.iter().zip(&function.spans).enumerate() {
+                // the source line is only shown when it changes
+                if line != Some(span.sline) {
+                    line = Some(span.sline);
+                    writeln!(
+                        f,
+                        "  {at:04}  {:<28}; line {}",
+                        op.to_string(),
+                        span.sline + 1
+                    )?;
+                } else {
+                    writeln!(f, "  {at:04}  {op}")?;
+                }
+            }
+            writeln!(f)?;
+        }
+
+        for (index, interface) in self.interfaces.iter().enumerate() {
+            writeln!(f, "interface {index} `{}`", interface.name)?;
+            for candidate in &interface.candidates {
+                let params = candidate.params.iter().map(RtTy::to_string);
+                let params = params.collect::<Vec<_>>().join(", ");
+                writeln!(f, "  ({params}) -> fn {}", candidate.function)?;
+            }
+        }
+        for (index, arms) in self.dispatches.iter().enumerate() {
+            let arms = arms.iter().map(Arm::to_string).collect::<Vec<_>>();
+            writeln!(f, "dispatch {index}: {}", arms.join(", "))?;
+        }
+        for union in &self.unions {
+            let members = union
+                .members
+                .iter()
+                .map(|(name, ty)| format!("{ty} {name}"))
+                .collect::<Vec<_>>();
+            writeln!(f, "union #{}: {}", union.id.0, members.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ * serialisation
+ */
+
+/// the start of every serialised program
+pub const MAGIC: &[u8; 4] = b"SDWB";
+/// bumped whenever the format changes, so stale caches are rejected rather than misread
+pub const VERSION: u64 = 3;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("not sdw bytecode")]
+    NotBytecode,
+    #[error("bytecode version {0} isn't supported (expected {VERSION})")]
+    Version(u64),
+    #[error("the bytecode ends unexpectedly")]
+    Truncated,
+    #[error("the bytecode is malformed: {0}")]
+    Malformed(&'static str),
+}
+
+/// whether `bytes` look like a serialised program, rather than source
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// integers are written as LEB128, with signed ones zigzag encoded first
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn unt(&mut self, mut unt: u64) {
+        loop {
+            let byte = (unt & 0x7f) as u8;
+            unt >>= 7;
+            if unt == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    fn int(&mut self, int: i64) {
+        self.unt(((int << 1) ^ (int >> 63)) as u64);
+    }
+
+    fn str(&mut self, str: &str) {
+        self.unt(str.len() as u64);
+        self.bytes.extend_from_slice(str.as_bytes());
+    }
+
+    fn id(&mut self, id: DefId) {
+        self.unt(id.0 as u64);
+    }
+
+    fn span(&mut self, span: Span) {
+        self.unt(span.sline);
+        self.unt(span.eline);
+        self.unt(span.scol);
+        self.unt(span.ecol);
+    }
+
+    fn ty(&mut self, ty: RtTy) {
+        match ty {
+            RtTy::Void => self.unt(0),
+            RtTy::Int => self.unt(1),
+            RtTy::Unt => self.unt(2),
+            RtTy::Bool => self.unt(3),
+            RtTy::Named(id) => {
+                self.unt(4);
+                self.id(id);
+            }
+            RtTy::Pointer => self.unt(5),
+            RtTy::Fn => self.unt(6),
+            RtTy::Any => self.unt(7),
+            RtTy::Str => self.unt(8),
+        }
+    }
+
+    fn callee(&mut self, callee: Callee) {
+        match callee {
+            Callee::Function(function) => {
+                self.unt(0);
+                self.unt(function.into());
+            }
+            Callee::Interface(interface) => {
+                self.unt(1);
+                self.unt(interface.into());
+            }
+        }
+    }
+
+    fn op(&mut self, op: &Op) {
+        self.unt(op_tag(op));
+        match op {
+            Op::Int(int) => self.int(*int),
+            Op::Unt(unt) => self.unt(*unt),
+            Op::Bool(bool) => self.unt((*bool).into()),
+            Op::Str(text) => self.str(text),
+            Op::Fn(id) | Op::Coerce(id) => self.id(*id),
+            Op::Load(n)
+            | Op::Declare(n)
+            | Op::Store(n)
+            | Op::Ref(n)
+            | Op::RefMember(n)
+            | Op::Member(n)
+            | Op::Drop(n)
+            | Op::Jump(n)
+            | Op::JumpUnless(n)
+            | Op::CallPtr { args: n } => self.unt((*n).into()),
+            Op::BiOp(op) => self.unt(BIOPS.iter().position(|o| o == op).unwrap() as u64),
+            Op::Struct { ty, order } => {
+                self.id(*ty);
+                self.unt(order.len() as u64);
+                for member in order {
+                    self.unt((*member).into());
+                }
+            }
+            Op::Union { ty, discriminant } => {
+                self.id(*ty);
+                self.unt((*discriminant).into());
+            }
+            Op::JumpKeep { when, to } => {
+                self.unt((*when).into());
+                self.unt((*to).into());
+            }
+            Op::Call { function: a, args } | Op::CallInterface { interface: a, args } => {
+                self.unt((*a).into());
+                self.unt((*args).into());
+            }
+            Op::Dispatch { table, arg, args } => {
+                self.unt((*table).into());
+                self.unt((*arg).into());
+                self.unt((*args).into());
+            }
+            Op::Intrinsic {
+                intrinsic,
+                args,
+                ret,
+            } => {
+                self.unt(Intrinsic::ALL.iter().position(|i| i == intrinsic).unwrap() as u64);
+                self.unt((*args).into());
+                self.ty(*ret);
+            }
+            Op::Trap(trap) => self.unt(*trap as u64),
+            Op::Void | Op::Temp | Op::Deref | Op::Pop | Op::Neg | Op::Not | Op::Return => {}
+        }
+    }
+}
+
+const BIOPS: [BiOps; 20] = [
+    BiOps::Add,
+    BiOps::Sub,
+    BiOps::Mul,
+    BiOps::Div,
+    BiOps::Mod,
+    BiOps::BitOr,
+    BiOps::BitAnd,
+    BiOps::BitNot,
+    BiOps::BitXor,
+    BiOps::BitRshift,
+    BiOps::BitLShift,
+    BiOps::LogOr,
+    BiOps::LogAnd,
+    BiOps::LogNot,
+    BiOps::Eq,
+    BiOps::NEq,
+    BiOps::Gr,
+    BiOps::Ls,
+    BiOps::GrEq,
+    BiOps::LsEq,
+];
+
+fn op_tag(op: &Op) -> u64 {
+    match op {
+        Op::Int(_) => 0,
+        Op::Unt(_) => 1,
+        Op::Bool(_) => 2,
+        Op::Void => 3,
+        Op::Fn(_) => 4,
+        Op::Load(_) => 5,
+        Op::Declare(_) => 6,
+        Op::Store(_) => 7,
+        Op::Ref(_) => 8,
+        Op::Temp => 9,
+        Op::RefMember(_) => 10,
+        Op::Deref => 11,
+        Op::Member(_) => 12,
+        Op::Pop => 13,
+        Op::Drop(_) => 14,
+        Op::Neg => 15,
+        Op::Not => 16,
+        Op::BiOp(_) => 17,
+        Op::Struct { .. } => 18,
+        Op::Union { .. } => 19,
+        Op::Coerce(_) => 20,
+        Op::Jump(_) => 21,
+        Op::JumpUnless(_) => 22,
+        Op::JumpKeep { .. } => 23,
+        Op::Call { .. } => 24,
+        Op::CallInterface { .. } => 25,
+        Op::CallPtr { .. } => 26,
+        Op::Dispatch { .. } => 27,
+        Op::Return => 28,
+        Op::Trap(_) => 29,
+        Op::Str(_) => 30,
+        Op::Intrinsic { .. } => 31,
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+type Decoded<T> = std::result::Result<T, DecodeError>;
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Decoded<u8> {
+        let (first, rest) = self.bytes.split_first().ok_or(DecodeError::Truncated)?;
+        self.bytes = rest;
+        Ok(*first)
+    }
+
+    fn unt(&mut self) -> Decoded<u64> {
+        let mut unt = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            unt |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(unt);
+            }
+        }
+        Err(DecodeError::Malformed("an integer is too long"))
+    }
+
+    fn u32(&mut self) -> Decoded<u32> {
+        u32::try_from(self.unt()?).map_err(|_| DecodeError::Malformed("an index is too large"))
+    }
+
+    /// a count of items to follow, which can't be more than the bytes left
+    fn len(&mut self) -> Decoded<usize> {
+        let len = self.unt()? as usize;
+        if len > self.bytes.len() {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(len)
+    }
+
+    fn int(&mut self) -> Decoded<i64> {
+        let unt = self.unt()?;
+        Ok((unt >> 1) as i64 ^ -((unt & 1) as i64))
+    }
+
+    fn bool(&mut self) -> Decoded<bool> {
+        match self.unt()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Malformed("a bool is neither true nor false")),
+        }
+    }
+
+    fn str(&mut self) -> Decoded<String> {
+        let len = self.len()?;
+        let (str, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        String::from_utf8(str.to_vec()).map_err(|_| DecodeError::Malformed("a string isn't utf-8"))
+    }
+
+    fn id(&mut self) -> Decoded<DefId> {
+        Ok(DefId(self.unt()? as usize))
+    }
+
+    fn span(&mut self) -> Decoded<Span> {
+        Ok(Span {
+            sline: self.unt()?,
+            eline: self.unt()?,
+            scol: self.unt()?,
+            ecol: self.unt()?,
+        })
+    }
+
+    fn ty(&mut self) -> Decoded<RtTy> {
+        Ok(match self.unt()? {
+            0 => RtTy::Void,
+            1 => RtTy::Int,
+            2 => RtTy::Unt,
+            3 => RtTy::Bool,
+            4 => RtTy::Named(self.id()?),
+            5 => RtTy::Pointer,
+            6 => RtTy::Fn,
+            7 => RtTy::Any,
+            8 => RtTy::Str,
+            _ => return Err(DecodeError::Malformed("unknown type")),
+        })
+    }
+
+    fn callee(&mut self) -> Decoded<Callee> {
+        Ok(match self.unt()? {
+            0 => Callee::Function(self.u32()?),
+            1 => Callee::Interface(self.u32()?),
+            _ => return Err(DecodeError::Malformed("unknown callee")),
+        })
+    }
+
+    fn arm(&mut self) -> Decoded<Arm> {
+        Ok(match self.unt()? {
+            0 => Arm::Call(self.callee()?),
+            1 => Arm::Dispatch {
+                table: self.u32()?,
+                arg: self.u32()?,
+            },
+            _ => return Err(DecodeError::Malformed("unknown dispatch arm")),
+        })
+    }
+
+    fn op(&mut self) -> Decoded<Op> {
+        Ok(match self.unt()? {
+            0 => Op::Int(self.int()?),
+            1 => Op::Unt(self.unt()?),
+            2 => Op::Bool(self.bool()?),
+            3 => Op::Void,
+            4 => Op::Fn(self.id()?),
+            5 => Op::Load(self.u32()?),
+            6 => Op::Declare(self.u32()?),
+            7 => Op::Store(self.u32()?),
+            8 => Op::Ref(self.u32()?),
+            9 => Op::Temp,
+            10 => Op::RefMember(self.u32()?),
+            11 => Op::Deref,
+            12 => Op::Member(self.u32()?),
+            13 => Op::Pop,
+            14 => Op::Drop(self.u32()?),
+            15 => Op::Neg,
+            16 => Op::Not,
+            17 => Op::BiOp(
+                *BIOPS
+                    .get(self.unt()? as usize)
+                    .ok_or(DecodeError::Malformed("unknown operator"))?,
+            ),
+            18 => {
+                let ty = self.id()?;
+                let len = self.len()?;
+                let order = (0..len).map(|_| self.u32()).collect::<Decoded<_>>()?;
+                Op::Struct { ty, order }
+            }
+            19 => Op::Union {
+                ty: self.id()?,
+                discriminant: self.u32()?,
+            },
+            20 => Op::Coerce(self.id()?),
+            21 => Op::Jump(self.u32()?),
+            22 => Op::JumpUnless(self.u32()?),
+            23 => Op::JumpKeep {
+                when: self.bool()?,
+                to: self.u32()?,
+            },
+            24 => Op::Call {
+                function: self.u32()?,
+                args: self.u32()?,
+            },
+            25 => Op::CallInterface {
+                interface: self.u32()?,
+                args: self.u32()?,
+            },
+            26 => Op::CallPtr { args: self.u32()? },
+            27 => Op::Dispatch {
+                table: self.u32()?,
+                arg: self.u32()?,
+                args: self.u32()?,
+            },
+            28 => Op::Return,
+            29 => Op::Trap(match self.unt()? {
+                0 => Trap::JumpIntoExpr,
+                1 => Trap::EmptyUnion,
+                _ => return Err(DecodeError::Malformed("unknown trap")),
+            }),
+            30 => Op::Str(self.str()?),
+            31 => Op::Intrinsic {
+                intrinsic: *Intrinsic::ALL
+                    .get(self.unt()? as usize)
+                    .ok_or(DecodeError::Malformed("unknown intrinsic"))?,
+                args: self.u32()?,
+                ret: self.ty()?,
+            },
+            _ => return Err(DecodeError::Malformed("unknown instruction")),
+        })
+    }
+}
+
+impl Program {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Encoder {
+            bytes: MAGIC.to_vec(),
+        };
+        out.unt(VERSION);
+        out.unt(self.init.into());
+        match self.main {
+            Some(main) => {
+                out.unt(1);
+                out.unt(main.into());
+            }
+            None => out.unt(0),
+        }
+
+        out.unt(self.functions.len() as u64);
+        for function in &self.functions {
+            match function.id {
+                Some(id) => {
+                    out.unt(1);
+                    out.id(id);
+                }
+                None => out.unt(0),
+            }
+            out.str(&function.name);
+            out.span(function.span);
+            out.unt(function.params.into());
+            out.unt(function.locals.into());
+            out.unt(function.code.len() as u64);
+            for (op, span) in function.code.iter().zip(&function.spans) {
+                out.op(op);
+                out.span(*span);
+            }
+        }
+
+        out.unt(self.interfaces.len() as u64);
+        for interface in &self.interfaces {
+            out.id(interface.stub);
+            out.str(&interface.name);
+            out.unt(interface.candidates.len() as u64);
+            for candidate in &interface.candidates {
+                out.unt(candidate.function.into());
+                out.unt(candidate.params.len() as u64);
+                for param in &candidate.params {
+                    out.ty(*param);
+                }
+            }
+        }
+
+        out.unt(self.unions.len() as u64);
+        for union in &self.unions {
+            out.id(union.id);
+            out.unt(union.members.len() as u64);
+            for (name, ty) in &union.members {
+                out.str(name);
+                out.ty(*ty);
+            }
+        }
+
+        out.unt(self.dispatches.len() as u64);
+        for arms in &self.dispatches {
+            out.unt(arms.len() as u64);
+            for arm in arms {
+                match arm {
+                    Arm::Call(callee) => {
+                        out.unt(0);
+                        out.callee(*callee);
+                    }
+                    Arm::Dispatch { table, arg } => {
+                        out.unt(1);
+                        out.unt((*table).into());
+                        out.unt((*arg).into());
+                    }
+                }
+            }
+        }
+        out.bytes
+    }
+
+    /// reads a program written by `encode`, checking it can be run without going out of bounds
+    pub fn decode(bytes: &[u8]) -> std::result::Result<Program, DecodeError> {
+        let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+            return Err(DecodeError::NotBytecode);
+        };
+        let mut input = Decoder { bytes };
+        let version = input.unt()?;
+        if version != VERSION {
+            return Err(DecodeError::Version(version));
+        }
+        let init = input.u32()?;
+        let main = match input.unt()? {
+            0 => None,
+            _ => Some(input.u32()?),
+        };
+
+        let mut functions = Vec::new();
+        for _ in 0..input.len()? {
+            let id = match input.unt()? {
+                0 => None,
+                _ => Some(input.id()?),
+            };
+            let name = input.str()?;
+            let span = input.span()?;
+            let params = input.u32()?;
+            let locals = input.u32()?;
+            let len = input.len()?;
+            let mut code = Vec::with_capacity(len);
+            let mut spans = Vec::with_capacity(len);
+            for _ in 0..len {
+                code.push(input.op()?);
+                spans.push(input.span()?);
+            }
+            functions.push(Function {
+                id,
+                name,
+                span,
+                params,
+                locals,
+                code,
+                spans,
+            });
+        }
+
+        let mut interfaces = Vec::new();
+        for _ in 0..input.len()? {
+            let stub = input.id()?;
+            let name = input.str()?;
+            let mut candidates = Vec::new();
+            for _ in 0..input.len()? {
+                let function = input.u32()?;
+                let params = (0..input.len()?)
+                    .map(|_| input.ty())
+                    .collect::<Decoded<_>>()?;
+                candidates.push(Candidate { function, params });
+            }
+            interfaces.push(Interface {
+                stub,
+                name,
+                candidates,
+            });
+        }
+
+        let mut unions = Vec::new();
+        for _ in 0..input.len()? {
+            let id = input.id()?;
+            let mut members = Vec::new();
+            for _ in 0..input.len()? {
+                members.push((input.str()?, input.ty()?));
+            }
+            unions.push(Union { id, members });
+        }
+
+        let mut dispatches = Vec::new();
+        for _ in 0..input.len()? {
+            let arms = (0..input.len()?)
+                .map(|_| input.arm())
+                .collect::<Decoded<_>>()?;
+            dispatches.push(arms);
+        }
+        if !input.bytes.is_empty() {
+            return Err(DecodeError::Malformed("trailing bytes"));
+        }
+
+        let program = Program {
+            init,
+            main,
+            functions,
+            interfaces,
+            unions,
+            dispatches,
+        };
+        program.validate()?;
+        Ok(program)
+    }
+
+    /// every index refers to something which exists, & every intrinsic is given as many
+    /// arguments as it takes. (what the instructions do to the stack isn't checked - the vm
+    /// raises an error if that's wrong)
+    fn validate(&self) -> std::result::Result<(), DecodeError> {
+        let functions = self.functions.len();
+        let callee = |callee: &Callee| match callee {
+            Callee::Function(function) => (*function as usize) < functions,
+            Callee::Interface(interface) => (*interface as usize) < self.interfaces.len(),
+        };
+        let check = |valid: bool, what: &'static str| {
+            if valid {
+                Ok(())
+            } else {
+                Err(DecodeError::Malformed(what))
+            }
+        };
+
+        check((self.init as usize) < functions, "no top level")?;
+        check(
+            self.main.is_none_or(|main| (main as usize) < functions),
+            "no entry point",
+        )?;
+        // (first, as checking the instructions follows the tables)
+        for (index, arms) in self.dispatches.iter().enumerate() {
+            for arm in arms {
+                let valid = match arm {
+                    Arm::Call(arm) => callee(arm),
+                    // only ever to a later table, so matching always ends
+                    Arm::Dispatch { table, .. } => {
+                        (*table as usize) > index && (*table as usize) < self.dispatches.len()
+                    }
+                };
+                check(valid, "a dispatch refers to something which doesn't exist")?;
+            }
+        }
+        for function in &self.functions {
+            let len = function.code.len() as u32;
+            check(
+                function.params <= function.locals,
+                "more params than locals",
+            )?;
+            // (each local is declared by an instruction - so a huge count can't exhaust memory)
+            check(
+                function.locals as usize <= function.params as usize + function.code.len(),
+                "more locals than instructions",
+            )?;
+            check(
+                matches!(function.code.last(), Some(Op::Return)),
+                "no return",
+            )?;
+            for op in &function.code {
+                let valid = match op {
+                    Op::Load(local) | Op::Declare(local) | Op::Store(local) | Op::Ref(local) => {
+                        *local < function.locals
+                    }
+                    Op::Jump(to) | Op::JumpUnless(to) | Op::JumpKeep { to, .. } => *to < len,
+                    Op::Call { function, .. } => (*function as usize) < functions,
+                    Op::CallInterface { interface, .. } => {
+                        (*interface as usize) < self.interfaces.len()
+                    }
+                    Op::Dispatch { table, arg, args } => {
+                        (*table as usize) < self.dispatches.len()
+                            && arg < args
+                            && self.nested_args(*table).iter().all(|arg| arg < args)
+                    }
+                    _ => true,
+                };
+                check(
+                    valid,
+                    "an instruction refers to something which doesn't exist",
+                )?;
+                if let Op::Intrinsic {
+                    intrinsic, args, ..
+                } = op
+                {
+                    check(
+                        intrinsic.arity().contains(&(*args as usize)),
+                        "an intrinsic is given the wrong number of arguments",
+                    )?;
+                }
+            }
+        }
+        for interface in &self.interfaces {
+            check(
+                interface
+                    .candidates
+                    .iter()
+                    .all(|candidate| (candidate.function as usize) < functions),
+                "an interface refers to a function which doesn't exist",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// every argument matched on by the tables `table` leads to
+    fn nested_args(&self, table: u32) -> Vec<u32> {
+        let mut args = Vec::new();
+        for arm in self.dispatches.get(table as usize).into_iter().flatten() {
+            if let Arm::Dispatch { table, arg } = arm {
+                args.push(*arg);
+                args.extend(self.nested_args(*table));
+            }
+        }
+        args
+    }
+}
@@ -0,0 +1,355 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct NodeId(usize);
+
+/// a point in a function's control flow - the start of a statement or expression
+struct Node {
+    succs: Vec<NodeId>,
+}
+
+/// the control flow graph of a single function body (or of the module's top level).
+/// nested functions get graphs of their own.
+struct Cfg {
+    nodes: Vec<Node>,
+    entry: NodeId,
+    /// reached by falling off the end of the body
+    end: NodeId,
+}
+
+impl Cfg {
+    /// whether each node can be reached from the entry
+    fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![self.entry];
+        while let Some(node) = stack.pop() {
+            if std::mem::replace(&mut seen[node.0], true) {
+                continue;
+            }
+            stack.extend(&self.nodes[node.0].succs);
+        }
+        seen
+    }
+}
+
+/// a statement (or tail) of a block, and the node control reaches it at
+struct Item {
+    span: Span,
+    node: NodeId,
+}
+
+/// the result of lowering a body - the graph, plus what's needed to check it
+struct Lowered {
+    cfg: Cfg,
+    /// the items of each block, in order
+    blocks: Vec<Vec<Item>>,
+    /// every `return`, and the node it starts at
+    returns: Vec<(Span, NodeId)>,
+}
+
+struct Builder<'a> {
+    types: &'a Types,
+    nodes: Vec<Node>,
+    exit: NodeId,
+    labels: HashMap<&'a str, NodeId>,
+    gotos: Vec<(NodeId, &'a str)>,
+    blocks: Vec<Vec<Item>>,
+    returns: Vec<(Span, NodeId)>,
+    /// function bodies nested within this one, to be lowered separately
+    nested: Vec<&'a Stmt>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(types: &'a Types) -> Self {
+        let mut builder = Self {
+            types,
+            nodes: Vec::new(),
+            exit: NodeId(0),
+            labels: HashMap::new(),
+            gotos: Vec::new(),
+            blocks: Vec::new(),
+            returns: Vec::new(),
+            nested: Vec::new(),
+        };
+        builder.exit = builder.node();
+        builder
+    }
+
+    fn node(&mut self) -> NodeId {
+        self.nodes.push(Node { succs: Vec::new() });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    fn connect(&mut self, preds: &[NodeId], to: NodeId) {
+        for pred in preds {
+            self.nodes[pred.0].succs.push(to);
+        }
+    }
+
+    /// a fresh node following all of `preds`
+    fn step(&mut self, preds: &[NodeId]) -> NodeId {
+        let node = self.node();
+        self.connect(preds, node);
+        node
+    }
+
+    fn finish(mut self, body: &'a Block) -> (Lowered, Vec<&'a Stmt>) {
+        let entry = self.node();
+        let ends = self.block(body, vec![entry]);
+        let end = self.step(&ends);
+        self.connect(&[end], self.exit);
+
+        for (goto, label) in std::mem::take(&mut self.gotos) {
+            // undeclared labels have already been reported by `labels::check`
+            if let Some(target) = self.labels.get(label).copied() {
+                self.connect(&[goto], target);
+            }
+        }
+
+        let lowered = Lowered {
+            cfg: Cfg {
+                nodes: self.nodes,
+                entry,
+                end,
+            },
+            blocks: self.blocks,
+            returns: self.returns,
+        };
+        (lowered, self.nested)
+    }
+
+    /// returns the nodes control leaves the block from
+    fn block(&mut self, block: &'a Block, mut preds: Vec<NodeId>) -> Vec<NodeId> {
+        let mut items = Vec::new();
+        for stmt in &block.stmts {
+            if let Stmt::Fn { .. } = stmt.spanned {
+                self.nested.push(&stmt.spanned);
+            }
+            // declarations aren't run, so can't be unreachable
+            if let Stmt::Fn { .. } | Stmt::Stub { .. } | Stmt::Type { .. } = stmt.spanned {
+                continue;
+            }
+
+            let start = self.step(&preds);
+            items.push(Item {
+                span: stmt.span,
+                node: start,
+            });
+            preds = self.stmt(&stmt.spanned, stmt.span, start);
+        }
+
+        if let Some(tail) = &block.tail {
+            let start = self.step(&preds);
+            items.push(Item {
+                span: tail.span,
+                node: start,
+            });
+            preds = self.expr(tail, vec![start]);
+        }
+        self.blocks.push(items);
+        preds
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt, span: Span, start: NodeId) -> Vec<NodeId> {
+        match stmt {
+            Stmt::Label { name } => {
+                self.labels.entry(&name.spanned).or_insert(start);
+                vec![start]
+            }
+            Stmt::Goto { name } => {
+                self.gotos.push((start, &name.spanned));
+                Vec::new()
+            }
+            Stmt::Return { expr } => {
+                self.returns.push((span, start));
+                let ends = match expr {
+                    Some(expr) => self.expr(expr, vec![start]),
+                    None => vec![start],
+                };
+                self.connect(&ends, self.exit);
+                Vec::new()
+            }
+            // the only ways out of a loop are `goto` & `return`
+            Stmt::Loop { block } => {
+                let ends = self.block(block, vec![start]);
+                self.connect(&ends, start);
+                Vec::new()
+            }
+            Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. }
+            | Stmt::Discard { expr } => self.expr(expr, vec![start]),
+            Stmt::Fn { .. } | Stmt::Stub { .. } | Stmt::Type { .. } => vec![start],
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Spanned<Expr>, preds: Vec<NodeId>) -> Vec<NodeId> {
+        match &expr.spanned {
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::ObjMember(..) => preds,
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Attributed { expr: inner, .. }
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => self.expr(inner, preds),
+            Expr::FnCall(_, args) => {
+                let mut preds = preds;
+                for arg in args {
+                    preds = self.expr(arg, preds);
+                }
+                if self.never_returns(expr.span) {
+                    Vec::new()
+                } else {
+                    preds
+                }
+            }
+            Expr::BiOp(left, op, right) => {
+                let left = self.expr(left, preds);
+                let mut ends = self.expr(right, left.clone());
+                // the right operand may be short-circuited
+                if let BiOps::LogAnd | BiOps::LogOr = op {
+                    ends.extend(left);
+                }
+                ends
+            }
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                let mut tested = self.expr(condition, preds);
+                let mut ends = self.block(&then.spanned, tested.clone());
+                for (condition, block) in elifs {
+                    tested = self.expr(condition, tested);
+                    ends.extend(self.block(&block.spanned, tested.clone()));
+                }
+                match r#else {
+                    Some(r#else) => ends.extend(self.block(&r#else.spanned, tested)),
+                    None => ends.extend(tested),
+                }
+                ends
+            }
+            Expr::Block(block) => self.block(block, preds),
+            Expr::StructLit { fields, .. } => {
+                let mut preds = preds;
+                match fields {
+                    StructLitFields::Positional(values) => {
+                        for value in values {
+                            preds = self.expr(value, preds);
+                        }
+                    }
+                    StructLitFields::Named(members) => {
+                        for (_, value) in members {
+                            preds = self.expr(value, preds);
+                        }
+                    }
+                }
+                preds
+            }
+        }
+    }
+
+    /// whether the call at `span` is to a `#[ no_return ]` function
+    fn never_returns(&self, span: Span) -> bool {
+        let no_return = |id: &DefId| self.types.sigs.get(id).is_some_and(|sig| sig.no_return);
+        match (self.types.call(span), self.types.dispatch(span)) {
+            (Some(id), _) => no_return(&id),
+            (None, Some(dispatch)) => dispatch.targets().iter().all(no_return),
+            (None, None) => false,
+        }
+    }
+}
+
+struct Checker<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+}
+
+impl<'a> Checker<'a> {
+    /// `func` is `None` for the module's top level
+    fn body(&mut self, body: &Block, func: Option<&Stmt>) {
+        let (lowered, nested) = Builder::new(self.types).finish(body);
+        let reachable = lowered.cfg.reachable();
+
+        for items in &lowered.blocks {
+            for pair in items.windows(2) {
+                let [before, item] = pair else {
+                    continue;
+                };
+                if reachable[before.node.0] && !reachable[item.node.0] {
+                    let warning = SdwErr::from_pos(FlowErrors::Unreachable, item.span)
+                        .with_note("control never continues past this", before.span);
+                    self.state.warnings.push(warning);
+                }
+            }
+        }
+
+        if let Some(Stmt::Fn {
+            attrs,
+            return_type,
+            name,
+            ..
+        }) = func
+        {
+            let ends = reachable[lowered.cfg.end.0];
+            let sig = self
+                .res
+                .def_at(name.span)
+                .and_then(|id| self.types.sigs.get(&id));
+
+            if let Some(attr) = attrs
+                .iter()
+                .find(|attr| attr.spanned.name.spanned == "no_return")
+            {
+                let returns = lowered
+                    .returns
+                    .iter()
+                    .filter(|(_, node)| reachable[node.0])
+                    .map(|(span, _)| *span)
+                    .chain(ends.then(|| body.tail.as_ref().map_or(name.span, |tail| tail.span)));
+                for span in returns {
+                    let err = SdwErr::from_pos(
+                        FlowErrors::ReturnsFromNoReturn(name.spanned.clone()),
+                        span,
+                    )
+                    .with_note("declared as never returning here", attr.span);
+                    self.state.errors.push(err);
+                }
+            } else if ends
+                && body.tail.is_none()
+                && sig.is_some_and(|sig| !matches!(sig.ret, Ty::Void | Ty::Error))
+            {
+                let last = body.stmts.last().map_or(name.span, |stmt| stmt.span);
+                let err = SdwErr::from_pos(FlowErrors::MissingReturn(name.spanned.clone()), last)
+                    .with_note(
+                        format!("`{}` must be returned", return_type.spanned),
+                        return_type.span,
+                    );
+                self.state.errors.push(err);
+            }
+        }
+
+        for func in nested {
+            if let Stmt::Fn { body, .. } = func {
+                self.body(body, Some(func));
+            }
+        }
+    }
+}
+
+/// checks every path through a function body: a function must `return` (or give a tail value)
+/// on every path that reaches the end of its body, a `#[ no_return ]` function must never
+/// return, and code which can't be reached raises a warning.
+pub fn check(state: &mut State, root: &Block, res: &Resolutions, types: &Types) {
+    let mut checker = Checker { state, res, types };
+    checker.body(root, None);
+}
@@ -0,0 +1,780 @@
+use crate::bytecode::{
+    Arm, Callee, Candidate, Function, Interface, Op, Program, RtTy, Trap, Union,
+};
+use crate::driver::Checked;
+use crate::interp;
+use crate::intrinsics::Intrinsic;
+use crate::overload::{self, Dispatch};
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// a label's position in the code, & how it's reached - which `goto`s may jump to it is only
+/// known once the whole function is compiled
+struct Label {
+    at: u32,
+    depth: u32,
+    path: Vec<Nesting>,
+}
+
+/// a `goto`, compiled as a placeholder `Drop` & `Jump` to be patched
+struct Goto {
+    at: usize,
+    label: DefId,
+    depth: u32,
+    path: Vec<Nesting>,
+}
+
+/// one of the blocks or expressions enclosing some code. a `goto` may leave any of them, but
+/// only enter blocks (as the interpreter does) - entering anything else would skip evaluating
+/// part of it, leaving the stack without the operands the rest of it expects.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Nesting {
+    id: u32,
+    block: bool,
+}
+
+/// the function being compiled
+#[derive(Default)]
+struct Current {
+    function: Option<DefId>,
+    code: Vec<Op>,
+    spans: Vec<Span>,
+    locals: HashMap<DefId, u32>,
+    /// how many values are on the stack, within this function
+    depth: u32,
+    path: Vec<Nesting>,
+    labels: HashMap<DefId, Label>,
+    gotos: Vec<Goto>,
+}
+
+struct Compiler<'a> {
+    res: &'a Resolutions,
+    types: &'a Types,
+    /// every function with a body, in the order they're compiled
+    bodies: Vec<(DefId, &'a Stmt)>,
+    /// `bodies` indices are offset by one, for the top level
+    functions: HashMap<DefId, u32>,
+    interfaces: HashMap<DefId, u32>,
+    dispatches: Vec<Vec<Arm>>,
+    nestings: u32,
+    current: Current,
+}
+
+impl<'a> Compiler<'a> {
+    fn collect(&mut self, block: &'a Block) {
+        for stmt in &block.stmts {
+            self.collect_stmt(&stmt.spanned);
+        }
+        if let Some(tail) = &block.tail {
+            self.collect_expr(tail);
+        }
+    }
+
+    fn collect_stmt(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Fn { name, body, .. } => {
+                if let Some(id) = self.res.def_at(name.span) {
+                    self.functions.insert(id, self.bodies.len() as u32 + 1);
+                    self.bodies.push((id, stmt));
+                }
+                self.collect(body);
+            }
+            Stmt::Loop { block } => self.collect(block),
+            Stmt::Return { expr: Some(expr) }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. }
+            | Stmt::Discard { expr } => self.collect_expr(expr),
+            _ => {}
+        }
+    }
+
+    fn collect_expr(&mut self, expr: &'a Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::Block(block) => self.collect(block),
+            Expr::Cond {
+                then,
+                elifs,
+                r#else,
+                ..
+            } => {
+                self.collect(&then.spanned);
+                for (_, block) in elifs {
+                    self.collect(&block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    self.collect(&r#else.spanned);
+                }
+            }
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.collect_expr(inner),
+            _ => {}
+        }
+    }
+
+    fn lower(&self, ty: &Ty) -> RtTy {
+        match ty {
+            Ty::Prim(PrimType::Int) => RtTy::Int,
+            Ty::Prim(PrimType::Unt) => RtTy::Unt,
+            Ty::Prim(PrimType::Bool) => RtTy::Bool,
+            Ty::Void => RtTy::Void,
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Generic | TypeDecl::Aggregate(_)) => RtTy::Any,
+                _ => RtTy::Named(*id),
+            },
+            Ty::Pointer(_) => RtTy::Pointer,
+            Ty::FnPtr { .. } => RtTy::Fn,
+            Ty::Prim(PrimType::String) => RtTy::Str,
+            Ty::Prim(PrimType::Float) | Ty::Never | Ty::Error => RtTy::Any,
+        }
+    }
+
+    fn union(&self, ty: &Ty) -> Option<DefId> {
+        match ty {
+            Ty::Named(id) if matches!(self.types.decls.get(id), Some(TypeDecl::Union(_))) => {
+                Some(*id)
+            }
+            _ => None,
+        }
+    }
+
+    fn callee(&self, id: DefId) -> Callee {
+        match self.functions.get(&id) {
+            Some(function) => Callee::Function(*function),
+            None => Callee::Interface(self.interfaces[&id]),
+        }
+    }
+
+    /// adds the table for `dispatch` (& any it leads to, after it), returning its index
+    fn table(&mut self, dispatch: &Dispatch) -> u32 {
+        let table = self.dispatches.len();
+        self.dispatches.push(Vec::new());
+        let arms = dispatch
+            .arms
+            .iter()
+            .map(|arm| match arm {
+                overload::Arm::Call(target) => Arm::Call(self.callee(*target)),
+                overload::Arm::Dispatch(nested) => Arm::Dispatch {
+                    table: self.table(nested),
+                    arg: nested.arg as u32,
+                },
+            })
+            .collect();
+        self.dispatches[table] = arms;
+        table as u32
+    }
+
+    /*
+     * emitting
+     */
+
+    /// adds an instruction, tracking how it changes the stack's depth
+    fn emit(&mut self, op: Op, span: Span) {
+        let (pops, pushes) = match &op {
+            Op::Int(_)
+            | Op::Unt(_)
+            | Op::Bool(_)
+            | Op::Str(_)
+            | Op::Void
+            | Op::Fn(_)
+            | Op::Load(_)
+            | Op::Ref(_) => (0, 1),
+            Op::Declare(_) | Op::Store(_) | Op::Pop | Op::JumpUnless(_) | Op::Return => (1, 0),
+            Op::Drop(count) => (*count, 0),
+            Op::BiOp(_) => (2, 1),
+            Op::Struct { order, .. } => (order.len() as u32, 1),
+            Op::Call { args, .. }
+            | Op::CallInterface { args, .. }
+            | Op::Dispatch { args, .. }
+            | Op::Intrinsic { args, .. } => (*args, 1),
+            Op::CallPtr { args } => (args + 1, 1),
+            Op::Temp
+            | Op::RefMember(_)
+            | Op::Deref
+            | Op::Member(_)
+            | Op::Neg
+            | Op::Not
+            | Op::Union { .. }
+            | Op::Coerce(_)
+            | Op::Jump(_)
+            | Op::JumpKeep { .. }
+            | Op::Trap(_) => (0, 0),
+        };
+        let current = &mut self.current;
+        current.depth = current.depth - pops + pushes;
+        current.code.push(op);
+        current.spans.push(span);
+    }
+
+    fn here(&self) -> u32 {
+        self.current.code.len() as u32
+    }
+
+    /// a jump to be pointed somewhere later, with `patch`
+    fn jump(&mut self, op: Op, span: Span) -> usize {
+        self.emit(op, span);
+        self.current.code.len() - 1
+    }
+
+    fn patch(&mut self, at: usize) {
+        let here = self.here();
+        match &mut self.current.code[at] {
+            Op::Jump(to) | Op::JumpUnless(to) | Op::JumpKeep { to, .. } => *to = here,
+            _ => unreachable!("compiler: patched a non-jump"),
+        }
+    }
+
+    fn coerce(&mut self, to: &Ty, span: Span) {
+        if let Some(union) = self.union(to) {
+            self.emit(Op::Coerce(union), span);
+        }
+    }
+
+    fn local(&mut self, id: DefId) -> u32 {
+        let count = self.current.locals.len() as u32;
+        *self.current.locals.entry(id).or_insert(count)
+    }
+
+    fn nest<T>(&mut self, block: bool, within: impl FnOnce(&mut Self) -> T) -> T {
+        self.nestings += 1;
+        let nesting = Nesting {
+            id: self.nestings,
+            block,
+        };
+        self.current.path.push(nesting);
+        let result = within(self);
+        self.current.path.pop();
+        result
+    }
+
+    /*
+     * functions
+     */
+
+    fn function(
+        &mut self,
+        id: Option<DefId>,
+        name: &str,
+        span: Span,
+        parameters: &[(Spanned<String>, Spanned<String>)],
+        body: &Block,
+    ) -> Function {
+        self.current = Current {
+            function: id,
+            ..Current::default()
+        };
+        let sig = id.map(|id| &self.types.sigs[&id]);
+
+        // arguments are converted to the union a parameter expects on the way in, so every
+        // kind of call (direct, dispatched, or through a pointer) does so
+        for (index, (_, param)) in parameters.iter().enumerate() {
+            let param = self
+                .res
+                .def_at(param.span)
+                .expect("compiler: unresolved parameter");
+            let local = self.local(param);
+            let ty = &self.types.locals[&param];
+            if let Some(union) = self.union(ty) {
+                debug_assert_eq!(local, index as u32);
+                self.emit(Op::Load(local), span);
+                self.emit(Op::Coerce(union), span);
+                self.emit(Op::Store(local), span);
+            }
+        }
+
+        self.block(body);
+        if let Some(sig) = sig {
+            self.coerce(&sig.ret, span);
+        }
+        self.emit(Op::Return, span);
+
+        let mut current = std::mem::take(&mut self.current);
+        for goto in current.gotos {
+            let label = &current.labels[&goto.label];
+            // the label's innermost enclosing block which also encloses the goto
+            let shared = label
+                .path
+                .iter()
+                .zip(&goto.path)
+                .take_while(|(a, b)| a == b)
+                .count();
+            let enterable = label.path[..shared]
+                .last()
+                .is_none_or(|nesting| nesting.block)
+                && label.path[shared..].iter().all(|nesting| nesting.block);
+            if enterable {
+                current.code[goto.at] = Op::Drop(goto.depth - label.depth);
+                current.code[goto.at + 1] = Op::Jump(label.at);
+            } else {
+                current.code[goto.at] = Op::Trap(Trap::JumpIntoExpr);
+            }
+        }
+        Function {
+            id,
+            name: name.to_owned(),
+            span,
+            params: parameters.len() as u32,
+            locals: current.locals.len() as u32,
+            code: current.code,
+            spans: current.spans,
+        }
+    }
+
+    /// leaves the block's value on the stack
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            let depth = self.current.depth;
+            self.stmt(&stmt.spanned, stmt.span);
+            // after a `return` or `goto`, the stack is as if the statement had finished
+            self.current.depth = depth;
+        }
+        match &block.tail {
+            Some(tail) => self.expr(tail),
+            None => self.emit(Op::Void, Span::default()),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, span: Span) {
+        match stmt {
+            Stmt::Fn { .. } | Stmt::Stub { .. } | Stmt::Type { .. } => {}
+            Stmt::Label { name } => {
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("compiler: unresolved label");
+                let label = Label {
+                    at: self.here(),
+                    depth: self.current.depth,
+                    path: self.current.path.clone(),
+                };
+                self.current.labels.insert(id, label);
+            }
+            Stmt::Goto { name } => {
+                let label = self
+                    .res
+                    .def_at(name.span)
+                    .expect("compiler: unresolved goto");
+                let goto = Goto {
+                    at: self.current.code.len(),
+                    label,
+                    depth: self.current.depth,
+                    path: self.current.path.clone(),
+                };
+                self.current.gotos.push(goto);
+                self.emit(Op::Drop(0), span);
+                self.emit(Op::Jump(0), span);
+            }
+            Stmt::Loop { block } => {
+                let start = self.here();
+                self.nest(true, |this| this.block(block));
+                self.emit(Op::Pop, span);
+                self.emit(Op::Jump(start), span);
+            }
+            Stmt::Return { expr } => {
+                match expr {
+                    Some(expr) => self.expr(expr),
+                    None => self.emit(Op::Void, span),
+                }
+                if let Some(function) = self.current.function {
+                    self.coerce(&self.types.sigs[&function].ret, span);
+                }
+                self.emit(Op::Return, span);
+            }
+            Stmt::VarDec { name, initialiser } => {
+                self.expr(initialiser);
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("compiler: unresolved let");
+                let local = self.local(id);
+                self.emit(Op::Declare(local), span);
+            }
+            Stmt::VarRes { name, updated } => {
+                self.expr(updated);
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("compiler: unresolved variable");
+                self.coerce(&self.types.locals[&id], span);
+                let local = self.local(id);
+                self.emit(Op::Store(local), span);
+            }
+            Stmt::Discard { expr } => {
+                self.expr(expr);
+                self.emit(Op::Pop, span);
+            }
+        }
+    }
+
+    /*
+     * expressions
+     */
+
+    fn ty(&self, expr: &Spanned<Expr>) -> &'a Ty {
+        self.types.expr(expr.span).unwrap_or(&Ty::Error)
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::Block(block) => self.nest(true, |this| this.block(block)),
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.expr(inner),
+            Expr::Cond { .. } => self.cond(expr),
+            _ => self.nest(false, |this| this.opaque(expr)),
+        }
+    }
+
+    /// an expression which can't be jumped into
+    fn opaque(&mut self, expr: &Spanned<Expr>) {
+        let span = expr.span;
+        match &expr.spanned {
+            Expr::IntLiteral(int) => match self.ty(expr) {
+                Ty::Prim(PrimType::Unt) => self.emit(Op::Unt(*int as u64), span),
+                _ => self.emit(Op::Int(*int), span),
+            },
+            Expr::BoolLiteral(bool) => self.emit(Op::Bool(*bool), span),
+            Expr::StrLiteral(text) => self.emit(Op::Str(text.clone()), span),
+            Expr::Variable(_) => match self.res.get(span) {
+                Some(Res::Def(id)) => {
+                    let local = self.local(*id);
+                    self.emit(Op::Load(local), span);
+                }
+                Some(Res::Overloads(ids)) => self.emit(Op::Fn(ids[0]), span),
+                None => unreachable!("compiler: unresolved variable"),
+            },
+            Expr::UnaryPos(inner) => self.expr(inner),
+            Expr::UnaryNeg(inner) => {
+                self.expr(inner);
+                self.emit(Op::Neg, span);
+            }
+            Expr::UnaryNot(inner) => {
+                self.expr(inner);
+                self.emit(Op::Not, span);
+            }
+            Expr::BiOp(left, op @ (BiOps::LogOr | BiOps::LogAnd), right) => {
+                self.expr(left);
+                let when = *op == BiOps::LogOr;
+                let skip = self.jump(Op::JumpKeep { when, to: 0 }, span);
+                self.emit(Op::Pop, span);
+                self.expr(right);
+                self.patch(skip);
+            }
+            Expr::BiOp(left, op, right) => {
+                self.expr(left);
+                self.expr(right);
+                self.emit(Op::BiOp(*op), span);
+            }
+            Expr::Referal(inner) => self.place(inner),
+            Expr::Indir(inner) => {
+                self.expr(inner);
+                self.emit(Op::Deref, span);
+            }
+            Expr::ObjMember(object, member) => {
+                let (local, pointer, index) = self.member(object, member);
+                self.emit(Op::Load(local), span);
+                if pointer {
+                    self.emit(Op::Deref, span);
+                }
+                self.emit(Op::Member(index), span);
+            }
+            Expr::FnCall(_, args) => self.call(span, args),
+            Expr::StructLit { fields, .. } => self.struct_lit(expr, fields),
+            Expr::Block(_) | Expr::SubExpr(_) | Expr::Attributed { .. } | Expr::Cond { .. } => {
+                unreachable!("compiler: enterable expression compiled as opaque")
+            }
+        }
+    }
+
+    fn cond(&mut self, expr: &Spanned<Expr>) {
+        let Expr::Cond {
+            condition,
+            then,
+            elifs,
+            r#else,
+        } = &expr.spanned
+        else {
+            unreachable!("compiler: not a condition");
+        };
+        let depth = self.current.depth;
+        let mut ends = Vec::new();
+        for (condition, block) in std::iter::once((condition, then))
+            .chain(elifs.iter().map(|(condition, block)| (condition, block)))
+        {
+            self.nest(false, |this| this.expr(condition));
+            let next = self.jump(Op::JumpUnless(0), condition.span);
+            self.nest(true, |this| this.block(&block.spanned));
+            ends.push(self.jump(Op::Jump(0), block.span));
+            self.current.depth = depth;
+            self.patch(next);
+        }
+        match r#else {
+            Some(block) => self.nest(true, |this| this.block(&block.spanned)),
+            None => self.emit(Op::Void, expr.span),
+        }
+        for end in ends {
+            self.patch(end);
+        }
+        self.coerce(self.ty(expr), expr.span);
+    }
+
+    /// the local holding `object`, whether it's a pointer, & the index of `member` within it
+    fn member(&mut self, object: &Spanned<String>, member: &Spanned<String>) -> (u32, bool, u32) {
+        let id = self
+            .res
+            .def_at(object.span)
+            .expect("compiler: unresolved object");
+        let ty = &self.types.locals[&id];
+        let index = self
+            .types
+            .members(ty)
+            .expect("compiler: member of a type without members")
+            .iter()
+            .position(|(name, _)| *name == member.spanned)
+            .expect("compiler: unknown member");
+        (self.local(id), matches!(ty, Ty::Pointer(_)), index as u32)
+    }
+
+    /// pushes a pointer to where a place expression (`x`, `x.member`, `*x`) is kept
+    fn place(&mut self, expr: &Spanned<Expr>) {
+        let span = expr.span;
+        match &expr.spanned {
+            Expr::Variable(_) => match self.res.get(span) {
+                Some(Res::Def(id)) => {
+                    let local = self.local(*id);
+                    self.emit(Op::Ref(local), span);
+                }
+                _ => {
+                    self.expr(expr);
+                    self.emit(Op::Temp, span);
+                }
+            },
+            Expr::SubExpr(inner) => self.place(inner),
+            Expr::Indir(inner) => self.expr(inner),
+            Expr::ObjMember(object, member) => {
+                let (local, pointer, index) = self.member(object, member);
+                if pointer {
+                    self.emit(Op::Load(local), span);
+                } else {
+                    self.emit(Op::Ref(local), span);
+                }
+                self.emit(Op::RefMember(index), span);
+            }
+            _ => {
+                self.expr(expr);
+                self.emit(Op::Temp, span);
+            }
+        }
+    }
+
+    fn call(&mut self, span: Span, args: &[Box<Spanned<Expr>>]) {
+        for arg in args {
+            self.expr(arg);
+        }
+        let count = args.len() as u32;
+
+        if let Some(dispatch) = self.types.dispatch(span) {
+            let table = self.table(dispatch);
+            let arg = dispatch.arg as u32;
+            self.emit(
+                Op::Dispatch {
+                    table,
+                    arg,
+                    args: count,
+                },
+                span,
+            );
+        } else if let Some(function) = self.types.call(span) {
+            if let Some(intrinsic) = Intrinsic::of(self.res, self.types, function) {
+                let ret = self.lower(&self.types.sigs[&function].ret);
+                self.emit(
+                    Op::Intrinsic {
+                        intrinsic,
+                        args: count,
+                        ret,
+                    },
+                    span,
+                );
+                return;
+            }
+            match self.callee(function) {
+                Callee::Function(function) => self.emit(
+                    Op::Call {
+                        function,
+                        args: count,
+                    },
+                    span,
+                ),
+                Callee::Interface(interface) => self.emit(
+                    Op::CallInterface {
+                        interface,
+                        args: count,
+                    },
+                    span,
+                ),
+            }
+        } else {
+            let Some(Res::Def(id)) = self.res.get(span) else {
+                unreachable!("compiler: unresolved call");
+            };
+            let local = self.local(*id);
+            self.emit(Op::Load(local), span);
+            self.emit(Op::CallPtr { args: count }, span);
+        }
+    }
+
+    fn struct_lit(&mut self, expr: &Spanned<Expr>, fields: &StructLitFields) {
+        let span = expr.span;
+        let Ty::Named(ty) = self.ty(expr) else {
+            unreachable!("compiler: struct literal of a non-struct");
+        };
+        let (members, union) = match self.types.decls.get(ty) {
+            Some(TypeDecl::Struct(members)) => (members, false),
+            Some(TypeDecl::Union(members)) => (members, true),
+            _ => unreachable!("compiler: struct literal of a non-struct"),
+        };
+
+        // evaluated in the order written, not the order declared
+        let mut order = Vec::new();
+        match fields {
+            StructLitFields::Positional(exprs) => {
+                for (index, value) in exprs.iter().enumerate() {
+                    self.expr(value);
+                    self.coerce(&members[index].1, value.span);
+                    order.push(index as u32);
+                }
+            }
+            StructLitFields::Named(exprs) => {
+                for (name, value) in exprs {
+                    let index = members
+                        .iter()
+                        .position(|(member, _)| *member == name.spanned)
+                        .expect("compiler: unknown member");
+                    self.expr(value);
+                    self.coerce(&members[index].1, value.span);
+                    order.push(index as u32);
+                }
+            }
+        }
+
+        if !union {
+            for index in 0..members.len() as u32 {
+                if !order.contains(&index) {
+                    self.emit(Op::Void, span);
+                    order.push(index);
+                }
+            }
+            self.emit(Op::Struct { ty: *ty, order }, span);
+            return;
+        }
+        // only the last member given is kept
+        let (StructLitFields::Named(_), Some(discriminant)) = (fields, order.pop()) else {
+            self.emit(Op::Drop(order.len() as u32), span);
+            self.emit(Op::Trap(Trap::EmptyUnion), span);
+            self.emit(Op::Void, span);
+            return;
+        };
+        for _ in order {
+            self.emit(Op::Pop, span);
+        }
+        self.emit(
+            Op::Union {
+                ty: *ty,
+                discriminant,
+            },
+            span,
+        );
+    }
+}
+
+/// compiles a checked module to bytecode
+pub fn compile(checked: &Checked) -> Program {
+    let Checked {
+        ast, res, types, ..
+    } = checked;
+    let mut compiler = Compiler {
+        res,
+        types,
+        bodies: Vec::new(),
+        functions: HashMap::new(),
+        interfaces: HashMap::new(),
+        dispatches: Vec::new(),
+        nestings: 0,
+        current: Current::default(),
+    };
+    compiler.collect(ast);
+
+    // a stub's implementations are any functions of the same name - whichever fits the
+    // arguments given best is picked at runtime
+    let mut interfaces = Vec::new();
+    for (id, def) in res.defs.iter().enumerate() {
+        if def.kind != DefKind::Stub || !types.sigs.contains_key(&DefId(id)) {
+            continue;
+        }
+        let arity = types.sigs[&DefId(id)].params.len();
+        let candidates = res
+            .defs
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| candidate.kind == DefKind::Fn && candidate.name == def.name)
+            .filter_map(|(candidate, _)| {
+                let function = *compiler.functions.get(&DefId(candidate))?;
+                let sig = types.sigs.get(&DefId(candidate))?;
+                (sig.params.len() == arity).then(|| Candidate {
+                    function,
+                    params: sig
+                        .params
+                        .iter()
+                        .map(|param| compiler.lower(param))
+                        .collect(),
+                })
+            })
+            .collect();
+        compiler
+            .interfaces
+            .insert(DefId(id), interfaces.len() as u32);
+        interfaces.push(Interface {
+            stub: DefId(id),
+            name: def.name.clone(),
+            candidates,
+        });
+    }
+
+    let mut unions = types
+        .decls
+        .iter()
+        .filter_map(|(id, decl)| match decl {
+            TypeDecl::Union(members) => Some(Union {
+                id: *id,
+                members: members
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), compiler.lower(ty)))
+                    .collect(),
+            }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    unions.sort_by_key(|union| union.id.0);
+
+    let mut functions = vec![compiler.function(None, "top level", Span::default(), &[], ast)];
+    for (id, stmt) in std::mem::take(&mut compiler.bodies) {
+        let Stmt::Fn {
+            name,
+            parameters,
+            body,
+            ..
+        } = stmt
+        else {
+            unreachable!("compiler: collected a non-function");
+        };
+        let function = compiler.function(Some(id), &name.spanned, name.span, parameters, body);
+        functions.push(function);
+    }
+
+    Program {
+        init: 0,
+        main: interp::main_fn(checked).map(|(main, _)| compiler.functions[&main]),
+        functions,
+        interfaces,
+        unions,
+        dispatches: compiler.dispatches,
+    }
+}
@@ -0,0 +1,305 @@
+use crate::driver;
+use crate::intrinsics::Intrinsic;
+use crate::overload;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// `ty`, with every mention of the generic `generic` replaced by `with`
+fn substitute(ty: &Ty, generic: DefId, with: &Ty) -> Ty {
+    match ty {
+        Ty::Named(id) if *id == generic => with.clone(),
+        Ty::Pointer(to) => Ty::Pointer(Box::new(substitute(to, generic, with))),
+        Ty::FnPtr { args, ret } => Ty::FnPtr {
+            args: args
+                .iter()
+                .map(|arg| substitute(arg, generic, with))
+                .collect(),
+            ret: Box::new(substitute(ret, generic, with)),
+        },
+        ty => ty.clone(),
+    }
+}
+
+fn mentions(ty: &Ty, generic: DefId) -> bool {
+    match ty {
+        Ty::Named(id) => *id == generic,
+        Ty::Pointer(to) => mentions(to, generic),
+        Ty::FnPtr { args, ret } => {
+            args.iter().any(|arg| mentions(arg, generic)) || mentions(ret, generic)
+        }
+        _ => false,
+    }
+}
+
+struct Checker<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    /// the stubs taking each generic as a parameter - its "interface"
+    required: HashMap<DefId, Vec<DefId>>,
+    /// every `fn` (but not stub), by name
+    fns: HashMap<&'a str, Vec<DefId>>,
+}
+
+impl<'a> Checker<'a> {
+    /// the generics `ty` stands for, if it's a generic or an aggregate of them
+    fn generics(&self, ty: &Ty) -> Option<Vec<DefId>> {
+        let Ty::Named(id) = ty else {
+            return None;
+        };
+        match self.types.decls.get(id)? {
+            TypeDecl::Generic => Some(vec![*id]),
+            TypeDecl::Aggregate(generics) => Some(generics.clone()),
+            _ => None,
+        }
+    }
+
+    /// checks a value of type `found`, at `span`, can stand in for `expected`
+    fn conforms(&mut self, found: &Ty, expected: &Ty, span: Span) {
+        let Some(generics) = self.generics(expected) else {
+            return;
+        };
+        // a generic passed along as another is checked where its concrete type was chosen
+        if matches!(found, Ty::Never | Ty::Error) || self.generics(found).is_some() {
+            return;
+        }
+
+        for generic in generics {
+            let Some(stubs) = self.required.get(&generic) else {
+                continue;
+            };
+            for stub in stubs {
+                let sig = &self.types.sigs[stub];
+                let params = sig
+                    .params
+                    .iter()
+                    .map(|param| substitute(param, generic, found))
+                    .collect::<Vec<_>>();
+                let name = self.res.def(*stub).name.as_str();
+
+                // an implementation for `found` itself, or one generic over the whole interface
+                let implemented = self.fns.get(name).is_some_and(|fns| {
+                    fns.iter().any(|id| {
+                        self.types
+                            .sigs
+                            .get(id)
+                            .is_some_and(|imp| imp.params == params || imp.params == sig.params)
+                    })
+                });
+                if implemented {
+                    continue;
+                }
+
+                let wanted = format!(
+                    "fn {} {}({})",
+                    substitute(&sig.ret, generic, found).display(self.res),
+                    name,
+                    params
+                        .iter()
+                        .map(|param| param.display(self.res))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let err = SdwErr::from_pos(
+                    TypeErrors::MissingImplementation {
+                        ty: found.display(self.res),
+                        wanted,
+                    },
+                    span,
+                )
+                .with_note(
+                    format!(
+                        "required by `{}`, as `{}` is used as a `{}`",
+                        overload::describe(self.types, self.res, *stub),
+                        found.display(self.res),
+                        expected.display(self.res)
+                    ),
+                    self.res.def(*stub).span,
+                );
+                self.state.errors.push(err);
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+        }
+        if let Some(tail) = &block.tail {
+            self.expr(tail);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { body, .. } => self.block(body),
+            Stmt::Loop { block } => self.block(block),
+            Stmt::Return { expr: Some(expr) }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. }
+            | Stmt::Discard { expr } => self.expr(expr),
+            Stmt::Stub { .. }
+            | Stmt::Type { .. }
+            | Stmt::Label { .. }
+            | Stmt::Goto { .. }
+            | Stmt::Return { expr: None } => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::ObjMember(..) => {}
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Attributed { expr: inner, .. }
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => self.expr(inner),
+            Expr::FnCall(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+                let Some(sig) = self
+                    .types
+                    .call(expr.span)
+                    .and_then(|id| self.types.sigs.get(&id))
+                else {
+                    return;
+                };
+                for (arg, param) in args.iter().zip(&sig.params) {
+                    if let Some(found) = self.types.expr(arg.span) {
+                        self.conforms(found, param, arg.span);
+                    }
+                }
+            }
+            Expr::BiOp(left, _, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.expr(condition);
+                self.block(&then.spanned);
+                for (condition, block) in elifs {
+                    self.expr(condition);
+                    self.block(&block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    self.block(&r#else.spanned);
+                }
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::StructLit { fields, .. } => {
+                let values = match fields {
+                    StructLitFields::Positional(values) => values.iter().collect::<Vec<_>>(),
+                    StructLitFields::Named(values) => {
+                        values.iter().map(|(_, value)| value).collect()
+                    }
+                };
+                for value in &values {
+                    self.expr(value);
+                }
+
+                let Some(members) = self
+                    .types
+                    .expr(expr.span)
+                    .and_then(|ty| self.types.members(ty))
+                else {
+                    return;
+                };
+                let member_tys = match fields {
+                    StructLitFields::Positional(_) => {
+                        members.iter().map(|(_, ty)| Some(ty)).collect::<Vec<_>>()
+                    }
+                    StructLitFields::Named(values) => values
+                        .iter()
+                        .map(|(name, _)| {
+                            members
+                                .iter()
+                                .find(|(member, _)| *member == name.spanned)
+                                .map(|(_, ty)| ty)
+                        })
+                        .collect(),
+                };
+                for (value, member) in values.iter().zip(member_tys) {
+                    if let (Some(found), Some(member)) = (self.types.expr(value.span), member) {
+                        self.conforms(found, member, value.span);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// checks generics are only stood in for by types implementing their interface.
+///
+/// a generic's interface is every stub taking it as a parameter (`type Print; fn print(Print);`).
+/// wherever a concrete type is passed as an argument or member of a generic type, each of those
+/// stubs needs an implementation for it - either a `fn` taking the concrete type in place of the
+/// generic, or a default `fn` taking the generic itself. an intrinsic stub can't be implemented
+/// at all, as that would take the place of what the backends provide (though the file's `fn`s
+/// may shadow `core`'s).
+pub fn check(state: &mut State, root: &Block, res: &Resolutions, types: &Types) {
+    let mut required: HashMap<DefId, Vec<DefId>> = HashMap::new();
+    let mut fns: HashMap<&str, Vec<DefId>> = HashMap::new();
+    let mut intrinsics = Vec::new();
+    for (idx, def) in res.defs.iter().enumerate() {
+        let id = DefId(idx);
+        let Some(sig) = types.sigs.get(&id) else {
+            continue;
+        };
+        match def.kind {
+            DefKind::Fn => fns.entry(&def.name).or_default().push(id),
+            DefKind::Stub => {
+                if Intrinsic::of(res, types, id).is_some() {
+                    intrinsics.push(id);
+                }
+                for (generic, decl) in &types.decls {
+                    if matches!(decl, TypeDecl::Generic)
+                        && sig.params.iter().any(|param| mentions(param, *generic))
+                    {
+                        required.entry(*generic).or_default().push(id);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for stub in intrinsics {
+        let def = res.def(stub);
+        for id in fns.get(def.name.as_str()).into_iter().flatten() {
+            if types.sigs[id].params != types.sigs[&stub].params
+                || driver::in_core(res.def(*id).span) != driver::in_core(def.span)
+            {
+                continue;
+            }
+            let err = SdwErr::from_pos(
+                TypeErrors::ImplementedIntrinsic(def.name.clone()),
+                res.def(*id).span,
+            )
+            .with_note("the intrinsic is declared here", def.span);
+            state.errors.push(err);
+        }
+    }
+
+    let mut checker = Checker {
+        state,
+        res,
+        types,
+        required,
+        fns,
+    };
+    checker.block(root);
+}
@@ -0,0 +1,502 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// the value of a constant expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Unt(u64),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn ty(self) -> Ty {
+        match self {
+            Value::Int(_) => Ty::INT,
+            Value::Unt(_) => Ty::UNT,
+            Value::Bool(_) => Ty::BOOL,
+        }
+    }
+
+    /// widened, so `int`s & `unt`s can be compared
+    pub fn integer(self) -> Option<i128> {
+        match self {
+            Value::Int(int) => Some(int.into()),
+            Value::Unt(unt) => Some(unt.into()),
+            Value::Bool(_) => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Unt(_) => "unt",
+            Value::Bool(_) => "bool",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(int) => write!(f, "{int}"),
+            Value::Unt(unt) => write!(f, "{unt}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    types: Option<&'a Types>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn eval(&self, expr: &Spanned<Expr>) -> Result<Option<Value>> {
+        macro_rules! operand {
+            ($expr:expr) => {
+                match self.eval($expr)? {
+                    Some(value) => value,
+                    None => return Ok(None),
+                }
+            };
+        }
+
+        Ok(Some(match &expr.spanned {
+            Expr::IntLiteral(int) => match self.types.and_then(|types| types.expr(expr.span)) {
+                Some(Ty::Prim(PrimType::Unt)) => Value::Unt(*int as u64),
+                _ => Value::Int(*int),
+            },
+            Expr::BoolLiteral(bool) => Value::Bool(*bool),
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => operand!(inner),
+            Expr::UnaryPos(inner) => operand!(inner),
+            Expr::UnaryNeg(inner) => match operand!(inner) {
+                Value::Int(int) => match int.checked_neg() {
+                    Some(int) => Value::Int(int),
+                    None => return overflow(format!("-{int}"), "int", expr.span),
+                },
+                Value::Unt(0) => Value::Unt(0),
+                Value::Unt(unt) => return overflow(format!("-{unt}"), "unt", expr.span),
+                Value::Bool(_) => return Ok(None),
+            },
+            Expr::UnaryNot(inner) => match operand!(inner) {
+                Value::Bool(bool) => Value::Bool(!bool),
+                _ => return Ok(None),
+            },
+            Expr::BiOp(left, op, right) => {
+                let left_value = operand!(left);
+                // `||` & `&&` only evaluate their right side when they need to
+                match (op, left_value) {
+                    (BiOps::LogOr, Value::Bool(true)) => return Ok(Some(Value::Bool(true))),
+                    (BiOps::LogAnd, Value::Bool(false)) => return Ok(Some(Value::Bool(false))),
+                    _ => {}
+                }
+                let right_value = operand!(right);
+                return biop(left_value, *op, right_value, expr.span);
+            }
+            Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::FnCall(..)
+            | Expr::Referal(_)
+            | Expr::Indir(_)
+            | Expr::ObjMember(..)
+            | Expr::Cond { .. }
+            | Expr::Block(_)
+            | Expr::StructLit { .. } => return Ok(None),
+        }))
+    }
+}
+
+fn overflow(expr: String, ty: &str, span: Span) -> Result<Option<Value>> {
+    Err(SdwErr::from_pos(
+        ConstErrors::Overflow {
+            expr,
+            ty: ty.to_owned(),
+        },
+        span,
+    ))
+}
+
+fn biop(left: Value, op: BiOps, right: Value, span: Span) -> Result<Option<Value>> {
+    let shown = || format!("{left} {} {right}", op.symbol());
+    let overflowed = || overflow(shown(), left.name(), span);
+
+    match op {
+        BiOps::Eq => return Ok(Some(Value::Bool(left == right))),
+        BiOps::NEq => return Ok(Some(Value::Bool(left != right))),
+        _ => {}
+    }
+
+    let value = match (left, right) {
+        (Value::Bool(left), Value::Bool(right)) => match op {
+            BiOps::LogOr => Value::Bool(left || right),
+            BiOps::LogAnd => Value::Bool(left && right),
+            _ => return Ok(None),
+        },
+        (Value::Int(left), Value::Int(right)) => {
+            let result = match op {
+                BiOps::Add => left.checked_add(right),
+                BiOps::Sub => left.checked_sub(right),
+                BiOps::Mul => left.checked_mul(right),
+                BiOps::Div | BiOps::Mod if right == 0 => {
+                    return Err(SdwErr::from_pos(ConstErrors::DivByZero(shown()), span));
+                }
+                BiOps::Div => left.checked_div(right),
+                BiOps::Mod => left.checked_rem(right),
+                BiOps::BitOr => Some(left | right),
+                BiOps::BitAnd => Some(left & right),
+                BiOps::BitXor => Some(left ^ right),
+                BiOps::BitLShift | BiOps::BitRshift => {
+                    let Some(amount) = u32::try_from(right).ok().filter(|amount| *amount < 64)
+                    else {
+                        return Err(SdwErr::from_pos(ConstErrors::OversizedShift(shown()), span));
+                    };
+                    // bits shifted out are lost, as at runtime
+                    Some(match op {
+                        BiOps::BitLShift => left << amount,
+                        _ => left >> amount,
+                    })
+                }
+                BiOps::Gr => return Ok(Some(Value::Bool(left > right))),
+                BiOps::Ls => return Ok(Some(Value::Bool(left < right))),
+                BiOps::GrEq => return Ok(Some(Value::Bool(left >= right))),
+                BiOps::LsEq => return Ok(Some(Value::Bool(left <= right))),
+                _ => return Ok(None),
+            };
+            match result {
+                Some(result) => Value::Int(result),
+                None => return overflowed(),
+            }
+        }
+        (Value::Unt(left), Value::Unt(right)) => {
+            let result = match op {
+                BiOps::Add => left.checked_add(right),
+                BiOps::Sub => left.checked_sub(right),
+                BiOps::Mul => left.checked_mul(right),
+                BiOps::Div | BiOps::Mod if right == 0 => {
+                    return Err(SdwErr::from_pos(ConstErrors::DivByZero(shown()), span));
+                }
+                BiOps::Div => left.checked_div(right),
+                BiOps::Mod => left.checked_rem(right),
+                BiOps::BitOr => Some(left | right),
+                BiOps::BitAnd => Some(left & right),
+                BiOps::BitXor => Some(left ^ right),
+                BiOps::BitLShift | BiOps::BitRshift => {
+                    if right >= 64 {
+                        return Err(SdwErr::from_pos(ConstErrors::OversizedShift(shown()), span));
+                    }
+                    Some(match op {
+                        BiOps::BitLShift => left << right,
+                        _ => left >> right,
+                    })
+                }
+                BiOps::Gr => return Ok(Some(Value::Bool(left > right))),
+                BiOps::Ls => return Ok(Some(Value::Bool(left < right))),
+                BiOps::GrEq => return Ok(Some(Value::Bool(left >= right))),
+                BiOps::LsEq => return Ok(Some(Value::Bool(left <= right))),
+                _ => return Ok(None),
+            };
+            match result {
+                Some(result) => Value::Unt(result),
+                None => return overflowed(),
+            }
+        }
+        // mismatched operands, which type checking has already reported
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// evaluates `expr`, if it is made up of only literals & the operators upon them.
+/// `types` tells `int` literals from `unt` ones; without them (as before type checking, for
+/// `#if`) every integer is an `int`.
+///
+/// `Ok(None)` means the expression isn't constant. `~` (as a binary operator) has no settled
+/// meaning yet, so is never folded.
+pub fn eval(expr: &Spanned<Expr>, types: Option<&Types>) -> Result<Option<Value>> {
+    Evaluator { types }.eval(expr)
+}
+
+/// an `#if` condition - which must be a constant `bool`. errors are raised into `state`, and
+/// the condition taken not to hold.
+pub fn condition(state: &mut State, expr: &Spanned<Expr>) -> bool {
+    match eval(expr, None) {
+        Ok(Some(Value::Bool(bool))) => bool,
+        Ok(Some(value)) => {
+            let err = SdwErr::from_pos(
+                ConstErrors::ConditionNotBool(value.name().into()),
+                expr.span,
+            );
+            state.errors.push(err);
+            false
+        }
+        Ok(None) => {
+            state
+                .errors
+                .push(SdwErr::from_pos(ConstErrors::NotConstant, expr.span));
+            false
+        }
+        Err(err) => {
+            state.errors.push(err);
+            false
+        }
+    }
+}
+
+/// the value of every constant expression (the largest, where they nest)
+#[derive(Default)]
+pub struct Consts {
+    values: HashMap<Span, Value>,
+}
+
+impl Consts {
+    pub fn get(&self, span: Span) -> Option<Value> {
+        self.values.get(&span).copied()
+    }
+}
+
+/// `#[ num_in_range lo hi ]`, once its arguments have been checked
+struct Range {
+    lo: i64,
+    hi: i64,
+    span: Span,
+}
+
+impl Range {
+    fn of(attr: &Spanned<Attribute>) -> std::result::Result<Range, SdwErr> {
+        match attr.spanned.args.as_slice() {
+            [lo, hi] => match (&lo.spanned, &hi.spanned) {
+                (AttrArg::Int(lo), AttrArg::Int(hi)) if lo <= hi => Ok(Range {
+                    lo: *lo,
+                    hi: *hi,
+                    span: attr.span,
+                }),
+                _ => Err(SdwErr::from_pos(ConstErrors::RangeArgs, attr.span)),
+            },
+            _ => Err(SdwErr::from_pos(ConstErrors::RangeArgs, attr.span)),
+        }
+    }
+}
+
+struct Folder<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    consts: Consts,
+    /// the range the enclosing function is declared to return within
+    returns: Option<Range>,
+}
+
+impl<'a> Folder<'a> {
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+        }
+        if let Some(tail) = &block.tail {
+            self.expr(tail);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { attrs, body, .. } => {
+                let range = attrs
+                    .iter()
+                    .find(|attr| attr.spanned.name.spanned == "num_in_range")
+                    .and_then(|attr| match Range::of(attr) {
+                        Ok(range) => Some(range),
+                        Err(err) => {
+                            self.state.errors.push(err);
+                            None
+                        }
+                    });
+                let outer = std::mem::replace(&mut self.returns, range);
+                self.block(body);
+                if let Some(tail) = &body.tail {
+                    self.returned(tail);
+                }
+                self.returns = outer;
+            }
+            Stmt::Loop { block } => self.block(block),
+            Stmt::Return { expr: Some(expr) } => {
+                self.expr(expr);
+                self.returned(expr);
+            }
+            Stmt::Discard { expr }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. } => self.expr(expr),
+            Stmt::Stub { .. }
+            | Stmt::Type { .. }
+            | Stmt::Label { .. }
+            | Stmt::Goto { .. }
+            | Stmt::Return { expr: None } => {}
+        }
+    }
+
+    /// checks a constant value returned from a `#[ num_in_range ]` function
+    fn returned(&mut self, expr: &Spanned<Expr>) {
+        let (Some(range), Some(value)) = (&self.returns, self.consts.get(expr.span)) else {
+            return;
+        };
+        if let Some(err) = out_of_range(value, range, expr.span) {
+            self.state.errors.push(err);
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        if let Expr::Attributed { attr, expr: inner } = &expr.spanned {
+            self.expr(inner);
+            if attr.spanned.name.spanned == "num_in_range" {
+                self.in_range(attr, inner);
+            }
+            return;
+        }
+
+        match eval(expr, Some(self.types)) {
+            Ok(Some(value)) => {
+                self.consts.values.insert(expr.span, value);
+                self.attributed_within(expr);
+                return;
+            }
+            Err(err) => {
+                self.state.errors.push(err);
+                return;
+            }
+            Ok(None) => {}
+        }
+
+        match &expr.spanned {
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::ObjMember(..)
+            | Expr::Attributed { .. } => {}
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => self.expr(inner),
+            Expr::FnCall(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::BiOp(left, _, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.expr(condition);
+                self.block(&then.spanned);
+                for (condition, block) in elifs {
+                    self.expr(condition);
+                    self.block(&block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    self.block(&r#else.spanned);
+                }
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::StructLit { fields, .. } => match fields {
+                StructLitFields::Positional(values) => {
+                    for value in values {
+                        self.expr(value);
+                    }
+                }
+                StructLitFields::Named(members) => {
+                    for (_, value) in members {
+                        self.expr(value);
+                    }
+                }
+            },
+        }
+    }
+
+    /// attributes within a constant expression, which is folded as a whole
+    fn attributed_within(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::Attributed { attr, expr: inner } => {
+                if attr.spanned.name.spanned == "num_in_range" {
+                    self.in_range(attr, inner);
+                }
+                self.attributed_within(inner);
+            }
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner) => self.attributed_within(inner),
+            Expr::BiOp(left, _, right) => {
+                self.attributed_within(left);
+                self.attributed_within(right);
+            }
+            _ => {}
+        }
+    }
+
+    /// `(#[ num_in_range lo hi ] expr)` - only checked where `expr` is constant
+    fn in_range(&mut self, attr: &Spanned<Attribute>, expr: &Spanned<Expr>) {
+        let range = match Range::of(attr) {
+            Ok(range) => range,
+            Err(err) => return self.state.errors.push(err),
+        };
+        match self.types.expr(expr.span) {
+            Some(ty) if ty.is_integer() || ty.is_wildcard() => {}
+            Some(ty) => {
+                let err = SdwErr::from_pos(
+                    ConstErrors::RangeOfNonInteger(ty.display(self.res)),
+                    expr.span,
+                );
+                return self.state.errors.push(err);
+            }
+            None => return,
+        }
+        if let Some(err) = eval(expr, Some(self.types))
+            .ok()
+            .flatten()
+            .and_then(|value| out_of_range(value, &range, expr.span))
+        {
+            self.state.errors.push(err);
+        }
+    }
+}
+
+fn out_of_range(value: Value, range: &Range, span: Span) -> Option<SdwErr> {
+    let int = value.integer()?;
+    if (i128::from(range.lo)..=i128::from(range.hi)).contains(&int) {
+        return None;
+    }
+    Some(
+        SdwErr::from_pos(
+            ConstErrors::OutOfRange {
+                value: value.to_string(),
+                range: format!("{}..={}", range.lo, range.hi),
+            },
+            span,
+        )
+        .with_note("the range is given here", range.span),
+    )
+}
+
+/// folds every constant expression, raising overflow, division by zero & oversized shifts as
+/// errors, and checks constants against any `#[ num_in_range lo hi ]` they're marked with
+/// (either inline, or on the function returning them).
+pub fn check(state: &mut State, root: &Block, res: &Resolutions, types: &Types) -> Consts {
+    let mut folder = Folder {
+        state,
+        res,
+        types,
+        consts: Consts::default(),
+        returns: None,
+    };
+    folder.block(root);
+    folder.consts
+}
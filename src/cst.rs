@@ -0,0 +1,943 @@
+use crate::consteval;
+use crate::lexer::{self, Token, TokenKind};
+use crate::parser;
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// what a node of the tree is. statements, expressions & bounds are named after the ast's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// the whole source
+    Root,
+    /// `{ .. }` - a body, a group of an `#if`, or an expression
+    Block,
+    Fn,
+    Stub,
+    Loop,
+    Label,
+    Goto,
+    Return,
+    Let,
+    Assign,
+    Type,
+    Discard,
+    /// `#if .. { .. } #else { .. }` - every group, not just the one chosen
+    Conditional,
+    /// `#[ name arg* ]`
+    Attribute,
+    /// a primitive or an alias - `int`, `Point`
+    NamedBound,
+    StructBound,
+    UnionBound,
+    PointerBound,
+    FnPtrBound,
+    AggregateBound,
+    Literal,
+    Name,
+    /// `-x`, `!x`, `&x`, `*x` & `+x`
+    Prefix,
+    Paren,
+    Call,
+    Binary,
+    Member,
+    If,
+    StructLit,
+    Attributed,
+    /// what didn't parse
+    Error,
+}
+
+impl NodeKind {
+    pub(crate) fn stmt(stmt: &Stmt) -> NodeKind {
+        match stmt {
+            Stmt::Fn { .. } => NodeKind::Fn,
+            Stmt::Stub { .. } => NodeKind::Stub,
+            Stmt::Loop { .. } => NodeKind::Loop,
+            Stmt::Label { .. } => NodeKind::Label,
+            Stmt::Goto { .. } => NodeKind::Goto,
+            Stmt::Return { .. } => NodeKind::Return,
+            Stmt::VarDec { .. } => NodeKind::Let,
+            Stmt::VarRes { .. } => NodeKind::Assign,
+            Stmt::Type { .. } => NodeKind::Type,
+            Stmt::Discard { .. } => NodeKind::Discard,
+        }
+    }
+
+    pub(crate) fn expr(expr: &Expr) -> NodeKind {
+        match expr {
+            Expr::IntLiteral(_) | Expr::BoolLiteral(_) | Expr::StrLiteral(_) => NodeKind::Literal,
+            Expr::Variable(_) => NodeKind::Name,
+            Expr::UnaryNot(_)
+            | Expr::UnaryNeg(_)
+            | Expr::UnaryPos(_)
+            | Expr::Referal(_)
+            | Expr::Indir(_) => NodeKind::Prefix,
+            Expr::SubExpr(_) => NodeKind::Paren,
+            Expr::FnCall(..) => NodeKind::Call,
+            Expr::BiOp(..) => NodeKind::Binary,
+            Expr::ObjMember(..) => NodeKind::Member,
+            Expr::Cond { .. } => NodeKind::If,
+            Expr::Block(_) => NodeKind::Block,
+            Expr::StructLit { .. } => NodeKind::StructLit,
+            Expr::Attributed { .. } => NodeKind::Attributed,
+        }
+    }
+
+    pub(crate) fn bound(bound: &Bound) -> NodeKind {
+        match bound {
+            Bound::Prim(_) | Bound::Alias(_) | Bound::Generic => NodeKind::NamedBound,
+            Bound::Struct(_) => NodeKind::StructBound,
+            Bound::Union(_) => NodeKind::UnionBound,
+            Bound::Pointer(_) => NodeKind::PointerBound,
+            Bound::FnPtr { .. } => NodeKind::FnPtrBound,
+            Bound::Aggregate(_) => NodeKind::AggregateBound,
+        }
+    }
+
+    pub fn is_stmt(self) -> bool {
+        matches!(
+            self,
+            NodeKind::Fn
+                | NodeKind::Stub
+                | NodeKind::Loop
+                | NodeKind::Label
+                | NodeKind::Goto
+                | NodeKind::Return
+                | NodeKind::Let
+                | NodeKind::Assign
+                | NodeKind::Type
+                | NodeKind::Discard
+        )
+    }
+
+    pub fn is_expr(self) -> bool {
+        matches!(
+            self,
+            NodeKind::Block
+                | NodeKind::Literal
+                | NodeKind::Name
+                | NodeKind::Prefix
+                | NodeKind::Paren
+                | NodeKind::Call
+                | NodeKind::Binary
+                | NodeKind::Member
+                | NodeKind::If
+                | NodeKind::StructLit
+                | NodeKind::Attributed
+        )
+    }
+
+    pub fn is_bound(self) -> bool {
+        matches!(
+            self,
+            NodeKind::NamedBound
+                | NodeKind::StructBound
+                | NodeKind::UnionBound
+                | NodeKind::PointerBound
+                | NodeKind::FnPtrBound
+                | NodeKind::AggregateBound
+        )
+    }
+}
+
+/// what the parser consumed, in order. `Start` & `Finish` bracket the lexemes of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    Start(NodeKind),
+    /// the next lexeme
+    Token,
+    Finish,
+}
+
+/// a node of the green tree: what it is & what's in it, but not where it is. identical
+/// subtrees may be shared.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    kind: NodeKind,
+    /// in bytes
+    text_len: usize,
+    children: Vec<GreenElement>,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> GreenNode {
+        let text_len = children.iter().map(GreenElement::text_len).sum();
+        GreenNode {
+            kind,
+            text_len,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// the length of its text, in bytes
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    kind: TokenKind,
+    text: String,
+}
+
+impl GreenToken {
+    pub fn new(kind: TokenKind, text: String) -> GreenToken {
+        GreenToken { kind, text }
+    }
+
+    pub fn kind(&self) -> &TokenKind {
+        &self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn text_len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.text_len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+/// a node of the red tree: a green node, along with where it is & what its parent is.
+/// these are made as the tree is walked, & are cheap to clone.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Rc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// of its text, in bytes from the start of the root's
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Rc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Rc<GreenNode>) -> SyntaxNode {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// itself, its parent, its parent's parent & so on up to the root
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        std::iter::successors(Some(self.clone()), |node| node.parent().cloned())
+    }
+
+    /// where its text is, in bytes
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.text_len
+    }
+
+    pub fn text(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0.green.children.iter().map(move |child| {
+            let at = offset;
+            offset += child.text_len();
+            match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    offset: at,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                    green: green.clone(),
+                    parent: self.clone(),
+                    offset: at,
+                }),
+            }
+        })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// itself & every node within it, parents before their children
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    /// every token within it, in order
+    pub fn descendant_tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.descendant_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// the token whose text covers `offset`
+    pub fn token_at(&self, offset: usize) -> Option<SyntaxToken> {
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) if node.text_range().contains(&offset) => {
+                    return node.token_at(offset);
+                }
+                SyntaxElement::Token(token) if token.text_range().contains(&offset) => {
+                    return Some(token);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// the tokens directly within it, bar trivia
+    fn parts(&self) -> Vec<SyntaxElement> {
+        self.children_with_tokens()
+            .filter(
+                |child| !matches!(child, SyntaxElement::Token(token) if token.kind().is_trivia()),
+            )
+            .collect()
+    }
+
+    fn child(&self, pred: impl Fn(NodeKind) -> bool) -> Option<SyntaxNode> {
+        self.children().find(|child| pred(child.kind()))
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> &TokenKind {
+        &self.green.kind
+    }
+
+    /// what the token lexed as, unless it's trivia
+    pub fn lexeme(&self) -> Option<&LexemeType> {
+        match &self.green.kind {
+            TokenKind::Lexeme(r#type) => Some(r#type),
+            _ => None,
+        }
+    }
+
+    pub fn green(&self) -> &Rc<GreenToken> {
+        &self.green
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &SyntaxNode) -> bool {
+        Rc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl PartialEq for SyntaxToken {
+    fn eq(&self, other: &SyntaxToken) -> bool {
+        Rc::ptr_eq(&self.green, &other.green) && self.offset == other.offset
+    }
+}
+
+impl Eq for SyntaxToken {}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.descendant_tokens() {
+            f.write_str(token.text())?;
+        }
+        Ok(())
+    }
+}
+
+/// the tree, a line per node or token - `Fn@0..16`, `Idn("main")@8..12 "main"`
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let depth = self.ancestors().count() - 1;
+        let range = self.text_range();
+        writeln!(
+            f,
+            "{:depth$}{:?}@{}..{}",
+            "",
+            self.kind(),
+            range.start,
+            range.end,
+            depth = depth * 2
+        )?;
+        for child in self.children_with_tokens() {
+            match child {
+                SyntaxElement::Node(node) => write!(f, "{node:?}")?,
+                SyntaxElement::Token(token) => {
+                    writeln!(f, "{:depth$}{token:?}", "", depth = depth * 2 + 2)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.text_range();
+        match self.kind() {
+            TokenKind::Lexeme(r#type) => write!(f, "{type:?}")?,
+            kind => write!(f, "{kind:?}")?,
+        }
+        write!(f, "@{}..{} {:?}", range.start, range.end, self.text())
+    }
+}
+
+/// builds a green tree, sharing identical tokens
+#[derive(Default)]
+struct Builder {
+    tokens: HashMap<(TokenKind, String), Rc<GreenToken>>,
+    /// the nodes yet to be finished, & their children so far
+    parents: Vec<(NodeKind, Vec<GreenElement>)>,
+}
+
+impl Builder {
+    fn token(&mut self, token: Token) {
+        let green = self
+            .tokens
+            .entry((token.kind, token.text))
+            .or_insert_with_key(|(kind, text)| Rc::new(GreenToken::new(kind.clone(), text.clone())))
+            .clone();
+        self.push(GreenElement::Token(green));
+    }
+
+    fn push(&mut self, element: GreenElement) {
+        let (_, children) = self.parents.last_mut().expect("cst: no node to add to");
+        children.push(element);
+    }
+
+    fn finish(&mut self) -> Rc<GreenNode> {
+        let (kind, children) = self.parents.pop().expect("cst: unbalanced events");
+        Rc::new(GreenNode::new(kind, children))
+    }
+}
+
+fn build(events: Vec<Event>, tokens: Vec<Token>) -> Rc<GreenNode> {
+    let mut builder = Builder {
+        parents: vec![(NodeKind::Root, Vec::new())],
+        ..Default::default()
+    };
+    let mut tokens = tokens.into_iter().peekable();
+    for event in events {
+        // trivia goes as far up the tree as it can - between nodes, rather than in them
+        if event != Event::Finish {
+            while let Some(token) = tokens.next_if(|token| token.kind.is_trivia()) {
+                builder.token(token);
+            }
+        }
+        match event {
+            Event::Start(kind) => builder.parents.push((kind, Vec::new())),
+            Event::Token => {
+                if let Some(token) = tokens.next() {
+                    builder.token(token);
+                }
+            }
+            Event::Finish => {
+                let node = builder.finish();
+                builder.push(GreenElement::Node(node));
+            }
+        }
+    }
+
+    // trailing trivia, & anything after where the parser gave up
+    for token in tokens {
+        builder.token(token);
+    }
+    builder.finish()
+}
+
+/// the lossless tree of `source`, whose text is always exactly `source`. it's built even if
+/// `source` doesn't lex or parse (the errors are left in `state`), with whatever didn't parse
+/// in `Error` nodes, or left between nodes.
+pub fn parse(state: &mut State, source: &str) -> SyntaxNode {
+    let tokens = lexer::lex_lossless(state, source);
+    let lexemes = tokens.iter().filter_map(Token::lexeme).collect();
+    let (block, events) = parser::parse_events(state, lexemes);
+    if let Err(err) = block {
+        state.errors.push(err);
+    }
+    SyntaxNode::new_root(build(events, tokens))
+}
+
+/// the ast of a tree from `parse` - as `parser::parse` gives it, bar the statements which
+/// didn't parse. `#if` conditions are evaluated again, so their errors are raised again.
+pub fn lower(state: &mut State, root: &SyntaxNode) -> Block {
+    let text = root.text();
+    let mut lines = vec![0];
+    lines.extend(text.match_indices('\n').map(|(at, _)| at + 1));
+    let mut lowering = Lowering { state, text, lines };
+    lowering.block(root)
+}
+
+struct Lowering<'a> {
+    state: &'a mut State,
+    text: String,
+    /// where each line starts
+    lines: Vec<usize>,
+}
+
+impl Lowering<'_> {
+    /// the span the lexer would give `range`
+    fn span(&self, range: Range<usize>) -> Span {
+        let (sline, scol) = self.position(range.start);
+        let (eline, ecol) = self.position(range.end);
+        Span {
+            sline,
+            eline: eline + 1,
+            scol,
+            ecol,
+        }
+    }
+
+    /// line (0-indexed) & column (1-indexed, in characters) of `offset`
+    fn position(&self, offset: usize) -> (SpanInt, SpanInt) {
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+        let col = self.text[self.lines[line]..offset].chars().count() + 1;
+        (line as SpanInt, col as SpanInt)
+    }
+
+    fn idn(&self, element: &SyntaxElement) -> Option<Spanned<String>> {
+        match element {
+            SyntaxElement::Token(token) => match token.lexeme()? {
+                LexemeType::Idn(idn) => {
+                    Some(Spanned::new(idn.clone(), self.span(token.text_range())))
+                }
+                _ => None,
+            },
+            SyntaxElement::Node(_) => None,
+        }
+    }
+
+    fn idns(&self, parts: &[SyntaxElement]) -> Vec<Spanned<String>> {
+        parts.iter().filter_map(|part| self.idn(part)).collect()
+    }
+
+    fn block(&mut self, node: &SyntaxNode) -> Block {
+        let children: Vec<_> = node.children().collect();
+        let mut stmts = Vec::new();
+        let mut tail = None;
+        for (idx, child) in children.iter().enumerate() {
+            // the parser skips past expressions which aren't the last thing, & aren't discarded
+            let last = idx + 1 == children.len();
+            match child.kind() {
+                NodeKind::Conditional => {
+                    let chosen = self.conditional(child);
+                    stmts.extend(chosen.stmts);
+                    if last {
+                        tail = chosen.tail;
+                    }
+                }
+                kind if kind.is_stmt() => stmts.extend(self.stmt(child)),
+                kind if kind.is_expr() && last => tail = self.expr(child).map(Box::new),
+                _ => {}
+            }
+        }
+        Block { stmts, tail }
+    }
+
+    /// the contents of the first group whose condition holds
+    fn conditional(&mut self, node: &SyntaxNode) -> Block {
+        let mut condition = false;
+        let mut holds = true;
+        for part in node.parts() {
+            match part {
+                SyntaxElement::Token(token) => match token.lexeme() {
+                    Some(LexemeType::If) => condition = true,
+                    // a lone `#else` always holds
+                    Some(LexemeType::Else) => holds = true,
+                    _ => {}
+                },
+                SyntaxElement::Node(node) if condition => {
+                    condition = false;
+                    holds = match self.expr(&node) {
+                        Some(expr) => consteval::condition(self.state, &expr),
+                        None => false,
+                    };
+                }
+                SyntaxElement::Node(node) if node.kind() == NodeKind::Block && holds => {
+                    return self.block(&node);
+                }
+                SyntaxElement::Node(_) => {}
+            }
+        }
+        Vec::new().into()
+    }
+
+    fn attributes(&mut self, node: &SyntaxNode) -> Option<Vec<Spanned<Attribute>>> {
+        node.children()
+            .filter(|child| child.kind() == NodeKind::Attribute)
+            .map(|attr| self.attribute(&attr))
+            .collect()
+    }
+
+    /// `#[ name arg* ]`
+    fn attribute(&mut self, node: &SyntaxNode) -> Option<Spanned<Attribute>> {
+        let parts = node.parts();
+        let name = self.idn(parts.get(2)?)?;
+        let mut args = Vec::new();
+        let mut tokens = parts[3..].iter().filter_map(|part| match part {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        });
+        while let Some(token) = tokens.next() {
+            let span = self.span(token.text_range());
+            let arg = match token.lexeme()? {
+                LexemeType::Intlit(int) => AttrArg::Int(*int),
+                LexemeType::Dash => {
+                    let int = tokens.next()?;
+                    let LexemeType::Intlit(magnitude) = int.lexeme()? else {
+                        return None;
+                    };
+                    let span = Span::from_to(span, self.span(int.text_range()));
+                    args.push(Spanned::new(AttrArg::Int(-magnitude), span));
+                    continue;
+                }
+                LexemeType::BoolLit(bool) => AttrArg::Bool(*bool),
+                LexemeType::Idn(idn) => AttrArg::Idn(idn.clone()),
+                LexemeType::RBrack => break,
+                _ => return None,
+            };
+            args.push(Spanned::new(arg, span));
+        }
+        let span = self.span(node.text_range());
+        Some(Spanned::new(Attribute { name, args }, span))
+    }
+
+    fn stmt(&mut self, node: &SyntaxNode) -> Option<Spanned<Stmt>> {
+        let span = self.span(node.text_range());
+        let parts = node.parts();
+        let first_idn = self.idns(&parts).into_iter().next();
+        let stmt = match node.kind() {
+            NodeKind::Fn | NodeKind::Stub => {
+                let attrs = self.attributes(node)?;
+                let paren = parts.iter().position(|part| {
+                    matches!(part, SyntaxElement::Token(token) if token.lexeme() == Some(&LexemeType::LParen))
+                })?;
+                let (return_type, name) = match self.idns(&parts[..paren]).as_slice() {
+                    // stubs may leave out the return type
+                    [name] => (Spanned::new("void".to_owned(), name.span), name.clone()),
+                    [return_type, name] => (return_type.clone(), name.clone()),
+                    _ => return None,
+                };
+                let parameters = self.idns(&parts[paren..]);
+                if node.kind() == NodeKind::Stub {
+                    Stmt::Stub {
+                        attrs,
+                        return_type,
+                        name,
+                        parameters,
+                    }
+                } else {
+                    let parameters = parameters
+                        .chunks_exact(2)
+                        .map(|pair| (pair[0].clone(), pair[1].clone()))
+                        .collect();
+                    let body = node.child(|kind| kind == NodeKind::Block)?;
+                    Stmt::Fn {
+                        attrs,
+                        return_type,
+                        name,
+                        parameters,
+                        body: Box::new(self.block(&body)),
+                    }
+                }
+            }
+            NodeKind::Loop => {
+                let block = node.child(|kind| kind == NodeKind::Block)?;
+                Stmt::Loop {
+                    block: Box::new(self.block(&block)),
+                }
+            }
+            NodeKind::Label => Stmt::Label {
+                name: first_idn.clone()?,
+            },
+            NodeKind::Goto => Stmt::Goto {
+                name: first_idn.clone()?,
+            },
+            NodeKind::Return => Stmt::Return {
+                expr: match node.child(NodeKind::is_expr) {
+                    Some(expr) => Some(self.expr(&expr)?),
+                    None => None,
+                },
+            },
+            NodeKind::Let => Stmt::VarDec {
+                name: first_idn.clone()?,
+                initialiser: self.expr(&node.child(NodeKind::is_expr)?)?,
+            },
+            NodeKind::Assign => Stmt::VarRes {
+                name: first_idn.clone()?,
+                updated: self.expr(&node.child(NodeKind::is_expr)?)?,
+            },
+            NodeKind::Type => {
+                let name = first_idn.clone()?;
+                let attrs = self.attributes(node)?;
+                let bound = match node.child(NodeKind::is_bound) {
+                    Some(bound) => self.bound(&bound)?,
+                    // `type Print;`
+                    None => Spanned::new(Bound::Generic, name.span),
+                };
+                Stmt::Type { attrs, name, bound }
+            }
+            NodeKind::Discard => Stmt::Discard {
+                expr: self.expr(&node.child(NodeKind::is_expr)?)?,
+            },
+            _ => return None,
+        };
+        Some(Spanned::new(stmt, span))
+    }
+
+    fn bound(&mut self, node: &SyntaxNode) -> Option<Spanned<Bound>> {
+        let span = self.span(node.text_range());
+        let parts = node.parts();
+        let bound = match node.kind() {
+            NodeKind::NamedBound => {
+                let name = self.idns(&parts).into_iter().next()?;
+                match PrimType::named(&name.spanned) {
+                    Some(prim) => Bound::Prim(Spanned::new(prim, name.span)),
+                    None => Bound::Alias(name),
+                }
+            }
+            NodeKind::StructBound | NodeKind::UnionBound => {
+                let braced = parts.iter().any(|part| {
+                    matches!(part, SyntaxElement::Token(token) if token.lexeme() == Some(&LexemeType::LBrace))
+                });
+                let members = if braced {
+                    let mut members = Vec::new();
+                    let mut bound = None;
+                    for part in &parts {
+                        match part {
+                            SyntaxElement::Node(node) => bound = Some(self.bound(node)?),
+                            SyntaxElement::Token(_) => {
+                                if let Some(name) = self.idn(part) {
+                                    members.push((bound.take()?, name));
+                                }
+                            }
+                        }
+                    }
+                    Some(members)
+                } else {
+                    None
+                };
+                if node.kind() == NodeKind::StructBound {
+                    Bound::Struct(members)
+                } else {
+                    Bound::Union(members)
+                }
+            }
+            NodeKind::PointerBound => {
+                Bound::Pointer(Box::new(self.bound(&node.child(NodeKind::is_bound)?)?))
+            }
+            NodeKind::FnPtrBound => {
+                // (args) -> return_type
+                let arrow = parts.iter().position(|part| {
+                    matches!(part, SyntaxElement::Token(token) if token.lexeme() == Some(&LexemeType::RAng))
+                })?;
+                Bound::FnPtr {
+                    args: self.idns(&parts[..arrow]),
+                    return_type: self.idns(&parts[arrow..]).into_iter().next()?,
+                }
+            }
+            NodeKind::AggregateBound => {
+                let first = node.child(|kind| kind == NodeKind::NamedBound)?;
+                let mut generics = self.idns(&first.parts());
+                generics.extend(self.idns(&parts));
+                Bound::Aggregate(generics)
+            }
+            _ => return None,
+        };
+        Some(Spanned::new(bound, span))
+    }
+
+    fn sub(&mut self, node: Option<&SyntaxNode>) -> Option<Box<Spanned<Expr>>> {
+        self.expr(node?).map(Box::new)
+    }
+
+    fn expr(&mut self, node: &SyntaxNode) -> Option<Spanned<Expr>> {
+        let span = self.span(node.text_range());
+        let parts = node.parts();
+        let exprs: Vec<_> = node
+            .children()
+            .filter(|child| child.kind().is_expr())
+            .collect();
+        let first = match parts.first()? {
+            SyntaxElement::Token(token) => token.lexeme(),
+            SyntaxElement::Node(_) => None,
+        };
+        let expr = match node.kind() {
+            NodeKind::Literal => match first? {
+                LexemeType::Intlit(int) => Expr::IntLiteral(*int),
+                LexemeType::BoolLit(bool) => Expr::BoolLiteral(*bool),
+                LexemeType::StrLit(str) => Expr::StrLiteral(str.clone()),
+                _ => return None,
+            },
+            NodeKind::Name => Expr::Variable(self.idn(&parts[0])?.spanned),
+            NodeKind::Prefix => {
+                let operand = self.sub(exprs.first())?;
+                match first? {
+                    LexemeType::Cross => Expr::UnaryPos(operand),
+                    LexemeType::Dash => Expr::UnaryNeg(operand),
+                    LexemeType::Bang => Expr::UnaryNot(operand),
+                    LexemeType::Amp => Expr::Referal(operand),
+                    LexemeType::Ast => Expr::Indir(operand),
+                    _ => return None,
+                }
+            }
+            NodeKind::Paren => Expr::SubExpr(self.sub(exprs.first())?),
+            NodeKind::Call => {
+                let name = self.idn(&parts[0])?.spanned;
+                let args = exprs
+                    .iter()
+                    .map(|arg| self.sub(Some(arg)))
+                    .collect::<Option<_>>()?;
+                Expr::FnCall(name, args)
+            }
+            NodeKind::Binary => {
+                // the operands are nodes, so the tokens left are the operator's
+                let symbols: Vec<_> = parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        SyntaxElement::Token(token) => token.lexeme(),
+                        SyntaxElement::Node(_) => None,
+                    })
+                    .collect();
+                let (op, _) = parser::biop(symbols.first()?, symbols.get(1).copied())?;
+                Expr::BiOp(self.sub(exprs.first())?, op, self.sub(exprs.get(1))?)
+            }
+            NodeKind::Member => match self.idns(&parts).as_slice() {
+                [object, member] => Expr::ObjMember(object.clone(), member.clone()),
+                _ => return None,
+            },
+            NodeKind::If => self.cond(&parts)?,
+            NodeKind::Block => Expr::Block(Box::new(self.block(node))),
+            NodeKind::StructLit => {
+                let mut names = self.idns(&parts).into_iter();
+                let ty = names.next()?;
+                let named = parts.iter().any(|part| {
+                    matches!(part, SyntaxElement::Token(token) if token.lexeme() == Some(&LexemeType::Period))
+                });
+                let mut values = Vec::new();
+                for value in &exprs {
+                    values.push(self.sub(Some(value))?);
+                }
+                let fields = if named {
+                    let names: Vec<_> = names.collect();
+                    if names.len() != values.len() {
+                        return None;
+                    }
+                    StructLitFields::Named(names.into_iter().zip(values).collect())
+                } else {
+                    StructLitFields::Positional(values)
+                };
+                Expr::StructLit { ty, fields }
+            }
+            NodeKind::Attributed => Expr::Attributed {
+                attr: self.attribute(&node.child(|kind| kind == NodeKind::Attribute)?)?,
+                expr: self.sub(exprs.first())?,
+            },
+            _ => return None,
+        };
+        Some(Spanned::new(expr, span))
+    }
+
+    /// if [cond] { [then] } [else if [cond] { [elif] }]?* [else { [else] }]?
+    fn cond(&mut self, parts: &[SyntaxElement]) -> Option<Expr> {
+        let mut expecting = false;
+        let mut pending = None;
+        let mut first = None;
+        let mut elifs = Vec::new();
+        let mut r#else = None;
+        for part in parts {
+            match part {
+                SyntaxElement::Token(token) => expecting |= token.lexeme() == Some(&LexemeType::If),
+                // (a condition may itself be a block)
+                SyntaxElement::Node(node) if expecting => {
+                    expecting = false;
+                    pending = Some(Box::new(self.expr(node)?));
+                }
+                SyntaxElement::Node(node) => {
+                    let block = Spanned::new(self.block(node), self.span(node.text_range()));
+                    match (pending.take(), first.is_none()) {
+                        (Some(condition), true) => first = Some((condition, block)),
+                        (Some(condition), false) => elifs.push((condition, block)),
+                        (None, _) => r#else = Some(block),
+                    }
+                }
+            }
+        }
+        let (condition, then) = first?;
+        Some(Expr::Cond {
+            condition,
+            then,
+            elifs,
+            r#else,
+        })
+    }
+}
@@ -0,0 +1,296 @@
+use crate::prelude::*;
+use crate::resolve::BUILTIN_TYPES;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum EdgeKind {
+    /// `type A B;`
+    Alias,
+    /// `type A struct { B member };` - a `B` is stored inside every `A`
+    Member(String),
+}
+
+/// a declaration's dependence on another, which (unless through a pointer) must be known first
+struct Edge {
+    to: DefId,
+    kind: EdgeKind,
+    /// where the other declaration is named
+    span: Span,
+}
+
+struct Decl<'a> {
+    name: &'a Spanned<String>,
+    edges: Vec<Edge>,
+    /// an alias of an undeclared name
+    dangling: Option<&'a Spanned<String>>,
+}
+
+struct Checker<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    decls: HashMap<DefId, Decl<'a>>,
+}
+
+impl<'a> Checker<'a> {
+    fn collect(&mut self, block: &'a Block) {
+        for stmt in &block.stmts {
+            match &stmt.spanned {
+                Stmt::Type { name, bound, .. } => self.declare(name, &bound.spanned),
+                Stmt::Fn { body, .. } => self.collect(body),
+                Stmt::Loop { block } => self.collect(block),
+                Stmt::Discard { expr } => self.collect_expr(expr),
+                _ => {}
+            }
+        }
+        if let Some(tail) = &block.tail {
+            self.collect_expr(tail);
+        }
+    }
+
+    /// types may be declared in any block, including those within expressions
+    fn collect_expr(&mut self, expr: &'a Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::Block(block) => self.collect(block),
+            Expr::Cond {
+                then,
+                elifs,
+                r#else,
+                ..
+            } => {
+                self.collect(&then.spanned);
+                for (_, block) in elifs {
+                    self.collect(&block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    self.collect(&r#else.spanned);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn declare(&mut self, name: &'a Spanned<String>, bound: &'a Bound) {
+        let Some(id) = self.res.def_at(name.span) else {
+            return;
+        };
+        let mut decl = Decl {
+            name,
+            edges: Vec::new(),
+            dangling: None,
+        };
+
+        match bound {
+            Bound::Alias(to) => match self.res.get(to.span) {
+                Some(Res::Def(to_id)) => decl.edges.push(Edge {
+                    to: *to_id,
+                    kind: EdgeKind::Alias,
+                    span: to.span,
+                }),
+                _ if BUILTIN_TYPES.contains(&to.spanned.as_str()) => {}
+                _ => decl.dangling = Some(to),
+            },
+            Bound::Struct(members) | Bound::Union(members) => {
+                self.members(members, &mut decl.edges)
+            }
+            Bound::Generic
+            | Bound::Aggregate(_)
+            | Bound::Prim(_)
+            | Bound::Pointer(_)
+            | Bound::FnPtr { .. } => {}
+        }
+        self.decls.insert(id, decl);
+    }
+
+    /// members stored by value; anything behind a pointer has a fixed size regardless
+    fn members(
+        &self,
+        members: &Option<Vec<(Spanned<Bound>, Spanned<String>)>>,
+        edges: &mut Vec<Edge>,
+    ) {
+        for (bound, name) in members.iter().flatten() {
+            match &bound.spanned {
+                Bound::Alias(to) => {
+                    if let Some(Res::Def(to_id)) = self.res.get(to.span) {
+                        edges.push(Edge {
+                            to: *to_id,
+                            kind: EdgeKind::Member(name.spanned.clone()),
+                            span: to.span,
+                        });
+                    }
+                }
+                Bound::Struct(members) | Bound::Union(members) => self.members(members, edges),
+                _ => {}
+            }
+        }
+    }
+
+    fn name(&self, id: DefId) -> &str {
+        &self.res.def(id).name
+    }
+
+    /// every distinct cycle through the declarations, as the edges taken around it
+    fn cycles(&self) -> Vec<Vec<(DefId, usize)>> {
+        let mut ids = self.decls.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+
+        let mut cycles = Vec::new();
+        let mut seen = HashSet::new();
+        for start in ids {
+            // `(decl, edge)` pairs from `start`, searched depth first
+            let mut path: Vec<(DefId, usize)> = Vec::new();
+            let mut on_path = vec![start];
+            let mut next = vec![0];
+            while let Some(edge) = next.last().copied() {
+                let current = *on_path.last().unwrap();
+                let Some(Edge { to, .. }) = self.decls[&current].edges.get(edge) else {
+                    next.pop();
+                    on_path.pop();
+                    path.pop();
+                    continue;
+                };
+                *next.last_mut().unwrap() += 1;
+
+                // only cycles whose smallest declaration is `start` are reported from here,
+                // so each is found once
+                if *to == start {
+                    let mut cycle = path.clone();
+                    cycle.push((current, edge));
+                    // several members of the same type make for one cycle, not several
+                    let key = cycle.iter().map(|(id, _)| id.0).collect::<Vec<_>>();
+                    if seen.insert(key) {
+                        cycles.push(cycle);
+                    }
+                } else if to.0 > start.0 && !on_path.contains(to) && self.decls.contains_key(to) {
+                    path.push((current, edge));
+                    on_path.push(*to);
+                    next.push(0);
+                }
+            }
+        }
+        cycles
+    }
+
+    fn report_cycle(&mut self, cycle: &[(DefId, usize)]) {
+        let edges = cycle
+            .iter()
+            .map(|(id, edge)| (*id, &self.decls[id].edges[*edge]))
+            .collect::<Vec<_>>();
+        let path = edges
+            .iter()
+            .map(|(id, _)| self.name(*id))
+            .chain([self.name(cycle[0].0)])
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        let start = self.decls[&cycle[0].0].name;
+        let members = edges
+            .iter()
+            .filter(|(_, edge)| edge.kind != EdgeKind::Alias)
+            .count();
+
+        let mut err = if members == 0 {
+            SdwErr::from_pos(TypeErrors::AliasCycle(path), start.span)
+        } else {
+            SdwErr::from_pos(
+                TypeErrors::InfinitelySized {
+                    name: start.spanned.clone(),
+                    path,
+                },
+                start.span,
+            )
+        };
+        for (id, edge) in &edges {
+            let note = match &edge.kind {
+                EdgeKind::Alias => {
+                    format!(
+                        "`{}` is an alias of `{}`",
+                        self.name(*id),
+                        self.name(edge.to)
+                    )
+                }
+                EdgeKind::Member(member) => format!(
+                    "`{}` stores a `{}` (as `{member}`) - use `&{}` to store a pointer instead",
+                    self.name(*id),
+                    self.name(edge.to),
+                    self.name(edge.to)
+                ),
+            };
+            err = err.with_note(note, edge.span);
+        }
+        self.state.errors.push(err);
+    }
+
+    /// reports an alias of an undeclared name, along with the aliases leading to it
+    fn report_dangling(&mut self, id: DefId, to: &Spanned<String>) {
+        let mut chain = vec![id];
+        while let Some(alias) = self
+            .decls
+            .iter()
+            .filter(|(alias, decl)| {
+                !chain.contains(alias)
+                    && decl.edges.iter().any(|edge| {
+                        edge.kind == EdgeKind::Alias && edge.to == chain[chain.len() - 1]
+                    })
+            })
+            .map(|(alias, _)| *alias)
+            .min_by_key(|alias| alias.0)
+        {
+            chain.push(alias);
+        }
+
+        let path = chain
+            .iter()
+            .rev()
+            .map(|id| self.name(*id))
+            .chain([to.spanned.as_str()])
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        let mut err = SdwErr::from_pos(
+            TypeErrors::DanglingAlias {
+                name: to.spanned.clone(),
+                path,
+            },
+            to.span,
+        );
+        for pair in chain.windows(2) {
+            let [to, alias] = pair else {
+                continue;
+            };
+            let decl = &self.decls[alias];
+            err = err.with_note(
+                format!(
+                    "`{}` is an alias of `{}`",
+                    decl.name.spanned,
+                    self.name(*to)
+                ),
+                decl.name.span,
+            );
+        }
+        self.state.errors.push(err);
+    }
+}
+
+/// checks type declarations can all be resolved to a type of finite size: there must be no
+/// alias cycles (`type A B; type B A;`), no struct or union may contain itself by value (only
+/// through a pointer), and aliases must refer to declared types.
+pub fn check(state: &mut State, root: &Block, res: &Resolutions) {
+    let mut checker = Checker {
+        state,
+        res,
+        decls: HashMap::new(),
+    };
+    checker.collect(root);
+
+    for cycle in checker.cycles() {
+        checker.report_cycle(&cycle);
+    }
+
+    let mut dangling = checker
+        .decls
+        .iter()
+        .filter_map(|(id, decl)| Some((*id, decl.dangling?)))
+        .collect::<Vec<_>>();
+    dangling.sort_by_key(|(id, _)| id.0);
+    for (id, to) in dangling {
+        checker.report_dangling(id, to);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::consteval::{self, Consts};
+use crate::prelude::*;
+use crate::{cfg, conform, cycles, labels, lexer, parser, resolve, typeck, unused};
+use std::collections::{HashMap, HashSet};
+
+/// the `core` module, which every file is checked along with
+pub const CORE: &str = include_str!("core.sdw");
+/// the line `core` is lexed as starting on, so its spans are never mistaken for the file's
+pub const CORE_LINE: SpanInt = 1 << 32;
+
+/// whether a span is within `core`, rather than the file
+pub fn in_core(span: Span) -> bool {
+    span.sline >= CORE_LINE
+}
+
+/// the stages a file goes through before it can be run or compiled, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Lex,
+    Parse,
+    Resolve,
+    Labels,
+    TypeDecls,
+    Types,
+    Constants,
+    Interfaces,
+    Unused,
+    ReturnPaths,
+}
+
+impl Stage {
+    /// shown whilst the stage runs
+    pub fn title(self) -> &'static str {
+        match self {
+            Stage::Lex => "lexing file",
+            Stage::Parse => "parsing file",
+            Stage::Resolve => "resolving names",
+            Stage::Labels => "checking control flow",
+            Stage::TypeDecls => "checking type declarations",
+            Stage::Types => "checking types",
+            Stage::Constants => "evaluating constants",
+            Stage::Interfaces => "checking interfaces",
+            Stage::Unused => "checking for unused values",
+            Stage::ReturnPaths => "checking return paths",
+        }
+    }
+
+    /// what was happening when an error was raised ("[..] raised whilst lexing")
+    pub fn process(self) -> &'static str {
+        match self {
+            Stage::Lex => "lexing",
+            Stage::Parse => "parsing",
+            stage => stage.title(),
+        }
+    }
+}
+
+/// hooks for reporting progress through `check`; everything is ignored by default
+pub trait Observer {
+    fn start(&mut self, _stage: Stage) {}
+    /// called after each stage, before stopping on any errors it raised
+    fn finish(&mut self, _stage: Stage, _state: &mut State) {}
+    fn lexed(&mut self, _lexemes: &[Lexeme]) {}
+    fn parsed(&mut self, _ast: &Block) {}
+}
+
+impl Observer for () {}
+
+/// a file which made it through every stage without errors
+pub struct Checked {
+    pub ast: Block,
+    pub res: Resolutions,
+    pub types: Types,
+    pub consts: Consts,
+}
+
+/// why `check` stopped
+pub enum Failure {
+    /// the stage raised errors, which are left in the `State`
+    Errors(Stage),
+    /// the parser couldn't continue at all
+    Unrecoverable(SdwErr),
+}
+
+/// runs `source` (along with `core`, whose declarations come first) through every stage,
+/// stopping after the first to raise an error. warnings are left in `state` for the caller.
+pub fn check(
+    state: &mut State,
+    source: &str,
+    observer: &mut impl Observer,
+) -> std::result::Result<Checked, Failure> {
+    check_as(state, source, observer, false)
+}
+
+/// `check`, but where the value `source` ends in is shown (by a repl) - so isn't unused
+pub fn check_shown(
+    state: &mut State,
+    source: &str,
+    observer: &mut impl Observer,
+) -> std::result::Result<Checked, Failure> {
+    check_as(state, source, observer, true)
+}
+
+fn check_as(
+    state: &mut State,
+    source: &str,
+    observer: &mut impl Observer,
+    shown: bool,
+) -> std::result::Result<Checked, Failure> {
+    macro_rules! stage {
+        ($stage:expr, $run:expr) => {{
+            observer.start($stage);
+            let result = $run;
+            observer.finish($stage, state);
+            if !state.errors.is_empty() {
+                return Err(Failure::Errors($stage));
+            }
+            result
+        }};
+    }
+
+    let (lexemes, core) = stage!(
+        Stage::Lex,
+        (
+            lexer::lex(state, source),
+            lexer::lex_at(state, CORE, CORE_LINE)
+        )
+    );
+    observer.lexed(&lexemes);
+
+    observer.start(Stage::Parse);
+    let mut ast = parser::parse(state, lexemes).map_err(Failure::Unrecoverable)?;
+    let core = parser::parse(state, core).map_err(Failure::Unrecoverable)?;
+    observer.finish(Stage::Parse, state);
+    if !state.errors.is_empty() {
+        return Err(Failure::Errors(Stage::Parse));
+    }
+    observer.parsed(&ast);
+    ast.stmts.splice(0..0, core.stmts);
+
+    let res = stage!(Stage::Resolve, resolve::resolve(state, &ast));
+    stage!(Stage::Labels, labels::check(state, &ast, &res));
+    stage!(Stage::TypeDecls, cycles::check(state, &ast, &res));
+    let types = stage!(Stage::Types, typeck::check(state, &ast, &res));
+    let consts = stage!(
+        Stage::Constants,
+        consteval::check(state, &ast, &res, &types)
+    );
+    stage!(Stage::Interfaces, conform::check(state, &ast, &res, &types));
+    stage!(
+        Stage::Unused,
+        unused::check(state, &ast, &res, &types, shown)
+    );
+    stage!(Stage::ReturnPaths, cfg::check(state, &ast, &res, &types));
+    prune(&mut ast, &res, &types);
+
+    Ok(Checked {
+        ast,
+        res,
+        types,
+        consts,
+    })
+}
+
+/// what's known of a file which may not check - for tools (such as the language server)
+/// which must work whilst it's being written
+#[derive(Default)]
+pub struct Analysis {
+    /// if it parsed
+    pub res: Option<Resolutions>,
+    /// if its names resolved
+    pub types: Option<Types>,
+}
+
+/// resolves & type checks `source` (along with `core`) as far as it can, whatever errors it
+/// has. the errors are left in `state`, though `check` gives more.
+pub fn analyse(state: &mut State, source: &str) -> Analysis {
+    let lexemes = lexer::lex(state, source);
+    let core = lexer::lex_at(state, CORE, CORE_LINE);
+    let (Ok(mut ast), Ok(core)) = (parser::parse(state, lexemes), parser::parse(state, core))
+    else {
+        return Analysis::default();
+    };
+    if !state.errors.is_empty() {
+        return Analysis::default();
+    }
+    ast.stmts.splice(0..0, core.stmts);
+
+    let res = resolve::resolve(state, &ast);
+    if state.errors.is_empty() {
+        // type checking relies on every name resolving, & types not containing themselves
+        cycles::check(state, &ast, &res);
+    }
+    let types = state
+        .errors
+        .is_empty()
+        .then(|| typeck::check(state, &ast, &res));
+    Analysis {
+        res: Some(res),
+        types,
+    }
+}
+
+/// drops the functions & stubs in `core` which the file never uses (however indirectly), so
+/// that every backend needn't emit all of `core` into every program
+fn prune(ast: &mut Block, res: &Resolutions, types: &Types) {
+    let mut items = HashMap::new();
+    for (index, stmt) in ast.stmts.iter().enumerate() {
+        if let Stmt::Fn { name, .. } | Stmt::Stub { name, .. } = &stmt.spanned {
+            if let Some(id) = res.def_at(name.span).filter(|_| in_core(stmt.span)) {
+                items.insert(id, index);
+            }
+        }
+    }
+
+    let mut used = Vec::new();
+    for stmt in ast.stmts.iter().filter(|stmt| !in_core(stmt.span)) {
+        uses_stmt(&stmt.spanned, res, types, &mut used);
+    }
+    if let Some(tail) = &ast.tail {
+        uses(tail, res, types, &mut used);
+    }
+
+    let mut kept = HashSet::new();
+    while let Some(id) = used.pop() {
+        if !kept.insert(id) {
+            continue;
+        }
+        let Some(index) = items.get(&id) else {
+            continue;
+        };
+        match &ast.stmts[*index].spanned {
+            Stmt::Fn { body, .. } => uses_block(body, res, types, &mut used),
+            // any function of the same name may implement a stub
+            _ => used.extend(items.keys().filter(|other| {
+                res.def(**other).kind == DefKind::Fn && res.def(**other).name == res.def(id).name
+            })),
+        }
+    }
+
+    let unused = items
+        .into_iter()
+        .filter_map(|(id, index)| (!kept.contains(&id)).then_some(index))
+        .collect::<HashSet<_>>();
+    let mut index = 0;
+    ast.stmts.retain(|_| {
+        index += 1;
+        !unused.contains(&(index - 1))
+    });
+}
+
+fn uses_block(block: &Block, res: &Resolutions, types: &Types, used: &mut Vec<DefId>) {
+    for stmt in &block.stmts {
+        uses_stmt(&stmt.spanned, res, types, used);
+    }
+    if let Some(tail) = &block.tail {
+        uses(tail, res, types, used);
+    }
+}
+
+fn uses_stmt(stmt: &Stmt, res: &Resolutions, types: &Types, used: &mut Vec<DefId>) {
+    match stmt {
+        Stmt::Fn { body, .. } | Stmt::Loop { block: body } => uses_block(body, res, types, used),
+        Stmt::Return { expr: Some(expr) }
+        | Stmt::VarDec {
+            initialiser: expr, ..
+        }
+        | Stmt::VarRes { updated: expr, .. }
+        | Stmt::Discard { expr } => uses(expr, res, types, used),
+        Stmt::Stub { .. }
+        | Stmt::Type { .. }
+        | Stmt::Label { .. }
+        | Stmt::Goto { .. }
+        | Stmt::Return { expr: None } => {}
+    }
+}
+
+/// the functions & stubs `expr` calls or takes a pointer to
+fn uses(expr: &Spanned<Expr>, res: &Resolutions, types: &Types, used: &mut Vec<DefId>) {
+    match &expr.spanned {
+        Expr::Variable(_) => {
+            if let Some(Res::Overloads(ids)) = res.get(expr.span) {
+                used.extend(ids);
+            }
+        }
+        Expr::FnCall(_, args) => {
+            used.extend(types.call(expr.span));
+            if let Some(dispatch) = types.dispatch(expr.span) {
+                used.extend(dispatch.targets());
+            }
+            for arg in args {
+                uses(arg, res, types, used);
+            }
+        }
+        Expr::UnaryNot(inner)
+        | Expr::UnaryNeg(inner)
+        | Expr::UnaryPos(inner)
+        | Expr::SubExpr(inner)
+        | Expr::Referal(inner)
+        | Expr::Indir(inner)
+        | Expr::Attributed { expr: inner, .. } => uses(inner, res, types, used),
+        Expr::BiOp(left, _, right) => {
+            uses(left, res, types, used);
+            uses(right, res, types, used);
+        }
+        Expr::Cond {
+            condition,
+            then,
+            elifs,
+            r#else,
+        } => {
+            uses(condition, res, types, used);
+            uses_block(&then.spanned, res, types, used);
+            for (condition, block) in elifs {
+                uses(condition, res, types, used);
+                uses_block(&block.spanned, res, types, used);
+            }
+            if let Some(r#else) = r#else {
+                uses_block(&r#else.spanned, res, types, used);
+            }
+        }
+        Expr::Block(block) => uses_block(block, res, types, used),
+        Expr::StructLit { fields, .. } => match fields {
+            StructLitFields::Positional(values) => {
+                for value in values {
+                    uses(value, res, types, used);
+                }
+            }
+            StructLitFields::Named(values) => {
+                for (_, value) in values {
+                    uses(value, res, types, used);
+                }
+            }
+        },
+        Expr::IntLiteral(_) | Expr::BoolLiteral(_) | Expr::StrLiteral(_) | Expr::ObjMember(..) => {}
+    }
+}
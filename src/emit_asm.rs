@@ -0,0 +1,1181 @@
+use crate::bytecode::Trap;
+use crate::driver::Checked;
+use crate::interp;
+use crate::intrinsics::{Intrinsic, SYSCALL_ARGS as SYSCALL_ARGS_LEN};
+use crate::ir::{BlockId, Function, Inst, Module, Terminator, Value};
+use crate::layout::{self, Layout, Layouts, Target};
+use crate::prelude::*;
+use crate::{lower, mono, passes};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+const TARGET: &str = "x86-64 assembly";
+
+/// the registers integer arguments are passed in, in order (System V)
+const ARGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+/// the registers the kernel takes a syscall's arguments in - its number goes in `%rax`
+const SYSCALL_ARGS: [&str; SYSCALL_ARGS_LEN] = ["%rdi", "%rsi", "%rdx", "%r10", "%r8", "%r9"];
+
+/// everything a program needs, whatever it does. `_start` is added after, as it depends on
+/// `main`'s signature.
+const RUNTIME: &str = r#"    .text
+
+# writes the NUL-terminated string at %rdi to stderr
+sdw_puts:
+    movq %rdi, %rsi
+    xorl %edx, %edx
+1:
+    cmpb $0, (%rsi,%rdx)
+    je 2f
+    incq %rdx
+    jmp 1b
+2:
+    movl $2, %edi
+    movl $1, %eax
+    syscall
+    ret
+
+# writes %rdi to stderr in decimal - as signed if %rsi isn't zero
+sdw_put_int:
+    subq $40, %rsp
+    movq %rdi, %rax
+    leaq 31(%rsp), %rcx
+    movb $0, (%rcx)
+    xorl %r8d, %r8d
+    testq %rsi, %rsi
+    jz 1f
+    testq %rax, %rax
+    jns 1f
+    negq %rax
+    movl $1, %r8d
+1:
+    movl $10, %r9d
+2:
+    xorl %edx, %edx
+    divq %r9
+    addb $48, %dl
+    decq %rcx
+    movb %dl, (%rcx)
+    testq %rax, %rax
+    jnz 2b
+    testl %r8d, %r8d
+    jz 3f
+    decq %rcx
+    movb $45, (%rcx)
+3:
+    movq %rcx, %rdi
+    call sdw_puts
+    addq $40, %rsp
+    ret
+
+# reports a runtime error & exits with 101. %rdi is the message, in which `%d` & `%u` are
+# replaced with %rsi then %rdx (as signed & unsigned), `%s` with the string they point to, and
+# `%%` with `%`
+sdw_fail:
+    movq %rdi, %rbx
+    movq %rsi, %r12
+    movq %rdx, %r13
+    leaq sdw_prefix(%rip), %rdi
+    call sdw_puts
+1:
+    movzbl (%rbx), %eax
+    testl %eax, %eax
+    jz 4f
+    cmpl $37, %eax
+    je 2f
+5:
+    movl $2, %edi
+    movq %rbx, %rsi
+    movl $1, %edx
+    movl $1, %eax
+    syscall
+    incq %rbx
+    jmp 1b
+2:
+    incq %rbx
+    movzbl (%rbx), %eax
+    cmpl $37, %eax
+    je 5b
+    incq %rbx
+    movq %r12, %rdi
+    movq %r13, %r12
+    cmpl $115, %eax
+    je 3f
+    xorl %esi, %esi
+    cmpl $100, %eax
+    sete %sil
+    call sdw_put_int
+    jmp 1b
+3:
+    call sdw_puts
+    jmp 1b
+4:
+    leaq sdw_newline(%rip), %rdi
+    call sdw_puts
+    movl $101, %edi
+    movl $60, %eax
+    syscall
+
+    .section .rodata
+sdw_prefix:
+    .asciz "a runtime error was raised whilst running: "
+sdw_newline:
+    .asciz "\n"
+"#;
+
+/// how a value is held
+#[derive(Debug, Clone)]
+enum Class {
+    /// not at all - `void`, & structs without members
+    Void,
+    /// in a register, or a slot as wide as one. the size is that of the value in memory
+    Scalar(u64),
+    /// in memory
+    Aggregate(Layout),
+}
+
+impl Class {
+    fn size(&self) -> u64 {
+        match self {
+            Class::Void => 0,
+            Class::Scalar(size) => *size,
+            Class::Aggregate(layout) => layout.size,
+        }
+    }
+}
+
+/// where an argument is passed
+#[derive(Debug, Clone)]
+enum Passed {
+    Ignored,
+    /// in these registers, an eightbyte in each
+    Regs(Vec<&'static str>),
+    /// on the stack, at this offset from where the arguments start
+    Stack(i64),
+}
+
+/// where a function's result comes back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Returned {
+    Ignored,
+    /// in `%rax`, then `%rdx`
+    Regs(usize),
+    /// written through a pointer the caller passes as a hidden first argument
+    Memory,
+}
+
+fn round(size: u64) -> i64 {
+    size.div_ceil(8) as i64 * 8
+}
+
+/// the low byte of a register
+fn byte(reg: &str) -> String {
+    match reg {
+        "%rax" => "%al".to_owned(),
+        "%rcx" => "%cl".to_owned(),
+        "%rdx" => "%dl".to_owned(),
+        "%rsi" => "%sil".to_owned(),
+        "%rdi" => "%dil".to_owned(),
+        reg => format!("{reg}b"),
+    }
+}
+
+/// the low four bytes of a register
+fn dword(reg: &str) -> String {
+    match reg {
+        "%rax" => "%eax".to_owned(),
+        "%rcx" => "%ecx".to_owned(),
+        "%rdx" => "%edx".to_owned(),
+        "%rsi" => "%esi".to_owned(),
+        "%rdi" => "%edi".to_owned(),
+        reg => format!("{reg}d"),
+    }
+}
+
+/// a string usable within `.asciz`. bytes outside of printable ascii are escaped in octal
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            }
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => {
+                let _ = write!(escaped, "\\{byte:03o}");
+            }
+        }
+    }
+    escaped
+}
+
+/// where a function's values are kept, relative to `%rbp`
+#[derive(Default)]
+struct Frame {
+    slots: HashMap<Value, i64>,
+    /// where a phi's value is put by each predecessor, before the phi's block copies it into
+    /// its own slot - so phis reading each other see the values from before the jump
+    incoming: HashMap<Value, i64>,
+    /// where the pointer to write the result through is kept, for large results
+    sret: Option<i64>,
+    size: i64,
+}
+
+impl Frame {
+    fn alloc(&mut self, size: u64) -> i64 {
+        self.size += round(size.max(1));
+        -self.size
+    }
+}
+
+struct Emitter<'a, 's> {
+    state: &'s mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    layouts: Layouts,
+    module: &'a Module,
+    out: String,
+    /// string constants, by contents
+    strings: HashMap<String, usize>,
+    /// unions whose member names are needed, to report reading one they don't hold
+    member_names: HashSet<DefId>,
+    /// the index of the function being emitted
+    index: usize,
+    frame: Frame,
+}
+
+impl<'a, 's> Emitter<'a, 's> {
+    fn unsupported(&mut self, what: String, span: Span) {
+        let err = EmitErrors::Unsupported {
+            what,
+            target: TARGET.to_owned(),
+        };
+        self.state.errors.push(SdwErr::from_pos(err, span));
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        self.out.push_str("    ");
+        self.out.push_str(line.as_ref());
+        self.out.push('\n');
+    }
+
+    fn label(&mut self, label: impl AsRef<str>) {
+        self.out.push_str(label.as_ref());
+        self.out.push_str(":\n");
+    }
+
+    fn string(&mut self, text: String) -> String {
+        let next = self.strings.len();
+        let index = *self.strings.entry(text).or_insert(next);
+        format!(".Lstr{index}")
+    }
+
+    fn symbol(&self, function: u32) -> String {
+        format!("f_{}", self.module.functions[function as usize].name)
+    }
+
+    fn block_label(&self, block: BlockId) -> String {
+        format!(".L{}_{}", self.index, block.0)
+    }
+
+    /*
+     * types
+     */
+
+    fn resolve<'t>(&'t self, ty: &'t Ty) -> &'t Ty {
+        match ty {
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Alias(inner)) => self.resolve(inner),
+                _ => ty,
+            },
+            ty => ty,
+        }
+    }
+
+    /// `None` for types without a layout (generics), & those this backend can't hold yet
+    fn class(&self, ty: &Ty) -> Option<Class> {
+        let ty = self.resolve(ty);
+        match ty {
+            Ty::Void | Ty::Never | Ty::Error => Some(Class::Void),
+            Ty::Prim(PrimType::String) => Some(Class::Aggregate(self.layouts.of(ty)?)),
+            Ty::Prim(PrimType::Float) => None,
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Struct(_) | TypeDecl::Union(_)) => {
+                    let layout = self.layouts.decl(*id)?.clone();
+                    Some(match layout.size {
+                        0 => Class::Void,
+                        _ => Class::Aggregate(layout),
+                    })
+                }
+                _ => None,
+            },
+            ty => Some(Class::Scalar(self.layouts.of(ty)?.size)),
+        }
+    }
+
+    /// the class of a value, which `function` has already checked it has
+    fn class_of(&self, function: &Function, value: Value) -> Class {
+        self.class(function.ty(value))
+            .expect("emit-asm: a value without a class")
+    }
+
+    /// the layout of what a value of this type points to
+    fn pointee(&self, ty: &Ty) -> Class {
+        match self.resolve(ty) {
+            Ty::Pointer(to) => self.class(to),
+            _ => None,
+        }
+        .expect("emit-asm: not a pointer")
+    }
+
+    fn returned(&self, ty: &Ty) -> Returned {
+        match self.class(ty) {
+            Some(Class::Void) | None => Returned::Ignored,
+            Some(Class::Scalar(_)) => Returned::Regs(1),
+            Some(Class::Aggregate(layout)) if layout.size <= 16 => {
+                Returned::Regs(layout.size.div_ceil(8) as usize)
+            }
+            Some(Class::Aggregate(_)) => Returned::Memory,
+        }
+    }
+
+    /// where each argument goes (System V, for values made of integers), & how many bytes of
+    /// the stack they take up
+    fn passed(&self, params: &[Ty], ret: &Ty) -> (Vec<Passed>, i64) {
+        let mut next = usize::from(self.returned(ret) == Returned::Memory);
+        let mut stack = 0;
+        let passed = params
+            .iter()
+            .map(|param| {
+                let class = self
+                    .class(param)
+                    .expect("emit-asm: a parameter without a class");
+                let eightbytes = match &class {
+                    Class::Void => return Passed::Ignored,
+                    Class::Scalar(_) => 1,
+                    Class::Aggregate(layout) if layout.size <= 16 => layout.size.div_ceil(8),
+                    Class::Aggregate(_) => 0,
+                } as usize;
+                // an argument is passed in registers only if all of it fits
+                if eightbytes != 0 && next + eightbytes <= ARGS.len() {
+                    next += eightbytes;
+                    Passed::Regs(ARGS[next - eightbytes..next].to_vec())
+                } else {
+                    let at = stack;
+                    stack += round(class.size());
+                    Passed::Stack(at)
+                }
+            })
+            .collect();
+        (passed, stack)
+    }
+
+    /*
+     * moving values about
+     */
+
+    fn slot(&self, value: Value) -> String {
+        format!("{}(%rbp)", self.frame.slots[&value])
+    }
+
+    /// a scalar value into a register
+    fn load(&mut self, function: &Function, value: Value, reg: &str) {
+        match function.inst(value) {
+            Inst::Alloca => {
+                let slot = self.slot(value);
+                self.line(format!("leaq {slot}, {reg}"));
+            }
+            _ if !self.frame.slots.contains_key(&value) => {
+                self.line(format!("xorl {0}, {0}", dword(reg)));
+            }
+            _ => {
+                let slot = self.slot(value);
+                self.line(format!("movq {slot}, {reg}"));
+            }
+        }
+    }
+
+    fn store(&mut self, reg: &str, value: Value) {
+        if let Some(slot) = self.frame.slots.get(&value) {
+            self.line(format!("movq {reg}, {slot}(%rbp)"));
+        }
+    }
+
+    /// `size` bytes from memory, zero-extended into a register
+    fn load_mem(&mut self, size: u64, at: &str, reg: &str) {
+        match size {
+            0 => self.line(format!("xorl {0}, {0}", dword(reg))),
+            1 => self.line(format!("movzbl {at}, {}", dword(reg))),
+            4 => self.line(format!("movl {at}, {}", dword(reg))),
+            _ => self.line(format!("movq {at}, {reg}")),
+        }
+    }
+
+    fn store_mem(&mut self, size: u64, reg: &str, at: &str) {
+        match size {
+            0 => {}
+            1 => self.line(format!("movb {}, {at}", byte(reg))),
+            4 => self.line(format!("movl {}, {at}", dword(reg))),
+            _ => self.line(format!("movq {reg}, {at}")),
+        }
+    }
+
+    /// copies `size` bytes through `%rax`
+    fn copy(&mut self, size: u64, (from, from_at): (&str, i64), (to, to_at): (&str, i64)) {
+        let mut done = 0;
+        for (width, suffix, reg) in [(8, "q", "%rax"), (4, "l", "%eax"), (2, "w", "%ax")] {
+            while size - done >= width {
+                let at = done as i64;
+                self.line(format!("mov{suffix} {}({from}), {reg}", from_at + at));
+                self.line(format!("mov{suffix} {reg}, {}({to})", to_at + at));
+                done += width;
+            }
+        }
+        if done < size {
+            let at = done as i64;
+            self.line(format!("movb {}({from}), %al", from_at + at));
+            self.line(format!("movb %al, {}({to})", to_at + at));
+        }
+    }
+
+    /// a value into memory, as it's laid out there. `base` mustn't be `%rax`
+    fn put(&mut self, function: &Function, value: Value, (base, at): (&str, i64)) {
+        match self.class_of(function, value) {
+            Class::Void => {}
+            Class::Scalar(size) => {
+                self.load(function, value, "%rax");
+                self.store_mem(size, "%rax", &format!("{at}({base})"));
+            }
+            Class::Aggregate(layout) => {
+                let slot = self.frame.slots[&value];
+                self.copy(layout.size, ("%rbp", slot), (base, at));
+            }
+        }
+    }
+
+    /// a value out of memory, into its slot. `base` mustn't be `%rax`
+    fn take(&mut self, function: &Function, (base, at): (&str, i64), value: Value) {
+        match self.class_of(function, value) {
+            Class::Void => {}
+            Class::Scalar(size) => {
+                self.load_mem(size, &format!("{at}({base})"), "%rax");
+                self.store("%rax", value);
+            }
+            Class::Aggregate(layout) => {
+                let slot = self.frame.slots[&value];
+                self.copy(layout.size, (base, at), ("%rbp", slot));
+            }
+        }
+    }
+
+    /// raises a runtime error - see `sdw_fail` for what `message` may contain
+    fn fail(&mut self, message: String) {
+        let label = self.string(message);
+        self.line(format!("leaq {label}(%rip), %rdi"));
+        self.line("call sdw_fail");
+    }
+
+    /// fails with `message` about `%rax` & `%rcx` unless the last comparison set `skip`
+    fn fail_unless(&mut self, skip: &str, message: String) {
+        self.line(format!("{skip} 1f"));
+        self.line("movq %rax, %rsi");
+        self.line("movq %rcx, %rdx");
+        self.fail(message);
+        self.label("1");
+    }
+
+    /*
+     * functions
+     */
+
+    fn function(&mut self, function: &'a Function) {
+        let span = function
+            .id
+            .map(|id| self.res.def(id).span)
+            .unwrap_or_default();
+        if function
+            .params
+            .iter()
+            .chain([&function.ret])
+            .any(|ty| self.class(ty).is_none())
+        {
+            let what = format!("`{}`, which is generic,", function.name);
+            self.unsupported(what, span);
+            return;
+        }
+
+        self.frame = Frame::default();
+        let mut phis = Vec::new();
+        for block in &function.blocks {
+            for &value in &block.insts {
+                let Some(class) = self.class(function.ty(value)) else {
+                    let what = format!("`{}`, which holds a generic,", function.name);
+                    self.unsupported(what, span);
+                    return;
+                };
+                let size = match function.inst(value) {
+                    Inst::Alloca => self.pointee(function.ty(value)).size(),
+                    _ if matches!(class, Class::Void) => continue,
+                    _ => class.size(),
+                };
+                let slot = self.frame.alloc(size);
+                self.frame.slots.insert(value, slot);
+                if let Inst::Phi(_) = function.inst(value) {
+                    let incoming = self.frame.alloc(size);
+                    self.frame.incoming.insert(value, incoming);
+                    phis.push(value);
+                }
+            }
+        }
+        if self.returned(&function.ret) == Returned::Memory {
+            self.frame.sret = Some(self.frame.alloc(8));
+        }
+        let size = (self.frame.size + 15) / 16 * 16;
+
+        let symbol = self.symbol(self.index as u32);
+        self.out.push('\n');
+        self.label(&symbol);
+        self.line("pushq %rbp");
+        self.line("movq %rsp, %rbp");
+        if size != 0 {
+            self.line(format!("subq ${size}, %rsp"));
+        }
+        self.params(function);
+
+        for block in function.reverse_postorder() {
+            let label = self.block_label(block);
+            self.label(label);
+            for &value in &function.block(block).insts {
+                if let Inst::Phi(_) = function.inst(value) {
+                    let size = self.class_of(function, value).size();
+                    let (from, to) = (self.frame.incoming[&value], self.frame.slots[&value]);
+                    self.copy(size, ("%rbp", from), ("%rbp", to));
+                } else {
+                    self.inst(function, value);
+                }
+            }
+            self.terminator(function, block);
+        }
+    }
+
+    /// copies the arguments from where they're passed into the slots of their `Param`s
+    fn params(&mut self, function: &Function) {
+        let (passed, _) = self.passed(&function.params, &function.ret);
+        if let Some(sret) = self.frame.sret {
+            self.line(format!("movq {}, {sret}(%rbp)", ARGS[0]));
+        }
+        for block in &function.blocks {
+            for &value in &block.insts {
+                let Inst::Param(index) = function.inst(value) else {
+                    continue;
+                };
+                let Some(&slot) = self.frame.slots.get(&value) else {
+                    continue;
+                };
+                match (&passed[*index as usize], self.class_of(function, value)) {
+                    (Passed::Ignored, _) => {}
+                    (Passed::Regs(regs), Class::Scalar(1)) => {
+                        self.line(format!("movzbl {}, %eax", byte(regs[0])));
+                        self.line(format!("movq %rax, {slot}(%rbp)"));
+                    }
+                    (Passed::Regs(regs), _) => {
+                        for (eightbyte, reg) in regs.iter().enumerate() {
+                            let at = slot + eightbyte as i64 * 8;
+                            self.line(format!("movq {reg}, {at}(%rbp)"));
+                        }
+                    }
+                    (Passed::Stack(at), class) => {
+                        // above the saved `%rbp` & the return address
+                        self.copy(
+                            round(class.size()) as u64,
+                            ("%rbp", 16 + at),
+                            ("%rbp", slot),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn inst(&mut self, function: &Function, value: Value) {
+        match function.inst(value) {
+            Inst::Param(_) | Inst::Void | Inst::Undef | Inst::Alloca | Inst::Phi(_) => {}
+            Inst::Int(int) => {
+                self.line(format!("movabsq ${int}, %rax"));
+                self.store("%rax", value);
+            }
+            Inst::Unt(unt) => {
+                self.line(format!("movabsq ${}, %rax", *unt as i64));
+                self.store("%rax", value);
+            }
+            Inst::Bool(bool) => {
+                self.line(format!("movq ${}, %rax", u8::from(*bool)));
+                self.store("%rax", value);
+            }
+            Inst::Str(text) => {
+                let Some(&slot) = self.frame.slots.get(&value) else {
+                    return;
+                };
+                let label = self.string(text.clone());
+                self.line(format!("leaq {label}(%rip), %rax"));
+                self.line(format!("movq %rax, {slot}(%rbp)"));
+                self.line(format!("movq ${}, {}(%rbp)", text.len(), slot + 8));
+            }
+            Inst::Fn(index) => {
+                let symbol = self.symbol(*index);
+                self.line(format!("leaq {symbol}(%rip), %rax"));
+                self.store("%rax", value);
+            }
+            Inst::Neg(operand) => {
+                self.load(function, *operand, "%rax");
+                let (ty, signed) = self.integer(function.ty(value));
+                if signed {
+                    self.line("movq %rax, %rcx");
+                    self.line("negq %rcx");
+                    self.fail_unless("jno", format!("`-%d` overflows `{ty}`"));
+                    self.line("movq %rcx, %rax");
+                } else {
+                    self.line("testq %rax, %rax");
+                    self.fail_unless("jz", format!("`-%u` overflows `{ty}`"));
+                }
+                self.store("%rax", value);
+            }
+            Inst::Not(operand) => {
+                self.load(function, *operand, "%rax");
+                self.line("xorq $1, %rax");
+                self.store("%rax", value);
+            }
+            Inst::BiOp(op, left, right) => {
+                self.load(function, *left, "%rax");
+                self.load(function, *right, "%rcx");
+                self.biop(function.ty(*left), *op);
+                self.store("%rax", value);
+            }
+            Inst::Load(pointer) => {
+                self.load(function, *pointer, "%rsi");
+                self.take(function, ("%rsi", 0), value);
+            }
+            Inst::Store { pointer, value } => {
+                self.load(function, *pointer, "%rdi");
+                self.put(function, *value, ("%rdi", 0));
+            }
+            Inst::MemberPtr(pointer, member) => {
+                self.load(function, *pointer, "%rcx");
+                let ty = match self.resolve(function.ty(*pointer)) {
+                    Ty::Pointer(to) => (**to).clone(),
+                    _ => unreachable!("emit-asm: a member through a non-pointer"),
+                };
+                let at = self.member(&ty, *member, ("%rcx", 0));
+                self.line(format!("leaq {at}(%rcx), %rax"));
+                self.store("%rax", value);
+            }
+            Inst::Member(object, member) => {
+                let Some(&slot) = self.frame.slots.get(object) else {
+                    return;
+                };
+                let at = self.member(function.ty(*object), *member, ("%rbp", slot));
+                self.take(function, ("%rbp", slot + at), value);
+            }
+            Inst::Struct(fields) => {
+                let Some(&slot) = self.frame.slots.get(&value) else {
+                    return;
+                };
+                let Some(Class::Aggregate(layout)) = self.class(function.ty(value)) else {
+                    return;
+                };
+                for (field, offset) in fields.iter().zip(&layout.fields) {
+                    self.put(function, *field, ("%rbp", slot + offset.offset as i64));
+                }
+            }
+            Inst::Union { tag, value: held } => {
+                let Some(Class::Aggregate(layout)) = self.class(function.ty(value)) else {
+                    return;
+                };
+                let slot = self.frame.slots[&value];
+                self.line(format!("movl ${tag}, {slot}(%rbp)"));
+                let at = layout.fields[*tag as usize].offset as i64;
+                self.put(function, *held, ("%rbp", slot + at));
+            }
+            Inst::Tag(union) => {
+                match self.frame.slots.get(union) {
+                    Some(slot) => self.line(format!("movl {slot}(%rbp), %eax")),
+                    None => self.line("xorl %eax, %eax"),
+                }
+                self.store("%rax", value);
+            }
+            Inst::Call {
+                function: index,
+                args,
+            } => {
+                let symbol = self.symbol(*index);
+                self.call(function, args, value, &format!("call {symbol}"));
+            }
+            Inst::CallPtr { pointer, args } => {
+                self.load(function, *pointer, "%r11");
+                self.call(function, args, value, "call *%r11");
+            }
+            // what's left is a call no implementation fits
+            Inst::CallInterface { stub, .. } => {
+                let err = RuntimeErrors::NoImplementation(self.res.def(*stub).name.clone());
+                self.fail(err.to_string());
+            }
+            Inst::Intrinsic { intrinsic, args } => {
+                self.intrinsic(function, *intrinsic, args, value)
+            }
+        }
+    }
+
+    fn intrinsic(
+        &mut self,
+        function: &Function,
+        intrinsic: Intrinsic,
+        args: &[Value],
+        value: Value,
+    ) {
+        // a string's pointer, then its length
+        let string = |this: &Self, at: i64| format!("{}(%rbp)", this.frame.slots[&args[0]] + at);
+        match intrinsic {
+            Intrinsic::Syscall => {
+                self.load(function, args[0], "%rax");
+                for (arg, reg) in args[1..].iter().zip(SYSCALL_ARGS) {
+                    self.load(function, *arg, reg);
+                }
+                self.line("syscall");
+                self.store("%rax", value);
+            }
+            // each allocation is mapped on its own, so is zeroed & aligned
+            Intrinsic::Alloc => {
+                self.load(function, args[0], "%rsi");
+                self.line("movabsq $4294967296, %rax");
+                self.line("cmpq %rax, %rsi");
+                self.line("jb 1f");
+                self.fail("the %d bytes at 0 aren't all in memory".to_owned());
+                self.label("1");
+                self.line("pushq %rsi");
+                self.line("pushq %rsi");
+                // mapping nothing is an error, but allocating nothing isn't
+                self.line("movl $1, %eax");
+                self.line("testq %rsi, %rsi");
+                self.line("cmovzq %rax, %rsi");
+                self.line("xorl %edi, %edi");
+                self.line("movl $3, %edx");
+                self.line("movl $34, %r10d");
+                self.line("movq $-1, %r8");
+                self.line("xorl %r9d, %r9d");
+                self.line("movl $9, %eax");
+                self.line("syscall");
+                self.line("popq %rsi");
+                self.line("popq %rsi");
+                self.line("testq %rax, %rax");
+                self.line("jns 1f");
+                self.fail("the %d bytes at 0 aren't all in memory".to_owned());
+                self.label("1");
+                self.store("%rax", value);
+            }
+            // literals are the only strings, & each is followed by a NUL
+            Intrinsic::Panic => {
+                let at = string(self, 0);
+                self.line(format!("movq {at}, %rsi"));
+                self.fail("panicked: %s".to_owned());
+            }
+            Intrinsic::Address | Intrinsic::Length => {
+                let at = string(
+                    self,
+                    if intrinsic == Intrinsic::Address {
+                        0
+                    } else {
+                        8
+                    },
+                );
+                self.line(format!("movq {at}, %rax"));
+                self.store("%rax", value);
+            }
+            Intrinsic::Byte => {
+                self.load(function, args[0], "%rax");
+                self.line("movzbq (%rax), %rax");
+                self.store("%rax", value);
+            }
+            Intrinsic::Store => {
+                self.load(function, args[0], "%rax");
+                self.load(function, args[1], "%rcx");
+                self.line("movb %cl, (%rax)");
+            }
+        }
+    }
+
+    /// `int` or `unt`, & whether it's signed
+    fn integer(&self, ty: &Ty) -> (&'static str, bool) {
+        match self.resolve(ty) {
+            Ty::Prim(PrimType::Int) => ("int", true),
+            _ => ("unt", false),
+        }
+    }
+
+    /// `%rax` op `%rcx`, into `%rax`, checked as the interpreter does
+    fn biop(&mut self, ty: &Ty, op: BiOps) {
+        let (name, signed) = self.integer(ty);
+        let spec = if signed { "%d" } else { "%u" };
+        let shown = format!("{spec} {} {spec}", op.symbol().replace('%', "%%"));
+        let overflow = RuntimeErrors::Overflow {
+            expr: shown.clone(),
+            ty: name.to_owned(),
+        }
+        .to_string();
+        let no_overflow = if signed { "jno" } else { "jnc" };
+
+        match op {
+            BiOps::Add | BiOps::Sub => {
+                let instruction = if op == BiOps::Add { "addq" } else { "subq" };
+                self.line("movq %rax, %rdx");
+                self.line(format!("{instruction} %rcx, %rdx"));
+                self.fail_unless(no_overflow, overflow);
+                self.line("movq %rdx, %rax");
+            }
+            BiOps::Mul if signed => {
+                self.line("movq %rax, %rdx");
+                self.line("imulq %rcx, %rdx");
+                self.fail_unless("jno", overflow);
+                self.line("movq %rdx, %rax");
+            }
+            BiOps::Mul => {
+                self.line("movq %rax, %rsi");
+                self.line("mulq %rcx");
+                self.line("movq %rax, %rdx");
+                self.line("movq %rsi, %rax");
+                self.fail_unless("jno", overflow);
+                self.line("movq %rdx, %rax");
+            }
+            BiOps::Div | BiOps::Mod => {
+                self.line("testq %rcx, %rcx");
+                self.fail_unless("jnz", RuntimeErrors::DivByZero(shown).to_string());
+                if signed {
+                    // the only quotient which doesn't fit
+                    self.line("cmpq $-1, %rcx");
+                    self.line("jne 2f");
+                    self.line("movabsq $-9223372036854775808, %rdx");
+                    self.line("cmpq %rdx, %rax");
+                    self.fail_unless("jne", overflow);
+                    self.label("2");
+                    self.line("cqto");
+                    self.line("idivq %rcx");
+                } else {
+                    self.line("xorl %edx, %edx");
+                    self.line("divq %rcx");
+                }
+                if op == BiOps::Mod {
+                    self.line("movq %rdx, %rax");
+                }
+            }
+            BiOps::BitLShift | BiOps::BitRshift => {
+                self.line("cmpq $64, %rcx");
+                self.fail_unless("jb", RuntimeErrors::OversizedShift(shown).to_string());
+                let instruction = match (op, signed) {
+                    (BiOps::BitLShift, _) => "shlq",
+                    (_, true) => "sarq",
+                    (_, false) => "shrq",
+                };
+                self.line(format!("{instruction} %cl, %rax"));
+            }
+            BiOps::BitOr => self.line("orq %rcx, %rax"),
+            BiOps::BitAnd => self.line("andq %rcx, %rax"),
+            BiOps::BitXor => self.line("xorq %rcx, %rax"),
+            BiOps::Eq | BiOps::NEq | BiOps::Gr | BiOps::Ls | BiOps::GrEq | BiOps::LsEq => {
+                let condition = match (op, signed) {
+                    (BiOps::Eq, _) => "e",
+                    (BiOps::NEq, _) => "ne",
+                    (BiOps::Gr, true) => "g",
+                    (BiOps::Ls, true) => "l",
+                    (BiOps::GrEq, true) => "ge",
+                    (BiOps::LsEq, true) => "le",
+                    (BiOps::Gr, false) => "a",
+                    (BiOps::Ls, false) => "b",
+                    (BiOps::GrEq, false) => "ae",
+                    _ => "be",
+                };
+                self.line("cmpq %rcx, %rax");
+                self.line(format!("set{condition} %al"));
+                self.line("movzbl %al, %eax");
+            }
+            BiOps::BitNot | BiOps::LogNot | BiOps::LogOr | BiOps::LogAnd => {
+                let err =
+                    RuntimeErrors::Unsupported(format!("`{}` as a binary operator", op.symbol()));
+                self.fail(err.to_string());
+            }
+        }
+    }
+
+    /// the offset of a member within a value of type `ty` at `object` - checking, for a
+    /// union, that it's the member held
+    fn member(&mut self, ty: &Ty, member: u32, (base, at): (&str, i64)) -> i64 {
+        let Some(Class::Aggregate(layout)) = self.class(ty) else {
+            return 0;
+        };
+        let field = &layout.fields[member as usize];
+        if layout.discriminant.is_some() {
+            let Ty::Named(id) = *self.resolve(ty) else {
+                unreachable!("emit-asm: an unnamed union");
+            };
+            self.member_names.insert(id);
+            let err = RuntimeErrors::WrongMember {
+                held: "%s".to_owned(),
+                member: field.name.clone(),
+            };
+            self.line(format!("cmpl ${member}, {at}({base})"));
+            self.line("je 1f");
+            self.line(format!("movl {at}({base}), %eax"));
+            self.line(format!("leaq .Lmembers{}(%rip), %rsi", id.0));
+            self.line("movq (%rsi,%rax,8), %rsi");
+            self.fail(err.to_string());
+            self.label("1");
+        }
+        field.offset as i64
+    }
+
+    /// a call, with `call` being the instruction itself
+    fn call(&mut self, function: &Function, args: &[Value], result: Value, call: &str) {
+        let params = args
+            .iter()
+            .map(|arg| function.ty(*arg).clone())
+            .collect::<Vec<_>>();
+        let ret = function.ty(result);
+        let (passed, stack) = self.passed(&params, ret);
+        let returned = self.returned(ret);
+
+        // the stack must be 16 byte aligned at the call
+        let reserved = (stack + 15) / 16 * 16;
+        if reserved != 0 {
+            self.line(format!("subq ${reserved}, %rsp"));
+        }
+        for (arg, passed) in args.iter().zip(&passed) {
+            if let Passed::Stack(at) = passed {
+                match self.class_of(function, *arg) {
+                    Class::Scalar(_) => {
+                        self.load(function, *arg, "%rax");
+                        self.line(format!("movq %rax, {at}(%rsp)"));
+                    }
+                    class => {
+                        let slot = self.frame.slots[arg];
+                        self.copy(round(class.size()) as u64, ("%rbp", slot), ("%rsp", *at));
+                    }
+                }
+            }
+        }
+        for (arg, passed) in args.iter().zip(&passed) {
+            if let Passed::Regs(regs) = passed {
+                if let Class::Scalar(_) = self.class_of(function, *arg) {
+                    self.load(function, *arg, regs[0]);
+                    continue;
+                }
+                let slot = self.frame.slots[arg];
+                for (eightbyte, reg) in regs.iter().enumerate() {
+                    let at = slot + eightbyte as i64 * 8;
+                    self.line(format!("movq {at}(%rbp), {reg}"));
+                }
+            }
+        }
+        if returned == Returned::Memory {
+            let slot = self.slot(result);
+            self.line(format!("leaq {slot}, {}", ARGS[0]));
+        }
+
+        self.line(call);
+        if reserved != 0 {
+            self.line(format!("addq ${reserved}, %rsp"));
+        }
+        match (returned, self.class_of(function, result)) {
+            (Returned::Regs(_), Class::Scalar(1)) => {
+                self.line("movzbl %al, %eax");
+                self.store("%rax", result);
+            }
+            (Returned::Regs(eightbytes), _) => {
+                let slot = self.frame.slots[&result];
+                for (eightbyte, reg) in ["%rax", "%rdx"][..eightbytes].iter().enumerate() {
+                    let at = slot + eightbyte as i64 * 8;
+                    self.line(format!("movq {reg}, {at}(%rbp)"));
+                }
+            }
+            (Returned::Ignored | Returned::Memory, _) => {}
+        }
+    }
+
+    fn terminator(&mut self, function: &Function, block: BlockId) {
+        let term = &function.block(block).term;
+        // phis of the blocks jumped to take their values from this one
+        for succ in term.succs() {
+            for &value in &function.block(succ).insts {
+                let Inst::Phi(incoming) = function.inst(value) else {
+                    continue;
+                };
+                let Some(&(_, from)) = incoming.iter().find(|(pred, _)| *pred == block) else {
+                    continue;
+                };
+                let Some(&to) = self.frame.incoming.get(&value) else {
+                    continue;
+                };
+                self.put(function, from, ("%rbp", to));
+            }
+        }
+
+        match term {
+            Terminator::Jump(to) => {
+                let label = self.block_label(*to);
+                self.line(format!("jmp {label}"));
+            }
+            Terminator::Branch {
+                condition,
+                then,
+                r#else,
+            } => {
+                self.load(function, *condition, "%rax");
+                self.line("testq %rax, %rax");
+                let (then, r#else) = (self.block_label(*then), self.block_label(*r#else));
+                self.line(format!("jnz {then}"));
+                self.line(format!("jmp {}", r#else));
+            }
+            Terminator::Switch { tag, targets } => {
+                self.load(function, *tag, "%rax");
+                for (index, target) in targets.iter().enumerate() {
+                    let label = self.block_label(*target);
+                    self.line(format!("cmpq ${index}, %rax"));
+                    self.line(format!("je {label}"));
+                }
+                self.line("ud2");
+            }
+            Terminator::Return(value) => {
+                match self.returned(&function.ret) {
+                    Returned::Ignored => {}
+                    Returned::Regs(_)
+                        if matches!(self.class_of(function, *value), Class::Scalar(_)) =>
+                    {
+                        self.load(function, *value, "%rax");
+                    }
+                    Returned::Regs(eightbytes) => {
+                        let slot = self.frame.slots[value];
+                        for (eightbyte, reg) in ["%rax", "%rdx"][..eightbytes].iter().enumerate() {
+                            let at = slot + eightbyte as i64 * 8;
+                            self.line(format!("movq {at}(%rbp), {reg}"));
+                        }
+                    }
+                    Returned::Memory => {
+                        let sret = self.frame.sret.expect("emit-asm: no result pointer");
+                        self.line(format!("movq {sret}(%rbp), %rdi"));
+                        self.put(function, *value, ("%rdi", 0));
+                        self.line("movq %rdi, %rax");
+                    }
+                }
+                self.line("leave");
+                self.line("ret");
+            }
+            Terminator::Trap(trap) => {
+                let err = match trap {
+                    Trap::JumpIntoExpr => RuntimeErrors::JumpIntoExpr,
+                    Trap::EmptyUnion => {
+                        RuntimeErrors::Unsupported("a union literal without a member".to_owned())
+                    }
+                };
+                self.fail(err.to_string());
+            }
+            Terminator::Unreachable => self.line("ud2"),
+        }
+    }
+
+    /// `_start`, which runs the top level, then `main`, then exits with what it returns
+    fn start(&mut self, main: u32) {
+        let ret = self.module.functions[main as usize].ret.clone();
+        let top = self.symbol(0);
+        let main = self.symbol(main);
+        self.out.push_str("\n    .globl _start\n");
+        self.label("_start");
+        self.line("xorl %ebp, %ebp");
+        self.line("andq $-16, %rsp");
+        self.line(format!("call {top}"));
+        let returned = self.returned(&ret);
+        if returned == Returned::Memory {
+            let size = (round(self.class(&ret).map_or(0, |class| class.size())) + 15) / 16 * 16;
+            self.line(format!("subq ${size}, %rsp"));
+            self.line("movq %rsp, %rdi");
+        }
+        self.line(format!("call {main}"));
+        match self.class(&ret) {
+            Some(Class::Scalar(1)) => self.line("movzbl %al, %edi"),
+            Some(Class::Scalar(_)) => self.line("movq %rax, %rdi"),
+            _ => self.line("xorl %edi, %edi"),
+        }
+        self.line("movl $60, %eax");
+        self.line("syscall");
+    }
+
+    /// the string constants, & tables of union member names
+    fn data(&mut self) {
+        let mut tables = String::new();
+        let mut ids = std::mem::take(&mut self.member_names)
+            .into_iter()
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+        for id in ids {
+            let Some(TypeDecl::Union(members)) = self.types.decls.get(&id) else {
+                continue;
+            };
+            let names = members
+                .iter()
+                .map(|(name, _)| self.string(name.clone()))
+                .collect::<Vec<_>>();
+            let _ = writeln!(tables, "    .p2align 3\n.Lmembers{}:", id.0);
+            for name in names {
+                let _ = writeln!(tables, "    .quad {name}");
+            }
+        }
+
+        let mut strings = self.strings.iter().collect::<Vec<_>>();
+        strings.sort_by_key(|(_, index)| **index);
+        let mut out = String::from("\n    .section .rodata\n");
+        for (text, index) in strings {
+            let _ = writeln!(out, ".Lstr{index}:\n    .asciz \"{}\"", escape(text));
+        }
+        out.push_str(&tables);
+        self.out.push_str(&out);
+    }
+}
+
+/// compiles a checked module to x86-64 assembly (in GNU syntax) for Linux, via its optimised
+/// IR, with a copy of each generic function for each set of types it's called with. it's a
+/// whole program: `_start` runs the top level, then exits with what `main` returns.
+pub fn emit(state: &mut State, checked: &Checked) -> String {
+    let Some((main, parameters)) = interp::main_fn(checked) else {
+        state
+            .errors
+            .push(SdwErr::from_pos(EmitErrors::NoMain, Span::default()));
+        return String::new();
+    };
+    if parameters != 0 {
+        let err = SdwErr::from_pos(EmitErrors::MainTakesParams, checked.res.def(main).span);
+        state.errors.push(err);
+        return String::new();
+    }
+
+    let mut module = lower::lower(checked);
+    mono::monomorphise(state, &mut module, checked, TARGET);
+    passes::optimise(&mut module);
+    let mut emitter = Emitter {
+        state,
+        res: &checked.res,
+        types: &checked.types,
+        layouts: layout::compute(&checked.types, &Target::X86_64),
+        module: &module,
+        out: String::from(RUNTIME),
+        strings: HashMap::new(),
+        member_names: HashSet::new(),
+        index: 0,
+        frame: Frame::default(),
+    };
+    emitter.out.push_str("\n    .text\n");
+    for (index, function) in module.functions.iter().enumerate() {
+        emitter.index = index;
+        emitter.function(function);
+    }
+    let main = module.main.expect("emit-asm: `main` wasn't lowered");
+    emitter.start(main);
+    emitter.data();
+    emitter.out
+}
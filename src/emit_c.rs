@@ -0,0 +1,1469 @@
+use crate::driver::Checked;
+use crate::interp;
+use crate::intrinsics::{Intrinsic, SYSCALL_ARGS};
+use crate::mono::{self, Infer, Inferred};
+use crate::overload::{Arm, Dispatch};
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+const TARGET: &str = "C";
+
+/// everything a program needs, whatever it does. arithmetic is checked, raising the same
+/// errors (with the same messages) as the interpreter.
+const RUNTIME: &str = r#"#include <inttypes.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <unistd.h>
+
+/* `void` is a value like any other */
+typedef struct { char unused; } sdw_void;
+#define sdw_unit ((sdw_void){ 0 })
+
+typedef struct { const char *ptr; int64_t len; } sdw_string;
+
+/* so C knows a function ends after a call which doesn't return */
+#ifdef __GNUC__
+#define sdw_noreturn __attribute__((noreturn))
+#else
+#define sdw_noreturn
+#endif
+
+/* a union's member names are only read when a member access is checked */
+#ifdef __GNUC__
+#define sdw_unused __attribute__((unused))
+#else
+#define sdw_unused
+#endif
+
+static sdw_noreturn void sdw_fail(const char *format, ...) {
+    va_list args;
+    va_start(args, format);
+    fputs("a runtime error was raised whilst running: ", stderr);
+    vfprintf(stderr, format, args);
+    fputc('\n', stderr);
+    va_end(args);
+    exit(101);
+}
+
+#define SDW_INT_OVERFLOW(op) \
+    sdw_fail("`%" PRId64 " " op " %" PRId64 "` overflows `int`", a, b)
+#define SDW_UNT_OVERFLOW(op) \
+    sdw_fail("`%" PRIu64 " " op " %" PRIu64 "` overflows `unt`", a, b)
+
+static inline int64_t sdw_neg_int(int64_t a) {
+    if (a == INT64_MIN) sdw_fail("`-%" PRId64 "` overflows `int`", a);
+    return -a;
+}
+static inline int64_t sdw_add_int(int64_t a, int64_t b) {
+    if ((b > 0 && a > INT64_MAX - b) || (b < 0 && a < INT64_MIN - b)) SDW_INT_OVERFLOW("+");
+    return a + b;
+}
+static inline int64_t sdw_sub_int(int64_t a, int64_t b) {
+    if ((b < 0 && a > INT64_MAX + b) || (b > 0 && a < INT64_MIN + b)) SDW_INT_OVERFLOW("-");
+    return a - b;
+}
+static inline int64_t sdw_mul_int(int64_t a, int64_t b) {
+    if (a > 0 ? (b > 0 ? a > INT64_MAX / b : b < INT64_MIN / a)
+              : (b > 0 ? a < INT64_MIN / b : a != 0 && b < INT64_MAX / a))
+        SDW_INT_OVERFLOW("*");
+    return a * b;
+}
+static inline int64_t sdw_div_int(int64_t a, int64_t b) {
+    if (b == 0) sdw_fail("`%" PRId64 " / %" PRId64 "` divides by zero", a, b);
+    if (a == INT64_MIN && b == -1) SDW_INT_OVERFLOW("/");
+    return a / b;
+}
+static inline int64_t sdw_mod_int(int64_t a, int64_t b) {
+    if (b == 0) sdw_fail("`%" PRId64 " %% %" PRId64 "` divides by zero", a, b);
+    if (a == INT64_MIN && b == -1) SDW_INT_OVERFLOW("%");
+    return a % b;
+}
+static inline int64_t sdw_shl_int(int64_t a, int64_t b) {
+    if (b < 0 || b >= 64)
+        sdw_fail("`%" PRId64 " << %" PRId64 "` shifts by more than the 64 bits of an integer", a, b);
+    return (int64_t)((uint64_t)a << b);
+}
+static inline int64_t sdw_shr_int(int64_t a, int64_t b) {
+    if (b < 0 || b >= 64)
+        sdw_fail("`%" PRId64 " >> %" PRId64 "` shifts by more than the 64 bits of an integer", a, b);
+    return a >> b;
+}
+
+static inline uint64_t sdw_neg_unt(uint64_t a) {
+    if (a != 0) sdw_fail("`-%" PRIu64 "` overflows `unt`", a);
+    return 0;
+}
+static inline uint64_t sdw_add_unt(uint64_t a, uint64_t b) {
+    if (a > UINT64_MAX - b) SDW_UNT_OVERFLOW("+");
+    return a + b;
+}
+static inline uint64_t sdw_sub_unt(uint64_t a, uint64_t b) {
+    if (b > a) SDW_UNT_OVERFLOW("-");
+    return a - b;
+}
+static inline uint64_t sdw_mul_unt(uint64_t a, uint64_t b) {
+    if (a != 0 && b > UINT64_MAX / a) SDW_UNT_OVERFLOW("*");
+    return a * b;
+}
+static inline uint64_t sdw_div_unt(uint64_t a, uint64_t b) {
+    if (b == 0) sdw_fail("`%" PRIu64 " / %" PRIu64 "` divides by zero", a, b);
+    return a / b;
+}
+static inline uint64_t sdw_mod_unt(uint64_t a, uint64_t b) {
+    if (b == 0) sdw_fail("`%" PRIu64 " %% %" PRIu64 "` divides by zero", a, b);
+    return a % b;
+}
+static inline uint64_t sdw_shl_unt(uint64_t a, uint64_t b) {
+    if (b >= 64)
+        sdw_fail("`%" PRIu64 " << %" PRIu64 "` shifts by more than the 64 bits of an integer", a, b);
+    return a << b;
+}
+static inline uint64_t sdw_shr_unt(uint64_t a, uint64_t b) {
+    if (b >= 64)
+        sdw_fail("`%" PRIu64 " >> %" PRIu64 "` shifts by more than the 64 bits of an integer", a, b);
+    return a >> b;
+}
+
+/* the intrinsics `core` declares - only the syscalls the interpreter emulates are made */
+static inline int64_t sdw_syscall(int64_t number, int64_t a, int64_t b, int64_t c, int64_t d,
+                                  int64_t e, int64_t f) {
+    (void)d, (void)e, (void)f;
+    switch (number) {
+    case 0:
+        if (a != 0) return -9;
+        return read(0, (void *)(intptr_t)b, c);
+    case 1:
+        if (a != 1 && a != 2) return -9;
+        return write(a, (const void *)(intptr_t)b, c);
+    case 60: case 231:
+        exit((int)a);
+    default:
+        sdw_fail("syscall %" PRId64 " isn't available here", number);
+    }
+    return 0;
+}
+
+static inline int64_t sdw_alloc(int64_t size) {
+    void *memory = NULL;
+    if (0 <= size && size < INT64_C(1) << 32) memory = calloc(size ? size : 1, 1);
+    if (!memory) sdw_fail("the %" PRId64 " bytes at 0 aren't all in memory", size);
+    return (int64_t)(intptr_t)memory;
+}
+
+static inline sdw_noreturn void sdw_panic(sdw_string message) {
+    sdw_fail("panicked: %.*s", (int)message.len, message.ptr);
+}
+"#;
+
+/// identifiers which can't be used as they are
+const RESERVED: [&str; 48] = [
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "bool",
+    "true",
+    "false",
+    "main",
+    "int64_t",
+    "uint64_t",
+    "INT64_C",
+    "UINT64_C",
+    "INT64_MIN",
+    "INT64_MAX",
+    "UINT64_MAX",
+    "as",
+    "tag",
+    "unused",
+];
+
+/// one of the blocks or expressions enclosing some code - see `compile::Nesting`
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Nesting {
+    id: u32,
+    block: bool,
+}
+
+/// the function being emitted
+#[derive(Default)]
+struct Current {
+    /// what's known of the generic values here - see `mono::Infer`
+    inferred: Inferred,
+    ret: Option<Ty>,
+    /// every variable & temporary is declared up front, so they live as long as the function
+    /// does (as in the interpreter), & labels never have to precede a declaration
+    decls: String,
+    body: String,
+    indent: usize,
+    names: HashMap<DefId, String>,
+    used: HashSet<String>,
+    temps: u32,
+    path: Vec<Nesting>,
+    labels: HashMap<DefId, Vec<Nesting>>,
+    /// each `goto` is written as a placeholder, until every label has been seen
+    gotos: Vec<(DefId, Vec<Nesting>)>,
+}
+
+/// one copy of a generic function, for the types of its parameters
+struct Instance {
+    function: DefId,
+    params: Vec<Ty>,
+    name: String,
+}
+
+struct Emitter<'a, 's> {
+    state: &'s mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    /// every function with a body, & what its generic values are in each copy
+    infer: Infer<'a>,
+    functions: HashMap<DefId, String>,
+    /// each emitted once all those before it have been
+    instances: Vec<Instance>,
+    /// the names of every type & function
+    globals: HashSet<String>,
+    type_names: HashMap<DefId, String>,
+    /// `typedef`s for function pointer types, by the type they name
+    fn_ptrs: Vec<(Ty, String)>,
+    fn_ptr_decls: String,
+    nestings: u32,
+    current: Current,
+}
+
+/// `name`, made into a valid C identifier which isn't already `used`
+fn unique(name: &str, used: &mut HashSet<String>) -> String {
+    let mut name = name.to_owned();
+    if RESERVED.contains(&name.as_str()) || name.starts_with("sdw_") {
+        name.push('_');
+    }
+    let mut candidate = name.clone();
+    let mut n = 1;
+    while used.contains(&candidate) {
+        candidate = format!("{name}_{n}");
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+impl<'a, 's> Emitter<'a, 's> {
+    fn unsupported(&mut self, what: String, span: Span) {
+        let err = EmitErrors::Unsupported {
+            what,
+            target: TARGET.to_owned(),
+        };
+        self.state.errors.push(SdwErr::from_pos(err, span));
+    }
+
+    /*
+     * types
+     */
+
+    fn union_members(&self, ty: &Ty) -> Option<&'a [(String, Ty)]> {
+        match ty {
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Union(members)) => Some(members),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// the C type for `ty`, for declaring something named `name`
+    fn declare(&mut self, ty: &Ty, name: &str) -> String {
+        let ty = self.c_ty(ty);
+        if ty.ends_with('*') {
+            format!("{ty}{name}")
+        } else {
+            format!("{ty} {name}")
+        }
+    }
+
+    fn c_ty(&mut self, ty: &Ty) -> String {
+        match ty {
+            Ty::Prim(PrimType::Int) => "int64_t".to_owned(),
+            Ty::Prim(PrimType::Unt) => "uint64_t".to_owned(),
+            Ty::Prim(PrimType::Bool) => "bool".to_owned(),
+            Ty::Prim(PrimType::Float) => "double".to_owned(),
+            Ty::Prim(PrimType::String) => "sdw_string".to_owned(),
+            Ty::Void | Ty::Never | Ty::Error => "sdw_void".to_owned(),
+            Ty::Named(id) => match self.type_names.get(id) {
+                Some(name) => name.clone(),
+                // generics are reported where they're used
+                None => "sdw_void".to_owned(),
+            },
+            Ty::Pointer(to) => {
+                let to = self.c_ty(to);
+                if to.ends_with('*') {
+                    format!("{to}*")
+                } else {
+                    format!("{to} *")
+                }
+            }
+            Ty::FnPtr { args, ret } => {
+                if let Some((_, name)) = self.fn_ptrs.iter().find(|(other, _)| other == ty) {
+                    return name.clone();
+                }
+                let name = format!("fn_ptr_{}", self.fn_ptrs.len());
+                self.fn_ptrs.push((ty.clone(), name.clone()));
+                let args = args.iter().map(|arg| self.c_ty(arg)).collect::<Vec<_>>();
+                let args = if args.is_empty() {
+                    "void".to_owned()
+                } else {
+                    args.join(", ")
+                };
+                let ret = self.c_ty(ret);
+                let _ = writeln!(self.fn_ptr_decls, "typedef {ret} (*{name})({args});");
+                name
+            }
+        }
+    }
+
+    /// struct & union definitions, each after those of the types it contains by value
+    fn type_definitions(&mut self) -> String {
+        let mut ids = self
+            .types
+            .decls
+            .iter()
+            .filter(|(_, decl)| matches!(decl, TypeDecl::Struct(_) | TypeDecl::Union(_)))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+
+        let mut done = HashSet::new();
+        let mut out = String::new();
+        for id in ids {
+            self.type_definition(id, &mut done, &mut out);
+        }
+        out
+    }
+
+    fn type_definition(&mut self, id: DefId, done: &mut HashSet<DefId>, out: &mut String) {
+        if !done.insert(id) {
+            return;
+        }
+        let (members, union) = match self.types.decls.get(&id) {
+            Some(TypeDecl::Struct(members)) => (members, false),
+            Some(TypeDecl::Union(members)) => (members, true),
+            _ => return,
+        };
+        // cycles were ruled out when checking types, so this always terminates
+        for (_, ty) in members {
+            if let Ty::Named(inner) = ty {
+                self.type_definition(*inner, done, out);
+            }
+        }
+
+        let name = self.type_names[&id].clone();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let mut used = HashSet::new();
+        let mut fields = Vec::new();
+        for (member, ty) in members {
+            if mono::is_generic(self.types, ty) {
+                let def = self.res.def(id);
+                let what = format!("`{}`, which holds a generic,", def.name);
+                self.unsupported(what, def.span);
+            }
+            let field = unique(member, &mut used);
+            fields.push(self.declare(ty, &field));
+        }
+        if fields.is_empty() {
+            fields.push("char unused".to_owned());
+        }
+
+        let _ = writeln!(out, "struct {name} {{");
+        if union {
+            let _ = writeln!(out, "    uint32_t tag;");
+            let _ = writeln!(out, "    union {{");
+            for field in fields {
+                let _ = writeln!(out, "        {field};");
+            }
+            let _ = writeln!(out, "    }} as;");
+        } else {
+            for field in fields {
+                let _ = writeln!(out, "    {field};");
+            }
+        }
+        let _ = writeln!(out, "}};");
+        if union {
+            let names = members
+                .iter()
+                .map(|(member, _)| format!("\"{member}\""))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                out,
+                "static sdw_unused const char *const {name}_members[] = {{ {} }};",
+                names.join(", ")
+            );
+        }
+    }
+
+    /// the C name of a struct or union member
+    fn field(&self, ty: &Ty, index: usize) -> String {
+        let members = self
+            .types
+            .members(ty)
+            .expect("emit-c: member of a type without members");
+        let mut used = HashSet::new();
+        let mut field = String::new();
+        for (member, _) in &members[..=index] {
+            field = unique(member, &mut used);
+        }
+        field
+    }
+
+    /*
+     * emitting
+     */
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        let current = &mut self.current;
+        for _ in 0..current.indent {
+            current.body.push_str("    ");
+        }
+        current.body.push_str(line.as_ref());
+        current.body.push('\n');
+    }
+
+    fn local(&mut self, id: DefId) -> String {
+        if let Some(name) = self.current.names.get(&id) {
+            return name.clone();
+        }
+        let name = unique(&self.res.def(id).name, &mut self.current.used);
+        self.current.names.insert(id, name.clone());
+        if let Some(ty) = self.local_ty(id) {
+            let decl = self.declare(&ty, &name);
+            let _ = writeln!(self.current.decls, "    {decl};");
+        }
+        name
+    }
+
+    /// a new variable, holding `value`
+    fn temp(&mut self, ty: &Ty, value: &str) -> String {
+        let name = self.temp_of(ty);
+        self.line(format!("{name} = {value};"));
+        name
+    }
+
+    /// a new (unassigned) variable
+    fn temp_of(&mut self, ty: &Ty) -> String {
+        self.current.temps += 1;
+        let name = unique(&format!("t{}", self.current.temps), &mut self.current.used);
+        let decl = self.declare(ty, &name);
+        let _ = writeln!(self.current.decls, "    {decl};");
+        name
+    }
+
+    fn nest<T>(&mut self, block: bool, within: impl FnOnce(&mut Self) -> T) -> T {
+        self.nestings += 1;
+        let nesting = Nesting {
+            id: self.nestings,
+            block,
+        };
+        self.current.path.push(nesting);
+        let result = within(self);
+        self.current.path.pop();
+        result
+    }
+
+    /// `value` (of type `from`) as a `to`, wrapping a variant into a union
+    fn coerce(&mut self, value: String, from: &Ty, to: &Ty) -> String {
+        let Ty::Named(union) = to else {
+            return value;
+        };
+        match self.types.discriminant(*union, from).filter(|_| from != to) {
+            Some(tag) => format!(
+                "({}){{ .tag = {tag}, .as.{} = {value} }}",
+                self.c_ty(to),
+                self.field(to, tag)
+            ),
+            None => value,
+        }
+    }
+
+    /// the type of `expr`, as the generics stand for here
+    fn ty(&self, expr: &Spanned<Expr>) -> Ty {
+        self.ty_at(expr.span)
+    }
+
+    fn ty_at(&self, span: Span) -> Ty {
+        let inferred = self.current.inferred.exprs.get(&span);
+        inferred
+            .or(self.types.expr(span))
+            .cloned()
+            .unwrap_or(Ty::Error)
+    }
+
+    /// the type of a variable or parameter, as the generics stand for here
+    fn local_ty(&self, id: DefId) -> Option<Ty> {
+        let inferred = self.current.inferred.locals.get(&id);
+        inferred.or(self.types.locals.get(&id)).cloned()
+    }
+
+    /*
+     * functions
+     */
+
+    /// `inferred` is what the generic values are, in a copy of a generic function
+    fn function(
+        &mut self,
+        id: Option<DefId>,
+        name: String,
+        inferred: Inferred,
+        parameters: &[(Spanned<String>, Spanned<String>)],
+        body: &Block,
+    ) -> String {
+        for (span, a, b) in &inferred.conflicts {
+            let what = mono::conflict(self.res, a, b);
+            self.unsupported(what, *span);
+        }
+        let sig = id.map(|id| &self.types.sigs[&id]);
+        let ret = sig.map(|sig| inferred.ret.clone().unwrap_or_else(|| sig.ret.clone()));
+        self.current = Current {
+            ret: ret.clone(),
+            inferred,
+            indent: 1,
+            ..Current::default()
+        };
+        // parameters are named first, so they keep their names
+        let mut params = Vec::new();
+        for (_, param) in parameters {
+            let id = self
+                .res
+                .def_at(param.span)
+                .expect("emit-c: unresolved parameter");
+            let name = unique(&param.spanned, &mut self.current.used);
+            self.current.names.insert(id, name.clone());
+            let ty = self.local_ty(id).expect("emit-c: untyped parameter");
+            params.push(self.declare(&ty, &name));
+        }
+
+        let value = self.block(body);
+        if let (Some(value), Some(ret)) = (value, &ret) {
+            let value = self.coerce(value, &self.block_ty(body), ret);
+            self.line(format!("return {value};"));
+        }
+
+        let mut current = std::mem::take(&mut self.current);
+        let mut targeted = HashSet::new();
+        for (index, (label, path)) in current.gotos.iter().enumerate() {
+            let placeholder = format!("\0goto {index}\0");
+            let enterable = current.labels.get(label).is_some_and(|label| {
+                let shared = label.iter().zip(path).take_while(|(a, b)| a == b).count();
+                label[..shared].last().is_none_or(|nesting| nesting.block)
+                    && label[shared..].iter().all(|nesting| nesting.block)
+            });
+            let replacement = if enterable {
+                targeted.insert(*label);
+                format!("goto {};", current.names[label])
+            } else {
+                let err = RuntimeErrors::JumpIntoExpr;
+                format!("sdw_fail(\"{err}\");")
+            };
+            current.body = current.body.replace(&placeholder, &replacement);
+        }
+        // labels never jumped to would only be warned about
+        for label in current.labels.keys() {
+            let placeholder = format!("\0label {}\0", label.0);
+            let replacement = if targeted.contains(label) {
+                format!("{}: ;", current.names[label])
+            } else {
+                String::new()
+            };
+            current.body = current.body.replace(&placeholder, &replacement);
+        }
+        let mut body = String::new();
+        for line in current.decls.lines().chain(current.body.lines()) {
+            if !line.trim().is_empty() {
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+
+        let ret = match ret {
+            Some(ret) => self.c_ty(&ret),
+            None => "void".to_owned(),
+        };
+        let params = if params.is_empty() {
+            "void".to_owned()
+        } else {
+            params.join(", ")
+        };
+        // everything is local to the one file
+        format!("static {ret} {name}({params}) {{\n{body}}}\n")
+    }
+
+    /// the copy of the function `id` taking `params`
+    fn body(&mut self, id: DefId, name: String, params: &[Ty]) -> String {
+        let Stmt::Fn {
+            parameters, body, ..
+        } = self.infer.bodies[&id]
+        else {
+            unreachable!("emit-c: collected a non-function");
+        };
+        let inferred = self.infer.copy(id, params).clone();
+        self.function(Some(id), name, inferred, parameters, body)
+    }
+
+    /// the block's value, unless it never produces one
+    fn block(&mut self, block: &Block) -> Option<String> {
+        let mut diverges = false;
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+            diverges = match &stmt.spanned {
+                // a label can be jumped to, so whatever follows is reachable again
+                Stmt::Label { .. } => false,
+                // the only ways out of a loop are `goto` & `return`, which go elsewhere
+                Stmt::Goto { .. } | Stmt::Return { .. } | Stmt::Loop { .. } => true,
+                Stmt::VarDec {
+                    initialiser: expr, ..
+                }
+                | Stmt::VarRes { updated: expr, .. }
+                | Stmt::Discard { expr } => diverges || self.ty(expr) == Ty::Never,
+                _ => diverges,
+            };
+        }
+        // the tail is still emitted, as it may hold labels
+        let value = match &block.tail {
+            Some(tail) => self.expr(tail),
+            None => Some("sdw_unit".to_owned()),
+        };
+        value.filter(|_| !diverges)
+    }
+
+    /// the type of the block's value, when it has one
+    fn block_ty(&self, block: &Block) -> Ty {
+        match &block.tail {
+            Some(tail) => self.ty(tail),
+            None => Ty::Void,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { .. } | Stmt::Stub { .. } | Stmt::Type { .. } => {}
+            Stmt::Label { name } => {
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("emit-c: unresolved label");
+                let label = unique(&format!("l_{}", name.spanned), &mut self.current.used);
+                self.current.names.insert(id, label);
+                self.current.labels.insert(id, self.current.path.clone());
+                // the indentation is left, so the label lines up with the code around it
+                let placeholder = format!("\0label {}\0", id.0);
+                self.line(placeholder);
+            }
+            Stmt::Goto { name } => {
+                let label = self.res.def_at(name.span).expect("emit-c: unresolved goto");
+                let index = self.current.gotos.len();
+                self.current.gotos.push((label, self.current.path.clone()));
+                self.line(format!("\0goto {index}\0"));
+            }
+            Stmt::Loop { block } => {
+                self.line("for (;;) {");
+                self.current.indent += 1;
+                self.nest(true, |this| this.block(block));
+                self.current.indent -= 1;
+                self.line("}");
+            }
+            Stmt::Return { expr } => {
+                let value = match expr {
+                    Some(expr) => match self.expr(expr) {
+                        Some(value) => (value, self.ty(expr)),
+                        None => return,
+                    },
+                    None => ("sdw_unit".to_owned(), Ty::Void),
+                };
+                match self.current.ret.clone() {
+                    Some(ret) => {
+                        let value = self.coerce(value.0, &value.1, &ret);
+                        self.line(format!("return {value};"));
+                    }
+                    None => self.line("return;"),
+                }
+            }
+            Stmt::VarDec {
+                name,
+                initialiser: value,
+            }
+            | Stmt::VarRes {
+                name,
+                updated: value,
+            } => {
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("emit-c: unresolved variable");
+                let local = self.local(id);
+                if let Some(result) = self.expr(value) {
+                    let to = self.local_ty(id).expect("emit-c: untyped variable");
+                    let result = self.coerce(result, &self.ty(value), &to);
+                    self.line(format!("{local} = {result};"));
+                }
+            }
+            Stmt::Discard { expr } => self.discard(expr),
+        }
+    }
+
+    /// an expression evaluated only for what it does, which needs nowhere to put its value
+    fn discard(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::FnCall(_, args) => {
+                self.nest(false, |this| this.call(expr.span, args, false));
+            }
+            Expr::Cond { .. } => {
+                self.cond(expr, false);
+            }
+            Expr::Block(block) => self.nest(true, |this| {
+                for stmt in &block.stmts {
+                    this.stmt(&stmt.spanned);
+                }
+                if let Some(tail) = &block.tail {
+                    this.discard(tail);
+                }
+            }),
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.discard(inner),
+            _ => {
+                self.expr(expr);
+            }
+        }
+    }
+
+    /*
+     * expressions
+     */
+
+    /// a C expression for the value, which is safe to evaluate any number of times, in any
+    /// order - everything with an effect is done first, in the order written. `None` if the
+    /// expression never produces a value (eg. it `return`s), & so nothing after it can run.
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Option<String> {
+        let value = match &expr.spanned {
+            Expr::Block(block) => self.nest(true, |this| this.block(block)),
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.expr(inner),
+            Expr::Cond { .. } => self.cond(expr, true),
+            _ => self.nest(false, |this| this.opaque(expr)),
+        }?;
+        match self.ty(expr) {
+            Ty::Never => None,
+            _ => Some(value),
+        }
+    }
+
+    fn opaque(&mut self, expr: &Spanned<Expr>) -> Option<String> {
+        let ty = &self.ty(expr);
+        Some(match &expr.spanned {
+            Expr::IntLiteral(int) => match ty {
+                Ty::Prim(PrimType::Unt) => format!("UINT64_C({})", *int as u64),
+                _ if *int == i64::MIN => "INT64_MIN".to_owned(),
+                _ => format!("INT64_C({int})"),
+            },
+            Expr::BoolLiteral(bool) => bool.to_string(),
+            Expr::StrLiteral(text) => {
+                format!("((sdw_string){{ {}, {} }})", literal(text), text.len())
+            }
+            Expr::Variable(_) => match self.res.get(expr.span) {
+                Some(Res::Def(id)) => {
+                    let local = self.local(*id);
+                    self.temp(ty, &local)
+                }
+                Some(Res::Overloads(ids)) => match self.functions.get(&ids[0]) {
+                    Some(function) => function.clone(),
+                    None if self.res.def(ids[0]).kind == DefKind::Stub => {
+                        let what = "a pointer to an interface stub".to_owned();
+                        self.unsupported(what, expr.span);
+                        "NULL".to_owned()
+                    }
+                    None => {
+                        let what = "a pointer to a generic function".to_owned();
+                        self.unsupported(what, expr.span);
+                        "NULL".to_owned()
+                    }
+                },
+                None => unreachable!("emit-c: unresolved variable"),
+            },
+            Expr::UnaryPos(inner) => self.expr(inner)?,
+            Expr::UnaryNeg(inner) => {
+                let value = self.expr(inner)?;
+                let helper = match ty {
+                    Ty::Prim(PrimType::Unt) => "sdw_neg_unt",
+                    _ => "sdw_neg_int",
+                };
+                self.temp(ty, &format!("{helper}({value})"))
+            }
+            Expr::UnaryNot(inner) => {
+                let value = self.expr(inner)?;
+                self.temp(ty, &format!("!{value}"))
+            }
+            Expr::BiOp(left, op @ (BiOps::LogOr | BiOps::LogAnd), right) => {
+                let value = self.expr(left)?;
+                let result = self.temp(ty, &value);
+                let test = match op {
+                    BiOps::LogOr => format!("if (!{result}) {{"),
+                    _ => format!("if ({result}) {{"),
+                };
+                self.line(test);
+                self.current.indent += 1;
+                if let Some(value) = self.expr(right) {
+                    self.line(format!("{result} = {value};"));
+                }
+                self.current.indent -= 1;
+                self.line("}");
+                result
+            }
+            Expr::BiOp(left, op, right) => {
+                let operands = self.ty(left);
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                let suffix = match operands {
+                    Ty::Prim(PrimType::Unt) => "unt",
+                    _ => "int",
+                };
+                let value = match op {
+                    BiOps::Add => format!("sdw_add_{suffix}({left}, {right})"),
+                    BiOps::Sub => format!("sdw_sub_{suffix}({left}, {right})"),
+                    BiOps::Mul => format!("sdw_mul_{suffix}({left}, {right})"),
+                    BiOps::Div => format!("sdw_div_{suffix}({left}, {right})"),
+                    BiOps::Mod => format!("sdw_mod_{suffix}({left}, {right})"),
+                    BiOps::BitLShift => format!("sdw_shl_{suffix}({left}, {right})"),
+                    BiOps::BitRshift => format!("sdw_shr_{suffix}({left}, {right})"),
+                    BiOps::BitOr | BiOps::BitAnd | BiOps::BitXor => {
+                        format!("{left} {} {right}", op.symbol())
+                    }
+                    BiOps::Eq | BiOps::NEq | BiOps::Gr | BiOps::Ls | BiOps::GrEq | BiOps::LsEq => {
+                        format!("{left} {} {right}", op.symbol())
+                    }
+                    BiOps::BitNot | BiOps::LogNot | BiOps::LogOr | BiOps::LogAnd => {
+                        let err = RuntimeErrors::Unsupported(format!(
+                            "`{}` as a binary operator",
+                            op.symbol()
+                        ));
+                        self.line(format!("sdw_fail(\"{err}\");"));
+                        return None;
+                    }
+                };
+                self.temp(ty, &value)
+            }
+            Expr::Referal(inner) => {
+                let place = self.place(inner)?;
+                format!("&{place}")
+            }
+            Expr::Indir(inner) => {
+                let pointer = self.expr(inner)?;
+                self.temp(ty, &format!("*{pointer}"))
+            }
+            Expr::ObjMember(object, member) => {
+                let place = self.member(object, member);
+                self.temp(ty, &place)
+            }
+            Expr::FnCall(_, args) => self.call(expr.span, args, true)?,
+            Expr::StructLit { fields, .. } => self.struct_lit(expr, fields)?,
+            Expr::Block(_) | Expr::SubExpr(_) | Expr::Attributed { .. } | Expr::Cond { .. } => {
+                unreachable!("emit-c: enterable expression emitted as opaque")
+            }
+        })
+    }
+
+    /// `wanted` is false when the value is discarded, so needs no variable to hold it
+    fn cond(&mut self, expr: &Spanned<Expr>, wanted: bool) -> Option<String> {
+        let Expr::Cond {
+            condition,
+            then,
+            elifs,
+            r#else,
+        } = &expr.spanned
+        else {
+            unreachable!("emit-c: not a condition");
+        };
+        let ty = &self.ty(expr);
+        let result = (wanted && *ty != Ty::Never).then(|| self.temp_of(ty));
+
+        let branches = std::iter::once((condition, then)).chain(elifs.iter().map(|(c, b)| (c, b)));
+        // each condition is evaluated within the `else` of the one before it
+        let mut opened = 0;
+        let mut reachable = true;
+        for (condition, block) in branches {
+            if opened > 0 {
+                self.line("} else {");
+                self.current.indent += 1;
+            }
+            let Some(condition) = self.nest(false, |this| this.expr(condition)) else {
+                reachable = false;
+                break;
+            };
+            self.line(format!("if ({condition}) {{"));
+            self.current.indent += 1;
+            self.branch(&block.spanned, result.as_deref(), ty);
+            self.current.indent -= 1;
+            opened += 1;
+        }
+        if reachable {
+            match (r#else, &result) {
+                (Some(block), _) => {
+                    self.line("} else {");
+                    self.current.indent += 1;
+                    self.branch(&block.spanned, result.as_deref(), ty);
+                    self.current.indent -= 1;
+                }
+                (None, Some(result)) => {
+                    self.line("} else {");
+                    let value = self.coerce("sdw_unit".to_owned(), &Ty::Void, ty);
+                    self.line(format!("    {result} = {value};"));
+                }
+                (None, None) => {}
+            }
+            self.line("}");
+        }
+        for _ in 1..opened {
+            self.current.indent -= 1;
+            self.line("}");
+        }
+        match result {
+            Some(result) => Some(result),
+            None => Some("sdw_unit".to_owned()),
+        }
+    }
+
+    fn branch(&mut self, block: &Block, result: Option<&str>, ty: &Ty) {
+        let Some(result) = result else {
+            self.nest(true, |this| {
+                for stmt in &block.stmts {
+                    this.stmt(&stmt.spanned);
+                }
+                if let Some(tail) = &block.tail {
+                    this.discard(tail);
+                }
+            });
+            return;
+        };
+        if let Some(value) = self.nest(true, |this| this.block(block)) {
+            let value = self.coerce(value, &self.block_ty(block), ty);
+            self.line(format!("{result} = {value};"));
+        }
+    }
+
+    /// a C lvalue for `object.member`, checking a union holds the member
+    fn member(&mut self, object: &Spanned<String>, member: &Spanned<String>) -> String {
+        let id = self
+            .res
+            .def_at(object.span)
+            .expect("emit-c: unresolved object");
+        let ty = &self.local_ty(id).expect("emit-c: untyped object");
+        let index = self
+            .types
+            .members(ty)
+            .expect("emit-c: member of a type without members")
+            .iter()
+            .position(|(name, _)| *name == member.spanned)
+            .expect("emit-c: unknown member");
+        let local = self.local(id);
+        let (access, target) = match ty {
+            Ty::Pointer(to) => ("->", &**to),
+            _ => (".", ty),
+        };
+        let field = self.field(target, index);
+        if self.union_members(target).is_none() {
+            return format!("{local}{access}{field}");
+        }
+
+        let union = self.c_ty(target);
+        self.line(format!(
+            "if ({local}{access}tag != {index}) sdw_fail(\"the union holds its `%s` member, not \
+             `%s`\", {union}_members[{local}{access}tag], \"{}\");",
+            member.spanned
+        ));
+        format!("{local}{access}as.{field}")
+    }
+
+    /// a C lvalue for where a place expression (`x`, `x.member`, `*x`) is kept
+    fn place(&mut self, expr: &Spanned<Expr>) -> Option<String> {
+        match &expr.spanned {
+            Expr::Variable(_) => match self.res.get(expr.span) {
+                Some(Res::Def(id)) => Some(self.local(*id)),
+                _ => self.expr(expr),
+            },
+            Expr::SubExpr(inner) => self.place(inner),
+            Expr::Indir(inner) => Some(format!("(*{})", self.expr(inner)?)),
+            Expr::ObjMember(object, member) => Some(self.member(object, member)),
+            // a temporary, which lives as long as the function
+            _ => {
+                let value = self.expr(expr)?;
+                Some(self.temp(&self.ty(expr), &value))
+            }
+        }
+    }
+
+    fn call(&mut self, span: Span, args: &[Box<Spanned<Expr>>], wanted: bool) -> Option<String> {
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.expr(arg)?);
+        }
+        let arg_tys = args.iter().map(|arg| self.ty(arg)).collect::<Vec<_>>();
+        let target = match (self.types.dispatch(span), self.types.call(span)) {
+            (None, Some(function)) if Intrinsic::of(self.res, self.types, function).is_none() => {
+                Some(self.target(function, &arg_tys)?)
+            }
+            _ => None,
+        };
+        let ty = &self.ty_at(span);
+        let result = (wanted && *ty != Ty::Never).then(|| self.temp_of(ty));
+        let assign = |call: String, ret: &Ty, this: &mut Self| match &result {
+            Some(result) => {
+                let call = this.coerce(call, ret, ty);
+                format!("{result} = {call};")
+            }
+            None => format!("{call};"),
+        };
+
+        if let Some(dispatch) = self.types.dispatch(span) {
+            let args = values
+                .into_iter()
+                .zip(arg_tys.iter().map(|ty| (*ty).clone()))
+                .collect::<Vec<_>>();
+            self.dispatch(dispatch, &args, &assign);
+        } else if let Some((name, params, ret)) = target {
+            let values = values
+                .into_iter()
+                .zip(&arg_tys)
+                .zip(&params)
+                .map(|((value, from), param)| self.coerce(value, from, param))
+                .collect::<Vec<_>>();
+            let call = format!("{name}({})", values.join(", "));
+            let call = assign(call, &ret, self);
+            self.line(call);
+        } else if let Some(function) = self.types.call(span) {
+            let sig = &self.types.sigs[&function];
+            if let Some(intrinsic) = Intrinsic::of(self.res, self.types, function) {
+                let call = match intrinsic {
+                    Intrinsic::Syscall => {
+                        let mut args = values
+                            .iter()
+                            .map(|value| format!("(int64_t)(intptr_t){value}"))
+                            .collect::<Vec<_>>();
+                        args.resize(SYSCALL_ARGS + 1, "0".to_owned());
+                        let call = format!("sdw_syscall({})", args.join(", "));
+                        match sig.ret {
+                            Ty::Void => format!("({call}, sdw_unit)"),
+                            _ => format!("({}){call}", self.c_ty(&sig.ret)),
+                        }
+                    }
+                    Intrinsic::Alloc => format!("sdw_alloc({})", values[0]),
+                    Intrinsic::Panic => format!("sdw_panic({})", values[0]),
+                    Intrinsic::Address => format!("(int64_t)(intptr_t){}.ptr", values[0]),
+                    Intrinsic::Length => format!("{}.len", values[0]),
+                    Intrinsic::Byte => format!("(int64_t)*(uint8_t *)(intptr_t){}", values[0]),
+                    Intrinsic::Store => format!(
+                        "(*(uint8_t *)(intptr_t){} = (uint8_t){}, sdw_unit)",
+                        values[0], values[1]
+                    ),
+                };
+                let call = assign(call, &sig.ret, self);
+                self.line(call);
+            }
+        } else {
+            let Some(Res::Def(id)) = self.res.get(span) else {
+                unreachable!("emit-c: unresolved call");
+            };
+            let Some(Ty::FnPtr { args: params, ret }) = &self.local_ty(*id) else {
+                unreachable!("emit-c: called a non-function");
+            };
+            let pointer = self.local(*id);
+            let values = values
+                .into_iter()
+                .zip(&arg_tys)
+                .zip(params)
+                .map(|((value, from), param)| self.coerce(value, from, param))
+                .collect::<Vec<_>>();
+            let call = format!("{pointer}({})", values.join(", "));
+            let call = assign(call, ret, self);
+            self.line(call);
+        }
+        match (*ty == Ty::Never, result) {
+            (true, _) => None,
+            (false, Some(result)) => Some(result),
+            (false, None) => Some("sdw_unit".to_owned()),
+        }
+    }
+
+    /// a `switch` on the tag of the union argument `dispatch.arg`, calling the overload for
+    /// each member - or switching on another argument too. `args` are the values & their types.
+    fn dispatch(
+        &mut self,
+        dispatch: &Dispatch,
+        args: &[(String, Ty)],
+        assign: &dyn Fn(String, &Ty, &mut Self) -> String,
+    ) {
+        let union = args[dispatch.arg].0.clone();
+        self.line(format!("switch ({union}.tag) {{"));
+        let members = self
+            .union_members(&Ty::Named(dispatch.union))
+            .expect("emit-c: dispatched on a non-union");
+        for (tag, arm) in dispatch.arms.iter().enumerate() {
+            // the last is the default, so the result is always assigned as far as C knows
+            let case = if tag + 1 == dispatch.arms.len() {
+                "default:".to_owned()
+            } else {
+                format!("case {tag}:")
+            };
+            let field = self.field(&Ty::Named(dispatch.union), tag);
+            let mut arm_args = args.to_vec();
+            arm_args[dispatch.arg] = (format!("{union}.as.{field}"), members[tag].1.clone());
+
+            match arm {
+                Arm::Call(target) => {
+                    let tys = arm_args
+                        .iter()
+                        .map(|(_, ty)| ty.clone())
+                        .collect::<Vec<_>>();
+                    let Some((function, params, ret)) = self.target(*target, &tys) else {
+                        self.line(format!("{case} break;"));
+                        continue;
+                    };
+                    let values = arm_args
+                        .into_iter()
+                        .zip(&params)
+                        .map(|((value, from), param)| self.coerce(value, &from, param))
+                        .collect::<Vec<_>>();
+                    let call = format!("{function}({})", values.join(", "));
+                    let call = assign(call, &ret, self);
+                    self.line(format!("{case} {call} break;"));
+                }
+                Arm::Dispatch(nested) => {
+                    self.line(format!("{case} {{"));
+                    self.current.indent += 1;
+                    self.dispatch(nested, &arm_args, assign);
+                    self.line("break;");
+                    self.current.indent -= 1;
+                    self.line("}");
+                }
+            }
+        }
+        self.line("}");
+    }
+
+    /// the C function a call of `function` (with arguments of the types `args`) goes to,
+    /// along with its parameter & return types - for a stub, its implementation, & for a
+    /// generic function, the copy for the types of its arguments. `None` if there isn't one
+    fn target(&mut self, function: DefId, args: &[Ty]) -> Option<(String, Vec<Ty>, Ty)> {
+        let Some((function, params)) = self.infer.target(function, args) else {
+            let err = RuntimeErrors::NoImplementation(self.res.def(function).name.clone());
+            self.line(format!("sdw_fail(\"{err}\");"));
+            return None;
+        };
+        let sig = &self.types.sigs[&function];
+        if let Some(name) = self.functions.get(&function) {
+            return Some((name.clone(), sig.params.clone(), sig.ret.clone()));
+        }
+        let ret = self.infer.copy(function, &params).ret.clone();
+        let ret = ret.unwrap_or_else(|| sig.ret.clone());
+        Some((self.instance(function, params.clone()), params, ret))
+    }
+
+    /// the name of the copy of `function` taking `params`, which is emitted later if it's new
+    fn instance(&mut self, function: DefId, params: Vec<Ty>) -> String {
+        if let Some(instance) = self
+            .instances
+            .iter()
+            .find(|instance| instance.function == function && instance.params == params)
+        {
+            return instance.name.clone();
+        }
+        let name = self.mangled(function, &params);
+        self.instances.push(Instance {
+            function,
+            params,
+            name: name.clone(),
+        });
+        name
+    }
+
+    /// overloads (& copies of a generic function) are told apart by the types of their
+    /// parameters
+    fn mangled(&mut self, function: DefId, params: &[Ty]) -> String {
+        let mut name = format!("f_{}", self.res.def(function).name);
+        if !params.is_empty() {
+            name.push_str("__");
+            for param in params {
+                mono::mangle(self.res, param, &mut name);
+            }
+        }
+        unique(&name, &mut self.globals)
+    }
+
+    fn struct_lit(&mut self, expr: &Spanned<Expr>, fields: &StructLitFields) -> Option<String> {
+        let ty = &self.ty(expr);
+        let Ty::Named(id) = ty else {
+            unreachable!("emit-c: struct literal of a non-struct");
+        };
+        let (members, union) = match self.types.decls.get(id) {
+            Some(TypeDecl::Struct(members)) => (members, false),
+            Some(TypeDecl::Union(members)) => (members, true),
+            _ => unreachable!("emit-c: struct literal of a non-struct"),
+        };
+
+        // evaluated in the order written, not the order declared
+        let mut values = Vec::new();
+        match fields {
+            StructLitFields::Positional(exprs) => {
+                for (index, value) in exprs.iter().enumerate() {
+                    let result = self.expr(value)?;
+                    let result = self.coerce(result, &self.ty(value), &members[index].1);
+                    values.push((index, result));
+                }
+            }
+            StructLitFields::Named(exprs) => {
+                for (name, value) in exprs {
+                    let index = members
+                        .iter()
+                        .position(|(member, _)| *member == name.spanned)
+                        .expect("emit-c: unknown member");
+                    let result = self.expr(value)?;
+                    let result = self.coerce(result, &self.ty(value), &members[index].1);
+                    values.push((index, result));
+                }
+            }
+        }
+
+        let c_ty = self.c_ty(ty);
+        if !union {
+            let fields = values
+                .iter()
+                .map(|(index, value)| format!(".{} = {value}", self.field(ty, *index)))
+                .collect::<Vec<_>>();
+            let fields = if fields.is_empty() {
+                "0".to_owned()
+            } else {
+                fields.join(", ")
+            };
+            return Some(self.temp(ty, &format!("({c_ty}){{ {fields} }}")));
+        }
+        // only the last member given is kept
+        match (fields, values.pop()) {
+            (StructLitFields::Named(_), Some((index, value))) => {
+                let field = self.field(ty, index);
+                let value = format!("({c_ty}){{ .tag = {index}, .as.{field} = {value} }}");
+                Some(self.temp(ty, &value))
+            }
+            _ => {
+                let err = RuntimeErrors::Unsupported("a union literal without a member".to_owned());
+                self.line(format!("sdw_fail(\"{err}\");"));
+                None
+            }
+        }
+    }
+}
+
+/// lowers a checked module to a single C99 file, with a copy of each generic function for
+/// each set of types it's called with. a program which uses what can't be emitted yet
+/// (pointers to generic functions & interface stubs, structs holding generics) raises errors
+/// in `state`.
+pub fn emit(state: &mut State, checked: &Checked) -> String {
+    let Checked {
+        ast, res, types, ..
+    } = checked;
+    let mut emitter = Emitter {
+        state,
+        res,
+        types,
+        infer: Infer::new(res, types, HashMap::new()),
+        functions: HashMap::new(),
+        instances: Vec::new(),
+        globals: HashSet::new(),
+        type_names: HashMap::new(),
+        fn_ptrs: Vec::new(),
+        fn_ptr_decls: String::new(),
+        nestings: 0,
+        current: Current::default(),
+    };
+
+    let Some((main, parameters)) = interp::main_fn(checked) else {
+        emitter
+            .state
+            .errors
+            .push(SdwErr::from_pos(EmitErrors::NoMain, Span::default()));
+        return String::new();
+    };
+    if parameters != 0 {
+        let err = SdwErr::from_pos(EmitErrors::MainTakesParams, res.def(main).span);
+        emitter.state.errors.push(err);
+        return String::new();
+    }
+
+    let mut forward = String::new();
+    let mut ids = types.decls.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    for id in ids {
+        if let Some(TypeDecl::Struct(_) | TypeDecl::Union(_)) = types.decls.get(&id) {
+            let name = unique(&format!("t_{}", res.def(id).name), &mut emitter.globals);
+            let _ = writeln!(forward, "typedef struct {name} {name};");
+            emitter.type_names.insert(id, name);
+        }
+    }
+
+    // a generic function is only emitted as a copy for each set of types it's called with
+    let mut bodies = Vec::new();
+    collect(&mut bodies, ast, res);
+    let mut concrete = Vec::new();
+    for (id, stmt) in bodies {
+        emitter.infer.bodies.insert(id, stmt);
+        if !emitter.infer.is_generic(id) {
+            let name = emitter.mangled(id, &types.sigs[&id].params);
+            emitter.functions.insert(id, name);
+            concrete.push(id);
+        }
+    }
+
+    let definitions = emitter.type_definitions();
+    let inferred = emitter.infer.top_level(ast);
+    let mut functions = vec![emitter.function(None, "sdw_init".to_owned(), inferred, &[], ast)];
+    for id in concrete {
+        let name = emitter.functions[&id].clone();
+        functions.push(emitter.body(id, name, &types.sigs[&id].params));
+    }
+    // emitting one copy may call for more
+    let mut next = 0;
+    while let Some(instance) = emitter.instances.get(next) {
+        let (id, name, params) = (
+            instance.function,
+            instance.name.clone(),
+            instance.params.clone(),
+        );
+        functions.push(emitter.body(id, name, &params));
+        next += 1;
+    }
+    let mut prototypes = String::new();
+    for function in &functions[1..] {
+        let _ = writeln!(
+            prototypes,
+            "{};",
+            function.lines().next().unwrap().trim_end_matches(" {")
+        );
+    }
+
+    let ret = &types.sigs[&main].ret;
+    let exit = if ret.is_integer() {
+        // as with `sdw run`, the result becomes the exit code
+        format!("return (int)(uint8_t){}();", emitter.functions[&main])
+    } else {
+        format!("{}();\n    return 0;", emitter.functions[&main])
+    };
+
+    let mut out = RUNTIME.to_owned();
+    for section in [&forward, &emitter.fn_ptr_decls, &definitions, &prototypes] {
+        if !section.is_empty() {
+            out.push('\n');
+            out.push_str(section);
+        }
+    }
+    for function in functions {
+        out.push('\n');
+        out.push_str(&function);
+    }
+    let _ = write!(
+        out,
+        "\nint main(void) {{\n    sdw_init();\n    {exit}\n}}\n"
+    );
+    out
+}
+
+/// `text` as a C string literal. bytes outside of printable ascii are escaped in octal, which
+/// (unlike hex) can't run on into the characters after it
+fn literal(text: &str) -> String {
+    let mut out = "\"".to_owned();
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            // `??` could start a trigraph
+            b' '..=b'~' if byte != b'?' => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{byte:03o}");
+            }
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// every function with a body, wherever it's declared
+fn collect<'a>(bodies: &mut Vec<(DefId, &'a Stmt)>, block: &'a Block, res: &Resolutions) {
+    let mut exprs = Vec::new();
+    for stmt in &block.stmts {
+        match &stmt.spanned {
+            Stmt::Fn { name, body, .. } => {
+                if let Some(id) = res.def_at(name.span) {
+                    bodies.push((id, &stmt.spanned));
+                }
+                collect(bodies, body, res);
+            }
+            Stmt::Loop { block } => collect(bodies, block, res),
+            Stmt::Return { expr: Some(expr) }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. }
+            | Stmt::Discard { expr } => exprs.push(expr),
+            _ => {}
+        }
+    }
+    exprs.extend(block.tail.as_deref());
+    while let Some(expr) = exprs.pop() {
+        match &expr.spanned {
+            Expr::Block(block) => collect(bodies, block, res),
+            Expr::Cond {
+                then,
+                elifs,
+                r#else,
+                ..
+            } => {
+                collect(bodies, &then.spanned, res);
+                for (_, block) in elifs {
+                    collect(bodies, &block.spanned, res);
+                }
+                if let Some(r#else) = r#else {
+                    collect(bodies, &r#else.spanned, res);
+                }
+            }
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => exprs.push(inner),
+            _ => {}
+        }
+    }
+}
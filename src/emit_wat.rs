@@ -0,0 +1,1302 @@
+use crate::bytecode::Trap;
+use crate::driver::Checked;
+use crate::interp;
+use crate::intrinsics::{Intrinsic, SYSCALL_ARGS};
+use crate::ir::{BlockId, Function, Inst, Module, Terminator, Value};
+use crate::layout::{self, Layout, Layouts, Target};
+use crate::prelude::*;
+use crate::relooper::{self, Shape};
+use crate::{lower, mono, passes};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const TARGET: &str = "WebAssembly";
+
+/// where runtime error messages are formatted, before being passed to `sdw.fail`
+const BUFFER: u32 = 0;
+const BUFFER_SIZE: u32 = 1024;
+/// where string constants start
+const DATA: u32 = BUFFER + BUFFER_SIZE;
+/// how much of linear memory the stack of aggregates & variables gets
+const STACK_SIZE: u32 = 1 << 20;
+const PAGE_SIZE: u32 = 1 << 16;
+
+/// how a value is held
+#[derive(Debug, Clone)]
+enum Class {
+    /// not at all - `void`, & structs without members
+    Void,
+    /// in a local of the value type, & as `size` bytes in memory
+    Scalar { ty: &'static str, size: u64 },
+    /// in linear memory, referred to by its address
+    Aggregate(Layout),
+}
+
+impl Class {
+    fn size(&self) -> u64 {
+        match self {
+            Class::Void => 0,
+            Class::Scalar { size, .. } => *size,
+            Class::Aggregate(layout) => layout.size,
+        }
+    }
+
+    /// the value type a value of the class is passed around as
+    fn wasm(&self) -> Option<&'static str> {
+        match self {
+            Class::Void => None,
+            Class::Scalar { ty, .. } => Some(ty),
+            Class::Aggregate(_) => Some("i32"),
+        }
+    }
+
+    fn load(&self) -> &'static str {
+        match self {
+            Class::Scalar { ty: "i32", size: 1 } => "i32.load8_u",
+            Class::Scalar { ty: "i64", .. } => "i64.load",
+            Class::Scalar { ty: "f64", .. } => "f64.load",
+            _ => "i32.load",
+        }
+    }
+
+    fn store(&self) -> &'static str {
+        match self {
+            Class::Scalar { ty: "i32", size: 1 } => "i32.store8",
+            Class::Scalar { ty: "i64", .. } => "i64.store",
+            Class::Scalar { ty: "f64", .. } => "f64.store",
+            _ => "i32.store",
+        }
+    }
+}
+
+fn align(offset: u32, to: u32) -> u32 {
+    offset.div_ceil(to) * to
+}
+
+/// where a function's values are kept - aggregates & variables in its frame, on the stack
+/// in linear memory, & everything else in locals
+#[derive(Default)]
+struct Frame {
+    slots: HashMap<Value, u32>,
+    /// where a phi's value is put by each predecessor, before the phi's block copies it into
+    /// its own slot (or local) - so phis reading each other see the values from before the
+    /// branch
+    incoming: HashMap<Value, u32>,
+    size: u32,
+    /// has a pointer to write its result through, as its first parameter
+    sret: bool,
+    labels: u32,
+}
+
+impl Frame {
+    fn alloc(&mut self, size: u64) -> u32 {
+        let at = align(self.size, 8);
+        self.size = at + size as u32;
+        at
+    }
+}
+
+struct Emitter<'a, 's> {
+    state: &'s mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    layouts: Layouts,
+    module: &'a Module,
+    out: String,
+    indent: usize,
+    /// the contents of linear memory from `DATA`
+    data: Vec<u8>,
+    strings: HashMap<String, u32>,
+    /// the table of a union's member names, by its id
+    member_names: HashMap<DefId, u32>,
+    /// the type of each function signature called through a pointer, by its text
+    fn_types: Vec<String>,
+    frame: Frame,
+}
+
+impl<'a, 's> Emitter<'a, 's> {
+    fn unsupported(&mut self, what: String, span: Span) {
+        let err = EmitErrors::Unsupported {
+            what,
+            target: TARGET.to_owned(),
+        };
+        self.state.errors.push(SdwErr::from_pos(err, span));
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(line.as_ref());
+        self.out.push('\n');
+    }
+
+    /// the address of a NUL-terminated string constant
+    fn string(&mut self, text: &str) -> u32 {
+        if let Some(at) = self.strings.get(text) {
+            return *at;
+        }
+        let at = DATA + self.data.len() as u32;
+        self.data.extend(text.as_bytes());
+        self.data.push(0);
+        self.strings.insert(text.to_owned(), at);
+        at
+    }
+
+    /// the address of a table of pointers to the names of a union's members
+    fn member_names(&mut self, id: DefId) -> u32 {
+        if let Some(at) = self.member_names.get(&id) {
+            return *at;
+        }
+        let Some(TypeDecl::Union(members)) = self.types.decls.get(&id) else {
+            unreachable!("emit-wat: member names of a non-union");
+        };
+        let names = members
+            .iter()
+            .map(|(name, _)| self.string(name))
+            .collect::<Vec<_>>();
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        let at = DATA + self.data.len() as u32;
+        for name in names {
+            self.data.extend(name.to_le_bytes());
+        }
+        self.member_names.insert(id, at);
+        at
+    }
+
+    fn symbol(&self, function: u32) -> String {
+        format!("$fn.{}", self.module.functions[function as usize].name)
+    }
+
+    fn new_label(&mut self) -> String {
+        self.frame.labels += 1;
+        format!("$l{}", self.frame.labels)
+    }
+
+    /*
+     * types
+     */
+
+    fn resolve<'t>(&'t self, ty: &'t Ty) -> &'t Ty {
+        match ty {
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Alias(inner)) => self.resolve(inner),
+                _ => ty,
+            },
+            ty => ty,
+        }
+    }
+
+    /// `None` for types without a layout (generics), & those this backend can't hold yet
+    fn class(&self, ty: &Ty) -> Option<Class> {
+        Some(match self.resolve(ty) {
+            Ty::Void | Ty::Never | Ty::Error => Class::Void,
+            Ty::Prim(PrimType::Int | PrimType::Unt) => Class::Scalar { ty: "i64", size: 8 },
+            Ty::Prim(PrimType::Float) => Class::Scalar { ty: "f64", size: 8 },
+            Ty::Prim(PrimType::Bool) => Class::Scalar { ty: "i32", size: 1 },
+            Ty::Prim(PrimType::String) => Class::Aggregate(self.layouts.of(&Ty::STRING)?),
+            Ty::Pointer(_) | Ty::FnPtr { .. } => Class::Scalar { ty: "i32", size: 4 },
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Struct(_) | TypeDecl::Union(_)) => {
+                    let layout = self.layouts.decl(*id)?.clone();
+                    match layout.size {
+                        0 => Class::Void,
+                        _ => Class::Aggregate(layout),
+                    }
+                }
+                _ => return None,
+            },
+        })
+    }
+
+    fn class_of(&self, function: &Function, value: Value) -> Class {
+        self.class(function.ty(value))
+            .expect("emit-wat: a value without a class")
+    }
+
+    /// `(param ..) (result ..)`, for a function taking & returning these types - results
+    /// which live in memory are written through a pointer passed first instead
+    fn signature(&self, params: &[Ty], ret: &Ty) -> String {
+        let ret = self.class(ret).expect("emit-wat: a result without a class");
+        let mut params = params
+            .iter()
+            .filter_map(|param| self.class(param).and_then(|class| class.wasm()))
+            .collect::<Vec<_>>();
+        let mut signature = String::new();
+        if let Class::Aggregate(_) = ret {
+            params.insert(0, "i32");
+        }
+        if !params.is_empty() {
+            let _ = write!(signature, "(param {})", params.join(" "));
+        }
+        if let Class::Scalar { ty, .. } = ret {
+            let _ = write!(
+                signature,
+                "{}(result {ty})",
+                if params.is_empty() { "" } else { " " }
+            );
+        }
+        signature
+    }
+
+    /*
+     * values
+     */
+
+    fn local(value: Value) -> String {
+        format!("$v{}", value.0)
+    }
+
+    /// pushes a value - the address of those in memory
+    fn get(&mut self, function: &Function, value: Value) {
+        match (function.inst(value), self.class_of(function, value)) {
+            (_, Class::Void) => {}
+            (Inst::Alloca, _) | (_, Class::Aggregate(_)) => self.address(function, value, 0),
+            _ => self.line(format!("local.get {}", Self::local(value))),
+        }
+    }
+
+    /// pushes the address of a value in memory, plus `offset`
+    fn address(&mut self, function: &Function, value: Value, offset: u64) {
+        match function.inst(value) {
+            Inst::Param(index) => self.line(format!("local.get $p{index}")),
+            _ => {
+                self.line("local.get $fp");
+                self.line(format!("i32.const {}", self.frame.slots[&value]));
+                self.line("i32.add");
+            }
+        }
+        if offset != 0 {
+            self.line(format!("i32.const {offset}"));
+            self.line("i32.add");
+        }
+    }
+
+    fn set(&mut self, function: &Function, value: Value) {
+        if let Class::Scalar { .. } = self.class_of(function, value) {
+            self.line(format!("local.set {}", Self::local(value)));
+        }
+    }
+
+    /// stores a value at the address on top of the stack
+    fn put(&mut self, function: &Function, value: Value) {
+        match self.class_of(function, value) {
+            Class::Void => self.line("drop"),
+            Class::Aggregate(layout) => {
+                self.address(function, value, 0);
+                self.line(format!("i32.const {}", layout.size));
+                self.line("memory.copy");
+            }
+            class => {
+                self.get(function, value);
+                self.line(class.store());
+            }
+        }
+    }
+
+    /// loads a value from the address on top of the stack
+    fn take(&mut self, function: &Function, value: Value) {
+        match self.class_of(function, value) {
+            Class::Void => self.line("drop"),
+            Class::Aggregate(layout) => {
+                // `memory.copy` wants the destination first
+                self.line("local.set $tmp");
+                self.address(function, value, 0);
+                self.line("local.get $tmp");
+                self.line(format!("i32.const {}", layout.size));
+                self.line("memory.copy");
+            }
+            class => {
+                self.line(class.load());
+                self.set(function, value);
+            }
+        }
+    }
+
+    /// raises a runtime error - see `$sdw_fail` for what `message` may contain
+    fn fail(&mut self, message: &str) {
+        let at = self.string(message);
+        self.line(format!("i32.const {at}"));
+        self.line("i64.const 0");
+        self.line("i64.const 0");
+        self.line("call $sdw_fail");
+    }
+
+    /*
+     * functions
+     */
+
+    fn function(&mut self, index: usize, function: &'a Function) {
+        let span = function
+            .id
+            .map(|id| self.res.def(id).span)
+            .unwrap_or_default();
+        if function
+            .params
+            .iter()
+            .chain([&function.ret])
+            .any(|ty| self.class(ty).is_none())
+        {
+            let what = format!("`{}`, which is generic,", function.name);
+            self.unsupported(what, span);
+            return;
+        }
+
+        self.frame = Frame::default();
+        let mut locals = vec![
+            "(local $label i32)".to_owned(),
+            "(local $fp i32)".to_owned(),
+        ];
+        locals.push("(local $tmp i32)".to_owned());
+        for block in &function.blocks {
+            for &value in &block.insts {
+                let Some(class) = self.class(function.ty(value)) else {
+                    let what = format!("`{}`, which holds a generic,", function.name);
+                    self.unsupported(what, span);
+                    return;
+                };
+                let inst = function.inst(value);
+                match (inst, &class) {
+                    (Inst::Alloca, _) => {
+                        let size = self.pointee(function.ty(value)).map_or(0, |c| c.size());
+                        let slot = self.frame.alloc(size);
+                        self.frame.slots.insert(value, slot);
+                    }
+                    (_, Class::Void) => {}
+                    (Inst::Param(_), Class::Aggregate(_)) => {}
+                    (_, Class::Aggregate(layout)) => {
+                        let slot = self.frame.alloc(layout.size);
+                        self.frame.slots.insert(value, slot);
+                        if let Inst::Phi(_) = inst {
+                            let incoming = self.frame.alloc(layout.size);
+                            self.frame.incoming.insert(value, incoming);
+                        }
+                    }
+                    (_, Class::Scalar { ty, .. }) => {
+                        locals.push(format!("(local {} {ty})", Self::local(value)));
+                        if let Inst::Phi(_) = inst {
+                            locals.push(format!("(local $in{} {ty})", value.0));
+                        }
+                    }
+                }
+            }
+        }
+        self.frame.size = align(self.frame.size, 16);
+
+        let ret = self.class(&function.ret).expect("emit-wat: checked above");
+        self.frame.sret = matches!(ret, Class::Aggregate(_));
+        let mut params = Vec::new();
+        if self.frame.sret {
+            params.push("(param $sret i32)".to_owned());
+        }
+        for (index, param) in function.params.iter().enumerate() {
+            if let Some(ty) = self.class(param).and_then(|class| class.wasm()) {
+                params.push(format!("(param $p{index} {ty})"));
+            }
+        }
+        let result = match ret {
+            Class::Scalar { ty, .. } => format!(" (result {ty})"),
+            _ => String::new(),
+        };
+
+        let symbol = self.symbol(index as u32);
+        self.line(format!("(func {symbol} {}{result}", params.join(" ")));
+        self.indent += 1;
+        for local in locals {
+            self.line(local);
+        }
+        // the frame is taken from the stack, which grows down
+        self.line("global.get $sp");
+        self.line(format!("i32.const {}", self.frame.size));
+        self.line("i32.sub");
+        self.line("local.tee $fp");
+        self.line("global.set $sp");
+        self.line("local.get $fp");
+        self.line("global.get $sdw_stack_end");
+        self.line("i32.lt_u");
+        self.line("if");
+        self.line("  unreachable");
+        self.line("end");
+
+        let shape = relooper::reloop(function);
+        self.shape(function, &shape, &HashMap::new());
+        self.line("unreachable");
+        self.indent -= 1;
+        self.line(")");
+    }
+
+    fn pointee(&self, ty: &Ty) -> Option<Class> {
+        match self.resolve(ty) {
+            Ty::Pointer(to) => self.class(to),
+            _ => None,
+        }
+    }
+
+    /// the structured control flow of a shape. `targets` are the labels to branch to, to get
+    /// to each block outside of it
+    fn shape(&mut self, function: &Function, shape: &Shape, targets: &HashMap<BlockId, String>) {
+        let mut inner = targets.clone();
+        let next = match shape {
+            Shape::Simple { next, .. }
+            | Shape::Loop { next, .. }
+            | Shape::Multiple { next, .. } => next.as_deref(),
+        };
+        // leaving the shape is branching to the end of a block around it
+        if let Some(next) = next {
+            let label = self.new_label();
+            self.line(format!("block {label}"));
+            self.indent += 1;
+            for entry in next.entries() {
+                inner.insert(entry, label.clone());
+            }
+        }
+
+        match shape {
+            Shape::Simple { block, .. } => self.block(function, *block, &inner),
+            Shape::Loop {
+                entries,
+                inner: body,
+                ..
+            } => {
+                let label = self.new_label();
+                self.line(format!("loop {label}"));
+                self.indent += 1;
+                for entry in entries {
+                    inner.insert(*entry, label.clone());
+                }
+                self.shape(function, body, &inner);
+                self.indent -= 1;
+                self.line("end");
+            }
+            Shape::Multiple { handled, .. } => {
+                for (entry, body) in handled {
+                    self.line("local.get $label");
+                    self.line(format!("i32.const {}", entry.0));
+                    self.line("i32.eq");
+                    self.line("if");
+                    self.indent += 1;
+                    self.shape(function, body, &inner);
+                    self.indent -= 1;
+                    self.line("end");
+                }
+            }
+        }
+
+        if let Some(next) = next {
+            self.indent -= 1;
+            self.line("end");
+            self.shape(function, next, targets);
+        }
+    }
+
+    fn block(&mut self, function: &Function, block: BlockId, targets: &HashMap<BlockId, String>) {
+        for &value in &function.block(block).insts {
+            match (function.inst(value), self.class_of(function, value)) {
+                (Inst::Phi(_), Class::Void) => {}
+                (Inst::Phi(_), Class::Aggregate(layout)) => {
+                    self.address(function, value, 0);
+                    self.line("local.get $fp");
+                    self.line(format!("i32.const {}", self.frame.incoming[&value]));
+                    self.line("i32.add");
+                    self.line(format!("i32.const {}", layout.size));
+                    self.line("memory.copy");
+                }
+                (Inst::Phi(_), _) => {
+                    self.line(format!("local.get $in{}", value.0));
+                    self.set(function, value);
+                }
+                _ => self.inst(function, value),
+            }
+        }
+        self.terminator(function, block, targets);
+    }
+
+    fn inst(&mut self, function: &Function, value: Value) {
+        match function.inst(value) {
+            Inst::Void | Inst::Undef | Inst::Alloca | Inst::Phi(_) => {}
+            Inst::Param(index) => {
+                if let Class::Scalar { .. } = self.class_of(function, value) {
+                    self.line(format!("local.get $p{index}"));
+                    self.set(function, value);
+                }
+            }
+            Inst::Int(int) => {
+                self.line(format!("i64.const {int}"));
+                self.set(function, value);
+            }
+            Inst::Unt(unt) => {
+                self.line(format!("i64.const {}", *unt as i64));
+                self.set(function, value);
+            }
+            Inst::Bool(bool) => {
+                self.line(format!("i32.const {}", u8::from(*bool)));
+                self.set(function, value);
+            }
+            Inst::Str(text) => {
+                let at = self.string(text);
+                self.address(function, value, 0);
+                self.line(format!("i32.const {at}"));
+                self.line("i32.store");
+                self.address(function, value, 4);
+                self.line(format!("i32.const {}", text.len()));
+                self.line("i32.store");
+            }
+            Inst::Fn(index) => {
+                // functions are in the table in order
+                self.line(format!("i32.const {index}"));
+                self.set(function, value);
+            }
+            Inst::Neg(operand) => {
+                self.get(function, *operand);
+                match self.class_of(function, value) {
+                    Class::Scalar { ty: "f64", .. } => self.line("f64.neg"),
+                    _ => {
+                        let (suffix, _) = self.integer(function.ty(value));
+                        self.line(format!("call $sdw_neg_{suffix}"));
+                    }
+                }
+                self.set(function, value);
+            }
+            Inst::Not(operand) => {
+                self.get(function, *operand);
+                self.line("i32.eqz");
+                self.set(function, value);
+            }
+            Inst::BiOp(op, left, right) => {
+                self.get(function, *left);
+                self.get(function, *right);
+                let class = self.class_of(function, *left);
+                self.biop(function.ty(*left), &class, *op);
+                self.set(function, value);
+            }
+            Inst::Load(pointer) => {
+                self.get(function, *pointer);
+                self.take(function, value);
+            }
+            Inst::Store { pointer, value } => {
+                self.get(function, *pointer);
+                self.put(function, *value);
+            }
+            Inst::MemberPtr(pointer, member) => {
+                let Ty::Pointer(to) = self.resolve(function.ty(*pointer)).clone() else {
+                    unreachable!("emit-wat: a member through a non-pointer");
+                };
+                self.get(function, *pointer);
+                self.line("local.set $tmp");
+                let offset = self.member(&to, *member);
+                self.line("local.get $tmp");
+                self.line(format!("i32.const {offset}"));
+                self.line("i32.add");
+                self.set(function, value);
+            }
+            Inst::Member(object, member) => {
+                if let Class::Void = self.class_of(function, *object) {
+                    return;
+                }
+                self.address(function, *object, 0);
+                self.line("local.set $tmp");
+                let offset = self.member(function.ty(*object), *member);
+                self.line("local.get $tmp");
+                self.line(format!("i32.const {offset}"));
+                self.line("i32.add");
+                self.take(function, value);
+            }
+            Inst::Struct(fields) => {
+                let Class::Aggregate(layout) = self.class_of(function, value) else {
+                    return;
+                };
+                for (field, at) in fields.iter().zip(&layout.fields) {
+                    self.address(function, value, at.offset);
+                    self.put(function, *field);
+                }
+            }
+            Inst::Union { tag, value: held } => {
+                let Class::Aggregate(layout) = self.class_of(function, value) else {
+                    return;
+                };
+                self.address(function, value, 0);
+                self.line(format!("i32.const {tag}"));
+                self.line("i32.store");
+                self.address(function, value, layout.fields[*tag as usize].offset);
+                self.put(function, *held);
+            }
+            Inst::Tag(union) => {
+                self.address(function, *union, 0);
+                self.line("i32.load");
+                self.line("i64.extend_i32_u");
+                self.set(function, value);
+            }
+            Inst::Call {
+                function: index,
+                args,
+            } => {
+                if let Class::Aggregate(_) = self.class_of(function, value) {
+                    self.address(function, value, 0);
+                }
+                for arg in args {
+                    self.get(function, *arg);
+                }
+                let symbol = self.symbol(*index);
+                self.line(format!("call {symbol}"));
+                self.set(function, value);
+            }
+            Inst::CallPtr { pointer, args } => {
+                if let Class::Aggregate(_) = self.class_of(function, value) {
+                    self.address(function, value, 0);
+                }
+                for arg in args {
+                    self.get(function, *arg);
+                }
+                self.get(function, *pointer);
+                let params = args
+                    .iter()
+                    .map(|arg| function.ty(*arg).clone())
+                    .collect::<Vec<_>>();
+                let signature = self.signature(&params, function.ty(value));
+                let index = match self.fn_types.iter().position(|ty| *ty == signature) {
+                    Some(index) => index,
+                    None => {
+                        self.fn_types.push(signature);
+                        self.fn_types.len() - 1
+                    }
+                };
+                self.line(format!("call_indirect (type $t{index})"));
+                self.set(function, value);
+            }
+            // what's left is a call no implementation fits
+            Inst::CallInterface { stub, .. } => {
+                let err = RuntimeErrors::NoImplementation(self.res.def(*stub).name.clone());
+                self.fail(&err.to_string());
+                self.line("unreachable");
+            }
+            Inst::Intrinsic { intrinsic, args } => {
+                self.intrinsic(function, *intrinsic, args, value)
+            }
+        }
+    }
+
+    fn intrinsic(
+        &mut self,
+        function: &Function,
+        intrinsic: Intrinsic,
+        args: &[Value],
+        value: Value,
+    ) {
+        match intrinsic {
+            Intrinsic::Syscall => {
+                for arg in args {
+                    self.get(function, *arg);
+                    if let Some("i32") = self.class_of(function, *arg).wasm() {
+                        self.line("i64.extend_i32_u");
+                    }
+                }
+                for _ in args.len()..=SYSCALL_ARGS {
+                    self.line("i64.const 0");
+                }
+                self.line("call $syscall");
+                match self.class_of(function, value) {
+                    Class::Void => self.line("drop"),
+                    _ => self.set(function, value),
+                }
+            }
+            Intrinsic::Alloc => {
+                self.get(function, args[0]);
+                self.line("call $sdw_alloc");
+                self.set(function, value);
+            }
+            // literals are the only strings, & each is followed by a NUL
+            Intrinsic::Panic => {
+                let at = self.string("panicked: %s");
+                self.line(format!("i32.const {at}"));
+                self.get(function, args[0]);
+                self.line("i32.load");
+                self.line("i64.extend_i32_u");
+                self.line("i64.const 0");
+                self.line("call $sdw_fail");
+            }
+            Intrinsic::Address | Intrinsic::Length => {
+                self.get(function, args[0]);
+                let offset = if intrinsic == Intrinsic::Address {
+                    0
+                } else {
+                    4
+                };
+                self.line(format!("i32.load offset={offset}"));
+                self.line("i64.extend_i32_u");
+                self.set(function, value);
+            }
+            Intrinsic::Byte => {
+                self.get(function, args[0]);
+                self.line("i32.wrap_i64");
+                self.line("i64.load8_u");
+                self.set(function, value);
+            }
+            Intrinsic::Store => {
+                self.get(function, args[0]);
+                self.line("i32.wrap_i64");
+                self.get(function, args[1]);
+                self.line("i64.store8");
+            }
+        }
+    }
+
+    /// the suffix of the runtime's functions for `int` or `unt`, & whether it's signed
+    fn integer(&self, ty: &Ty) -> (&'static str, bool) {
+        match self.resolve(ty) {
+            Ty::Prim(PrimType::Int) => ("int", true),
+            _ => ("unt", false),
+        }
+    }
+
+    /// the two operands on the stack, into the result
+    fn biop(&mut self, ty: &Ty, class: &Class, op: BiOps) {
+        let (suffix, signed) = self.integer(ty);
+        let sign = if signed { "_s" } else { "_u" };
+        let wasm = class.wasm().unwrap_or("i32");
+        let compare = |name: &str| match wasm {
+            "f64" => format!("f64.{name}"),
+            _ => format!("{wasm}.{name}{sign}"),
+        };
+        let line = match op {
+            // floats aren't checked
+            BiOps::Add if wasm == "f64" => "f64.add".to_owned(),
+            BiOps::Sub if wasm == "f64" => "f64.sub".to_owned(),
+            BiOps::Mul if wasm == "f64" => "f64.mul".to_owned(),
+            BiOps::Div if wasm == "f64" => "f64.div".to_owned(),
+            BiOps::Add => format!("call $sdw_add_{suffix}"),
+            BiOps::Sub => format!("call $sdw_sub_{suffix}"),
+            BiOps::Mul => format!("call $sdw_mul_{suffix}"),
+            BiOps::Div => format!("call $sdw_div_{suffix}"),
+            BiOps::Mod => format!("call $sdw_mod_{suffix}"),
+            BiOps::BitLShift => format!("call $sdw_shl_{suffix}"),
+            BiOps::BitRshift => format!("call $sdw_shr_{suffix}"),
+            BiOps::BitOr => format!("{wasm}.or"),
+            BiOps::BitAnd => format!("{wasm}.and"),
+            BiOps::BitXor => format!("{wasm}.xor"),
+            // `void`s are always equal
+            BiOps::Eq if class.wasm().is_none() => "i32.const 1".to_owned(),
+            BiOps::NEq if class.wasm().is_none() => "i32.const 0".to_owned(),
+            BiOps::Eq => format!("{wasm}.eq"),
+            BiOps::NEq => format!("{wasm}.ne"),
+            BiOps::Gr => compare("gt"),
+            BiOps::Ls => compare("lt"),
+            BiOps::GrEq => compare("ge"),
+            BiOps::LsEq => compare("le"),
+            BiOps::BitNot | BiOps::LogNot | BiOps::LogOr | BiOps::LogAnd => {
+                let err =
+                    RuntimeErrors::Unsupported(format!("`{}` as a binary operator", op.symbol()));
+                self.fail(&err.to_string());
+                "unreachable".to_owned()
+            }
+        };
+        self.line(line);
+    }
+
+    /// the offset of a member of a value of type `ty`, whose address is in `$tmp` - checking,
+    /// for a union, that it's the member held
+    fn member(&mut self, ty: &Ty, member: u32) -> u64 {
+        let Some(Class::Aggregate(layout)) = self.class(ty) else {
+            return 0;
+        };
+        let field = &layout.fields[member as usize];
+        if layout.discriminant.is_some() {
+            let Ty::Named(id) = *self.resolve(ty) else {
+                unreachable!("emit-wat: an unnamed union");
+            };
+            let names = self.member_names(id);
+            let err = RuntimeErrors::WrongMember {
+                held: "%s".to_owned(),
+                member: field.name.clone(),
+            };
+            let message = self.string(&err.to_string());
+            self.line("local.get $tmp");
+            self.line("i32.load");
+            self.line(format!("i32.const {member}"));
+            self.line("i32.ne");
+            self.line("if");
+            self.indent += 1;
+            self.line(format!("i32.const {message}"));
+            self.line("local.get $tmp");
+            self.line("i32.load");
+            self.line("i32.const 4");
+            self.line("i32.mul");
+            self.line(format!("i32.load offset={names}"));
+            self.line("i64.extend_i32_u");
+            self.line("i64.const 0");
+            self.line("call $sdw_fail");
+            self.indent -= 1;
+            self.line("end");
+        }
+        field.offset
+    }
+
+    /// sets `label` to the block, then branches to it
+    fn branch(&mut self, to: BlockId, targets: &HashMap<BlockId, String>) {
+        self.line(format!("i32.const {}", to.0));
+        self.line("local.set $label");
+        self.line(format!("br {}", targets[&to]));
+    }
+
+    fn terminator(
+        &mut self,
+        function: &Function,
+        block: BlockId,
+        targets: &HashMap<BlockId, String>,
+    ) {
+        let term = &function.block(block).term;
+        // phis of the blocks branched to take their values from this one
+        for succ in term.succs() {
+            for &value in &function.block(succ).insts {
+                let Inst::Phi(incoming) = function.inst(value) else {
+                    continue;
+                };
+                let Some(&(_, from)) = incoming.iter().find(|(pred, _)| *pred == block) else {
+                    continue;
+                };
+                match self.class_of(function, value) {
+                    Class::Void => {}
+                    Class::Aggregate(_) => {
+                        self.line("local.get $fp");
+                        self.line(format!("i32.const {}", self.frame.incoming[&value]));
+                        self.line("i32.add");
+                        self.put(function, from);
+                    }
+                    Class::Scalar { .. } => {
+                        self.get(function, from);
+                        self.line(format!("local.set $in{}", value.0));
+                    }
+                }
+            }
+        }
+
+        match term {
+            Terminator::Jump(to) => self.branch(*to, targets),
+            Terminator::Branch {
+                condition,
+                then,
+                r#else,
+            } => {
+                self.get(function, *condition);
+                self.line("if");
+                self.indent += 1;
+                self.branch(*then, targets);
+                self.indent -= 1;
+                self.line("end");
+                self.branch(*r#else, targets);
+            }
+            Terminator::Switch { tag, targets: arms } => {
+                for (index, arm) in arms.iter().enumerate() {
+                    self.get(function, *tag);
+                    self.line(format!("i64.const {index}"));
+                    self.line("i64.eq");
+                    self.line("if");
+                    self.indent += 1;
+                    self.branch(*arm, targets);
+                    self.indent -= 1;
+                    self.line("end");
+                }
+                self.line("unreachable");
+            }
+            Terminator::Return(value) => {
+                if self.frame.sret {
+                    self.line("local.get $sret");
+                    self.put(function, *value);
+                } else {
+                    self.get(function, *value);
+                }
+                self.line("local.get $fp");
+                self.line(format!("i32.const {}", self.frame.size));
+                self.line("i32.add");
+                self.line("global.set $sp");
+                self.line("return");
+            }
+            Terminator::Trap(trap) => {
+                let err = match trap {
+                    Trap::JumpIntoExpr => RuntimeErrors::JumpIntoExpr,
+                    Trap::EmptyUnion => {
+                        RuntimeErrors::Unsupported("a union literal without a member".to_owned())
+                    }
+                };
+                self.fail(&err.to_string());
+                self.line("unreachable");
+            }
+            Terminator::Unreachable => self.line("unreachable"),
+        }
+    }
+
+    /// checked arithmetic, raising the same errors (with the same messages) as the
+    /// interpreter
+    fn runtime(&mut self) {
+        for (suffix, signed) in [("int", true), ("unt", false)] {
+            let spec = if signed { "%d" } else { "%u" };
+            let shown = |op: BiOps| format!("{spec} {} {spec}", op.symbol().replace('%', "%%"));
+            let overflow = |op: BiOps| {
+                RuntimeErrors::Overflow {
+                    expr: shown(op),
+                    ty: suffix.to_owned(),
+                }
+                .to_string()
+            };
+            let fail = |this: &mut Self, message: String| {
+                let at = this.string(&message);
+                format!("(call $sdw_fail (i32.const {at}) (local.get $a) (local.get $b))")
+            };
+            let neg = RuntimeErrors::Overflow {
+                expr: format!("-{spec}"),
+                ty: suffix.to_owned(),
+            }
+            .to_string();
+            let neg = fail(self, neg);
+            let add = fail(self, overflow(BiOps::Add));
+            let sub = fail(self, overflow(BiOps::Sub));
+            let mul = fail(self, overflow(BiOps::Mul));
+            let div_overflow = fail(self, overflow(BiOps::Div));
+            let mod_overflow = fail(self, overflow(BiOps::Mod));
+            let div_zero = fail(
+                self,
+                RuntimeErrors::DivByZero(shown(BiOps::Div)).to_string(),
+            );
+            let mod_zero = fail(
+                self,
+                RuntimeErrors::DivByZero(shown(BiOps::Mod)).to_string(),
+            );
+            let shl = fail(
+                self,
+                RuntimeErrors::OversizedShift(shown(BiOps::BitLShift)).to_string(),
+            );
+            let shr = fail(
+                self,
+                RuntimeErrors::OversizedShift(shown(BiOps::BitRshift)).to_string(),
+            );
+            let s = if signed { "s" } else { "u" };
+
+            let header = |name: &str| {
+                format!("(func $sdw_{name}_{suffix} (param $a i64) (param $b i64) (result i64)")
+            };
+            let mut functions = Vec::new();
+            if signed {
+                functions.push(format!(
+                    "(func $sdw_neg_int (param $a i64) (result i64) (local $b i64)
+  (if (i64.eq (local.get $a) (i64.const -9223372036854775808)) (then {neg}))
+  (i64.sub (i64.const 0) (local.get $a)))"
+                ));
+                functions.push(format!(
+                    "{} (local $r i64)
+  (local.set $r (i64.add (local.get $a) (local.get $b)))
+  (if (i64.lt_s (i64.and (i64.xor (local.get $a) (local.get $r))
+                         (i64.xor (local.get $b) (local.get $r))) (i64.const 0))
+    (then {add}))
+  (local.get $r))",
+                    header("add")
+                ));
+                functions.push(format!(
+                    "{} (local $r i64)
+  (local.set $r (i64.sub (local.get $a) (local.get $b)))
+  (if (i64.lt_s (i64.and (i64.xor (local.get $a) (local.get $b))
+                         (i64.xor (local.get $a) (local.get $r))) (i64.const 0))
+    (then {sub}))
+  (local.get $r))",
+                    header("sub")
+                ));
+                functions.push(format!(
+                    "{} (local $r i64)
+  (local.set $r (i64.mul (local.get $a) (local.get $b)))
+  (if (i64.eq (local.get $a) (i64.const -1))
+    (then (if (i64.eq (local.get $b) (i64.const -9223372036854775808)) (then {mul})))
+    (else (if (i64.ne (local.get $a) (i64.const 0))
+      (then (if (i64.ne (i64.div_s (local.get $r) (local.get $a)) (local.get $b))
+        (then {mul}))))))
+  (local.get $r))",
+                    header("mul")
+                ));
+            } else {
+                functions.push(format!(
+                    "(func $sdw_neg_unt (param $a i64) (result i64) (local $b i64)
+  (if (i64.ne (local.get $a) (i64.const 0)) (then {neg}))
+  (i64.const 0))"
+                ));
+                functions.push(format!(
+                    "{} (local $r i64)
+  (local.set $r (i64.add (local.get $a) (local.get $b)))
+  (if (i64.lt_u (local.get $r) (local.get $a)) (then {add}))
+  (local.get $r))",
+                    header("add")
+                ));
+                functions.push(format!(
+                    "{}
+  (if (i64.lt_u (local.get $a) (local.get $b)) (then {sub}))
+  (i64.sub (local.get $a) (local.get $b)))",
+                    header("sub")
+                ));
+                functions.push(format!(
+                    "{} (local $r i64)
+  (local.set $r (i64.mul (local.get $a) (local.get $b)))
+  (if (i64.ne (local.get $a) (i64.const 0))
+    (then (if (i64.ne (i64.div_u (local.get $r) (local.get $a)) (local.get $b))
+      (then {mul}))))
+  (local.get $r))",
+                    header("mul")
+                ));
+            }
+            // the only quotient which doesn't fit
+            let min_by_minus_one = |overflow: &str| match signed {
+                true => format!(
+                    "
+  (if (i32.and (i64.eq (local.get $a) (i64.const -9223372036854775808))
+               (i64.eq (local.get $b) (i64.const -1)))
+    (then {overflow}))"
+                ),
+                false => String::new(),
+            };
+            functions.push(format!(
+                "{}
+  (if (i64.eqz (local.get $b)) (then {div_zero})){}
+  (i64.div_{s} (local.get $a) (local.get $b)))",
+                header("div"),
+                min_by_minus_one(&div_overflow)
+            ));
+            functions.push(format!(
+                "{}
+  (if (i64.eqz (local.get $b)) (then {mod_zero})){}
+  (i64.rem_{s} (local.get $a) (local.get $b)))",
+                header("mod"),
+                min_by_minus_one(&mod_overflow)
+            ));
+            functions.push(format!(
+                "{}
+  (if (i64.ge_u (local.get $b) (i64.const 64)) (then {shl}))
+  (i64.shl (local.get $a) (local.get $b)))",
+                header("shl")
+            ));
+            functions.push(format!(
+                "{}
+  (if (i64.ge_u (local.get $b) (i64.const 64)) (then {shr}))
+  (i64.shr_{s} (local.get $a) (local.get $b)))",
+                header("shr")
+            ));
+            for function in functions {
+                for line in function.lines() {
+                    self.line(line);
+                }
+            }
+        }
+    }
+}
+
+/// the parts of the runtime which don't depend on the program: formatting & reporting
+/// runtime errors
+const FAIL: &str = r#"(func $sdw_put_str (param $out i32) (param $str i32) (result i32)
+  (local $c i32)
+  (block $done
+    (loop $next
+      (local.set $c (i32.load8_u (local.get $str)))
+      (br_if $done (i32.eqz (local.get $c)))
+      (i32.store8 (local.get $out) (local.get $c))
+      (local.set $out (i32.add (local.get $out) (i32.const 1)))
+      (local.set $str (i32.add (local.get $str) (i32.const 1)))
+      (br $next)))
+  (local.get $out))
+(func $sdw_put_int (param $out i32) (param $value i64) (param $signed i32) (result i32)
+  (local $start i32) (local $end i32) (local $c i32)
+  (if (i32.and (local.get $signed) (i64.lt_s (local.get $value) (i64.const 0)))
+    (then
+      (i32.store8 (local.get $out) (i32.const 45))
+      (local.set $out (i32.add (local.get $out) (i32.const 1)))
+      (local.set $value (i64.sub (i64.const 0) (local.get $value)))))
+  ;; the digits are written backwards, then reversed
+  (local.set $start (local.get $out))
+  (loop $digits
+    (i32.store8 (local.get $out)
+      (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $value) (i64.const 10)))))
+    (local.set $out (i32.add (local.get $out) (i32.const 1)))
+    (local.set $value (i64.div_u (local.get $value) (i64.const 10)))
+    (br_if $digits (i64.ne (local.get $value) (i64.const 0))))
+  (local.set $end (i32.sub (local.get $out) (i32.const 1)))
+  (block $done
+    (loop $reverse
+      (br_if $done (i32.ge_u (local.get $start) (local.get $end)))
+      (local.set $c (i32.load8_u (local.get $start)))
+      (i32.store8 (local.get $start) (i32.load8_u (local.get $end)))
+      (i32.store8 (local.get $end) (local.get $c))
+      (local.set $start (i32.add (local.get $start) (i32.const 1)))
+      (local.set $end (i32.sub (local.get $end) (i32.const 1)))
+      (br $reverse)))
+  (local.get $out))
+;; reports a runtime error through `sdw.fail`. the message is `$format`, in which `%d` & `%u`
+;; are replaced with `$a` then `$b` (as signed & unsigned), `%s` with the string they point to,
+;; & `%%` with `%`
+(func $sdw_fail (param $format i32) (param $a i64) (param $b i64)
+  (local $out i32) (local $c i32)
+  (local.set $out (i32.const BUFFER))
+  (block $done
+    (loop $next
+      (local.set $c (i32.load8_u (local.get $format)))
+      (br_if $done (i32.eqz (local.get $c)))
+      (local.set $format (i32.add (local.get $format) (i32.const 1)))
+      (if (i32.eq (local.get $c) (i32.const 37))
+        (then
+          (local.set $c (i32.load8_u (local.get $format)))
+          (local.set $format (i32.add (local.get $format) (i32.const 1)))
+          (if (i32.ne (local.get $c) (i32.const 37))
+            (then
+              (if (i32.eq (local.get $c) (i32.const 115))
+                (then
+                  (local.set $out
+                    (call $sdw_put_str (local.get $out) (i32.wrap_i64 (local.get $a)))))
+                (else
+                  (local.set $out (call $sdw_put_int
+                    (local.get $out) (local.get $a) (i32.eq (local.get $c) (i32.const 100))))))
+              (local.set $a (local.get $b))
+              (br $next)))))
+      (i32.store8 (local.get $out) (local.get $c))
+      (local.set $out (i32.add (local.get $out) (i32.const 1)))
+      (br $next)))
+  (call $fail (i32.const BUFFER) (i32.sub (local.get $out) (i32.const BUFFER)))
+  (unreachable))"#;
+
+/// hands out memory from the end of the stack on, which is never freed - growing memory when
+/// it runs out
+const ALLOC: &str = r#"(func $sdw_alloc (param $size i64) (result i64)
+  (local $at i32) (local $end i64)
+  (if (i64.ge_u (local.get $size) (i64.const 4294967296))
+    (then (call $sdw_fail (i32.const MESSAGE) (local.get $size) (i64.const 0))))
+  (local.set $at (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+  (local.set $end (i64.add (i64.extend_i32_u (local.get $at)) (local.get $size)))
+  (if (i64.gt_u (local.get $end) (i64.const 4294967296))
+    (then (call $sdw_fail (i32.const MESSAGE) (local.get $size) (i64.const 0))))
+  (if (i64.gt_u (local.get $end) (i64.mul (i64.extend_i32_u (memory.size)) (i64.const PAGE)))
+    (then
+      (if (i32.eq (i32.const -1) (memory.grow (i32.wrap_i64 (i64.sub
+            (i64.div_u (i64.add (local.get $end) (i64.const PAGE_LESS_ONE)) (i64.const PAGE))
+            (i64.extend_i32_u (memory.size))))))
+        (then (call $sdw_fail (i32.const MESSAGE) (local.get $size) (i64.const 0))))))
+  (global.set $heap (i32.wrap_i64 (local.get $end)))
+  (i64.extend_i32_u (local.get $at)))"#;
+
+/// a string usable within a WebAssembly text string
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                let _ = write!(escaped, "\\{}", *byte as char);
+            }
+            0x20..=0x7e => escaped.push(*byte as char),
+            _ => {
+                let _ = write!(escaped, "\\{byte:02x}");
+            }
+        }
+    }
+    escaped
+}
+
+/// compiles a checked module to the WebAssembly text format, via its optimised IR, with a
+/// copy of each generic function for each set of types it's called with.
+///
+/// the module imports `sdw.fail(message, length)`, called with a runtime error's message
+/// (in its exported `memory`) before it traps, & `sdw.syscall(number, [6 arguments])` for
+/// `core`'s `syscall`s, whose addresses are into `memory`. it exports `main`, which runs the
+/// top level then returns what `main` does.
+pub fn emit(state: &mut State, checked: &Checked) -> String {
+    let Some((main, parameters)) = interp::main_fn(checked) else {
+        state
+            .errors
+            .push(SdwErr::from_pos(EmitErrors::NoMain, Span::default()));
+        return String::new();
+    };
+    if parameters != 0 {
+        let err = SdwErr::from_pos(EmitErrors::MainTakesParams, checked.res.def(main).span);
+        state.errors.push(err);
+        return String::new();
+    }
+
+    let mut module = lower::lower(checked);
+    mono::monomorphise(state, &mut module, checked, TARGET);
+    passes::optimise(&mut module);
+    let mut emitter = Emitter {
+        state,
+        res: &checked.res,
+        types: &checked.types,
+        layouts: layout::compute(&checked.types, &Target::WASM32),
+        module: &module,
+        out: String::new(),
+        indent: 1,
+        data: Vec::new(),
+        strings: HashMap::new(),
+        member_names: HashMap::new(),
+        fn_types: Vec::new(),
+        frame: Frame::default(),
+    };
+    for (index, function) in module.functions.iter().enumerate() {
+        emitter.function(index, function);
+    }
+    emitter.runtime();
+    for line in FAIL.replace("BUFFER", &BUFFER.to_string()).lines() {
+        emitter.line(line);
+    }
+    let message = emitter.string("the %d bytes at 0 aren't all in memory");
+    let alloc = ALLOC
+        .replace("MESSAGE", &message.to_string())
+        .replace("PAGE_LESS_ONE", &(PAGE_SIZE - 1).to_string())
+        .replace("PAGE", &PAGE_SIZE.to_string());
+    for line in alloc.lines() {
+        emitter.line(line);
+    }
+
+    // `main` runs after the top level. a result in memory is written to the top of the stack
+    let main = module.main.expect("emit-wat: `main` wasn't lowered");
+    let ret = emitter
+        .class(&module.functions[main as usize].ret)
+        .unwrap_or(Class::Void);
+    let result = match ret {
+        Class::Scalar { ty, .. } => format!(" (result {ty})"),
+        _ => String::new(),
+    };
+    emitter.line(format!("(func $sdw_main{result}"));
+    emitter.indent += 1;
+    emitter.line(format!("call {}", emitter.symbol(0)));
+    if let Class::Aggregate(layout) = &ret {
+        emitter.line("global.get $sp");
+        emitter.line(format!("i32.const {}", align(layout.size as u32, 16)));
+        emitter.line("i32.sub");
+        emitter.line("global.set $sp");
+        emitter.line("global.get $sp");
+    }
+    emitter.line(format!("call {}", emitter.symbol(main)));
+    emitter.indent -= 1;
+    emitter.line(")");
+
+    let stack_end = align(DATA + emitter.data.len() as u32, 16);
+    let top = stack_end + STACK_SIZE;
+    let pages = top.div_ceil(PAGE_SIZE);
+    let mut out = String::from("(module\n");
+    out.push_str("  (import \"sdw\" \"fail\" (func $fail (param i32 i32)))\n");
+    let _ = writeln!(
+        out,
+        "  (import \"sdw\" \"syscall\" (func $syscall (param{}) (result i64)))",
+        " i64".repeat(SYSCALL_ARGS + 1)
+    );
+    for (index, signature) in emitter.fn_types.iter().enumerate() {
+        let _ = writeln!(out, "  (type $t{index} (func {signature}))");
+    }
+    let _ = writeln!(out, "  (memory (export \"memory\") {pages})");
+    let _ = writeln!(out, "  (global $sp (mut i32) (i32.const {top}))");
+    let _ = writeln!(out, "  (global $sdw_stack_end i32 (i32.const {stack_end}))");
+    let _ = writeln!(out, "  (global $heap (mut i32) (i32.const {top}))");
+    let functions = (0..module.functions.len())
+        .map(|index| emitter.symbol(index as u32))
+        .collect::<Vec<_>>();
+    let _ = writeln!(out, "  (table {} funcref)", functions.len());
+    let _ = writeln!(out, "  (elem (i32.const 0) func {})", functions.join(" "));
+    let _ = writeln!(
+        out,
+        "  (data (i32.const {DATA}) \"{}\")",
+        escape(&emitter.data)
+    );
+    out.push_str("  (export \"main\" (func $sdw_main))\n");
+    out.push_str(&emitter.out);
+    out.push_str(")\n");
+    out
+}
@@ -1,4 +1,5 @@
 use crate::common::Span;
+use crate::driver::{self, CORE, CORE_LINE};
 use owo_colors::OwoColorize;
 use thiserror::Error;
 
@@ -8,83 +9,144 @@ pub type Result<T> = std::result::Result<T, SdwErr>;
 pub struct SdwErr {
     pub ty: ErrType,
     pub span: Span,
+    /// secondary locations relevant to the error (eg. a previous declaration)
+    pub notes: Vec<Note>,
+}
+
+#[derive(Debug)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
 }
 
 fn repeat_char(ch: char, len: usize) -> String {
-    std::iter::repeat(ch).take(len).collect::<String>()
+    std::iter::repeat_n(ch, len).collect::<String>()
+}
+
+/// the text a span points into - the file's, or `core`'s - and the span within it
+fn locate(raw: &str, span: Span) -> (&str, Span) {
+    if !driver::in_core(span) {
+        return (raw, span);
+    }
+    let span = Span {
+        sline: span.sline - CORE_LINE,
+        eline: span.eline - CORE_LINE,
+        ..span
+    };
+    (CORE, span)
 }
 
 impl SdwErr {
-    fn header(&self) {
-        eprint!(
-            "{} ",
-            format!(
-                "[SDW E/{}]",
-                match self.ty {
-                    ErrType::Lex(_) => "L",
-                    ErrType::Parse(_) => "P",
-                },
-            )
-            .red()
+    fn header(&self, warning: bool) {
+        let code = format!(
+            "[SDW {}/{}]",
+            if warning { "W" } else { "E" },
+            self.ty.code(),
         );
+        if warning {
+            eprint!("{} ", code.yellow());
+        } else {
+            eprint!("{} ", code.red());
+        }
         eprintln!("{}", self.ty);
+        let (within, span) = match driver::in_core(self.span) {
+            true => (" of `core`", locate("", self.span).1),
+            false => ("", self.span),
+        };
         eprintln!(
-            "{} error occurred at {}, {}.",
+            "{} {} occurred at {}{within}, {}.",
             "->".blue(),
-            ("line ".to_owned() + &(self.span.sline + 1).to_string()).blue(),
-            ("character ".to_owned() + &(self.span.scol + 1).to_string()).blue()
+            if warning { "warning" } else { "error" },
+            ("line ".to_owned() + &(span.sline + 1).to_string()).blue(),
+            ("character ".to_owned() + &span.scol.to_string()).blue()
         );
     }
 
-    fn body(&self, raw: &str) {
-        let lines = raw.split('\n').collect::<Vec<&str>>();
-        if self.span.sline > 0 {
+    fn body(&self, raw: &str, notice: &str) {
+        let (text, span) = locate(raw, self.span);
+        let lines = text.split('\n').collect::<Vec<&str>>();
+        if span.sline > 0 {
             eprintln!("{}", "[ .. ]".bright_green());
         };
 
-        for idx in self.span.sline..self.span.eline {
-            let line = lines.get(idx as usize).unwrap();
-
-            // ew
-            let scol = if idx == self.span.sline {
-                self.span.scol as usize
-            } else {
-                1
-            };
-            let ecol = if idx + 1 == self.span.eline {
-                self.span.ecol as usize
-            } else {
-                line.len() + 1
-            };
-            let notice = if idx + 1 == self.span.eline {
-                " - error occured here"
-            } else {
-                ""
-            };
-
-            eprintln!("{}", line);
-            eprintln!(
-                "{}{}{}",
-                repeat_char(' ', scol - 1),
-                repeat_char('^', ecol - scol).red(),
-                notice.red()
-            );
-        }
+        snippet(&lines, span, '^', notice);
 
-        if self.span.eline as usize + 1 != lines.len() {
+        if span.eline as usize + 1 != lines.len() {
             eprintln!("{}", "[ .. ]".bright_green());
         };
+
+        for note in &self.notes {
+            eprintln!("{} {}", "note:".blue(), note.message);
+            if let Some(span) = note.span {
+                let (text, span) = locate(raw, span);
+                snippet(&text.split('\n').collect::<Vec<_>>(), span, '-', "");
+            }
+        }
     }
 
     pub fn print(&self, raw: &str) {
-        self.header();
-        self.body(raw);
+        self.header(false);
+        self.body(raw, " - error occured here");
+    }
+
+    pub fn print_warning(&self, raw: &str) {
+        self.header(true);
+        self.body(raw, " - warning raised here");
     }
 
     pub fn from_pos<T: Into<ErrType>>(err: T, span: Span) -> Self {
         Self {
             ty: err.into(),
             span,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>, span: Span) -> Self {
+        self.notes.push(Note {
+            message: message.into(),
+            span: Some(span),
+        });
+        self
+    }
+}
+
+/// prints the lines covered by `span`, underlining the spanned section with `marker`
+fn snippet(lines: &[&str], span: Span, marker: char, notice: &str) {
+    for idx in span.sline..span.eline {
+        let Some(line) = lines.get(idx as usize) else {
+            break;
+        };
+
+        // ew
+        let scol = if idx == span.sline {
+            span.scol as usize
+        } else {
+            1
+        };
+        let ecol = if idx + 1 == span.eline {
+            span.ecol as usize
+        } else {
+            line.len() + 1
+        };
+        let notice = if idx + 1 == span.eline { notice } else { "" };
+
+        eprintln!("{}", line);
+        let underline = repeat_char(marker, ecol.saturating_sub(scol));
+        if marker == '^' {
+            eprintln!(
+                "{}{}{}",
+                repeat_char(' ', scol.saturating_sub(1)),
+                underline.red(),
+                notice.red()
+            );
+        } else {
+            eprintln!(
+                "{}{}{}",
+                repeat_char(' ', scol.saturating_sub(1)),
+                underline.blue(),
+                notice.blue()
+            );
         }
     }
 }
@@ -118,6 +180,30 @@ OUTPUT:
 pub enum ErrType {
     Lex(LexErrors),
     Parse(ParseErrors),
+    Resolve(ResolveErrors),
+    Flow(FlowErrors),
+    Type(TypeErrors),
+    Const(ConstErrors),
+    Runtime(RuntimeErrors),
+    Emit(EmitErrors),
+    Format(FormatErrors),
+}
+
+impl ErrType {
+    /// the letter shown for the kind of error (`[SDW E/P]`)
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrType::Lex(_) => "L",
+            ErrType::Parse(_) => "P",
+            ErrType::Resolve(_) => "R",
+            ErrType::Flow(_) => "F",
+            ErrType::Type(_) => "T",
+            ErrType::Const(_) => "C",
+            ErrType::Runtime(_) => "X",
+            ErrType::Emit(_) => "B",
+            ErrType::Format(_) => "S",
+        }
+    }
 }
 
 impl std::fmt::Display for ErrType {
@@ -128,6 +214,13 @@ impl std::fmt::Display for ErrType {
             match self {
                 Self::Lex(err) => format!("{}", err),
                 Self::Parse(err) => format!("{}", err),
+                Self::Resolve(err) => format!("{}", err),
+                Self::Flow(err) => format!("{}", err),
+                Self::Type(err) => format!("{}", err),
+                Self::Const(err) => format!("{}", err),
+                Self::Runtime(err) => format!("{}", err),
+                Self::Emit(err) => format!("{}", err),
+                Self::Format(err) => format!("{}", err),
             }
         )
     }
@@ -139,6 +232,12 @@ pub enum LexErrors {
     UnrecognisedToken(String),
     #[error("an unrecognised type was encountered: '{0}'")]
     UnrecognisedType(String),
+    #[error("this string is never closed (expected a `\"`)")]
+    UnterminatedString,
+    #[error("this comment is never closed (expected a `*/`)")]
+    UnterminatedComment,
+    #[error("`\\{0}` isn't an escape sequence")]
+    UnknownEscape(char),
 }
 
 impl From<LexErrors> for ErrType {
@@ -170,6 +269,8 @@ pub enum ParseErrors {
     BlockNotOpened,
     #[error("block not closed - expected a closing brace (`}}`)")]
     BlockNotClosed,
+    #[error("closing brace (`}}`) without a block to close")]
+    UnmatchedBrace,
     #[error("label was not given a name")]
     LabelName,
     #[error("goto label should start with an `@`")]
@@ -196,9 +297,32 @@ pub enum ParseErrors {
     ExpectedFnPtrReturnTy,
     #[error("type bound not recognised")]
     InvalidBound,
+    #[error("only type names can be combined with `+`")]
+    AggregateNotAlias,
+
+    #[error("expected an attribute (`#[ name [..] ]`)")]
+    AttributeNotOpened,
+    #[error("attribute was not given a name")]
+    AttributeName,
+    #[error("attribute arguments must be integers, booleans or identifiers, and end with a `]`")]
+    AttributeArg,
+    #[error("attributes can only be applied to `fn` & `type` declarations, or to expressions")]
+    MisplacedAttribute,
+    #[error("`#else` must follow an `#if`")]
+    ElseWithoutIf,
 
     #[error("subexpr not closed - expected a closing paren (`)`)")]
     SubExprNotClosed,
+    #[error("expected an expression")]
+    ExpectedExpr,
+    #[error("expected a condition following `if`")]
+    ExpectedCondition,
+    #[error("struct literal not closed - expected a closing brace (`}}`)")]
+    StructLitNotClosed,
+    #[error("struct literal fields did not have a comma delimeter")]
+    StructLitNoDel,
+    #[error("struct literals cannot mix named (`.name = expr`) and positional fields")]
+    MixedStructLitFields,
 
     #[error("reached the end of the token stack; {0}")]
     TkStackEmpty(Box<ParseErrors>),
@@ -217,3 +341,218 @@ impl From<ParseErrors> for ErrType {
         ErrType::Parse(other)
     }
 }
+
+#[derive(Error, Debug)]
+pub enum ResolveErrors {
+    #[error("use of undeclared {kind} `{name}`")]
+    Undeclared { kind: &'static str, name: String },
+    #[error("the {kind} `{name}` is declared more than once in this scope")]
+    Duplicate { kind: &'static str, name: String },
+    #[error("the {kind} `{name}` shadows an earlier declaration")]
+    Shadowed { kind: &'static str, name: String },
+}
+
+impl From<ResolveErrors> for ErrType {
+    fn from(other: ResolveErrors) -> ErrType {
+        ErrType::Resolve(other)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FlowErrors {
+    #[error("the label `@{0}` is declared more than once in this function")]
+    DuplicateLabel(String),
+    #[error("no label `@{0}` in this function")]
+    UndeclaredLabel(String),
+    #[error("this `goto` skips the declaration of `{0}`, which is used after the label")]
+    GotoSkipsLet(String),
+    #[error("unreachable code")]
+    Unreachable,
+    #[error("`{0}` can reach the end of its body without returning a value")]
+    MissingReturn(String),
+    #[error("`{0}` is declared `no_return`, but can return here")]
+    ReturnsFromNoReturn(String),
+}
+
+impl From<FlowErrors> for ErrType {
+    fn from(other: FlowErrors) -> ErrType {
+        ErrType::Flow(other)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TypeErrors {
+    #[error("mismatched types - expected `{expected}`, found `{found}`")]
+    Mismatch { expected: String, found: String },
+    #[error("mismatched operands - `{left}` and `{right}`")]
+    OperandMismatch { left: String, right: String },
+    #[error("expected an integer (`int` or `unt`), found `{0}`")]
+    ExpectedInteger(String),
+    #[error("only primitives & pointers can be compared, found `{0}`")]
+    NotComparable(String),
+    #[error("cannot dereference `{0}`, which is not a pointer")]
+    ExpectedPointer(String),
+    #[error("only variables, members & dereferences can be referenced with `&`")]
+    NotAPlace,
+    #[error("`{0}` has no members")]
+    NoMembers(String),
+    #[error("`{ty}` has no member `{member}`")]
+    NoMember { ty: String, member: String },
+    #[error("the member `{0}` is given more than once")]
+    DuplicateMember(String),
+    #[error("`{ty}` is missing a value for its member `{member}`")]
+    MissingMember { ty: String, member: String },
+    #[error("`{0}` is not a struct or union, so can't be built with a literal")]
+    NotAStruct(String),
+    #[error("`{ty}` has {expected} member(s), but {found} were given")]
+    FieldCount {
+        ty: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("the union `{0}` must be built by naming a member (`{0} {{ .name = [..] }}`)")]
+    PositionalUnion(String),
+    #[error("a `{0}` holds only one of its members at a time")]
+    UnionFieldCount(String),
+    #[error("`{0}` is not a function")]
+    NotCallable(String),
+    #[error("expected {expected} argument(s), found {found}")]
+    ArgCount { expected: usize, found: usize },
+    #[error("no overload of `{name}` takes ({args})")]
+    NoMatchingOverload { name: String, args: String },
+    #[error("call to `{name}` with ({args}) is ambiguous")]
+    AmbiguousCall { name: String, args: String },
+    #[error("no overload of `{name}` takes ({args}), so it can't be dispatched to")]
+    MissingVariant { name: String, args: String },
+    #[error("`{0}` is declared more than once with the same parameter types")]
+    ConflictingOverloads(String),
+    #[error("`{0}` has several overloads, so can't be used as a value")]
+    OverloadedValue(String),
+    #[error("`{ty}` is used as a generic, but has no implementation `{wanted}`")]
+    MissingImplementation { ty: String, wanted: String },
+    #[error("`{0}` is an intrinsic, which each backend provides - it can't be implemented")]
+    ImplementedIntrinsic(String),
+    #[error("this `{0}` value is never used - discard it explicitly with a `;`")]
+    UnusedValue(String),
+    #[error("a `{0}` is discarded without being read")]
+    MustBeRead(String),
+    #[error("alias cycle - {0}")]
+    AliasCycle(String),
+    #[error("`{name}` contains itself, so would be infinitely large - {path}")]
+    InfinitelySized { name: String, path: String },
+    #[error("`{name}` is never declared - {path}")]
+    DanglingAlias { name: String, path: String },
+    #[error("`return` outside of a function")]
+    ReturnOutsideFn,
+    #[error("`{0}` can't be bound to a `void` value")]
+    VoidBinding(String),
+    #[error("cannot assign to `{0}`, which is not a variable")]
+    NotAssignable(String),
+    #[error("struct & union members must name their type - declare it seperately")]
+    AnonymousAggregate,
+    #[error("only generics can be combined with `+`, but `{0}` is not generic")]
+    AggregateOfConcrete(String),
+}
+
+impl From<TypeErrors> for ErrType {
+    fn from(other: TypeErrors) -> ErrType {
+        ErrType::Type(other)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConstErrors {
+    #[error("`{expr}` overflows `{ty}`")]
+    Overflow { expr: String, ty: String },
+    #[error("`{0}` divides by zero")]
+    DivByZero(String),
+    #[error("`{0}` shifts by more than the 64 bits of an integer")]
+    OversizedShift(String),
+    #[error("`#if` conditions must be constant")]
+    NotConstant,
+    #[error("`#if` conditions must be a `bool`, found `{0}`")]
+    ConditionNotBool(String),
+    #[error("`num_in_range` takes two integers - the smallest & largest values allowed")]
+    RangeArgs,
+    #[error("`num_in_range` only applies to integers, found `{0}`")]
+    RangeOfNonInteger(String),
+    #[error("`{value}` is outside of the range `{range}`")]
+    OutOfRange { value: String, range: String },
+}
+
+impl From<ConstErrors> for ErrType {
+    fn from(other: ConstErrors) -> ErrType {
+        ErrType::Const(other)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RuntimeErrors {
+    #[error("no `fn main()` to run")]
+    NoMain,
+    #[error("`main` can't take any parameters")]
+    MainTakesParams,
+    #[error("`{expr}` overflows `{ty}`")]
+    Overflow { expr: String, ty: String },
+    #[error("`{0}` divides by zero")]
+    DivByZero(String),
+    #[error("`{0}` shifts by more than the 64 bits of an integer")]
+    OversizedShift(String),
+    #[error("`{0}` has no meaning yet")]
+    Unsupported(String),
+    #[error("this pointer refers to a variable which no longer exists")]
+    DanglingPointer,
+    #[error("the union holds its `{held}` member, not `{member}`")]
+    WrongMember { held: String, member: String },
+    #[error("cannot jump into the middle of an expression")]
+    JumpIntoExpr,
+    #[error("`{0}` has no implementation for these arguments")]
+    NoImplementation(String),
+    #[error("the call stack overflowed, at {0} calls deep")]
+    StackOverflow(usize),
+    #[error("panicked: {0}")]
+    Panic(String),
+    #[error("syscall {0} isn't available here")]
+    UnknownSyscall(i64),
+    #[error("the {length} bytes at {address} aren't all in memory")]
+    OutOfBounds { address: i64, length: i64 },
+    /// only ever raised by the vm, running bytecode which wasn't compiled by `sdw`
+    #[error("the bytecode is malformed: {0}")]
+    Malformed(&'static str),
+}
+
+impl From<RuntimeErrors> for ErrType {
+    fn from(other: RuntimeErrors) -> ErrType {
+        ErrType::Runtime(other)
+    }
+}
+
+/// raised by the backends, for programs they can't (yet) compile
+#[derive(Error, Debug)]
+pub enum EmitErrors {
+    #[error("no `fn main()` to start from")]
+    NoMain,
+    #[error("`main` can't take any parameters")]
+    MainTakesParams,
+    #[error("{what} can't be compiled to {target} yet")]
+    Unsupported { what: String, target: String },
+}
+
+impl From<EmitErrors> for ErrType {
+    fn from(other: EmitErrors) -> ErrType {
+        ErrType::Emit(other)
+    }
+}
+
+/// raised by the formatter, for output it won't write
+#[derive(Error, Debug)]
+pub enum FormatErrors {
+    #[error("formatting would have changed the code from here, so nothing was formatted")]
+    Changed,
+}
+
+impl From<FormatErrors> for ErrType {
+    fn from(other: FormatErrors) -> ErrType {
+        ErrType::Format(other)
+    }
+}
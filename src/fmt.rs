@@ -0,0 +1,324 @@
+use crate::lexer::{self, Token, TokenKind};
+use crate::parser;
+use crate::prelude::*;
+use crate::pretty::{self, Style};
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+
+/// a line & a column in it
+pub(crate) type Position = (SpanInt, SpanInt);
+
+/// a comment, on its own, after code, or (a `/* .. */` one) before code on the same line
+#[derive(Debug, Clone)]
+pub(crate) struct Comment {
+    pub line: SpanInt,
+    pub col: SpanInt,
+    pub text: String,
+    /// whether code follows it on its line
+    pub leading: bool,
+}
+
+/// the comments in a file, & which lines had something on them - so that `pretty` can put
+/// the comments back by the code they were written by as it lays it out (& keep blank lines
+/// between statements)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Comments {
+    pending: VecDeque<Comment>,
+    written: HashSet<SpanInt>,
+}
+
+impl Comments {
+    fn new(tokens: &[Token]) -> Comments {
+        let mut comments = Comments::default();
+        for (index, token) in tokens.iter().enumerate() {
+            if token.kind == TokenKind::Whitespace {
+                continue;
+            }
+            // (a block comment may span lines)
+            comments.written.extend(token.span.sline..token.span.eline);
+            if token.kind == TokenKind::Comment {
+                let one_line =
+                    |token: &Token| !token.text.contains('\n') && !token.text.starts_with("//");
+                let leading = one_line(token)
+                    && tokens[index + 1..]
+                        .iter()
+                        .find(|token| {
+                            token.text.contains('\n')
+                                || !(token.kind == TokenKind::Whitespace
+                                    || token.kind == TokenKind::Comment && one_line(token))
+                        })
+                        .is_some_and(|token| !token.kind.is_trivia());
+                comments.pending.push_back(Comment {
+                    line: token.span.sline,
+                    col: token.span.scol,
+                    text: token.text.trim_end().to_owned(),
+                    leading,
+                });
+            }
+        }
+        comments
+    }
+
+    /// takes the comments (yet to be put back) before `at`
+    pub(crate) fn before(&mut self, at: Position) -> Vec<Comment> {
+        let count = self
+            .pending
+            .iter()
+            .take_while(|comment| (comment.line, comment.col) < at)
+            .count();
+        self.pending.drain(..count).collect()
+    }
+
+    /// takes the comments (yet to be put back) before `at` which end their lines - up to one
+    /// with code after it, which goes with that code
+    pub(crate) fn trailing(&mut self, at: Position) -> Vec<Comment> {
+        let count = self
+            .pending
+            .iter()
+            .take_while(|comment| (comment.line, comment.col) < at && !comment.leading)
+            .count();
+        self.pending.drain(..count).collect()
+    }
+
+    /// whether any comment (yet to be put back) is from `from` up to `to`
+    pub(crate) fn within(&self, from: Position, to: Position) -> bool {
+        self.pending
+            .iter()
+            .any(|comment| (from..to).contains(&(comment.line, comment.col)))
+    }
+
+    /// whether the line before `line` was blank
+    pub(crate) fn blank_before(&self, line: SpanInt) -> bool {
+        line > 0 && !self.written.contains(&(line - 1))
+    }
+}
+
+/// an `#if` & each of its groups, which are laid out on their own - the parser keeps only
+/// the group chosen
+struct Conditional {
+    /// each group's condition (but a final `#else`'s), & its contents. comments in the
+    /// conditions or between the groups are kept at the start of the group after them.
+    groups: Vec<(Option<Vec<Token>>, Vec<Token>)>,
+}
+
+/// stands in for the `index`th conditional while the code around it is laid out. it can't
+/// be lexed, so is never in the source
+fn placeholder(index: usize) -> String {
+    format!("\0conditional {index}\0")
+}
+
+fn is(token: Option<&Token>, lexeme: LexemeType) -> bool {
+    token.is_some_and(|token| token.kind == TokenKind::Lexeme(lexeme))
+}
+
+/// `tokens` with each conditional (outside those) replaced by a placeholder statement - or
+/// the block's tail, where it ends the block - & the conditionals replaced. if a group isn't
+/// braced, the error is left in `state`.
+fn conditionals(state: &mut State, tokens: &[Token]) -> Option<(Vec<Token>, Vec<Conditional>)> {
+    // the next token (from `from`) which isn't trivia
+    let next = |from: usize| (from..tokens.len()).find(|at| !tokens[*at].kind.is_trivia());
+    let lexeme = |from: usize| next(from).map(|at| &tokens[at]);
+
+    let mut kept = Vec::new();
+    let mut conditionals = Vec::new();
+    let mut at = 0;
+    while at < tokens.len() {
+        let hash = &tokens[at];
+        if !is(Some(hash), LexemeType::Hash) || !is(lexeme(at + 1), LexemeType::If) {
+            kept.push(hash.clone());
+            at += 1;
+            continue;
+        }
+
+        let mut groups = Vec::new();
+        let mut comments = Vec::new();
+        // just after the `#if`, `#else if` or `#else`
+        let mut from = next(at + 1).unwrap() + 1;
+        let mut conditional = true;
+        let end = loop {
+            let mut condition = Vec::new();
+            let mut depth = 0;
+            let Some(open) = (from..tokens.len()).find(|index| {
+                let token = &tokens[*index];
+                match &token.kind {
+                    TokenKind::Lexeme(LexemeType::LParen | LexemeType::LBrack) => depth += 1,
+                    TokenKind::Lexeme(LexemeType::RParen | LexemeType::RBrack) => depth -= 1,
+                    TokenKind::Lexeme(LexemeType::LBrace) if depth == 0 => return true,
+                    TokenKind::Comment => comments.push(token.clone()),
+                    _ => {}
+                }
+                if !token.kind.is_trivia() {
+                    condition.push(token.clone());
+                }
+                false
+            }) else {
+                let err = SdwErr::from_pos(ParseErrors::BlockNotOpened, tokens[from - 1].span);
+                state.errors.push(err);
+                return None;
+            };
+            let mut depth = 0;
+            let Some(close) = (open..tokens.len()).find(|index| {
+                match tokens[*index].kind {
+                    TokenKind::Lexeme(LexemeType::LBrace) => depth += 1,
+                    TokenKind::Lexeme(LexemeType::RBrace) => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            }) else {
+                let err = SdwErr::from_pos(ParseErrors::BlockNotClosed, tokens[open].span);
+                state.errors.push(err);
+                return None;
+            };
+            let mut contents = std::mem::take(&mut comments);
+            contents.extend_from_slice(&tokens[open + 1..close]);
+            groups.push((conditional.then_some(condition), contents));
+
+            let Some(hash) = next(close + 1).filter(|_| conditional) else {
+                break close;
+            };
+            let Some(r#else) = next(hash + 1) else {
+                break close;
+            };
+            if !is(tokens.get(hash), LexemeType::Hash) || !is(tokens.get(r#else), LexemeType::Else)
+            {
+                break close;
+            }
+            comments.extend(
+                tokens[close + 1..hash]
+                    .iter()
+                    .filter(|token| token.kind == TokenKind::Comment)
+                    .cloned(),
+            );
+            conditional = is(lexeme(r#else + 1), LexemeType::If);
+            from = match conditional {
+                true => next(r#else + 1).unwrap() + 1,
+                false => r#else + 1,
+            };
+        };
+
+        let span = Span::from_to(hash.span, tokens[end].span);
+        let name = LexemeType::Idn(placeholder(conditionals.len()));
+        kept.push(Token {
+            kind: TokenKind::Lexeme(name),
+            text: String::new(),
+            span,
+        });
+        let ends_block = lexeme(end + 1).is_none_or(|token| is(Some(token), LexemeType::RBrace));
+        if !ends_block {
+            kept.push(Token {
+                kind: TokenKind::Lexeme(LexemeType::Semi),
+                text: ";".to_owned(),
+                span: tokens[end].span,
+            });
+        }
+        conditionals.push(Conditional { groups });
+        at = end + 1;
+    }
+    Some((kept, conditionals))
+}
+
+fn parse(state: &mut State, tokens: &[Token]) -> Option<Block> {
+    let lexemes: Vec<Lexeme> = tokens.iter().filter_map(Token::lexeme).collect();
+    match parser::parse(state, lexemes) {
+        Ok(block) if state.errors.is_empty() => Some(block),
+        Ok(_) => None,
+        Err(err) => {
+            state.errors.push(err);
+            None
+        }
+    }
+}
+
+/// `tokens` laid out `depth` levels deep, with every group of each conditional
+fn layout(state: &mut State, tokens: &[Token], style: &Style, depth: usize) -> Option<String> {
+    let (tokens, conditionals) = conditionals(state, tokens)?;
+    let block = parse(state, &tokens)?;
+    let comments = RefCell::new(Comments::new(&tokens));
+    let mut source = pretty::layout(&block, style, &comments, depth);
+
+    for (index, conditional) in conditionals.iter().enumerate() {
+        let placeholder = placeholder(index);
+        let at = source.find(&placeholder)?;
+        let line = source[..at].rfind('\n').map_or(0, |line| line + 1);
+        let indent = source[line..].len() - source[line..].trim_start().len();
+        let depth = indent.checked_div(style.indent).unwrap_or(0);
+        let indent = " ".repeat(indent);
+
+        let mut text = String::new();
+        for (index, (condition, contents)) in conditional.groups.iter().enumerate() {
+            text += if index == 0 { "#if" } else { " #else" };
+            if let Some(condition) = condition {
+                text += if index == 0 { " " } else { " if " };
+                let condition = parse(state, condition)?.tail?;
+                let column = indent.len() + text.rsplit('\n').next().unwrap_or_default().len();
+                text += &pretty::condition(&condition.spanned, style, depth, column);
+            }
+            let contents = layout(state, contents, style, depth + 1)?;
+            match contents.trim_end_matches('\n') {
+                "" => text += " {}",
+                contents => text += &format!(" {{\n{contents}\n{indent}}}"),
+            }
+        }
+        let end = at + placeholder.len();
+        let end = if source[end..].starts_with(';') {
+            end + 1
+        } else {
+            end
+        };
+        source.replace_range(at..end, &text);
+    }
+    Some(source)
+}
+
+/// `source` laid out canonically in `style`, keeping its comments (& single blank lines between
+/// statements), & every group of a conditional. if it doesn't lex or fully parse - or laying
+/// it out would change its code - the errors are left in `state`.
+pub fn format(state: &mut State, source: &str, style: &Style) -> Option<String> {
+    let tokens = lexer::lex_lossless(state, source);
+    if !state.errors.is_empty() {
+        return None;
+    }
+    // (every group must parse, not only the one chosen)
+    parse(state, &tokens)?;
+    let formatted = layout(state, &tokens, style, 0)?;
+    unchanged(state, &tokens, &formatted).then_some(formatted)
+}
+
+/// the code in `tokens` - without trivia, or the trailing commas `pretty` adds & removes
+fn code(tokens: &[Token]) -> Vec<&Token> {
+    let code: Vec<_> = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .collect();
+    let closes = |token: Option<&&Token>| {
+        token.is_some_and(|token| {
+            is(Some(token), LexemeType::RParen)
+                || is(Some(token), LexemeType::RBrace)
+                || is(Some(token), LexemeType::RBrack)
+        })
+    };
+    (0..code.len())
+        .filter(|at| !is(Some(code[*at]), LexemeType::Comma) || !closes(code.get(at + 1)))
+        .map(|at| code[at])
+        .collect()
+}
+
+/// whether `formatted` is the same code as `tokens` - so that a mistake in laying it out can
+/// never lose or change any of it. if not, the error is left in `state`.
+fn unchanged(state: &mut State, tokens: &[Token], formatted: &str) -> bool {
+    let formatted = lexer::lex_lossless(&mut State::new(), formatted);
+    let (before, after) = (code(tokens), code(&formatted));
+    let Some(at) = (0..before.len().max(after.len()))
+        .find(|at| before.get(*at).map(|t| &t.kind) != after.get(*at).map(|t| &t.kind))
+    else {
+        return true;
+    };
+    let span = before
+        .get(at)
+        .or(before.last())
+        .map_or_else(Span::default, |t| t.span);
+    state
+        .errors
+        .push(SdwErr::from_pos(FormatErrors::Changed, span));
+    false
+}
@@ -0,0 +1,1083 @@
+use crate::bytecode::RtTy;
+use crate::driver::{self, Checked};
+use crate::errors::Note;
+use crate::intrinsics::{Called, Host, Intrinsic};
+use crate::overload::Arm;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// the deepest the call stack may grow before the program is stopped
+pub const MAX_DEPTH: usize = 10_000;
+
+const STACK_SIZE: usize = 1 << 30;
+
+/// a runtime value. structs & unions are held inline, as they are laid out in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Void,
+    Int(i64),
+    Unt(u64),
+    Bool(bool),
+    Str(String),
+    Struct {
+        ty: DefId,
+        fields: Vec<Value>,
+    },
+    Union {
+        ty: DefId,
+        discriminant: usize,
+        value: Box<Value>,
+    },
+    Pointer(Pointer),
+    Fn(DefId),
+}
+
+impl Value {
+    /// the type a value was built as, where that can be told from the value alone
+    fn ty(&self) -> Option<Ty> {
+        Some(match self {
+            Value::Void => Ty::Void,
+            Value::Int(_) => Ty::INT,
+            Value::Unt(_) => Ty::UNT,
+            Value::Bool(_) => Ty::BOOL,
+            Value::Str(_) => Ty::STRING,
+            Value::Struct { ty, .. } | Value::Union { ty, .. } => Ty::Named(*ty),
+            Value::Pointer(_) | Value::Fn(_) => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Int(int) => write!(f, "{int}"),
+            Value::Unt(unt) => write!(f, "{unt}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Str(text) => write!(f, "{text:?}"),
+            Value::Struct { fields, .. } => {
+                let fields = fields.iter().map(Value::to_string).collect::<Vec<_>>();
+                write!(f, "{{ {} }}", fields.join(", "))
+            }
+            Value::Union {
+                discriminant,
+                value,
+                ..
+            } => write!(f, "{{ #{discriminant} = {value} }}"),
+            Value::Pointer(pointer) => write!(f, "&{}", pointer.slot),
+            Value::Fn(id) => write!(f, "fn #{}", id.0),
+        }
+    }
+}
+
+/// a variable (by its slot in memory), and the path of members taken within it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    pub(crate) slot: usize,
+    /// the slot is reused once its variable is gone, so each use of it is told apart
+    pub(crate) generation: u64,
+    pub(crate) path: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Slot {
+    value: Value,
+    generation: u64,
+}
+
+/// a function call in progress (or the module's top level)
+struct Frame<'a> {
+    function: Option<DefId>,
+    /// where each of the function's variables & parameters is kept
+    slots: HashMap<DefId, usize>,
+    /// how much memory was in use before the call, & is all that is left after it
+    base: usize,
+    call: Option<(&'a str, Span)>,
+}
+
+/// why evaluation stopped short of producing a value
+enum Unwind {
+    Return(Value),
+    Goto(DefId),
+    Error(Box<SdwErr>),
+    /// the program exited (by a syscall), with this code
+    Exit(i64),
+}
+
+impl From<SdwErr> for Unwind {
+    fn from(err: SdwErr) -> Unwind {
+        Unwind::Error(Box::new(err))
+    }
+}
+
+type Flow<T> = std::result::Result<T, Unwind>;
+
+fn error<T>(err: RuntimeErrors, span: Span) -> Flow<T> {
+    Err(SdwErr::from_pos(err, span).into())
+}
+
+/// the parameters & body of a function
+struct Function<'a> {
+    parameters: &'a [(Spanned<String>, Spanned<String>)],
+    body: &'a Block,
+}
+
+struct Interpreter<'a> {
+    res: &'a Resolutions,
+    types: &'a Types,
+    fns: HashMap<DefId, Function<'a>>,
+    memory: Vec<Slot>,
+    generation: u64,
+    frames: Vec<Frame<'a>>,
+    host: Host,
+}
+
+impl<'a> Interpreter<'a> {
+    fn collect(&mut self, block: &'a Block) {
+        for stmt in &block.stmts {
+            self.collect_stmt(&stmt.spanned);
+        }
+        if let Some(tail) = &block.tail {
+            self.collect_expr(tail);
+        }
+    }
+
+    fn collect_stmt(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Fn {
+                name,
+                parameters,
+                body,
+                ..
+            } => {
+                if let Some(id) = self.res.def_at(name.span) {
+                    self.fns.insert(id, Function { parameters, body });
+                }
+                self.collect(body);
+            }
+            Stmt::Loop { block } => self.collect(block),
+            Stmt::Return { expr: Some(expr) }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. }
+            | Stmt::Discard { expr } => self.collect_expr(expr),
+            _ => {}
+        }
+    }
+
+    /// functions may be declared in any block, including those within expressions
+    fn collect_expr(&mut self, expr: &'a Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::Block(block) => self.collect(block),
+            Expr::Cond {
+                then,
+                elifs,
+                r#else,
+                ..
+            } => {
+                self.collect(&then.spanned);
+                for (_, block) in elifs {
+                    self.collect(&block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    self.collect(&r#else.spanned);
+                }
+            }
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.collect_expr(inner),
+            _ => {}
+        }
+    }
+
+    /*
+     * memory
+     */
+
+    fn frame(&mut self) -> &mut Frame<'a> {
+        self.frames.last_mut().expect("interpreter: no frame")
+    }
+
+    fn allocate(&mut self, value: Value) -> usize {
+        self.generation += 1;
+        self.memory.push(Slot {
+            value,
+            generation: self.generation,
+        });
+        self.memory.len() - 1
+    }
+
+    /// a `let` run again (eg. in a loop) reuses its slot, but pointers to the old variable dangle
+    fn declare(&mut self, id: DefId, value: Value) {
+        match self.frame().slots.get(&id).copied() {
+            Some(slot) => {
+                self.generation += 1;
+                self.memory[slot] = Slot {
+                    value,
+                    generation: self.generation,
+                };
+            }
+            None => {
+                let slot = self.allocate(value);
+                self.frame().slots.insert(id, slot);
+            }
+        }
+    }
+
+    fn variable(&self, id: DefId) -> Pointer {
+        let slot = self.frames.last().expect("interpreter: no frame").slots[&id];
+        Pointer {
+            slot,
+            generation: self.memory[slot].generation,
+            path: Vec::new(),
+        }
+    }
+
+    fn load(&self, pointer: &Pointer, span: Span) -> Flow<Value> {
+        let mut value = match self.memory.get(pointer.slot) {
+            Some(slot) if slot.generation == pointer.generation => &slot.value,
+            _ => return error(RuntimeErrors::DanglingPointer, span),
+        };
+        for member in &pointer.path {
+            value = self.member(value, *member, span)?;
+        }
+        Ok(value.clone())
+    }
+
+    fn member<'v>(&self, value: &'v Value, member: usize, span: Span) -> Flow<&'v Value> {
+        match value {
+            Value::Struct { fields, .. } => Ok(&fields[member]),
+            Value::Union {
+                ty,
+                discriminant,
+                value,
+            } => {
+                if *discriminant == member {
+                    return Ok(value);
+                }
+                let Some(TypeDecl::Union(members)) = self.types.decls.get(ty) else {
+                    unreachable!("interpreter: union value of a non-union type");
+                };
+                error(
+                    RuntimeErrors::WrongMember {
+                        held: members[*discriminant].0.clone(),
+                        member: members[member].0.clone(),
+                    },
+                    span,
+                )
+            }
+            _ => unreachable!("interpreter: member of a value without members"),
+        }
+    }
+
+    /// converts a variant to a union, where one is expected
+    fn coerce(&self, value: Value, to: &Ty) -> Value {
+        let Ty::Named(union) = to else {
+            return value;
+        };
+        if !matches!(self.types.decls.get(union), Some(TypeDecl::Union(_))) {
+            return value;
+        }
+        if matches!(&value, Value::Union { ty, .. } if ty == union) {
+            return value;
+        }
+        match value
+            .ty()
+            .and_then(|ty| self.types.discriminant(*union, &ty))
+        {
+            Some(discriminant) => Value::Union {
+                ty: *union,
+                discriminant,
+                value: Box::new(value),
+            },
+            None => value,
+        }
+    }
+
+    /// the type an expression was checked to have
+    fn ty(&self, expr: &Spanned<Expr>) -> &'a Ty {
+        self.types.expr(expr.span).unwrap_or(&Ty::Error)
+    }
+
+    /*
+     * control flow
+     */
+
+    /// the statement (or, as `stmts.len()`, the tail) of `block` containing `label`
+    fn position(&self, block: &Block, label: DefId) -> Option<usize> {
+        let at = self.res.def(label).span;
+        let contains = |span: Span| {
+            (span.sline, span.scol) <= (at.sline, at.scol)
+                && (at.eline, at.ecol) <= (span.eline, span.ecol)
+        };
+        block
+            .stmts
+            .iter()
+            .position(|stmt| contains(stmt.span))
+            .or_else(|| {
+                block
+                    .tail
+                    .as_ref()
+                    .filter(|tail| contains(tail.span))
+                    .map(|_| block.stmts.len())
+            })
+    }
+
+    /// `seek` is a label within the block to start from, having been jumped to
+    fn block(&mut self, block: &Block, seek: Option<DefId>) -> Flow<Value> {
+        let next = match seek {
+            Some(label) => self.position(block, label).unwrap_or(0),
+            None => 0,
+        };
+        self.block_from(block, next, seek)
+    }
+
+    /// like `block`, but starting from the statement `next`
+    fn block_from(
+        &mut self,
+        block: &Block,
+        mut next: usize,
+        mut seek: Option<DefId>,
+    ) -> Flow<Value> {
+        loop {
+            let result = match block.stmts.get(next) {
+                Some(stmt) => self.stmt(&stmt.spanned, seek.take()).map(|()| None),
+                None => match &block.tail {
+                    Some(tail) => self.seek_expr(tail, seek.take()).map(Some),
+                    None => Ok(Some(Value::Void)),
+                },
+            };
+            match result {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => next += 1,
+                Err(Unwind::Goto(label)) => match self.position(block, label) {
+                    Some(at) => {
+                        next = at;
+                        seek = Some(label);
+                    }
+                    None => return Err(Unwind::Goto(label)),
+                },
+                Err(unwind) => return Err(unwind),
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, seek: Option<DefId>) -> Flow<()> {
+        match stmt {
+            Stmt::Fn { .. } | Stmt::Stub { .. } | Stmt::Type { .. } | Stmt::Label { .. } => {}
+            Stmt::Loop { block } => {
+                let mut seek = seek;
+                loop {
+                    self.block(block, seek.take())?;
+                }
+            }
+            Stmt::Goto { name } => {
+                let label = self
+                    .res
+                    .def_at(name.span)
+                    .expect("interpreter: unresolved goto");
+                return Err(Unwind::Goto(label));
+            }
+            Stmt::Return { expr } => {
+                let value = match expr {
+                    Some(expr) => self.seek_expr(expr, seek)?,
+                    None => Value::Void,
+                };
+                let value = match self.frame().function {
+                    Some(function) => self.coerce(value, &self.types.sigs[&function].ret),
+                    None => value,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::VarDec { name, initialiser } => {
+                let value = self.seek_expr(initialiser, seek)?;
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("interpreter: unresolved let");
+                self.declare(id, value);
+            }
+            Stmt::VarRes { name, updated } => {
+                let value = self.seek_expr(updated, seek)?;
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("interpreter: unresolved variable");
+                let value = self.coerce(value, &self.types.locals[&id]);
+                let slot = self.frame().slots[&id];
+                self.memory[slot].value = value;
+            }
+            Stmt::Discard { expr } => {
+                self.seek_expr(expr, seek)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// like `expr`, but starting from the label `seek` within it. only blocks (& the
+    /// branches of an `if`) can be jumped into - not the middle of any other expression.
+    fn seek_expr(&mut self, expr: &Spanned<Expr>, seek: Option<DefId>) -> Flow<Value> {
+        let Some(label) = seek else {
+            return self.expr(expr);
+        };
+        match &expr.spanned {
+            Expr::Block(block) => self.block(block, seek),
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => {
+                self.seek_expr(inner, seek)
+            }
+            Expr::Cond {
+                then,
+                elifs,
+                r#else,
+                ..
+            } => {
+                let branch = std::iter::once(then)
+                    .chain(elifs.iter().map(|(_, block)| block))
+                    .chain(r#else)
+                    .find(|branch| self.position(&branch.spanned, label).is_some());
+                match branch {
+                    Some(branch) => {
+                        let value = self.block(&branch.spanned, seek)?;
+                        Ok(self.coerce(value, self.ty(expr)))
+                    }
+                    None => error(RuntimeErrors::JumpIntoExpr, expr.span),
+                }
+            }
+            _ => error(RuntimeErrors::JumpIntoExpr, expr.span),
+        }
+    }
+
+    /*
+     * expressions
+     */
+
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Flow<Value> {
+        Ok(match &expr.spanned {
+            Expr::IntLiteral(int) => match self.ty(expr) {
+                Ty::Prim(PrimType::Unt) => Value::Unt(*int as u64),
+                _ => Value::Int(*int),
+            },
+            Expr::BoolLiteral(bool) => Value::Bool(*bool),
+            Expr::StrLiteral(text) => Value::Str(text.clone()),
+            Expr::Variable(_) => match self.res.get(expr.span) {
+                Some(Res::Def(id)) => {
+                    let pointer = self.variable(*id);
+                    self.load(&pointer, expr.span)?
+                }
+                Some(Res::Overloads(ids)) => Value::Fn(ids[0]),
+                None => unreachable!("interpreter: unresolved variable"),
+            },
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.expr(inner)?,
+            Expr::UnaryPos(inner) => self.expr(inner)?,
+            Expr::UnaryNeg(inner) => {
+                let value = self.expr(inner)?;
+                negate(value).or_else(|err| error(err, expr.span))?
+            }
+            Expr::UnaryNot(inner) => match self.expr(inner)? {
+                Value::Bool(bool) => Value::Bool(!bool),
+                _ => unreachable!("interpreter: `!` of a non-bool"),
+            },
+            Expr::BiOp(left, op, right) => {
+                let left = self.expr(left)?;
+                // `||` & `&&` only evaluate their right side when they need to
+                match (op, &left) {
+                    (BiOps::LogOr, Value::Bool(true)) => return Ok(Value::Bool(true)),
+                    (BiOps::LogAnd, Value::Bool(false)) => return Ok(Value::Bool(false)),
+                    _ => {}
+                }
+                let right = self.expr(right)?;
+                biop(left, *op, right).or_else(|err| error(err, expr.span))?
+            }
+            Expr::Referal(inner) => Value::Pointer(self.place(inner)?),
+            Expr::Indir(inner) => match self.expr(inner)? {
+                Value::Pointer(pointer) => self.load(&pointer, expr.span)?,
+                _ => unreachable!("interpreter: dereferenced a non-pointer"),
+            },
+            Expr::ObjMember(..) => {
+                let pointer = self.place(expr)?;
+                self.load(&pointer, expr.span)?
+            }
+            Expr::FnCall(_, args) => self.call(expr.span, args)?,
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                let branches = std::iter::once((Some(condition), then))
+                    .chain(
+                        elifs
+                            .iter()
+                            .map(|(condition, block)| (Some(condition), block)),
+                    )
+                    .chain(r#else.as_ref().map(|block| (None, block)));
+                let mut value = Value::Void;
+                for (condition, block) in branches {
+                    let holds = match condition {
+                        Some(condition) => self.expr(condition)? == Value::Bool(true),
+                        None => true,
+                    };
+                    if holds {
+                        value = self.block(&block.spanned, None)?;
+                        break;
+                    }
+                }
+                self.coerce(value, self.ty(expr))
+            }
+            Expr::Block(block) => self.block(block, None)?,
+            Expr::StructLit { fields, .. } => self.struct_lit(expr, fields)?,
+        })
+    }
+
+    /// where a place expression (`x`, `x.member`, `*x`) is kept
+    fn place(&mut self, expr: &Spanned<Expr>) -> Flow<Pointer> {
+        match &expr.spanned {
+            Expr::Variable(_) => match self.res.get(expr.span) {
+                Some(Res::Def(id)) => Ok(self.variable(*id)),
+                _ => self.temporary(expr),
+            },
+            Expr::SubExpr(inner) => self.place(inner),
+            Expr::Indir(inner) => match self.expr(inner)? {
+                Value::Pointer(pointer) => Ok(pointer),
+                _ => unreachable!("interpreter: dereferenced a non-pointer"),
+            },
+            Expr::ObjMember(object, member) => {
+                let id = self
+                    .res
+                    .def_at(object.span)
+                    .expect("interpreter: unresolved object");
+                let object_ty = &self.types.locals[&id];
+                // members are reached through pointers to structs, too
+                let mut pointer = match object_ty {
+                    Ty::Pointer(_) => match self.load(&self.variable(id), object.span)? {
+                        Value::Pointer(pointer) => pointer,
+                        _ => unreachable!("interpreter: pointer variable without a pointer"),
+                    },
+                    _ => self.variable(id),
+                };
+                let members = self
+                    .types
+                    .members(object_ty)
+                    .expect("interpreter: member of a type without members");
+                let index = members
+                    .iter()
+                    .position(|(name, _)| *name == member.spanned)
+                    .expect("interpreter: unknown member");
+                pointer.path.push(index);
+                Ok(pointer)
+            }
+            _ => self.temporary(expr),
+        }
+    }
+
+    /// somewhere to keep a value which isn't otherwise stored, so it can be pointed to
+    fn temporary(&mut self, expr: &Spanned<Expr>) -> Flow<Pointer> {
+        let value = self.expr(expr)?;
+        let slot = self.allocate(value);
+        Ok(Pointer {
+            slot,
+            generation: self.memory[slot].generation,
+            path: Vec::new(),
+        })
+    }
+
+    fn struct_lit(&mut self, expr: &Spanned<Expr>, fields: &StructLitFields) -> Flow<Value> {
+        let Ty::Named(ty) = self.ty(expr) else {
+            unreachable!("interpreter: struct literal of a non-struct");
+        };
+        let (members, union) = match self.types.decls.get(ty) {
+            Some(TypeDecl::Struct(members)) => (members, false),
+            Some(TypeDecl::Union(members)) => (members, true),
+            _ => unreachable!("interpreter: struct literal of a non-struct"),
+        };
+
+        let mut values = vec![Value::Void; members.len()];
+        let mut given = None;
+        match fields {
+            StructLitFields::Positional(exprs) => {
+                for (index, value) in exprs.iter().enumerate() {
+                    values[index] = self.expr(value)?;
+                }
+            }
+            // evaluated in the order written, not the order declared
+            StructLitFields::Named(exprs) => {
+                for (name, value) in exprs {
+                    let index = members
+                        .iter()
+                        .position(|(member, _)| *member == name.spanned)
+                        .expect("interpreter: unknown member");
+                    values[index] = self.expr(value)?;
+                    given = Some(index);
+                }
+            }
+        }
+        let values = values
+            .into_iter()
+            .zip(members)
+            .map(|(value, (_, member))| self.coerce(value, member));
+
+        if !union {
+            return Ok(Value::Struct {
+                ty: *ty,
+                fields: values.collect(),
+            });
+        }
+        let Some(discriminant) = given else {
+            return error(
+                RuntimeErrors::Unsupported("a union literal without a member".to_owned()),
+                expr.span,
+            );
+        };
+        Ok(Value::Union {
+            ty: *ty,
+            discriminant,
+            value: Box::new(values.into_iter().nth(discriminant).unwrap()),
+        })
+    }
+
+    /*
+     * calls
+     */
+
+    fn call(&mut self, span: Span, args: &[Box<Spanned<Expr>>]) -> Flow<Value> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.expr(arg)?);
+        }
+
+        let function = if let Some(mut dispatch) = self.types.dispatch(span) {
+            loop {
+                let Value::Union {
+                    discriminant,
+                    value,
+                    ..
+                } = values[dispatch.arg].clone()
+                else {
+                    unreachable!("interpreter: dispatched on a non-union");
+                };
+                values[dispatch.arg] = *value;
+                match &dispatch.arms[discriminant] {
+                    Arm::Call(function) => break *function,
+                    Arm::Dispatch(nested) => dispatch = nested,
+                }
+            }
+        } else if let Some(function) = self.types.call(span) {
+            function
+        } else {
+            // through a function pointer
+            let Some(Res::Def(id)) = self.res.get(span) else {
+                unreachable!("interpreter: unresolved call");
+            };
+            match self.load(&self.variable(*id), span)? {
+                Value::Fn(function) => function,
+                _ => unreachable!("interpreter: called a non-function"),
+            }
+        };
+        self.invoke(function, values, Some(span))
+    }
+
+    /// the implementation of an interface stub, for the arguments it's actually given
+    fn implementation(&self, stub: DefId, args: &[Value], span: Span) -> Flow<DefId> {
+        let name = &self.res.def(stub).name;
+        let accepts = |param: &Ty, arg: &Value| -> Option<usize> {
+            match (param, arg.ty()) {
+                (param, Some(arg)) if *param == arg => Some(1),
+                (Ty::Named(id), _)
+                    if matches!(
+                        self.types.decls.get(id),
+                        Some(TypeDecl::Generic | TypeDecl::Aggregate(_))
+                    ) =>
+                {
+                    Some(0)
+                }
+                (param, Some(arg)) if self.types.coerces(&arg, param) => Some(0),
+                (Ty::Pointer(_), None) if matches!(arg, Value::Pointer(_)) => Some(0),
+                (Ty::FnPtr { .. }, None) if matches!(arg, Value::Fn(_)) => Some(0),
+                _ => None,
+            }
+        };
+
+        // the closest match wins - an exact implementation, over a default one
+        let found = self
+            .res
+            .defs
+            .iter()
+            .enumerate()
+            .filter(|(_, def)| def.kind == DefKind::Fn && def.name == *name)
+            .filter_map(|(id, _)| {
+                let sig = self.types.sigs.get(&DefId(id))?;
+                if sig.params.len() != args.len() {
+                    return None;
+                }
+                let score = sig
+                    .params
+                    .iter()
+                    .zip(args)
+                    .map(|(param, arg)| accepts(param, arg))
+                    .sum::<Option<usize>>()?;
+                Some((DefId(id), score))
+            })
+            .max_by_key(|(id, score)| (*score, std::cmp::Reverse(id.0)));
+        match found {
+            Some((id, _)) => Ok(id),
+            None => error(RuntimeErrors::NoImplementation(name.clone()), span),
+        }
+    }
+
+    /// `call` is where the function is called from - `main` isn't called from anywhere
+    fn invoke(&mut self, mut function: DefId, args: Vec<Value>, call: Option<Span>) -> Flow<Value> {
+        let span = call.unwrap_or(self.res.def(function).span);
+        if !self.fns.contains_key(&function) {
+            if let Some(intrinsic) = Intrinsic::of(self.res, self.types, function) {
+                let ret = match self.types.sigs[&function].ret {
+                    Ty::Void => RtTy::Void,
+                    Ty::Prim(PrimType::Unt) => RtTy::Unt,
+                    _ => RtTy::Int,
+                };
+                return match self.host.call(intrinsic, &args, ret) {
+                    Ok(Called::Returned(value)) => Ok(value),
+                    Ok(Called::Exited(code)) => Err(Unwind::Exit(code)),
+                    Err(err) => error(err, span),
+                };
+            }
+            function = self.implementation(function, &args, span)?;
+        }
+        if self.frames.len() >= MAX_DEPTH {
+            return error(RuntimeErrors::StackOverflow(self.frames.len()), span);
+        }
+
+        let def = self.res.def(function);
+        let sig = &self.types.sigs[&function];
+        self.frames.push(Frame {
+            function: Some(function),
+            slots: HashMap::new(),
+            base: self.memory.len(),
+            call: call.map(|span| (def.name.as_str(), span)),
+        });
+        let Function { parameters, body } = self.fns[&function];
+        for ((_, name), (arg, param)) in parameters.iter().zip(args.into_iter().zip(&sig.params)) {
+            let id = self
+                .res
+                .def_at(name.span)
+                .expect("interpreter: unresolved parameter");
+            let arg = self.coerce(arg, param);
+            self.declare(id, arg);
+        }
+
+        let result = match self.block(body, None) {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(self.coerce(value, &sig.ret)),
+            Err(Unwind::Goto(_)) => unreachable!("interpreter: `goto` out of a function"),
+            Err(unwind) => Err(unwind),
+        };
+        let frame = self.frames.pop().expect("interpreter: no frame");
+        self.memory.truncate(frame.base);
+        result.map_err(|unwind| match (unwind, frame.call) {
+            // the call stack, innermost first
+            (Unwind::Error(err), Some((name, span))) => Unwind::Error(Box::new(
+                err.with_note(format!("in `{name}`, called here"), span),
+            )),
+            (unwind, _) => unwind,
+        })
+    }
+}
+
+fn overflow(expr: String, ty: &str) -> RuntimeErrors {
+    RuntimeErrors::Overflow {
+        expr,
+        ty: ty.to_owned(),
+    }
+}
+
+pub(crate) fn negate(value: Value) -> std::result::Result<Value, RuntimeErrors> {
+    match value {
+        Value::Int(int) => match int.checked_neg() {
+            Some(int) => Ok(Value::Int(int)),
+            None => Err(overflow(format!("-{int}"), "int")),
+        },
+        Value::Unt(0) => Ok(Value::Unt(0)),
+        Value::Unt(unt) => Err(overflow(format!("-{unt}"), "unt")),
+        _ => Err(RuntimeErrors::Malformed("a non-integer is negated")),
+    }
+}
+
+/// shared with the vm, which must raise exactly the same errors. (operands the checker would
+/// never let through can only come from malformed bytecode)
+pub(crate) fn biop(
+    left: Value,
+    op: BiOps,
+    right: Value,
+) -> std::result::Result<Value, RuntimeErrors> {
+    let shown = || format!("{left} {} {right}", op.symbol());
+
+    match op {
+        BiOps::Eq => return Ok(Value::Bool(left == right)),
+        BiOps::NEq => return Ok(Value::Bool(left != right)),
+        BiOps::BitNot | BiOps::LogNot => {
+            return Err(RuntimeErrors::Unsupported(format!(
+                "`{}` as a binary operator",
+                op.symbol()
+            )))
+        }
+        _ => {}
+    }
+
+    macro_rules! integers {
+        ($left:expr, $right:expr, $ty:literal, $wrap:path) => {{
+            let (left, right) = ($left, $right);
+            let result = match op {
+                BiOps::Add => left.checked_add(right),
+                BiOps::Sub => left.checked_sub(right),
+                BiOps::Mul => left.checked_mul(right),
+                BiOps::Div | BiOps::Mod if right == 0 => {
+                    return Err(RuntimeErrors::DivByZero(shown()));
+                }
+                BiOps::Div => left.checked_div(right),
+                BiOps::Mod => left.checked_rem(right),
+                BiOps::BitOr => Some(left | right),
+                BiOps::BitAnd => Some(left & right),
+                BiOps::BitXor => Some(left ^ right),
+                BiOps::BitLShift | BiOps::BitRshift => {
+                    let Some(amount) = u32::try_from(right).ok().filter(|amount| *amount < 64)
+                    else {
+                        return Err(RuntimeErrors::OversizedShift(shown()));
+                    };
+                    // bits shifted out are lost
+                    Some(match op {
+                        BiOps::BitLShift => left << amount,
+                        _ => left >> amount,
+                    })
+                }
+                BiOps::Gr => return Ok(Value::Bool(left > right)),
+                BiOps::Ls => return Ok(Value::Bool(left < right)),
+                BiOps::GrEq => return Ok(Value::Bool(left >= right)),
+                BiOps::LsEq => return Ok(Value::Bool(left <= right)),
+                _ => return Err(RuntimeErrors::Malformed("a logical operator on integers")),
+            };
+            match result {
+                Some(result) => $wrap(result),
+                None => return Err(overflow(shown(), $ty)),
+            }
+        }};
+    }
+
+    Ok(match (&left, &right) {
+        (Value::Int(l), Value::Int(r)) => integers!(*l, *r, "int", Value::Int),
+        (Value::Unt(l), Value::Unt(r)) => integers!(*l, *r, "unt", Value::Unt),
+        (Value::Bool(l), Value::Bool(r)) => match op {
+            BiOps::LogOr => Value::Bool(*l || *r),
+            BiOps::LogAnd => Value::Bool(*l && *r),
+            _ => return Err(RuntimeErrors::Malformed("arithmetic on bools")),
+        },
+        _ => return Err(RuntimeErrors::Malformed("mismatched operands")),
+    })
+}
+
+/// the `main` function declared at the top level of the module
+pub(crate) fn main_fn(checked: &Checked) -> Option<(DefId, usize)> {
+    checked
+        .ast
+        .stmts
+        .iter()
+        .find_map(|stmt| match &stmt.spanned {
+            Stmt::Fn {
+                name, parameters, ..
+            } if name.spanned == "main" => Some((checked.res.def_at(name.span)?, parameters.len())),
+            _ => None,
+        })
+}
+
+/// runs a checked module: its top level first, then `main`, whose result is returned.
+/// a runtime error is given with the call stack (innermost first) as its notes.
+pub fn run(checked: &Checked) -> std::result::Result<Value, SdwErr> {
+    on_big_stack(|| execute(checked))
+}
+
+/// the interpreter recurses on the host's stack, so needs far more of it than a thread has
+/// by default to reach `MAX_DEPTH`
+fn on_big_stack<T: Send>(run: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, run)
+            .expect("interpreter: couldn't spawn a thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+fn interpreter(checked: &Checked) -> Interpreter<'_> {
+    let mut interpreter = Interpreter {
+        res: &checked.res,
+        types: &checked.types,
+        fns: HashMap::new(),
+        memory: Vec::new(),
+        generation: 0,
+        frames: vec![Frame {
+            function: None,
+            slots: HashMap::new(),
+            base: 0,
+            call: None,
+        }],
+        host: Host::default(),
+    };
+    interpreter.collect(&checked.ast);
+    interpreter
+}
+
+fn execute(checked: &Checked) -> std::result::Result<Value, SdwErr> {
+    let mut interpreter = interpreter(checked);
+
+    let Some((main, parameters)) = main_fn(checked) else {
+        return Err(SdwErr::from_pos(RuntimeErrors::NoMain, Span::default()));
+    };
+    if parameters != 0 {
+        let span = checked.res.def(main).span;
+        return Err(SdwErr::from_pos(RuntimeErrors::MainTakesParams, span));
+    }
+
+    let result = match interpreter.block(&checked.ast, None) {
+        Ok(_) | Err(Unwind::Return(_)) => interpreter.invoke(main, Vec::new(), None),
+        Err(unwind) => Err(unwind),
+    };
+    match result {
+        Ok(value) => Ok(value),
+        // exiting is as if `main` returned the code
+        Err(Unwind::Exit(code)) => Ok(Value::Int(code)),
+        Err(Unwind::Error(err)) => {
+            let span = checked.res.def(main).span;
+            Err(collapse(err.with_note("in `main`", span)))
+        }
+        Err(Unwind::Return(_) | Unwind::Goto(_)) => unreachable!("interpreter: escaped `main`"),
+    }
+}
+
+/// how the top level ended, when run on its own
+#[derive(Debug)]
+pub enum Ended {
+    /// with the value of its last expression
+    Value(Value),
+    Exited(i64),
+}
+
+/// what the repl keeps between inputs: the top level's variables, & the memory they're in.
+/// each input is checked again along with those before it, which may number definitions
+/// differently (a new `fn` is declared before the `let`s above it), so what's kept is
+/// renumbered by the name & place of each definition.
+#[derive(Debug, Default)]
+pub struct Globals {
+    memory: Vec<Slot>,
+    generation: u64,
+    slots: HashMap<DefId, usize>,
+    host: Host,
+    /// the definitions the ids kept refer to
+    defs: Vec<(String, Span)>,
+}
+
+impl Globals {
+    /// refers to definitions as `res` numbers them, forgetting the variables it doesn't have
+    /// (those of input which was rejected)
+    fn renumber(&mut self, res: &Resolutions) {
+        let defs = res
+            .defs
+            .iter()
+            .map(|def| (def.name.clone(), def.span))
+            .collect::<Vec<_>>();
+        let ids = defs
+            .iter()
+            .enumerate()
+            .map(|(id, def)| (def, DefId(id)))
+            .collect::<HashMap<_, _>>();
+        let renumbered = |id: DefId| ids.get(self.defs.get(id.0)?).copied();
+
+        self.slots = self
+            .slots
+            .iter()
+            .filter_map(|(id, slot)| Some((renumbered(*id)?, *slot)))
+            .collect();
+        fn renumber(value: &mut Value, renumbered: &impl Fn(DefId) -> Option<DefId>) {
+            match value {
+                Value::Struct { ty, fields } => {
+                    *ty = renumbered(*ty).unwrap_or(*ty);
+                    for field in fields {
+                        renumber(field, renumbered);
+                    }
+                }
+                Value::Union { ty, value, .. } => {
+                    *ty = renumbered(*ty).unwrap_or(*ty);
+                    renumber(value, renumbered);
+                }
+                Value::Fn(id) => *id = renumbered(*id).unwrap_or(*id),
+                _ => {}
+            }
+        }
+        for slot in &mut self.memory {
+            renumber(&mut slot.value, &renumbered);
+        }
+        self.defs = defs;
+    }
+}
+
+/// runs the top level's statements from line `from` on (without calling `main`), for the
+/// repl - those before it ran with earlier input, leaving their variables in `globals`.
+pub fn evaluate(
+    checked: &Checked,
+    globals: &mut Globals,
+    from: SpanInt,
+) -> std::result::Result<Ended, SdwErr> {
+    globals.renumber(&checked.res);
+    on_big_stack(|| {
+        let mut interpreter = interpreter(checked);
+        interpreter.memory = std::mem::take(&mut globals.memory);
+        interpreter.generation = globals.generation;
+        interpreter.frames[0].slots = std::mem::take(&mut globals.slots);
+        interpreter.host = std::mem::take(&mut globals.host);
+
+        let stmts = &checked.ast.stmts;
+        let next = stmts
+            .iter()
+            .position(|stmt| stmt.span.sline >= from && !driver::in_core(stmt.span))
+            .unwrap_or(stmts.len());
+        let ended = interpreter.block_from(&checked.ast, next, None);
+        // every call has returned (or unwound), so only the top level's variables are left
+        let Interpreter {
+            memory,
+            generation,
+            mut frames,
+            host,
+            ..
+        } = interpreter;
+        globals.memory = memory;
+        globals.generation = generation;
+        globals.slots = frames.pop().expect("interpreter: no frame").slots;
+        globals.host = host;
+
+        match ended {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(Ended::Value(value)),
+            Err(Unwind::Exit(code)) => Ok(Ended::Exited(code)),
+            Err(Unwind::Error(err)) => Err(collapse(*err)),
+            Err(Unwind::Goto(_)) => unreachable!("interpreter: escaped the top level"),
+        }
+    })
+}
+
+/// the same call repeated (by recursion) is only shown once, with a count
+pub(crate) fn collapse(mut err: SdwErr) -> SdwErr {
+    let mut notes: Vec<(Note, usize)> = Vec::new();
+    for note in err.notes {
+        match notes.last_mut() {
+            Some((last, count)) if last.message == note.message && last.span == note.span => {
+                *count += 1
+            }
+            _ => notes.push((note, 1)),
+        }
+    }
+    err.notes = notes
+        .into_iter()
+        .map(|(mut note, count)| {
+            if count > 1 {
+                note.message = format!("{} ({count} times)", note.message);
+            }
+            note
+        })
+        .collect();
+    err
+}
@@ -0,0 +1,278 @@
+use crate::bytecode::RtTy;
+use crate::interp::Value;
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// the stubs `core` declares, which have no implementation in shadow but are provided by each
+/// backend instead. they're told apart by name & signature alone, so a program may declare
+/// more of them (eg. a `syscall` taking a pointer) itself - while its `fn`s of the same
+/// names shadow `core`'s stubs, rather than implementing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intrinsic {
+    /// `fn int syscall(int, [..])` - the call's number, then up to six arguments
+    Syscall,
+    /// `fn int alloc(int)` - the address of that many zeroed bytes, which are never freed
+    Alloc,
+    /// `#[ no_return ] fn void panic(string)` - raises a runtime error, with the message
+    Panic,
+    /// `fn int address(string)` - where a string's bytes are
+    Address,
+    /// `fn int length(string)` - how many bytes a string has
+    Length,
+    /// `fn int byte(int)` - the byte at an address
+    Byte,
+    /// `fn void store(int, int)` - sets the byte at an address (the first) to the second's
+    /// lowest
+    Store,
+}
+
+/// the most arguments a syscall takes, after its number
+pub const SYSCALL_ARGS: usize = 6;
+
+fn resolve<'t>(types: &'t Types, ty: &'t Ty) -> &'t Ty {
+    match ty {
+        Ty::Named(id) => match types.decls.get(id) {
+            Some(TypeDecl::Alias(inner)) => resolve(types, inner),
+            _ => ty,
+        },
+        ty => ty,
+    }
+}
+
+impl Intrinsic {
+    pub const ALL: [Intrinsic; 7] = [
+        Intrinsic::Syscall,
+        Intrinsic::Alloc,
+        Intrinsic::Panic,
+        Intrinsic::Address,
+        Intrinsic::Length,
+        Intrinsic::Byte,
+        Intrinsic::Store,
+    ];
+
+    /// how many arguments it may be called with
+    pub fn arity(self) -> std::ops::RangeInclusive<usize> {
+        match self {
+            Intrinsic::Syscall => 1..=SYSCALL_ARGS + 1,
+            Intrinsic::Store => 2..=2,
+            _ => 1..=1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Intrinsic::Syscall => "syscall",
+            Intrinsic::Alloc => "alloc",
+            Intrinsic::Panic => "panic",
+            Intrinsic::Address => "address",
+            Intrinsic::Length => "length",
+            Intrinsic::Byte => "byte",
+            Intrinsic::Store => "store",
+        }
+    }
+
+    /// the intrinsic a stub is, if it's one
+    pub fn of(res: &Resolutions, types: &Types, stub: DefId) -> Option<Intrinsic> {
+        let def = res.def(stub);
+        let sig = types.sigs.get(&stub)?;
+        if def.kind != DefKind::Stub {
+            return None;
+        }
+        let intrinsic = *Intrinsic::ALL
+            .iter()
+            .find(|intrinsic| intrinsic.name() == def.name)?;
+
+        let params = sig
+            .params
+            .iter()
+            .map(|param| resolve(types, param))
+            .collect::<Vec<_>>();
+        let ret = resolve(types, &sig.ret);
+        let fits = match intrinsic {
+            // anything which fits in a register
+            Intrinsic::Syscall => {
+                intrinsic.arity().contains(&params.len())
+                    && params.iter().all(|param| {
+                        matches!(
+                            param,
+                            Ty::Prim(PrimType::Int | PrimType::Unt | PrimType::Bool)
+                                | Ty::Pointer(_)
+                        )
+                    })
+                    && matches!(ret, Ty::Void | Ty::Prim(PrimType::Int | PrimType::Unt))
+            }
+            Intrinsic::Alloc | Intrinsic::Byte => params == [&Ty::INT] && *ret == Ty::INT,
+            Intrinsic::Panic => params == [&Ty::STRING] && *ret == Ty::Void,
+            Intrinsic::Store => params == [&Ty::INT, &Ty::INT] && *ret == Ty::Void,
+            Intrinsic::Address | Intrinsic::Length => params == [&Ty::STRING] && *ret == Ty::INT,
+        };
+        fits.then_some(intrinsic)
+    }
+}
+
+/*
+ * emulation
+ */
+
+/// the address of the first byte of `Host`'s memory - so `0` is never a valid one
+pub const BASE: i64 = 0x10000;
+
+/// the memory & syscalls of the machine a program runs on, for the interpreter & the vm
+/// (which run it themselves). only reading stdin, writing to stdout & stderr, and exiting
+/// are emulated.
+#[derive(Debug, Default)]
+pub struct Host {
+    /// from `BASE`. strings' bytes are copied in when their address is first taken
+    memory: Vec<u8>,
+    strings: HashMap<String, i64>,
+}
+
+/// what calling an intrinsic did
+#[derive(Debug)]
+pub enum Called {
+    Returned(Value),
+    /// the program asked to exit, with this code
+    Exited(i64),
+}
+
+impl Host {
+    fn reserve(&mut self, size: usize) -> i64 {
+        // every allocation is aligned, as it would be by `malloc`
+        while !self.memory.len().is_multiple_of(8) {
+            self.memory.push(0);
+        }
+        let address = BASE + self.memory.len() as i64;
+        self.memory.resize(self.memory.len() + size, 0);
+        address
+    }
+
+    pub fn alloc(&mut self, size: i64) -> std::result::Result<i64, RuntimeErrors> {
+        // far more than would ever be wanted, rather than exhausting the host's memory
+        if !(0..1 << 32).contains(&size) {
+            return Err(RuntimeErrors::OutOfBounds {
+                address: 0,
+                length: size,
+            });
+        }
+        Ok(self.reserve(size as usize))
+    }
+
+    pub fn address(&mut self, text: &str) -> i64 {
+        if let Some(address) = self.strings.get(text) {
+            return *address;
+        }
+        let address = self.reserve(text.len());
+        let at = (address - BASE) as usize;
+        self.memory[at..at + text.len()].copy_from_slice(text.as_bytes());
+        self.strings.insert(text.to_owned(), address);
+        address
+    }
+
+    fn bytes(
+        &mut self,
+        address: i64,
+        length: i64,
+    ) -> std::result::Result<&mut [u8], RuntimeErrors> {
+        let start = address.wrapping_sub(BASE);
+        match start.checked_add(length) {
+            Some(end) if 0 <= start && start <= end && end <= self.memory.len() as i64 => {
+                Ok(&mut self.memory[start as usize..end as usize])
+            }
+            _ => Err(RuntimeErrors::OutOfBounds { address, length }),
+        }
+    }
+
+    /// `args` are the call's number, then its arguments. like the kernel, it returns a
+    /// negated error number when a call fails.
+    pub fn syscall(&mut self, args: &[i64]) -> std::result::Result<Called, RuntimeErrors> {
+        const EBADF: i64 = 9;
+        const EIO: i64 = 5;
+        let arg = |index: usize| args.get(index).copied().unwrap_or(0);
+
+        let returned = match arg(0) {
+            // read
+            0 => {
+                let (fd, address, length) = (arg(1), arg(2), arg(3));
+                let buffer = self.bytes(address, length)?;
+                match fd {
+                    0 => std::io::stdin()
+                        .read(buffer)
+                        .map_or(-EIO, |read| read as i64),
+                    _ => -EBADF,
+                }
+            }
+            // write
+            1 => {
+                let (fd, address, length) = (arg(1), arg(2), arg(3));
+                let buffer = self.bytes(address, length)?;
+                let written = match fd {
+                    1 => {
+                        let mut stdout = std::io::stdout();
+                        stdout.write_all(buffer).and_then(|()| stdout.flush())
+                    }
+                    2 => std::io::stderr().write_all(buffer),
+                    _ => return Ok(Called::Returned(Value::Int(-EBADF))),
+                };
+                written.map_or(-EIO, |()| length)
+            }
+            // exit & exit_group
+            60 | 231 => return Ok(Called::Exited(arg(1))),
+            number => return Err(RuntimeErrors::UnknownSyscall(number)),
+        };
+        Ok(Called::Returned(Value::Int(returned)))
+    }
+
+    /// calls an intrinsic, declared to return `ret` - shared by the interpreter & vm, which
+    /// must behave exactly the same
+    pub fn call(
+        &mut self,
+        intrinsic: Intrinsic,
+        args: &[Value],
+        ret: RtTy,
+    ) -> std::result::Result<Called, RuntimeErrors> {
+        let int = |value: &Value| match value {
+            Value::Int(int) => Ok(*int),
+            Value::Unt(unt) => Ok(*unt as i64),
+            Value::Bool(bool) => Ok(i64::from(*bool)),
+            _ => Err(RuntimeErrors::Unsupported(
+                "passing a pointer to a syscall, rather than an address,".to_owned(),
+            )),
+        };
+        // (the checker only lets strings through, but bytecode may have been tampered with)
+        let text = |value: &Value| match value {
+            Value::Str(text) => Ok(text.clone()),
+            _ => Err(RuntimeErrors::Malformed(
+                "a string intrinsic given a non-string",
+            )),
+        };
+
+        let value = match intrinsic {
+            Intrinsic::Syscall => {
+                let args = args
+                    .iter()
+                    .map(int)
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                match self.syscall(&args)? {
+                    Called::Returned(Value::Int(int)) => match ret {
+                        RtTy::Void => Value::Void,
+                        RtTy::Unt => Value::Unt(int as u64),
+                        _ => Value::Int(int),
+                    },
+                    called => return Ok(called),
+                }
+            }
+            Intrinsic::Alloc => Value::Int(self.alloc(int(&args[0])?)?),
+            Intrinsic::Panic => return Err(RuntimeErrors::Panic(text(&args[0])?)),
+            Intrinsic::Address => Value::Int(self.address(&text(&args[0])?)),
+            Intrinsic::Length => Value::Int(text(&args[0])?.len() as i64),
+            Intrinsic::Byte => Value::Int(self.bytes(int(&args[0])?, 1)?[0].into()),
+            Intrinsic::Store => {
+                let byte = int(&args[1])? as u8;
+                self.bytes(int(&args[0])?, 1)?[0] = byte;
+                Value::Void
+            }
+        };
+        Ok(Called::Returned(value))
+    }
+}
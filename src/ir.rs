@@ -0,0 +1,655 @@
+use crate::bytecode::Trap;
+use crate::intrinsics::Intrinsic;
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// the result of an instruction. each is assigned exactly once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inst {
+    /// the function's argument at this index, always in the entry block
+    Param(u32),
+    Int(i64),
+    Unt(u64),
+    Bool(bool),
+    /// a `string` value - the bytes are the backend's to place
+    Str(String),
+    Void,
+    /// a value never read before it's written - eg. a variable whose `let` a `goto` skipped
+    Undef,
+    /// a pointer to one of the module's functions
+    Fn(u32),
+    Neg(Value),
+    Not(Value),
+    /// never `||` or `&&`, which are lowered to branches
+    BiOp(BiOps, Value, Value),
+    /// a slot for a variable whose address is taken, always in the entry block
+    Alloca,
+    Load(Value),
+    Store {
+        pointer: Value,
+        value: Value,
+    },
+    /// a pointer to a member of the struct or union pointed to - for a union, only the member
+    /// it holds
+    MemberPtr(Value, u32),
+    /// a member of a struct or union value - for a union, only the member it holds
+    Member(Value, u32),
+    /// every member, in declaration order
+    Struct(Vec<Value>),
+    Union {
+        tag: u32,
+        value: Value,
+    },
+    /// which member a union holds, as an `unt`
+    Tag(Value),
+    Call {
+        function: u32,
+        args: Vec<Value>,
+    },
+    /// a call to whichever implementation of a stub fits the arguments, chosen at runtime
+    CallInterface {
+        stub: DefId,
+        args: Vec<Value>,
+    },
+    CallPtr {
+        pointer: Value,
+        args: Vec<Value>,
+    },
+    /// a call of one of `core`'s intrinsics, which each backend provides itself
+    Intrinsic {
+        intrinsic: Intrinsic,
+        args: Vec<Value>,
+    },
+    /// the value from whichever predecessor control came from. phis lead their block
+    Phi(Vec<(BlockId, Value)>),
+}
+
+impl Inst {
+    pub fn operands(&self) -> Vec<Value> {
+        let mut operands = Vec::new();
+        self.visit(|value| operands.push(value));
+        operands
+    }
+
+    fn visit(&self, mut visit: impl FnMut(Value)) {
+        match self {
+            Inst::Param(_)
+            | Inst::Int(_)
+            | Inst::Unt(_)
+            | Inst::Bool(_)
+            | Inst::Str(_)
+            | Inst::Void
+            | Inst::Undef
+            | Inst::Fn(_)
+            | Inst::Alloca => {}
+            Inst::Neg(value)
+            | Inst::Not(value)
+            | Inst::Load(value)
+            | Inst::MemberPtr(value, _)
+            | Inst::Member(value, _)
+            | Inst::Union { value, .. }
+            | Inst::Tag(value) => visit(*value),
+            Inst::BiOp(_, left, right) => {
+                visit(*left);
+                visit(*right);
+            }
+            Inst::Store { pointer, value } => {
+                visit(*pointer);
+                visit(*value);
+            }
+            Inst::Struct(values)
+            | Inst::Call { args: values, .. }
+            | Inst::CallInterface { args: values, .. }
+            | Inst::Intrinsic { args: values, .. } => values.iter().copied().for_each(visit),
+            Inst::CallPtr { pointer, args } => {
+                visit(*pointer);
+                args.iter().copied().for_each(visit);
+            }
+            Inst::Phi(incoming) => incoming.iter().for_each(|(_, value)| visit(*value)),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Inst::Param(_)
+            | Inst::Int(_)
+            | Inst::Unt(_)
+            | Inst::Bool(_)
+            | Inst::Str(_)
+            | Inst::Void
+            | Inst::Undef
+            | Inst::Fn(_)
+            | Inst::Alloca => vec![],
+            Inst::Neg(value)
+            | Inst::Not(value)
+            | Inst::Load(value)
+            | Inst::MemberPtr(value, _)
+            | Inst::Member(value, _)
+            | Inst::Union { value, .. }
+            | Inst::Tag(value) => vec![value],
+            Inst::BiOp(_, left, right) => vec![left, right],
+            Inst::Store { pointer, value } => vec![pointer, value],
+            Inst::Struct(values)
+            | Inst::Call { args: values, .. }
+            | Inst::CallInterface { args: values, .. }
+            | Inst::Intrinsic { args: values, .. } => values.iter_mut().collect(),
+            Inst::CallPtr { pointer, args } => std::iter::once(pointer).chain(args).collect(),
+            Inst::Phi(incoming) => incoming.iter_mut().map(|(_, value)| value).collect(),
+        }
+    }
+
+    /// whether removing the instruction (when its result is unused) could change what the
+    /// program does - by writing memory, calling something, or raising a runtime error
+    pub fn has_effects(&self) -> bool {
+        match self {
+            Inst::Store { .. }
+            | Inst::Call { .. }
+            | Inst::CallInterface { .. }
+            | Inst::CallPtr { .. }
+            | Inst::Intrinsic { .. }
+            | Inst::Neg(_)
+            // pointers may dangle
+            | Inst::Load(_)
+            // unions are checked to hold the member
+            | Inst::Member(..)
+            | Inst::MemberPtr(..) => true,
+            Inst::BiOp(op, ..) => matches!(
+                op,
+                BiOps::Add
+                    | BiOps::Sub
+                    | BiOps::Mul
+                    | BiOps::Div
+                    | BiOps::Mod
+                    | BiOps::BitLShift
+                    | BiOps::BitRshift
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch {
+        condition: Value,
+        then: BlockId,
+        r#else: BlockId,
+    },
+    /// on a union's tag, to the target for the member it holds
+    Switch {
+        tag: Value,
+        targets: Vec<BlockId>,
+    },
+    Return(Value),
+    Trap(Trap),
+    /// after a call which never returns
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn succs(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(to) => vec![*to],
+            Terminator::Branch { then, r#else, .. } => vec![*then, *r#else],
+            Terminator::Switch { targets, .. } => targets.clone(),
+            Terminator::Return(_) | Terminator::Trap(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn succs_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Jump(to) => vec![to],
+            Terminator::Branch { then, r#else, .. } => vec![then, r#else],
+            Terminator::Switch { targets, .. } => targets.iter_mut().collect(),
+            Terminator::Return(_) | Terminator::Trap(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Branch { condition, .. } => vec![condition],
+            Terminator::Switch { tag, .. } => vec![tag],
+            Terminator::Return(value) => vec![value],
+            Terminator::Jump(_) | Terminator::Trap(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Terminator::Branch { condition, .. } => vec![*condition],
+            Terminator::Switch { tag, .. } => vec![*tag],
+            Terminator::Return(value) => vec![*value],
+            Terminator::Jump(_) | Terminator::Trap(_) | Terminator::Unreachable => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub insts: Vec<Value>,
+    pub term: Terminator,
+}
+
+/// a function (or the module's top level) as a graph of basic blocks, in SSA form
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub id: Option<DefId>,
+    /// unique within the module
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    /// every instruction & the type of its result, by `Value` - including those no longer in
+    /// any block
+    pub insts: Vec<Inst>,
+    pub tys: Vec<Ty>,
+    /// the first is the entry
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    pub fn inst(&self, value: Value) -> &Inst {
+        &self.insts[value.0 as usize]
+    }
+
+    pub fn ty(&self, value: Value) -> &Ty {
+        &self.tys[value.0 as usize]
+    }
+
+    pub fn block(&self, block: BlockId) -> &Block {
+        &self.blocks[block.0 as usize]
+    }
+
+    /// the predecessors of every block, in order - a block branching to another twice is
+    /// listed twice
+    pub fn preds(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for succ in block.term.succs() {
+                preds[succ.0 as usize].push(BlockId(index as u32));
+            }
+        }
+        preds
+    }
+
+    /// the blocks reachable from the entry, in reverse postorder
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut order = Vec::new();
+        // (block, whether its successors have been visited)
+        let mut stack = vec![(BlockId(0), false)];
+        while let Some((block, done)) = stack.pop() {
+            if done {
+                order.push(block);
+                continue;
+            }
+            if std::mem::replace(&mut seen[block.0 as usize], true) {
+                continue;
+            }
+            stack.push((block, true));
+            for succ in self.block(block).term.succs().into_iter().rev() {
+                if !seen[succ.0 as usize] {
+                    stack.push((succ, false));
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// replaces every use of a value in `replacements` - following chains, so `a -> b -> c`
+    /// replaces `a` with `c`
+    pub fn replace_uses(&mut self, replacements: &HashMap<Value, Value>) {
+        if replacements.is_empty() {
+            return;
+        }
+        let resolve = |mut value: Value| {
+            while let Some(next) = replacements.get(&value) {
+                if *next == value {
+                    break;
+                }
+                value = *next;
+            }
+            value
+        };
+        for block in &mut self.blocks {
+            for value in &block.insts {
+                for operand in self.insts[value.0 as usize].operands_mut() {
+                    *operand = resolve(*operand);
+                }
+            }
+            for operand in block.term.operands_mut() {
+                *operand = resolve(*operand);
+            }
+        }
+    }
+
+    /// removes phis which only ever give one value (besides themselves), using that value
+    /// in their place. whether any were removed
+    pub fn remove_trivial_phis(&mut self) -> bool {
+        let mut replacements: HashMap<Value, Value> = HashMap::new();
+        let resolve = |replacements: &HashMap<Value, Value>, mut value: Value| {
+            while let Some(next) = replacements.get(&value) {
+                value = *next;
+            }
+            value
+        };
+        // removing one phi may make another trivial
+        loop {
+            let mut changed = false;
+            for block in &self.blocks {
+                for value in &block.insts {
+                    let Inst::Phi(incoming) = &self.insts[value.0 as usize] else {
+                        continue;
+                    };
+                    if replacements.contains_key(value) {
+                        continue;
+                    }
+                    let mut only = None;
+                    let mut trivial = true;
+                    for (_, operand) in incoming {
+                        let operand = resolve(&replacements, *operand);
+                        if operand == *value || Some(operand) == only {
+                            continue;
+                        }
+                        if only.is_some() {
+                            trivial = false;
+                            break;
+                        }
+                        only = Some(operand);
+                    }
+                    if let (true, Some(only)) = (trivial, only) {
+                        replacements.insert(*value, only);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        for block in &mut self.blocks {
+            block
+                .insts
+                .retain(|value| !replacements.contains_key(value));
+        }
+        self.replace_uses(&replacements);
+        !replacements.is_empty()
+    }
+
+    /// drops the blocks `keep` rejects, renumbering the rest. nothing kept may still branch
+    /// to one dropped, though phis may still list them (& are tidied)
+    pub fn retain_blocks(&mut self, keep: impl Fn(BlockId) -> bool) {
+        let mut renumbered = HashMap::new();
+        let mut kept = Vec::new();
+        for (index, block) in std::mem::take(&mut self.blocks).into_iter().enumerate() {
+            if keep(BlockId(index as u32)) {
+                renumbered.insert(BlockId(index as u32), BlockId(kept.len() as u32));
+                kept.push(block);
+            }
+        }
+        for block in &mut kept {
+            for succ in block.term.succs_mut() {
+                *succ = renumbered[succ];
+            }
+            for value in &block.insts {
+                if let Inst::Phi(incoming) = &mut self.insts[value.0 as usize] {
+                    incoming.retain(|(pred, _)| renumbered.contains_key(pred));
+                    for (pred, _) in incoming {
+                        *pred = renumbered[pred];
+                    }
+                }
+            }
+        }
+        self.blocks = kept;
+    }
+
+    /// checks the function is well formed: phis lead their block, with one value for each
+    /// predecessor, & every value used is defined in some block. `Err` describes the first
+    /// problem found.
+    pub fn verify(&self) -> std::result::Result<(), String> {
+        let preds = self.preds();
+        let mut defined = HashSet::new();
+        for block in &self.blocks {
+            defined.extend(block.insts.iter().copied());
+        }
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let id = BlockId(index as u32);
+            let mut phis = true;
+            for value in &block.insts {
+                let inst = self.inst(*value);
+                match inst {
+                    Inst::Phi(incoming) => {
+                        if !phis {
+                            return Err(format!("{value} follows a non-phi in {id}"));
+                        }
+                        let mut from = incoming.iter().map(|(pred, _)| *pred).collect::<Vec<_>>();
+                        let mut expected = preds[index].clone();
+                        from.sort();
+                        expected.sort();
+                        expected.dedup();
+                        from.dedup();
+                        if from != expected {
+                            return Err(format!("{value} doesn't match the preds of {id}"));
+                        }
+                    }
+                    _ => phis = false,
+                }
+                if let Some(operand) = inst.operands().into_iter().find(|v| !defined.contains(v)) {
+                    return Err(format!("{value} uses {operand}, which isn't defined"));
+                }
+                if matches!(inst, Inst::Param(_) | Inst::Alloca) && index != 0 {
+                    return Err(format!("{value} belongs in the entry block"));
+                }
+            }
+            for succ in block.term.succs() {
+                if succ.0 as usize >= self.blocks.len() {
+                    return Err(format!("{id} branches to {succ}, which doesn't exist"));
+                }
+            }
+            if let Some(operand) = block
+                .term
+                .operands()
+                .into_iter()
+                .find(|v| !defined.contains(v))
+            {
+                return Err(format!("{id} uses {operand}, which isn't defined"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// a whole program, lowered
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// the first is the module's top level, which runs before `main`
+    pub functions: Vec<Function>,
+    pub main: Option<u32>,
+    /// struct & union declarations, by id
+    pub types: Vec<(DefId, String, TypeDecl)>,
+    /// names of the generics & stubs referred to
+    pub names: HashMap<DefId, String>,
+}
+
+impl Module {
+    fn ty(&self, ty: &Ty) -> String {
+        match ty {
+            Ty::Prim(prim) => match prim {
+                PrimType::Int => "int",
+                PrimType::Unt => "unt",
+                PrimType::Float => "float",
+                PrimType::Bool => "bool",
+                PrimType::String => "string",
+            }
+            .to_owned(),
+            Ty::Void | Ty::Never | Ty::Error => "void".to_owned(),
+            Ty::Named(id) => self.names[id].clone(),
+            Ty::Pointer(to) => format!("&{}", self.ty(to)),
+            Ty::FnPtr { args, ret } => format!(
+                "({}) -> {}",
+                args.iter()
+                    .map(|arg| self.ty(arg))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.ty(ret)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+/// values are numbered in the order they're printed, so dumps don't depend on how many were
+/// created & thrown away along the way
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (_, name, decl) in &self.types {
+            let (kind, members) = match decl {
+                TypeDecl::Struct(members) => ("struct", members),
+                TypeDecl::Union(members) => ("union", members),
+                _ => continue,
+            };
+            let members = members
+                .iter()
+                .map(|(member, ty)| format!("{} {member}", self.ty(ty)))
+                .collect::<Vec<_>>();
+            writeln!(f, "type {name} = {kind} {{ {} }}", members.join(", "))?;
+        }
+
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 || !self.types.is_empty() {
+                writeln!(f)?;
+            }
+            let params = function
+                .params
+                .iter()
+                .map(|param| self.ty(param))
+                .collect::<Vec<_>>();
+            let role = if Some(index as u32) == self.main {
+                " (entry point)"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "fn @{}({}) -> {}{role} {{",
+                function.name,
+                params.join(", "),
+                self.ty(&function.ret)
+            )?;
+
+            let mut numbers = HashMap::new();
+            for block in &function.blocks {
+                for value in &block.insts {
+                    let number = Value(numbers.len() as u32);
+                    numbers.insert(*value, number);
+                }
+            }
+            // values no longer defined anywhere show as they are, which `verify` complains of
+            let show = |value: &Value| numbers.get(value).copied().unwrap_or(*value);
+            let shows = |values: &[Value]| {
+                values
+                    .iter()
+                    .map(|value| show(value).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            for (index, block) in function.blocks.iter().enumerate() {
+                writeln!(f, "{}:", BlockId(index as u32))?;
+                for value in &block.insts {
+                    let inst = match function.inst(*value) {
+                        Inst::Param(index) => format!("param {index}"),
+                        Inst::Int(int) => format!("int {int}"),
+                        Inst::Unt(unt) => format!("unt {unt}"),
+                        Inst::Bool(bool) => format!("bool {bool}"),
+                        Inst::Str(text) => format!("str {text:?}"),
+                        Inst::Void => "void".to_owned(),
+                        Inst::Undef => "undef".to_owned(),
+                        Inst::Fn(function) => {
+                            format!("fn @{}", self.functions[*function as usize].name)
+                        }
+                        Inst::Neg(value) => format!("neg {}", show(value)),
+                        Inst::Not(value) => format!("not {}", show(value)),
+                        Inst::BiOp(op, left, right) => {
+                            format!("op {} {}, {}", op.symbol(), show(left), show(right))
+                        }
+                        Inst::Alloca => "alloca".to_owned(),
+                        Inst::Load(pointer) => format!("load {}", show(pointer)),
+                        Inst::Store { pointer, value } => {
+                            format!("store {}, {}", show(pointer), show(value))
+                        }
+                        Inst::MemberPtr(pointer, member) => {
+                            format!("member.ptr {}, {member}", show(pointer))
+                        }
+                        Inst::Member(value, member) => format!("member {}, {member}", show(value)),
+                        Inst::Struct(values) => format!("struct [{}]", shows(values)),
+                        Inst::Union { tag, value } => format!("union {tag}, {}", show(value)),
+                        Inst::Tag(value) => format!("tag {}", show(value)),
+                        Inst::Call { function, args } => format!(
+                            "call @{}({})",
+                            self.functions[*function as usize].name,
+                            shows(args)
+                        ),
+                        Inst::CallInterface { stub, args } => {
+                            format!("call.interface @{}({})", self.names[stub], shows(args))
+                        }
+                        Inst::CallPtr { pointer, args } => {
+                            format!("call.ptr {}({})", show(pointer), shows(args))
+                        }
+                        Inst::Intrinsic { intrinsic, args } => {
+                            format!("intrinsic {}({})", intrinsic.name(), shows(args))
+                        }
+                        Inst::Phi(incoming) => {
+                            let incoming = incoming
+                                .iter()
+                                .map(|(pred, value)| format!("[{pred}: {}]", show(value)))
+                                .collect::<Vec<_>>();
+                            format!("phi {}", incoming.join(", "))
+                        }
+                    };
+                    let ty = self.ty(function.ty(*value));
+                    writeln!(f, "    {}: {ty} = {inst}", show(value))?;
+                }
+                let term = match &block.term {
+                    Terminator::Jump(to) => format!("jump {to}"),
+                    Terminator::Branch {
+                        condition,
+                        then,
+                        r#else,
+                    } => format!("branch {}, {then}, {}", show(condition), r#else),
+                    Terminator::Switch { tag, targets } => {
+                        let targets = targets.iter().map(BlockId::to_string).collect::<Vec<_>>();
+                        format!("switch {}, [{}]", show(tag), targets.join(", "))
+                    }
+                    Terminator::Return(value) => format!("return {}", show(value)),
+                    Terminator::Trap(trap) => format!("trap {trap:?}"),
+                    Terminator::Unreachable => "unreachable".to_owned(),
+                };
+                writeln!(f, "    {term}")?;
+            }
+            writeln!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt;
+use std::ops::Index;
+
+/// a json value, for the language server. objects keep their keys in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Arr(Vec<Json>),
+    Obj(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+    /// `text` as json, unless it isn't valid (or has anything after the value but whitespace)
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut reader = Reader {
+            chars: text.chars().collect(),
+            at: 0,
+        };
+        let value = reader.value()?;
+        reader.space();
+        (reader.at == reader.chars.len()).then_some(value)
+    }
+
+    pub fn obj<const N: usize>(fields: [(&str, Json); N]) -> Json {
+        Json::Obj(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// the value of the object's `key`
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Obj(fields) => fields
+                .iter()
+                .find(|(field, _)| field == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(str) => Some(str),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Num(num) if *num >= 0.0 && num.fract() == 0.0 => Some(*num as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Arr(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// `json["key"]` - `Null` if there's no such key
+impl Index<&str> for Json {
+    type Output = Json;
+
+    fn index(&self, key: &str) -> &Json {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+/// `json[0]` - `Null` if there's no such item
+impl Index<usize> for Json {
+    type Output = Json;
+
+    fn index(&self, idx: usize) -> &Json {
+        self.as_array()
+            .and_then(|items| items.get(idx))
+            .unwrap_or(&NULL)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(str: &str) -> Json {
+        Json::Str(str.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(str: String) -> Json {
+        Json::Str(str)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(bool: bool) -> Json {
+        Json::Bool(bool)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(num: usize) -> Json {
+        Json::Num(num as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Json {
+        Json::Arr(items)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+/// compact, with no whitespace
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(bool) => write!(f, "{bool}"),
+            Json::Num(num) if num.fract() == 0.0 && num.abs() < 1e15 => {
+                write!(f, "{}", *num as i64)
+            }
+            Json::Num(num) if num.is_finite() => write!(f, "{num}"),
+            // json has no infinities or nans
+            Json::Num(_) => write!(f, "null"),
+            Json::Str(str) => escape(f, str),
+            Json::Arr(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Obj(fields) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    escape(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn escape(f: &mut fmt::Formatter<'_>, str: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in str.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{ch}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Reader {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Reader {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.peek()?;
+        self.at += 1;
+        Some(next)
+    }
+
+    fn space(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|ch| matches!(ch, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.at += 1;
+        }
+    }
+
+    /// consumes `word`, if it's next
+    fn word(&mut self, word: &str) -> bool {
+        let end = self.at + word.len();
+        if self
+            .chars
+            .get(self.at..end)
+            .is_some_and(|chars| chars.iter().copied().eq(word.chars()))
+        {
+            self.at = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.space();
+        Some(match self.peek()? {
+            'n' if self.word("null") => Json::Null,
+            't' if self.word("true") => Json::Bool(true),
+            'f' if self.word("false") => Json::Bool(false),
+            '"' => Json::Str(self.string()?),
+            '[' => {
+                self.at += 1;
+                let mut items = Vec::new();
+                self.space();
+                if self.peek() == Some(']') {
+                    self.at += 1;
+                    return Some(Json::Arr(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.space();
+                    match self.next()? {
+                        ',' => continue,
+                        ']' => break,
+                        _ => return None,
+                    }
+                }
+                Json::Arr(items)
+            }
+            '{' => {
+                self.at += 1;
+                let mut fields = Vec::new();
+                self.space();
+                if self.peek() == Some('}') {
+                    self.at += 1;
+                    return Some(Json::Obj(fields));
+                }
+                loop {
+                    self.space();
+                    if self.peek() != Some('"') {
+                        return None;
+                    }
+                    let key = self.string()?;
+                    self.space();
+                    if self.next()? != ':' {
+                        return None;
+                    }
+                    fields.push((key, self.value()?));
+                    self.space();
+                    match self.next()? {
+                        ',' => continue,
+                        '}' => break,
+                        _ => return None,
+                    }
+                }
+                Json::Obj(fields)
+            }
+            '-' | '0'..='9' => {
+                let start = self.at;
+                while self.peek().is_some_and(|ch| {
+                    ch.is_ascii_digit() || matches!(ch, '-' | '+' | '.' | 'e' | 'E')
+                }) {
+                    self.at += 1;
+                }
+                let number: String = self.chars[start..self.at].iter().collect();
+                Json::Num(number.parse().ok()?)
+            }
+            _ => return None,
+        })
+    }
+
+    /// a string, from its opening quote
+    fn string(&mut self) -> Option<String> {
+        self.at += 1;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Some(string),
+                '\\' => match self.next()? {
+                    '"' => string.push('"'),
+                    '\\' => string.push('\\'),
+                    '/' => string.push('/'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'n' => string.push('\n'),
+                    'r' => string.push('\r'),
+                    't' => string.push('\t'),
+                    'u' => {
+                        let unit = self.hex()?;
+                        // characters outside the bmp are written as a pair of surrogates
+                        let ch = if (0xd800..0xdc00).contains(&unit) && self.word("\\u") {
+                            let low = self.hex()?;
+                            char::from_u32(
+                                0x10000 + ((unit - 0xd800) << 10) + (low.checked_sub(0xdc00)?),
+                            )
+                        } else {
+                            char::from_u32(unit)
+                        };
+                        string.push(ch.unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    _ => return None,
+                },
+                ch => string.push(ch),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Option<u32> {
+        let digits: String = (0..4).map(|_| self.next()).collect::<Option<_>>()?;
+        u32::from_str_radix(&digits, 16).ok()
+    }
+}
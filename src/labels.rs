@@ -0,0 +1,252 @@
+use crate::prelude::*;
+
+/// a label or `goto`, along with the variables in scope at that point
+struct Site {
+    name: Spanned<String>,
+    in_scope: Vec<DefId>,
+}
+
+/// the labels & `goto`s of a single function (or of the module's top level)
+#[derive(Default)]
+struct FnSites {
+    labels: Vec<Site>,
+    gotos: Vec<Site>,
+}
+
+struct Checker<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    /// variables declared with `let` which are in scope at the current point
+    in_scope: Vec<DefId>,
+    sites: FnSites,
+}
+
+impl<'a> Checker<'a> {
+    fn site(&self, name: &Spanned<String>) -> Site {
+        Site {
+            name: name.clone(),
+            in_scope: self.in_scope.clone(),
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        let depth = self.in_scope.len();
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+        }
+        if let Some(tail) = &block.tail {
+            self.expr(tail);
+        }
+        self.in_scope.truncate(depth);
+    }
+
+    /// labels are function-wide, so a nested function gets a clean slate
+    fn function(&mut self, body: &Block) {
+        let in_scope = std::mem::take(&mut self.in_scope);
+        let sites = std::mem::take(&mut self.sites);
+        self.block(body);
+        let body_sites = std::mem::replace(&mut self.sites, sites);
+        self.in_scope = in_scope;
+        self.finish(body_sites);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { body, .. } => self.function(body),
+            Stmt::Loop { block } => self.block(block),
+            Stmt::Label { name } => {
+                let site = self.site(name);
+                self.sites.labels.push(site);
+            }
+            Stmt::Goto { name } => {
+                let site = self.site(name);
+                self.sites.gotos.push(site);
+            }
+            Stmt::Return { expr: Some(expr) } => self.expr(expr),
+            Stmt::VarDec { name, initialiser } => {
+                self.expr(initialiser);
+                if let Some(id) = self.res.def_at(name.span) {
+                    self.in_scope.push(id);
+                }
+            }
+            Stmt::VarRes { updated, .. } => self.expr(updated),
+            Stmt::Discard { expr } => self.expr(expr),
+            Stmt::Stub { .. } | Stmt::Type { .. } | Stmt::Return { expr: None } => {}
+        }
+    }
+
+    /// only expressions containing blocks can contain labels
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::ObjMember(..) => {}
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Attributed { expr: inner, .. }
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => self.expr(inner),
+            Expr::FnCall(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::BiOp(left, _, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.expr(condition);
+                self.block(&then.spanned);
+                for (condition, block) in elifs {
+                    self.expr(condition);
+                    self.block(&block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    self.block(&r#else.spanned);
+                }
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::StructLit { fields, .. } => match fields {
+                StructLitFields::Positional(values) => {
+                    for value in values {
+                        self.expr(value);
+                    }
+                }
+                StructLitFields::Named(members) => {
+                    for (_, value) in members {
+                        self.expr(value);
+                    }
+                }
+            },
+        }
+    }
+
+    fn finish(&mut self, sites: FnSites) {
+        for (idx, label) in sites.labels.iter().enumerate() {
+            if let Some(first) = sites.labels[..idx]
+                .iter()
+                .find(|prev| prev.name.spanned == label.name.spanned)
+            {
+                let err = SdwErr::from_pos(
+                    FlowErrors::DuplicateLabel(label.name.spanned.clone()),
+                    label.name.span,
+                )
+                .with_note("first declared here", first.name.span);
+                self.state.errors.push(err);
+            }
+        }
+
+        for goto in &sites.gotos {
+            let Some(label) = sites
+                .labels
+                .iter()
+                .find(|label| label.name.spanned == goto.name.spanned)
+            else {
+                let mut err = SdwErr::from_pos(
+                    FlowErrors::UndeclaredLabel(goto.name.spanned.clone()),
+                    goto.name.span,
+                );
+                if let Some(similar) = suggest(&goto.name.spanned, &sites.labels) {
+                    err = err.with_note(
+                        format!("did you mean `@{}`?", similar.name.spanned),
+                        similar.name.span,
+                    );
+                }
+                self.state.errors.push(err);
+                continue;
+            };
+
+            // anything in scope at the label, but not at the `goto`, has had its `let` skipped.
+            // (jumping backwards never skips a declaration - it is simply run again)
+            for skipped in label
+                .in_scope
+                .iter()
+                .filter(|id| !goto.in_scope.contains(id))
+            {
+                let Some(used) = self.first_use_after(*skipped, label.name.span) else {
+                    continue;
+                };
+                let def = self.res.def(*skipped);
+                let err =
+                    SdwErr::from_pos(FlowErrors::GotoSkipsLet(def.name.clone()), goto.name.span)
+                        .with_note(format!("`{}` is declared here", def.name), def.span)
+                        .with_note("and used here, after the label", used);
+                self.state.errors.push(err);
+            }
+        }
+    }
+
+    fn first_use_after(&self, id: DefId, after: Span) -> Option<Span> {
+        self.res
+            .iter()
+            .filter(|(span, res)| {
+                matches!(res, Res::Def(def) if *def == id) && **span != self.res.def(id).span
+            })
+            .map(|(span, _)| *span)
+            .filter(|span| (span.sline, span.scol) > (after.sline, after.scol))
+            .min_by_key(|span| (span.sline, span.scol))
+    }
+}
+
+/// the closest label to a misspelled one, if any is close enough to be a likely typo
+fn suggest<'s>(name: &str, labels: &'s [Site]) -> Option<&'s Site> {
+    let max = (name.len() / 3).max(1);
+    labels
+        .iter()
+        .map(|label| (edit_distance(name, &label.name.spanned), label))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, label)| label)
+}
+
+/// edit distance, counting an adjacent transposition (`tpo` -> `top`) as a single edit
+fn edit_distance(from: &str, to: &str) -> usize {
+    let from = from.chars().collect::<Vec<_>>();
+    let to = to.chars().collect::<Vec<_>>();
+    let mut table = vec![vec![0; to.len() + 1]; from.len() + 1];
+    for (i, row) in table.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in table[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=from.len() {
+        for j in 1..=to.len() {
+            let cost = usize::from(from[i - 1] != to[j - 1]);
+            table[i][j] = (table[i - 1][j] + 1)
+                .min(table[i][j - 1] + 1)
+                .min(table[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && from[i - 1] == to[j - 2] && from[i - 2] == to[j - 1] {
+                table[i][j] = table[i][j].min(table[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    table[from.len()][to.len()]
+}
+
+/// validates labels & `goto`s: labels must be unique within their function, every `goto` must
+/// target a label of its own function, and a jump may not skip a `let` whose variable is used
+/// after the label.
+pub fn check(state: &mut State, root: &Block, res: &Resolutions) {
+    let mut checker = Checker {
+        state,
+        res,
+        in_scope: Vec::new(),
+        sites: FnSites::default(),
+    };
+
+    checker.block(root);
+    let sites = std::mem::take(&mut checker.sites);
+    checker.finish(sites);
+}
@@ -0,0 +1,250 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// what a backend is compiling for, as far as laying out values is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub name: &'static str,
+    /// in bytes
+    pub pointer_width: u64,
+    pub endianness: Endianness,
+}
+
+impl Target {
+    pub const X86_64: Target = Target {
+        name: "x86_64",
+        pointer_width: 8,
+        endianness: Endianness::Little,
+    };
+    pub const AARCH64: Target = Target {
+        name: "aarch64",
+        pointer_width: 8,
+        endianness: Endianness::Little,
+    };
+    pub const WASM32: Target = Target {
+        name: "wasm32",
+        pointer_width: 4,
+        endianness: Endianness::Little,
+    };
+    pub const POWERPC64: Target = Target {
+        name: "powerpc64",
+        pointer_width: 8,
+        endianness: Endianness::Big,
+    };
+    pub const ALL: [Target; 4] = [
+        Target::X86_64,
+        Target::AARCH64,
+        Target::WASM32,
+        Target::POWERPC64,
+    ];
+
+    pub fn named(name: &str) -> Option<Target> {
+        Target::ALL.into_iter().find(|target| target.name == name)
+    }
+}
+
+/// where a member sits within its struct or union
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// a union is laid out as its discriminant, followed by its members overlapping one another
+/// (like the C `struct { uint32_t tag; union { [..] } value; }`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+    /// in declaration order
+    pub fields: Vec<Field>,
+    /// unions only
+    pub discriminant: Option<Field>,
+}
+
+impl Layout {
+    fn scalar(size: u64) -> Layout {
+        Layout {
+            size,
+            align: size.max(1),
+            fields: Vec::new(),
+            discriminant: None,
+        }
+    }
+}
+
+/// the size of a union's discriminant
+pub const DISCRIMINANT_SIZE: u64 = 4;
+
+fn align_to(offset: u64, align: u64) -> u64 {
+    offset.div_ceil(align) * align
+}
+
+/// the layout of every declared type which has one. generics (and aggregates of them) have no
+/// layout of their own; a value of one is laid out as whatever type stands in for it.
+pub struct Layouts {
+    pub target: Target,
+    decls: HashMap<DefId, Layout>,
+}
+
+impl Layouts {
+    pub fn decl(&self, id: DefId) -> Option<&Layout> {
+        self.decls.get(&id)
+    }
+
+    pub fn of(&self, ty: &Ty) -> Option<Layout> {
+        match ty {
+            Ty::Named(id) => self.decls.get(id).cloned(),
+            ty => scalar(ty, &self.target),
+        }
+    }
+}
+
+/// the layout of a type which doesn't name a declaration
+fn scalar(ty: &Ty, target: &Target) -> Option<Layout> {
+    Some(match ty {
+        Ty::Prim(PrimType::Int | PrimType::Unt | PrimType::Float) => Layout::scalar(8),
+        Ty::Prim(PrimType::Bool) => Layout::scalar(1),
+        // a pointer to the bytes, and their length
+        Ty::Prim(PrimType::String) => {
+            let width = target.pointer_width;
+            Layout {
+                size: width * 2,
+                align: width,
+                fields: vec![
+                    Field {
+                        name: "ptr".to_owned(),
+                        offset: 0,
+                        size: width,
+                    },
+                    Field {
+                        name: "len".to_owned(),
+                        offset: width,
+                        size: width,
+                    },
+                ],
+                discriminant: None,
+            }
+        }
+        Ty::Pointer(_) | Ty::FnPtr { .. } => Layout::scalar(target.pointer_width),
+        Ty::Void | Ty::Never => Layout::scalar(0),
+        Ty::Named(_) | Ty::Error => return None,
+    })
+}
+
+struct Computer<'a> {
+    types: &'a Types,
+    target: &'a Target,
+    done: HashMap<DefId, Option<Layout>>,
+}
+
+impl<'a> Computer<'a> {
+    fn ty(&mut self, ty: &Ty) -> Option<Layout> {
+        match ty {
+            Ty::Named(id) => self.decl(*id),
+            ty => scalar(ty, self.target),
+        }
+    }
+
+    fn decl(&mut self, id: DefId) -> Option<Layout> {
+        if let Some(done) = self.done.get(&id) {
+            return done.clone();
+        }
+        // guards against types containing themselves, which `cycles::check` has already reported
+        self.done.insert(id, None);
+
+        let layout = match self.types.decls.get(&id)? {
+            TypeDecl::Struct(members) => self.structure(members),
+            TypeDecl::Union(members) => self.union(members),
+            TypeDecl::Alias(ty) => self.ty(ty),
+            TypeDecl::Generic | TypeDecl::Aggregate(_) => None,
+        };
+        self.done.insert(id, layout.clone());
+        layout
+    }
+
+    /// members are placed in order, each at the next offset suiting its alignment (as in C)
+    fn structure(&mut self, members: &[(String, Ty)]) -> Option<Layout> {
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        let mut align = 1;
+        for (name, ty) in members {
+            let member = self.ty(ty)?;
+            offset = align_to(offset, member.align);
+            fields.push(Field {
+                name: name.clone(),
+                offset,
+                size: member.size,
+            });
+            offset += member.size;
+            align = align.max(member.align);
+        }
+
+        Some(Layout {
+            size: align_to(offset, align),
+            align,
+            fields,
+            discriminant: None,
+        })
+    }
+
+    fn union(&mut self, members: &[(String, Ty)]) -> Option<Layout> {
+        let layouts = members
+            .iter()
+            .map(|(_, ty)| self.ty(ty))
+            .collect::<Option<Vec<_>>>()?;
+        let payload_align = layouts.iter().map(|layout| layout.align).max().unwrap_or(1);
+        let payload_size = layouts.iter().map(|layout| layout.size).max().unwrap_or(0);
+
+        let offset = align_to(DISCRIMINANT_SIZE, payload_align);
+        let align = payload_align.max(DISCRIMINANT_SIZE);
+        let fields = members
+            .iter()
+            .zip(&layouts)
+            .map(|((name, _), layout)| Field {
+                name: name.clone(),
+                offset,
+                size: layout.size,
+            })
+            .collect();
+
+        Some(Layout {
+            size: align_to(offset + align_to(payload_size, payload_align), align),
+            align,
+            fields,
+            discriminant: Some(Field {
+                name: "discriminant".to_owned(),
+                offset: 0,
+                size: DISCRIMINANT_SIZE,
+            }),
+        })
+    }
+}
+
+/// computes the size, alignment & member offsets of every declared type for `target`
+pub fn compute(types: &Types, target: &Target) -> Layouts {
+    let mut computer = Computer {
+        types,
+        target,
+        done: HashMap::new(),
+    };
+
+    let mut ids = types.decls.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| id.0);
+    let decls = ids
+        .into_iter()
+        .filter_map(|id| Some((id, computer.decl(id)?)))
+        .collect();
+
+    Layouts {
+        target: target.clone(),
+        decls,
+    }
+}
@@ -7,7 +7,7 @@ lazy_static! {
     static ref IDN_REGEX: Regex = Regex::new(r"[_a-zA-Z][_a-zA-Z0-9]*").unwrap();
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum LexemeType {
     // arithmetic operators
     /// +
@@ -71,7 +71,8 @@ pub enum LexemeType {
     Idn(String),
     Intlit(i64), // TODO: integer sizes??
     BoolLit(bool),
-    // TODO: string lit?
+    /// `"hello\n"`, with its escapes already replaced
+    StrLit(String),
 
     // keywords
     // procedures
@@ -167,19 +168,62 @@ impl FromStr for LexemeType {
 
 pub type Lexeme = Spanned<LexemeType>;
 
+/// a piece of source, as `lex_lossless` gives it. the text of every token, in order, is
+/// exactly the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Lexeme(LexemeType),
+    Whitespace,
+    /// `// ..`, up to (but not including) the end of the line, or `/* .. */`, which may span
+    /// lines
+    Comment,
+    /// text which didn't lex, whose error has been raised
+    Error,
+}
+
+impl Token {
+    /// the lexeme the token is, unless it's trivia
+    pub fn lexeme(&self) -> Option<Lexeme> {
+        match &self.kind {
+            TokenKind::Lexeme(r#type) => Some(Lexeme::new(r#type.clone(), self.span)),
+            _ => None,
+        }
+    }
+}
+
+impl TokenKind {
+    /// whether `lex` throws the token away
+    pub fn is_trivia(&self) -> bool {
+        !matches!(self, TokenKind::Lexeme(_))
+    }
+}
+
 struct LexBuffer {
     stream: String,
-    position: Span,
+    /// line (0-indexed) & column (1-indexed) of the start of `stream`
+    line: SpanInt,
+    col: SpanInt,
     // idx is 1D
     idx: usize,
+    /// everything eaten since this was last taken
+    taken: String,
 }
 
 impl LexBuffer {
-    fn new(stream: String) -> Self {
+    fn new(stream: String, line: SpanInt) -> Self {
         Self {
             stream,
-            position: Span::default(),
+            line,
+            col: 1,
             idx: 0,
+            taken: String::new(),
         }
     }
 
@@ -187,6 +231,20 @@ impl LexBuffer {
         self.stream.is_empty()
     }
 
+    /// the character `by` past the current one, if there is one
+    fn peek(&self, by: usize) -> Option<char> {
+        self.stream.chars().nth(self.idx + by)
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            sline: self.line,
+            eline: self.line + 1,
+            scol: self.col,
+            ecol: self.col + self.idx as SpanInt,
+        }
+    }
+
     fn over(&self) -> char {
         self.stream.chars().nth(self.idx).unwrap_or_else(|| {
             panic!(
@@ -200,27 +258,31 @@ impl LexBuffer {
 
     fn adv(&mut self, by: usize) {
         self.idx += by;
-        self.position.ecol += by as u64;
     }
 
     fn eat(&mut self) -> String {
-        let chunk = self.stream.drain(..self.idx).collect();
-        self.position.sline = self.position.eline;
-        // TODO:
-        // HACK:
-        //      this is necessary, but why? why not for `[s/e]line`?
-        //      something is severly fucky wucky and it'll be hell to debug lol
-        self.position.scol = self.position.ecol + 1;
+        // `idx` counts characters, not bytes
+        let end = self
+            .stream
+            .char_indices()
+            .nth(self.idx)
+            .map_or(self.stream.len(), |(end, _)| end);
+        let chunk: String = self.stream.drain(..end).collect();
+        for ch in chunk.chars() {
+            if ch == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
         self.idx = 0;
+        self.taken.push_str(&chunk);
         chunk
     }
 
     fn tok(&mut self) -> Result<Lexeme> {
-        let span = Span {
-            ecol: self.position.ecol + 1,
-            eline: self.position.eline + 1,
-            ..self.position
-        };
+        let span = self.span();
         let chunk = self.eat();
         let r#type = chunk.parse().map_err(|err: UnknownLexeme| {
             SdwErr::from_pos(LexErrors::UnrecognisedToken(err.0), span)
@@ -231,58 +293,170 @@ impl LexBuffer {
             span,
         })
     }
+
+    /// a string literal, starting at its opening quote. it can't span lines.
+    fn str(&mut self) -> Result<Lexeme> {
+        let mut text = String::new();
+        let mut escape = None;
+        self.adv(1);
+        loop {
+            match self.peek(0) {
+                None | Some('\n') => {
+                    let span = self.span();
+                    self.eat();
+                    return Err(SdwErr::from_pos(LexErrors::UnterminatedString, span));
+                }
+                Some('"') => break,
+                Some('\\') => {
+                    let Some(ch) = self.peek(1) else {
+                        self.adv(1);
+                        continue;
+                    };
+                    match ch {
+                        'n' => text.push('\n'),
+                        't' => text.push('\t'),
+                        '0' => text.push('\0'),
+                        '\\' | '"' => text.push(ch),
+                        ch => escape = escape.or(Some(ch)),
+                    }
+                    self.adv(2);
+                }
+                Some(ch) => {
+                    text.push(ch);
+                    self.adv(1);
+                }
+            }
+        }
+        self.adv(1);
+
+        let span = self.span();
+        self.eat();
+        if let Some(ch) = escape {
+            return Err(SdwErr::from_pos(LexErrors::UnknownEscape(ch), span));
+        }
+        Ok(Lexeme {
+            spanned: LexemeType::StrLit(text),
+            span,
+        })
+    }
 }
 
-macro_rules! err {
-    ($state:expr, $result:expr, $name:ident => $stmt:expr) => {{
+/// the token a lexeme becomes - or if it didn't lex, an `Error` (raising the error)
+macro_rules! kind {
+    ($state:expr, $result:expr) => {{
         match $result {
-            Ok($name) => $stmt,
-            Err(err) => $state.errors.push(err),
+            Ok(lexeme) => TokenKind::Lexeme(lexeme.spanned),
+            Err(err) => {
+                $state.errors.push(err);
+                TokenKind::Error
+            }
         }
     }};
 }
 
 pub fn lex(state: &mut State, raw: &str) -> Vec<Lexeme> {
-    let mut lexemes = Vec::new();
-    let mut buffer = LexBuffer::new(raw.to_owned());
+    lex_at(state, raw, 0)
+}
+
+/// like `lex`, but as if `raw` started at `line` (0-indexed) - for source bundled with the
+/// compiler, whose spans mustn't overlap the file's
+pub fn lex_at(state: &mut State, raw: &str, line: SpanInt) -> Vec<Lexeme> {
+    tokens(state, raw, line)
+        .into_iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Lexeme(r#type) => Some(Lexeme {
+                spanned: r#type,
+                span: token.span,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// like `lex`, but keeping everything `lex` throws away - whitespace, comments & text which
+/// didn't lex - for tools which mustn't lose any of the source
+pub fn lex_lossless(state: &mut State, raw: &str) -> Vec<Token> {
+    tokens(state, raw, 0)
+}
+
+fn tokens(state: &mut State, raw: &str, line: SpanInt) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut buffer = LexBuffer::new(raw.to_owned(), line);
 
     while !buffer.done() {
-        if buffer.over().is_ascii_alphabetic() || buffer.over() == '_' {
+        let (line, col) = (buffer.line, buffer.col);
+        let kind = if buffer.over().is_ascii_alphabetic() || buffer.over() == '_' {
             buffer.adv(1);
-            while buffer.over().is_ascii_alphanumeric() || buffer.over() == '_' {
+            while buffer
+                .peek(0)
+                .is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+            {
                 buffer.adv(1);
             }
 
-            err![state, buffer.tok(), ok => lexemes.push(ok)];
-            continue;
-        }
-
-        if buffer.over().is_ascii_digit() {
+            kind![state, buffer.tok()]
+        } else if buffer.over().is_ascii_digit() {
             buffer.adv(1);
-            while buffer.over().is_ascii_digit() {
+            while buffer.peek(0).is_some_and(|ch| ch.is_ascii_digit()) {
                 buffer.adv(1);
             }
 
-            err![state, buffer.tok(), ok => lexemes.push(ok)];
-            continue;
-        }
-
-        if buffer.over().is_ascii_whitespace() {
+            kind![state, buffer.tok()]
+        } else if buffer.over() == '/' && buffer.peek(1) == Some('/') {
+            // comments run to the end of the line
+            while buffer.peek(0).is_some_and(|ch| ch != '\n') {
+                buffer.adv(1);
+            }
+            buffer.eat();
+            TokenKind::Comment
+        } else if buffer.over() == '/' && buffer.peek(1) == Some('*') {
+            // block comments run to the first `*/` (so don't nest), across lines if need be
+            buffer.adv(2);
+            while buffer.peek(0).is_some()
+                && (buffer.peek(0), buffer.peek(1)) != (Some('*'), Some('/'))
+            {
+                buffer.adv(1);
+            }
+            if buffer.peek(0).is_some() {
+                buffer.adv(2);
+                buffer.eat();
+                TokenKind::Comment
+            } else {
+                buffer.eat();
+                let span = Span {
+                    sline: line,
+                    eline: line + 1,
+                    scol: col,
+                    ecol: col + 2,
+                };
+                let err = SdwErr::from_pos(LexErrors::UnterminatedComment, span);
+                state.errors.push(err);
+                TokenKind::Error
+            }
+        } else if buffer.over() == '"' {
+            kind![state, buffer.str()]
+        } else if buffer.over().is_ascii_whitespace() {
             // HACK: escaping via `buffer.done()` feels camp, though i *think* it's reasonable?
             while !buffer.done() && buffer.over().is_ascii_whitespace() {
                 buffer.adv(1);
-                if buffer.eat() == "\n" {
-                    buffer.position.eline += 1;
-                    buffer.position.ecol = 0;
-                }
+                buffer.eat();
             }
+            TokenKind::Whitespace
+        } else {
+            buffer.adv(1);
+            kind![state, buffer.tok()]
+        };
 
-            continue;
-        }
-
-        buffer.adv(1);
-        err![state, buffer.tok(), ok => lexemes.push(ok)];
+        // (a lexeme's span, where it's on one line - a comment may end on another)
+        let span = Span {
+            sline: line,
+            eline: buffer.line + 1,
+            scol: col,
+            ecol: buffer.col,
+        };
+        let text = std::mem::take(&mut buffer.taken);
+        tokens.push(Token { kind, text, span });
     }
 
-    lexemes
+    tokens
 }
@@ -1,6 +1,36 @@
+pub mod bytecode;
+pub mod cfg;
+pub mod compile;
+pub mod conform;
+pub mod consteval;
+pub mod cst;
+pub mod cycles;
+pub mod driver;
+pub mod emit_asm;
+pub mod emit_c;
+pub mod emit_wat;
 pub mod errors;
+pub mod fmt;
+pub mod interp;
+pub mod intrinsics;
+pub mod ir;
+pub mod json;
+pub mod labels;
+pub mod layout;
 pub mod lexer;
+pub mod lower;
+pub mod lsp;
+pub mod mono;
+pub mod overload;
 pub mod parser;
+pub mod passes;
+pub mod pretty;
+pub mod relooper;
+pub mod repl;
+pub mod resolve;
+pub mod typeck;
+pub mod unused;
+pub mod vm;
 
 pub mod common {
     use owo_colors::OwoColorize;
@@ -8,11 +38,24 @@ pub mod common {
     #[derive(Default)]
     pub struct State {
         pub errors: Vec<crate::errors::SdwErr>,
+        /// non-fatal diagnostics; these never stop compilation
+        pub warnings: Vec<crate::errors::SdwErr>,
     }
 
     impl State {
         pub fn new() -> Self {
-            Self { errors: Vec::new() }
+            Self {
+                errors: Vec::new(),
+                warnings: Vec::new(),
+            }
+        }
+
+        /// prints, then clears, any warnings raised so far.
+        pub fn print_warns(&mut self, contents: &str) {
+            for warning in self.warnings.drain(..) {
+                eprintln!("\n~= {} =~", "warning".yellow());
+                warning.print_warning(contents);
+            }
         }
 
         /// expects caller to error out.
@@ -39,7 +82,7 @@ pub mod common {
     pub type SpanInt = u64;
 
     /// (sline, eline] & (scol, ecol]
-    #[derive(Debug, Clone, Copy, Default)]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
     pub struct Span {
         pub sline: SpanInt,
         pub eline: SpanInt,
@@ -76,7 +119,12 @@ pub mod common {
 
 pub mod prelude {
     pub use crate::common::*;
-    pub use crate::errors::{ErrType, LexErrors, ParseErrors, Result, SdwErr};
+    pub use crate::errors::{
+        ConstErrors, EmitErrors, ErrType, FlowErrors, FormatErrors, LexErrors, ParseErrors,
+        ResolveErrors, Result, RuntimeErrors, SdwErr, TypeErrors,
+    };
     pub use crate::lexer::{Lexeme, LexemeType};
     pub use crate::parser::prelude::*;
+    pub use crate::resolve::{Def, DefId, DefKind, Res, Resolutions};
+    pub use crate::typeck::{Signature, Ty, TypeDecl, Types};
 }
@@ -0,0 +1,1016 @@
+use crate::bytecode::Trap;
+use crate::driver::Checked;
+use crate::interp;
+use crate::intrinsics::Intrinsic;
+use crate::ir::{self, BlockId, Function, Inst, Module, Terminator, Value};
+use crate::overload::{Arm, Dispatch};
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// one of the blocks or expressions enclosing some code - see `compile::Nesting`
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Nesting {
+    id: u32,
+    block: bool,
+}
+
+/// a `goto`, whose block is left unterminated until every label has been seen
+struct Goto {
+    from: BlockId,
+    label: DefId,
+    path: Vec<Nesting>,
+}
+
+/// the function being lowered. variables become SSA values as they're assigned, following
+/// Braun et al.'s "Simple and Efficient Construction of SSA Form": reading one looks back
+/// through the block's predecessors, placing phis where they meet. a block is "sealed" once
+/// all its predecessors are known - until then, phis are placed in it without operands.
+#[derive(Default)]
+struct Current {
+    ret: Option<Ty>,
+    insts: Vec<Inst>,
+    tys: Vec<Ty>,
+    blocks: Vec<ir::Block>,
+    preds: Vec<Vec<BlockId>>,
+    /// the block being added to - `None` after a `return` or `goto`, until a label (if ever)
+    block: Option<BlockId>,
+    /// blocks for code which can never run, which are left out of the graph
+    dead: HashSet<BlockId>,
+    sealed: HashSet<BlockId>,
+    defs: HashMap<(DefId, BlockId), Value>,
+    incomplete: HashMap<BlockId, Vec<(DefId, Value)>>,
+    /// variables whose address is taken are kept in memory, rather than as values
+    slots: HashMap<DefId, Value>,
+    labels: HashMap<DefId, BlockId>,
+    label_paths: HashMap<DefId, Vec<Nesting>>,
+    gotos: Vec<Goto>,
+    path: Vec<Nesting>,
+}
+
+struct Lowerer<'a> {
+    res: &'a Resolutions,
+    types: &'a Types,
+    functions: HashMap<DefId, u32>,
+    nestings: u32,
+    current: Current,
+}
+
+impl<'a> Lowerer<'a> {
+    /*
+     * building
+     */
+
+    fn new_block(&mut self) -> BlockId {
+        let current = &mut self.current;
+        current.blocks.push(ir::Block {
+            insts: Vec::new(),
+            term: Terminator::Unreachable,
+        });
+        current.preds.push(Vec::new());
+        BlockId(current.blocks.len() as u32 - 1)
+    }
+
+    /// the block being added to, starting an unreachable one after a `return` or `goto`
+    fn block(&mut self) -> BlockId {
+        match self.current.block {
+            Some(block) => block,
+            None => {
+                let block = self.new_block();
+                self.current.sealed.insert(block);
+                self.current.dead.insert(block);
+                self.current.block = Some(block);
+                block
+            }
+        }
+    }
+
+    /// the block being added to, unless the code can never run
+    fn live(&self) -> Option<BlockId> {
+        self.current
+            .block
+            .filter(|block| !self.current.dead.contains(block))
+    }
+
+    fn value(&mut self, inst: Inst, ty: Ty) -> Value {
+        self.current.insts.push(inst);
+        self.current.tys.push(ty);
+        Value(self.current.insts.len() as u32 - 1)
+    }
+
+    fn push(&mut self, inst: Inst, ty: Ty) -> Value {
+        let block = self.block();
+        let value = self.value(inst, ty);
+        self.current.blocks[block.0 as usize].insts.push(value);
+        value
+    }
+
+    /// adds an instruction after a block's phis (& in the entry, its parameters & slots)
+    fn push_front(&mut self, block: BlockId, inst: Inst, ty: Ty) -> Value {
+        let value = self.value(inst, ty);
+        let current = &mut self.current;
+        let insts = &current.blocks[block.0 as usize].insts;
+        let at = insts
+            .iter()
+            .take_while(|value| {
+                matches!(
+                    current.insts[value.0 as usize],
+                    Inst::Phi(_) | Inst::Param(_) | Inst::Alloca
+                )
+            })
+            .count();
+        current.blocks[block.0 as usize].insts.insert(at, value);
+        value
+    }
+
+    fn phi(&mut self, block: BlockId, ty: Ty) -> Value {
+        let value = self.value(Inst::Phi(Vec::new()), ty);
+        let current = &mut self.current;
+        let insts = &current.blocks[block.0 as usize].insts;
+        let at = insts
+            .iter()
+            .take_while(|value| matches!(current.insts[value.0 as usize], Inst::Phi(_)))
+            .count();
+        current.blocks[block.0 as usize].insts.insert(at, value);
+        value
+    }
+
+    fn terminate(&mut self, term: Terminator) {
+        let Some(block) = self.current.block.take() else {
+            return;
+        };
+        if self.current.dead.contains(&block) {
+            return;
+        }
+        for succ in term.succs() {
+            self.current.preds[succ.0 as usize].push(block);
+        }
+        self.current.blocks[block.0 as usize].term = term;
+    }
+
+    fn seal(&mut self, block: BlockId) {
+        for (var, phi) in self.current.incomplete.remove(&block).unwrap_or_default() {
+            self.fill(var, phi, block);
+        }
+        self.current.sealed.insert(block);
+    }
+
+    /// continues from the end of a join, whose value is one of `incoming`
+    fn join(&mut self, join: BlockId, incoming: Vec<(BlockId, Value)>, ty: &Ty) -> Value {
+        self.seal(join);
+        if incoming.is_empty() {
+            self.current.block = None;
+            return self.push(Inst::Undef, ty.clone());
+        }
+        self.current.block = Some(join);
+        if incoming.iter().all(|(_, value)| *value == incoming[0].1) {
+            return incoming[0].1;
+        }
+        let phi = self.phi(join, ty.clone());
+        self.current.insts[phi.0 as usize] = Inst::Phi(incoming);
+        phi
+    }
+
+    fn nest<T>(&mut self, block: bool, within: impl FnOnce(&mut Self) -> T) -> T {
+        self.nestings += 1;
+        let nesting = Nesting {
+            id: self.nestings,
+            block,
+        };
+        self.current.path.push(nesting);
+        let result = within(self);
+        self.current.path.pop();
+        result
+    }
+
+    /*
+     * variables
+     */
+
+    fn write(&mut self, var: DefId, value: Value) {
+        match self.current.slots.get(&var).copied() {
+            Some(slot) => {
+                self.push(
+                    Inst::Store {
+                        pointer: slot,
+                        value,
+                    },
+                    Ty::Void,
+                );
+            }
+            None => {
+                let block = self.block();
+                self.current.defs.insert((var, block), value);
+            }
+        }
+    }
+
+    fn read(&mut self, var: DefId) -> Value {
+        let ty = self.types.locals[&var].clone();
+        match self.current.slots.get(&var).copied() {
+            Some(slot) => self.push(Inst::Load(slot), ty),
+            None => {
+                let block = self.block();
+                self.read_in(var, block)
+            }
+        }
+    }
+
+    fn read_in(&mut self, var: DefId, block: BlockId) -> Value {
+        if let Some(value) = self.current.defs.get(&(var, block)) {
+            return *value;
+        }
+        let ty = self.types.locals[&var].clone();
+        let preds = self.current.preds[block.0 as usize].clone();
+        let value = if !self.current.sealed.contains(&block) {
+            let phi = self.phi(block, ty);
+            self.current
+                .incomplete
+                .entry(block)
+                .or_default()
+                .push((var, phi));
+            phi
+        } else if let [pred] = preds[..] {
+            self.read_in(var, pred)
+        } else if preds.is_empty() {
+            self.push_front(block, Inst::Undef, ty)
+        } else {
+            // recorded first, so a loop back to here finds the phi
+            let phi = self.phi(block, ty);
+            self.current.defs.insert((var, block), phi);
+            self.fill(var, phi, block);
+            phi
+        };
+        self.current.defs.insert((var, block), value);
+        value
+    }
+
+    fn fill(&mut self, var: DefId, phi: Value, block: BlockId) {
+        let mut incoming = Vec::new();
+        for pred in self.current.preds[block.0 as usize].clone() {
+            incoming.push((pred, self.read_in(var, pred)));
+        }
+        self.current.insts[phi.0 as usize] = Inst::Phi(incoming);
+    }
+
+    /// variables whose address is taken (directly, or of one of their members)
+    fn taken(&self, block: &Block, out: &mut HashSet<DefId>) {
+        for stmt in &block.stmts {
+            match &stmt.spanned {
+                Stmt::Loop { block } => self.taken(block, out),
+                Stmt::Return { expr: Some(expr) }
+                | Stmt::VarDec {
+                    initialiser: expr, ..
+                }
+                | Stmt::VarRes { updated: expr, .. }
+                | Stmt::Discard { expr } => self.taken_expr(expr, out),
+                _ => {}
+            }
+        }
+        if let Some(tail) = &block.tail {
+            self.taken_expr(tail, out);
+        }
+    }
+
+    fn taken_expr(&self, expr: &Spanned<Expr>, out: &mut HashSet<DefId>) {
+        match &expr.spanned {
+            Expr::Referal(inner) => {
+                let mut place = &**inner;
+                while let Expr::SubExpr(inner) = &place.spanned {
+                    place = inner;
+                }
+                match &place.spanned {
+                    Expr::Variable(_) => {
+                        if let Some(Res::Def(id)) = self.res.get(place.span) {
+                            out.insert(*id);
+                        }
+                    }
+                    Expr::ObjMember(object, _) => {
+                        if let Some(id) = self.res.def_at(object.span) {
+                            if !matches!(self.types.locals.get(&id), Some(Ty::Pointer(_))) {
+                                out.insert(id);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                self.taken_expr(inner, out);
+            }
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Indir(inner)
+            | Expr::Attributed { expr: inner, .. } => self.taken_expr(inner, out),
+            Expr::BiOp(left, _, right) => {
+                self.taken_expr(left, out);
+                self.taken_expr(right, out);
+            }
+            Expr::FnCall(_, args) => args.iter().for_each(|arg| self.taken_expr(arg, out)),
+            Expr::Block(block) => self.taken(block, out),
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.taken_expr(condition, out);
+                self.taken(&then.spanned, out);
+                for (condition, block) in elifs {
+                    self.taken_expr(condition, out);
+                    self.taken(&block.spanned, out);
+                }
+                if let Some(r#else) = r#else {
+                    self.taken(&r#else.spanned, out);
+                }
+            }
+            Expr::StructLit { fields, .. } => match fields {
+                StructLitFields::Positional(values) => {
+                    values.iter().for_each(|value| self.taken_expr(value, out))
+                }
+                StructLitFields::Named(values) => values
+                    .iter()
+                    .for_each(|(_, value)| self.taken_expr(value, out)),
+            },
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::ObjMember(..) => {}
+        }
+    }
+
+    /// a slot in the entry block, which lives as long as the function
+    fn alloca(&mut self, ty: &Ty) -> Value {
+        self.push_front(BlockId(0), Inst::Alloca, Ty::Pointer(Box::new(ty.clone())))
+    }
+
+    /// `value` as a `to`, wrapping a variant into its union
+    fn coerce(&mut self, value: Value, to: &Ty) -> Value {
+        let Ty::Named(union) = to else {
+            return value;
+        };
+        let from = &self.current.tys[value.0 as usize];
+        match self.types.discriminant(*union, from).filter(|_| from != to) {
+            Some(tag) => self.push(
+                Inst::Union {
+                    tag: tag as u32,
+                    value,
+                },
+                to.clone(),
+            ),
+            None => value,
+        }
+    }
+
+    /*
+     * functions
+     */
+
+    fn function(
+        &mut self,
+        id: Option<DefId>,
+        name: String,
+        parameters: &[(Spanned<String>, Spanned<String>)],
+        body: &Block,
+    ) -> Function {
+        let sig = id.map(|id| &self.types.sigs[&id]);
+        self.current = Current {
+            ret: sig.map(|sig| sig.ret.clone()),
+            ..Current::default()
+        };
+        let entry = self.new_block();
+        self.current.sealed.insert(entry);
+        self.current.block = Some(entry);
+
+        let mut taken = HashSet::new();
+        self.taken(body, &mut taken);
+        for (index, (_, param)) in parameters.iter().enumerate() {
+            let param = self
+                .res
+                .def_at(param.span)
+                .expect("lowering: unresolved parameter");
+            let ty = self.types.locals[&param].clone();
+            let value = self.push(Inst::Param(index as u32), ty.clone());
+            if taken.remove(&param) {
+                let slot = self.alloca(&ty);
+                self.current.slots.insert(param, slot);
+            }
+            self.write(param, value);
+        }
+        let mut taken = taken.into_iter().collect::<Vec<_>>();
+        taken.sort_by_key(|id| id.0);
+        for var in taken {
+            if let Some(ty) = self.types.locals.get(&var).cloned() {
+                let slot = self.alloca(&ty);
+                self.current.slots.insert(var, slot);
+            }
+        }
+
+        let value = self.nest(true, |this| this.block_value(body));
+        let value = match sig {
+            Some(sig) => self.coerce(value, &sig.ret),
+            None => self.push(Inst::Void, Ty::Void),
+        };
+        self.terminate(Terminator::Return(value));
+
+        for goto in std::mem::take(&mut self.current.gotos) {
+            let label = &self.current.label_paths[&goto.label];
+            let shared = label
+                .iter()
+                .zip(&goto.path)
+                .take_while(|(a, b)| a == b)
+                .count();
+            let enterable = label[..shared].last().is_none_or(|nesting| nesting.block)
+                && label[shared..].iter().all(|nesting| nesting.block);
+            self.current.block = Some(goto.from);
+            if enterable {
+                self.terminate(Terminator::Jump(self.current.labels[&goto.label]));
+            } else {
+                self.terminate(Terminator::Trap(Trap::JumpIntoExpr));
+            }
+        }
+        for label in self.current.labels.values().copied().collect::<Vec<_>>() {
+            self.seal(label);
+        }
+
+        let current = std::mem::take(&mut self.current);
+        let mut function = Function {
+            id,
+            name,
+            params: sig.map(|sig| sig.params.clone()).unwrap_or_default(),
+            ret: sig.map_or(Ty::Void, |sig| sig.ret.clone()),
+            insts: current.insts,
+            tys: current.tys,
+            blocks: current.blocks,
+        };
+        function.remove_trivial_phis();
+        function
+    }
+
+    fn block_value(&mut self, block: &Block) -> Value {
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+        }
+        match &block.tail {
+            Some(tail) => self.expr(tail),
+            None => self.push(Inst::Void, Ty::Void),
+        }
+    }
+
+    fn label(&mut self, label: DefId) -> BlockId {
+        if let Some(block) = self.current.labels.get(&label) {
+            return *block;
+        }
+        let block = self.new_block();
+        self.current.labels.insert(label, block);
+        block
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { .. } | Stmt::Stub { .. } | Stmt::Type { .. } => {}
+            Stmt::Label { name } => {
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("lowering: unresolved label");
+                let block = self.label(id);
+                self.terminate(Terminator::Jump(block));
+                self.current
+                    .label_paths
+                    .insert(id, self.current.path.clone());
+                self.current.block = Some(block);
+            }
+            Stmt::Goto { name } => {
+                let label = self
+                    .res
+                    .def_at(name.span)
+                    .expect("lowering: unresolved goto");
+                self.label(label);
+                if let Some(from) = self.live() {
+                    self.current.block = None;
+                    let path = self.current.path.clone();
+                    self.current.gotos.push(Goto { from, label, path });
+                }
+            }
+            Stmt::Loop { block } => {
+                let header = self.new_block();
+                self.terminate(Terminator::Jump(header));
+                self.current.block = Some(header);
+                self.nest(true, |this| this.block_value(block));
+                self.terminate(Terminator::Jump(header));
+                self.seal(header);
+            }
+            Stmt::Return { expr } => {
+                let value = match expr {
+                    Some(expr) => self.expr(expr),
+                    None => self.push(Inst::Void, Ty::Void),
+                };
+                let value = match self.current.ret.clone() {
+                    Some(ret) => self.coerce(value, &ret),
+                    None => value,
+                };
+                self.terminate(Terminator::Return(value));
+            }
+            Stmt::VarDec {
+                name,
+                initialiser: value,
+            }
+            | Stmt::VarRes {
+                name,
+                updated: value,
+            } => {
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("lowering: unresolved variable");
+                let value = self.expr(value);
+                let value = self.coerce(value, &self.types.locals[&id]);
+                self.write(id, value);
+            }
+            Stmt::Discard { expr } => {
+                self.expr(expr);
+            }
+        }
+    }
+
+    /*
+     * expressions
+     */
+
+    fn ty(&self, expr: &Spanned<Expr>) -> Ty {
+        match self.types.expr(expr.span) {
+            Some(Ty::Never | Ty::Error) | None => Ty::Void,
+            Some(ty) => ty.clone(),
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Value {
+        match &expr.spanned {
+            Expr::Block(block) => self.nest(true, |this| this.block_value(block)),
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => self.expr(inner),
+            Expr::Cond { .. } => self.cond(expr),
+            _ => self.nest(false, |this| this.opaque(expr)),
+        }
+    }
+
+    fn opaque(&mut self, expr: &Spanned<Expr>) -> Value {
+        let ty = self.ty(expr);
+        match &expr.spanned {
+            Expr::IntLiteral(int) => match ty {
+                Ty::Prim(PrimType::Unt) => self.push(Inst::Unt(*int as u64), ty),
+                _ => self.push(Inst::Int(*int), ty),
+            },
+            Expr::BoolLiteral(bool) => self.push(Inst::Bool(*bool), ty),
+            Expr::StrLiteral(text) => self.push(Inst::Str(text.clone()), ty),
+            Expr::Variable(_) => match self.res.get(expr.span) {
+                Some(Res::Def(id)) => self.read(*id),
+                Some(Res::Overloads(ids)) => match self.functions.get(&ids[0]) {
+                    Some(function) => self.push(Inst::Fn(*function), ty),
+                    // a stub has no code of its own to point to
+                    None => self.push(Inst::Undef, ty),
+                },
+                None => unreachable!("lowering: unresolved variable"),
+            },
+            Expr::UnaryPos(inner) => self.expr(inner),
+            Expr::UnaryNeg(inner) => {
+                let value = self.expr(inner);
+                self.push(Inst::Neg(value), ty)
+            }
+            Expr::UnaryNot(inner) => {
+                let value = self.expr(inner);
+                self.push(Inst::Not(value), ty)
+            }
+            Expr::BiOp(left, op @ (BiOps::LogOr | BiOps::LogAnd), right) => {
+                let left = self.expr(left);
+                let from = self.live();
+                let rest = self.new_block();
+                let join = self.new_block();
+                let (then, r#else) = match op {
+                    BiOps::LogOr => (join, rest),
+                    _ => (rest, join),
+                };
+                self.terminate(Terminator::Branch {
+                    condition: left,
+                    then,
+                    r#else,
+                });
+                self.seal(rest);
+                self.current.block = Some(rest);
+                let right = self.expr(right);
+
+                let mut incoming = from
+                    .map(|from| (from, left))
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                incoming.extend(self.live().map(|end| (end, right)));
+                self.terminate(Terminator::Jump(join));
+                self.join(join, incoming, &ty)
+            }
+            Expr::BiOp(left, op, right) => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                self.push(Inst::BiOp(*op, left, right), ty)
+            }
+            Expr::Referal(inner) => self.place(inner),
+            Expr::Indir(inner) => {
+                let pointer = self.expr(inner);
+                self.push(Inst::Load(pointer), ty)
+            }
+            Expr::ObjMember(object, member) => match self.member(object, member) {
+                Ok(pointer) => self.push(Inst::Load(pointer), ty),
+                Err((value, index)) => self.push(Inst::Member(value, index), ty),
+            },
+            Expr::FnCall(_, args) => self.call(expr.span, args),
+            Expr::StructLit { fields, .. } => self.struct_lit(expr, fields),
+            Expr::Block(_) | Expr::SubExpr(_) | Expr::Attributed { .. } | Expr::Cond { .. } => {
+                unreachable!("lowering: enterable expression lowered as opaque")
+            }
+        }
+    }
+
+    fn cond(&mut self, expr: &Spanned<Expr>) -> Value {
+        let Expr::Cond {
+            condition,
+            then,
+            elifs,
+            r#else,
+        } = &expr.spanned
+        else {
+            unreachable!("lowering: not a condition");
+        };
+        let ty = self.ty(expr);
+        let join = self.new_block();
+        let mut incoming = Vec::new();
+        for (condition, block) in std::iter::once((condition, then))
+            .chain(elifs.iter().map(|(condition, block)| (condition, block)))
+        {
+            let condition = self.nest(false, |this| this.expr(condition));
+            let then = self.new_block();
+            let next = self.new_block();
+            self.terminate(Terminator::Branch {
+                condition,
+                then,
+                r#else: next,
+            });
+            self.seal(then);
+            self.seal(next);
+
+            self.current.block = Some(then);
+            let value = self.nest(true, |this| this.block_value(&block.spanned));
+            let value = self.coerce(value, &ty);
+            incoming.extend(self.live().map(|end| (end, value)));
+            self.terminate(Terminator::Jump(join));
+            self.current.block = Some(next);
+        }
+        let value = match r#else {
+            Some(block) => self.nest(true, |this| this.block_value(&block.spanned)),
+            None => self.push(Inst::Void, Ty::Void),
+        };
+        let value = self.coerce(value, &ty);
+        incoming.extend(self.live().map(|end| (end, value)));
+        self.terminate(Terminator::Jump(join));
+        self.join(join, incoming, &ty)
+    }
+
+    /// a pointer to `object.member`, or if the object isn't in memory, its value & the
+    /// member's index
+    fn member(
+        &mut self,
+        object: &Spanned<String>,
+        member: &Spanned<String>,
+    ) -> std::result::Result<Value, (Value, u32)> {
+        let id = self
+            .res
+            .def_at(object.span)
+            .expect("lowering: unresolved object");
+        let ty = &self.types.locals[&id];
+        let members = self
+            .types
+            .members(ty)
+            .expect("lowering: member of a type without members");
+        let index = members
+            .iter()
+            .position(|(name, _)| *name == member.spanned)
+            .expect("lowering: unknown member");
+        let pointer = Ty::Pointer(Box::new(members[index].1.clone()));
+
+        let object = match (ty, self.current.slots.get(&id).copied()) {
+            (_, Some(slot)) if !matches!(ty, Ty::Pointer(_)) => slot,
+            (Ty::Pointer(_), _) => self.read(id),
+            _ => {
+                let value = self.read(id);
+                return Err((value, index as u32));
+            }
+        };
+        Ok(self.push(Inst::MemberPtr(object, index as u32), pointer))
+    }
+
+    /// a pointer to where a place expression (`x`, `x.member`, `*x`) is kept
+    fn place(&mut self, expr: &Spanned<Expr>) -> Value {
+        match &expr.spanned {
+            Expr::Variable(_) => {
+                if let Some(Res::Def(id)) = self.res.get(expr.span) {
+                    if let Some(slot) = self.current.slots.get(id) {
+                        return *slot;
+                    }
+                }
+            }
+            Expr::SubExpr(inner) => return self.place(inner),
+            Expr::Indir(inner) => return self.expr(inner),
+            Expr::ObjMember(object, member) => {
+                if let Ok(pointer) = self.member(object, member) {
+                    return pointer;
+                }
+            }
+            _ => {}
+        }
+        // a temporary, which lives as long as the function
+        let ty = self.ty(expr);
+        let value = self.expr(expr);
+        let slot = self.alloca(&ty);
+        self.push(
+            Inst::Store {
+                pointer: slot,
+                value,
+            },
+            Ty::Void,
+        );
+        slot
+    }
+
+    /// a direct call, or a call of a stub
+    fn call_function(&mut self, function: DefId, args: &[Value]) -> Value {
+        let sig = &self.types.sigs[&function];
+        let args = args
+            .iter()
+            .zip(&sig.params)
+            .map(|(arg, param)| self.coerce(*arg, param))
+            .collect();
+        let inst = match self.functions.get(&function) {
+            Some(function) => Inst::Call {
+                function: *function,
+                args,
+            },
+            None => match Intrinsic::of(self.res, self.types, function) {
+                Some(intrinsic) => Inst::Intrinsic { intrinsic, args },
+                None => Inst::CallInterface {
+                    stub: function,
+                    args,
+                },
+            },
+        };
+        let value = self.push(inst, sig.ret.clone());
+        if sig.no_return {
+            self.terminate(Terminator::Unreachable);
+        }
+        value
+    }
+
+    fn call(&mut self, span: Span, args: &[Box<Spanned<Expr>>]) -> Value {
+        let ty = match self.types.expr(span) {
+            Some(Ty::Never | Ty::Error) | None => Ty::Void,
+            Some(ty) => ty.clone(),
+        };
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.expr(arg));
+        }
+
+        if let Some(dispatch) = self.types.dispatch(span) {
+            self.dispatch(dispatch, values, &ty)
+        } else if let Some(function) = self.types.call(span) {
+            self.call_function(function, &values)
+        } else {
+            let Some(Res::Def(id)) = self.res.get(span) else {
+                unreachable!("lowering: unresolved call");
+            };
+            let Ty::FnPtr { args: params, ret } = self.types.locals[id].clone() else {
+                unreachable!("lowering: called a non-function");
+            };
+            let pointer = self.read(*id);
+            let args = values
+                .into_iter()
+                .zip(&params)
+                .map(|(arg, param)| self.coerce(arg, param))
+                .collect();
+            self.push(Inst::CallPtr { pointer, args }, *ret)
+        }
+    }
+
+    /// a switch on the tag of the union argument `dispatch.arg`, calling the overload for each
+    /// member - or switching on another argument too
+    fn dispatch(&mut self, dispatch: &Dispatch, values: Vec<Value>, ty: &Ty) -> Value {
+        let members = match self.types.decls.get(&dispatch.union) {
+            Some(TypeDecl::Union(members)) => members,
+            _ => unreachable!("lowering: dispatched on a non-union"),
+        };
+        let union = values[dispatch.arg];
+        let tag = self.push(Inst::Tag(union), Ty::UNT);
+        let blocks = dispatch
+            .arms
+            .iter()
+            .map(|_| self.new_block())
+            .collect::<Vec<_>>();
+        let join = self.new_block();
+        self.terminate(Terminator::Switch {
+            tag,
+            targets: blocks.clone(),
+        });
+
+        let mut incoming = Vec::new();
+        for (index, (block, arm)) in blocks.into_iter().zip(&dispatch.arms).enumerate() {
+            self.seal(block);
+            self.current.block = Some(block);
+            let mut args = values.clone();
+            args[dispatch.arg] =
+                self.push(Inst::Member(union, index as u32), members[index].1.clone());
+            let value = match arm {
+                Arm::Call(function) => {
+                    let value = self.call_function(*function, &args);
+                    self.coerce(value, ty)
+                }
+                Arm::Dispatch(nested) => self.dispatch(nested, args, ty),
+            };
+            incoming.extend(self.live().map(|end| (end, value)));
+            self.terminate(Terminator::Jump(join));
+        }
+        self.join(join, incoming, ty)
+    }
+
+    fn struct_lit(&mut self, expr: &Spanned<Expr>, fields: &StructLitFields) -> Value {
+        let ty = self.ty(expr);
+        let Ty::Named(id) = ty else {
+            unreachable!("lowering: struct literal of a non-struct");
+        };
+        let (members, union) = match self.types.decls.get(&id) {
+            Some(TypeDecl::Struct(members)) => (members, false),
+            Some(TypeDecl::Union(members)) => (members, true),
+            _ => unreachable!("lowering: struct literal of a non-struct"),
+        };
+
+        // evaluated in the order written, not the order declared
+        let mut values = Vec::new();
+        match fields {
+            StructLitFields::Positional(exprs) => {
+                for (index, value) in exprs.iter().enumerate() {
+                    let value = self.expr(value);
+                    values.push((index, self.coerce(value, &members[index].1)));
+                }
+            }
+            StructLitFields::Named(exprs) => {
+                for (name, value) in exprs {
+                    let index = members
+                        .iter()
+                        .position(|(member, _)| *member == name.spanned)
+                        .expect("lowering: unknown member");
+                    let value = self.expr(value);
+                    values.push((index, self.coerce(value, &members[index].1)));
+                }
+            }
+        }
+
+        if !union {
+            // members left out are never read
+            let mut fields = Vec::new();
+            for (index, (_, member)) in members.iter().enumerate() {
+                match values.iter().find(|(given, _)| *given == index) {
+                    Some((_, value)) => fields.push(*value),
+                    None => fields.push(self.push(Inst::Undef, member.clone())),
+                }
+            }
+            return self.push(Inst::Struct(fields), ty);
+        }
+        // only the last member given is kept
+        match (fields, values.pop()) {
+            (StructLitFields::Named(_), Some((tag, value))) => self.push(
+                Inst::Union {
+                    tag: tag as u32,
+                    value,
+                },
+                ty,
+            ),
+            _ => {
+                self.terminate(Terminator::Trap(Trap::EmptyUnion));
+                self.push(Inst::Undef, ty)
+            }
+        }
+    }
+}
+
+/// lowers a checked module to SSA form, without optimising it
+pub fn lower(checked: &Checked) -> Module {
+    let Checked {
+        ast, res, types, ..
+    } = checked;
+    let mut lowerer = Lowerer {
+        res,
+        types,
+        functions: HashMap::new(),
+        nestings: 0,
+        current: Current::default(),
+    };
+
+    let mut bodies = Vec::new();
+    collect(&mut bodies, ast, res);
+    // overloads are told apart by their order
+    let mut names = Vec::new();
+    for (index, (id, _)) in bodies.iter().enumerate() {
+        let name = &res.def(*id).name;
+        let overloads = bodies
+            .iter()
+            .filter(|(other, _)| res.def(*other).name == *name)
+            .count();
+        let name = if overloads > 1 {
+            let nth = bodies[..index]
+                .iter()
+                .filter(|(other, _)| res.def(*other).name == *name)
+                .count();
+            format!("{name}.{nth}")
+        } else {
+            name.clone()
+        };
+        lowerer.functions.insert(*id, index as u32 + 1);
+        names.push(name);
+    }
+
+    let mut functions = vec![lowerer.function(None, ".init".to_owned(), &[], ast)];
+    for ((id, stmt), name) in bodies.into_iter().zip(names) {
+        let Stmt::Fn {
+            parameters, body, ..
+        } = stmt
+        else {
+            unreachable!("lowering: collected a non-function");
+        };
+        functions.push(lowerer.function(Some(id), name, parameters, body));
+    }
+
+    let mut decls = types
+        .decls
+        .iter()
+        .filter(|(_, decl)| matches!(decl, TypeDecl::Struct(_) | TypeDecl::Union(_)))
+        .map(|(id, decl)| (*id, res.def(*id).name.clone(), decl.clone()))
+        .collect::<Vec<_>>();
+    decls.sort_by_key(|(id, ..)| id.0);
+    let names = res
+        .defs
+        .iter()
+        .enumerate()
+        .filter(|(id, def)| def.kind == DefKind::Stub || types.decls.contains_key(&DefId(*id)))
+        .map(|(id, def)| (DefId(id), def.name.clone()))
+        .collect();
+
+    Module {
+        main: interp::main_fn(checked).map(|(main, _)| lowerer.functions[&main]),
+        functions,
+        types: decls,
+        names,
+    }
+}
+
+/// every function with a body, wherever it's declared
+fn collect<'a>(bodies: &mut Vec<(DefId, &'a Stmt)>, block: &'a Block, res: &Resolutions) {
+    let mut exprs = Vec::new();
+    for stmt in &block.stmts {
+        match &stmt.spanned {
+            Stmt::Fn { name, body, .. } => {
+                if let Some(id) = res.def_at(name.span) {
+                    bodies.push((id, &stmt.spanned));
+                }
+                collect(bodies, body, res);
+            }
+            Stmt::Loop { block } => collect(bodies, block, res),
+            Stmt::Return { expr: Some(expr) }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. }
+            | Stmt::Discard { expr } => exprs.push(expr),
+            _ => {}
+        }
+    }
+    exprs.extend(block.tail.as_deref());
+    // in the order written, as functions are numbered by it
+    exprs.reverse();
+    while let Some(expr) = exprs.pop() {
+        match &expr.spanned {
+            Expr::Block(block) => collect(bodies, block, res),
+            Expr::Cond {
+                then,
+                elifs,
+                r#else,
+                ..
+            } => {
+                collect(bodies, &then.spanned, res);
+                for (_, block) in elifs {
+                    collect(bodies, &block.spanned, res);
+                }
+                if let Some(r#else) = r#else {
+                    collect(bodies, &r#else.spanned, res);
+                }
+            }
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => exprs.push(inner),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,637 @@
+use crate::cst::{self, NodeKind, SyntaxElement, SyntaxNode};
+use crate::driver::{self, Analysis};
+use crate::json::Json;
+use crate::lexer::{self, TokenKind};
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// the kinds of semantic token, by the index sent for each
+const TOKEN_TYPES: [&str; 7] = [
+    "keyword", "type", "variable", "number", "string", "operator", "comment",
+];
+
+/// `SymbolKind`s, as the protocol numbers them
+const FUNCTION: usize = 12;
+const STRUCT: usize = 23;
+const ENUM: usize = 10;
+const TYPE_PARAMETER: usize = 26;
+
+/// an error for a request, as a json-rpc code & message
+type Refusal = (i64, String);
+
+/// a file the client has open
+struct Document {
+    text: String,
+    /// where each line starts, in bytes
+    lines: Vec<usize>,
+    analysis: Analysis,
+}
+
+/// speaks the language server protocol over `input` & `output` until the client says `exit`,
+/// giving the code to exit with
+pub fn serve(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<i32> {
+    let mut server = Server {
+        documents: HashMap::new(),
+        shut_down: false,
+    };
+    loop {
+        let Some(message) = receive(input)? else {
+            // the client went away without saying `exit`
+            return Ok(1);
+        };
+        let Some(message) = Json::parse(&message) else {
+            let error = Json::obj([
+                ("code", Json::Num(-32700.0)),
+                ("message", "the message isn't json".into()),
+            ]);
+            let response = Json::obj([
+                ("jsonrpc", "2.0".into()),
+                ("id", Json::Null),
+                ("error", error),
+            ]);
+            send(output, &response)?;
+            continue;
+        };
+
+        let params = &message["params"];
+        match (message["method"].as_str(), message.get("id")) {
+            (Some("exit"), _) => return Ok(if server.shut_down { 0 } else { 1 }),
+            (Some(method), Some(id)) => {
+                let response = match server.request(method, params) {
+                    Ok(result) => Json::obj([
+                        ("jsonrpc", "2.0".into()),
+                        ("id", id.clone()),
+                        ("result", result),
+                    ]),
+                    Err((code, message)) => Json::obj([
+                        ("jsonrpc", "2.0".into()),
+                        ("id", id.clone()),
+                        (
+                            "error",
+                            Json::obj([
+                                ("code", Json::Num(code as f64)),
+                                ("message", message.into()),
+                            ]),
+                        ),
+                    ]),
+                };
+                send(output, &response)?;
+            }
+            (Some(method), None) => {
+                for notification in server.notify(method, params) {
+                    send(output, &notification)?;
+                }
+            }
+            // (a response to something we never asked)
+            (None, _) => {}
+        }
+    }
+}
+
+/// the body of the next message, or `None` at the end of the input
+fn receive(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "a message without a `Content-Length`",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn send(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+struct Server {
+    /// by uri
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl Server {
+    fn request(&mut self, method: &str, params: &Json) -> std::result::Result<Json, Refusal> {
+        if method == "initialize" {
+            return Ok(initialize());
+        }
+        if method == "shutdown" {
+            self.shut_down = true;
+            return Ok(Json::Null);
+        }
+
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self.documents.get(uri);
+        let position = || {
+            let document = document.ok_or_else(|| (-32602, format!("'{uri}' isn't open")))?;
+            let at = document.locate(&params["position"]);
+            Ok((
+                document,
+                at.ok_or_else(|| (-32602, "no such position".to_owned()))?,
+            ))
+        };
+        match method {
+            "textDocument/documentSymbol" => Ok(document.map_or(Json::Null, |document| {
+                let root = cst::parse(&mut State::new(), &document.text);
+                document.symbols(&root).into()
+            })),
+            "textDocument/semanticTokens/full" => Ok(document.map_or(Json::Null, |document| {
+                Json::obj([("data", document.semantic_tokens().into())])
+            })),
+            "textDocument/definition" => {
+                let (document, (line, col)) = position()?;
+                Ok(document.definition(uri, line, col))
+            }
+            "textDocument/hover" => {
+                let (document, (line, col)) = position()?;
+                Ok(document.hover(line, col))
+            }
+            _ => Err((-32601, format!("`{method}` isn't supported"))),
+        }
+    }
+
+    /// handles a notification, giving any to send back
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // (we only ever ask for the whole text)
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish(uri, Vec::new())];
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Vec::new();
+        };
+
+        let document = Document::new(text.to_owned());
+        let diagnostics = document.diagnostics(uri);
+        self.documents.insert(uri.to_owned(), document);
+        vec![publish(uri, diagnostics)]
+    }
+}
+
+fn initialize() -> Json {
+    let legend = Json::obj([
+        (
+            "tokenTypes",
+            TOKEN_TYPES
+                .iter()
+                .map(|&ty| ty.into())
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+        ("tokenModifiers", Json::Arr(Vec::new())),
+    ]);
+    let capabilities = Json::obj([
+        // the whole text, on every change
+        ("textDocumentSync", 1.into()),
+        ("documentSymbolProvider", true.into()),
+        ("definitionProvider", true.into()),
+        ("hoverProvider", true.into()),
+        (
+            "semanticTokensProvider",
+            Json::obj([("legend", legend), ("full", true.into())]),
+        ),
+    ]);
+    Json::obj([
+        ("capabilities", capabilities),
+        (
+            "serverInfo",
+            Json::obj([
+                ("name", "sdw".into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+            ]),
+        ),
+    ])
+}
+
+/// how many characters the name something resolved to is
+fn width(res: &Resolutions, resolved: &Res) -> SpanInt {
+    let id = match resolved {
+        Res::Def(id) => Some(id),
+        Res::Overloads(ids) => ids.first(),
+    };
+    id.map_or(0, |id| res.def(*id).name.chars().count() as SpanInt)
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::obj([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            Json::obj([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+        ),
+    ])
+}
+
+impl Document {
+    fn new(text: String) -> Document {
+        let mut lines = vec![0];
+        lines.extend(text.match_indices('\n').map(|(at, _)| at + 1));
+        let analysis = driver::analyse(&mut State::new(), &text);
+        Document {
+            text,
+            lines,
+            analysis,
+        }
+    }
+
+    /// the text of `line`, without its newline
+    fn line(&self, line: SpanInt) -> &str {
+        let Some(&start) = self.lines.get(line as usize) else {
+            return "";
+        };
+        let end = self
+            .lines
+            .get(line as usize + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        &self.text[start..end]
+    }
+
+    /// the position of the `col`th (1-indexed) character of `line` - which the protocol counts
+    /// in utf-16 code units
+    fn position(&self, line: SpanInt, col: SpanInt) -> Json {
+        let character: usize = self
+            .line(line)
+            .chars()
+            .take(col.saturating_sub(1) as usize)
+            .map(char::len_utf16)
+            .sum();
+        Json::obj([
+            ("line", (line as usize).into()),
+            ("character", character.into()),
+        ])
+    }
+
+    fn range(&self, span: Span) -> Json {
+        Json::obj([
+            ("start", self.position(span.sline, span.scol)),
+            (
+                "end",
+                self.position(span.eline.saturating_sub(1), span.ecol),
+            ),
+        ])
+    }
+
+    /// the range of some of the text, from the byte `range.start` up to `range.end`
+    fn text_range(&self, range: std::ops::Range<usize>) -> Json {
+        let at = |offset: usize| {
+            let line = self.lines.partition_point(|&start| start <= offset) - 1;
+            let col = self.text[self.lines[line]..offset].chars().count() + 1;
+            self.position(line as SpanInt, col as SpanInt)
+        };
+        Json::obj([("start", at(range.start)), ("end", at(range.end))])
+    }
+
+    /// the line & (1-indexed) character a position points to
+    fn locate(&self, position: &Json) -> Option<(SpanInt, SpanInt)> {
+        let line = position["line"].as_u64()?;
+        let character = position["character"].as_u64()? as usize;
+        let mut units = 0;
+        let col = self
+            .line(line)
+            .chars()
+            .take_while(|ch| {
+                units += ch.len_utf16();
+                units <= character
+            })
+            .count();
+        Some((line, col as SpanInt + 1))
+    }
+
+    fn location(&self, uri: &str, span: Span) -> Json {
+        Json::obj([("uri", uri.into()), ("range", self.range(span))])
+    }
+
+    /// everything `check` raises, errors & warnings
+    fn diagnostics(&self, uri: &str) -> Vec<Json> {
+        let mut state = State::new();
+        if let Err(driver::Failure::Unrecoverable(err)) =
+            driver::check(&mut state, &self.text, &mut ())
+        {
+            state.errors.push(err);
+        }
+
+        let errors = state.errors.iter().map(|err| (err, 1));
+        let warnings = state.warnings.iter().map(|warning| (warning, 2));
+        errors
+            .chain(warnings)
+            .map(|(err, severity)| {
+                let mut message = err.ty.to_string();
+                // `core` isn't a file the client can open
+                let range = if driver::in_core(err.span) {
+                    message.push_str(" (within `core`)");
+                    self.range(Span::default())
+                } else {
+                    self.range(err.span)
+                };
+                let related: Vec<Json> = err
+                    .notes
+                    .iter()
+                    .filter_map(|note| {
+                        let span = note.span.filter(|span| !driver::in_core(*span))?;
+                        Some(Json::obj([
+                            ("location", self.location(uri, span)),
+                            ("message", note.message.clone().into()),
+                        ]))
+                    })
+                    .collect();
+                let letter = if severity == 1 { "E" } else { "W" };
+                Json::obj([
+                    ("range", range),
+                    ("severity", severity.into()),
+                    ("code", format!("SDW {letter}/{}", err.ty.code()).into()),
+                    ("source", "sdw".into()),
+                    ("message", message.into()),
+                    ("relatedInformation", related.into()),
+                ])
+            })
+            .collect()
+    }
+
+    /// every `fn`, stub & `type` declared in `node`, with those declared within them.
+    /// (`mod` is lexed, but there's no such declaration yet.)
+    fn symbols(&self, node: &SyntaxNode) -> Vec<Json> {
+        let mut symbols = Vec::new();
+        for child in node.children() {
+            let kind = match child.kind() {
+                NodeKind::Fn | NodeKind::Stub => FUNCTION,
+                NodeKind::Type => match child.children().find(|bound| bound.kind().is_bound()) {
+                    Some(bound) if bound.kind() == NodeKind::StructBound => STRUCT,
+                    Some(bound) if bound.kind() == NodeKind::UnionBound => ENUM,
+                    _ => TYPE_PARAMETER,
+                },
+                _ => {
+                    symbols.extend(self.symbols(&child));
+                    continue;
+                }
+            };
+
+            let idns: Vec<_> = child
+                .children_with_tokens()
+                .take_while(|part| {
+                    !matches!(part, SyntaxElement::Token(token) if token.lexeme() == Some(&LexemeType::LParen))
+                })
+                .filter_map(|part| match part {
+                    SyntaxElement::Token(token) if matches!(token.lexeme(), Some(LexemeType::Idn(_))) => {
+                        Some(token)
+                    }
+                    _ => None,
+                })
+                .collect();
+            // a function's name is the last before its parameters, a type's the first
+            let name = match child.kind() {
+                NodeKind::Type => idns.first(),
+                _ => idns.last(),
+            };
+            let Some(name) = name else {
+                continue;
+            };
+            let mut symbol = Json::obj([
+                ("name", name.text().into()),
+                ("kind", kind.into()),
+                ("range", self.text_range(child.text_range())),
+                ("selectionRange", self.text_range(name.text_range())),
+                ("children", self.symbols(&child).into()),
+            ]);
+            if let (NodeKind::Stub, Json::Obj(fields)) = (child.kind(), &mut symbol) {
+                fields.push(("detail".to_owned(), "stub".into()));
+            }
+            symbols.push(symbol);
+        }
+        symbols
+    }
+
+    /// what's resolved at `col` of `line`. calls are resolved by the span of the whole call,
+    /// but it's only their name which is pointed at.
+    fn resolved(&self, line: SpanInt, col: SpanInt) -> Option<(Span, &Res)> {
+        let res = self.analysis.res.as_ref()?;
+        res.iter()
+            .find(|(span, resolved)| {
+                span.sline == line && (span.scol..span.scol + width(res, resolved)).contains(&col)
+            })
+            .map(|(span, resolved)| (*span, resolved))
+    }
+
+    /// what `span` (from `resolved`) refers to - for an overloaded call, the function the
+    /// call was resolved to by type, or otherwise every candidate
+    fn referents(&self, span: Span, resolved: &Res) -> Vec<DefId> {
+        match resolved {
+            Res::Def(id) => vec![*id],
+            Res::Overloads(ids) => match self
+                .analysis
+                .types
+                .as_ref()
+                .and_then(|types| types.call(span))
+            {
+                Some(id) => vec![id],
+                None => ids.clone(),
+            },
+        }
+    }
+
+    fn definition(&self, uri: &str, line: SpanInt, col: SpanInt) -> Json {
+        let (Some(res), Some((span, resolved))) = (&self.analysis.res, self.resolved(line, col))
+        else {
+            return Json::Null;
+        };
+        let locations: Vec<Json> = self
+            .referents(span, resolved)
+            .into_iter()
+            .map(|id| res.def(id).span)
+            .filter(|span| !driver::in_core(*span))
+            .map(|span| self.location(uri, span))
+            .collect();
+        match locations.len() {
+            0 => Json::Null,
+            1 => locations.into_iter().next().unwrap_or(Json::Null),
+            _ => locations.into(),
+        }
+    }
+
+    fn hover(&self, line: SpanInt, col: SpanInt) -> Json {
+        let (Some(res), Some((span, resolved))) = (&self.analysis.res, self.resolved(line, col))
+        else {
+            return Json::Null;
+        };
+        let value = self
+            .referents(span, resolved)
+            .into_iter()
+            .map(|id| self.describe(res, id))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let range = Span {
+            ecol: span.scol + width(res, resolved),
+            eline: span.sline + 1,
+            ..span
+        };
+        Json::obj([
+            (
+                "contents",
+                Json::obj([("kind", "plaintext".into()), ("value", value.into())]),
+            ),
+            ("range", self.range(range)),
+        ])
+    }
+
+    /// a declaration, as it would be written - `fn int add(int, int)`, `total: int` ..
+    fn describe(&self, res: &Resolutions, id: DefId) -> String {
+        let def = res.def(id);
+        let types = self.analysis.types.as_ref();
+        let ty = |ty: &Ty| ty.display(res);
+        match def.kind {
+            DefKind::Fn | DefKind::Stub => match types.and_then(|types| types.sigs.get(&id)) {
+                Some(sig) => format!(
+                    "fn {} {}({})",
+                    ty(&sig.ret),
+                    def.name,
+                    sig.params.iter().map(ty).collect::<Vec<_>>().join(", ")
+                ),
+                None => format!("fn {}", def.name),
+            },
+            DefKind::Var | DefKind::Param => match types.and_then(|types| types.locals.get(&id)) {
+                Some(local) => format!("{}: {}", def.name, ty(local)),
+                None => def.name.clone(),
+            },
+            DefKind::Type => {
+                let members = |members: &[(String, Ty)]| {
+                    members
+                        .iter()
+                        .map(|(name, member)| format!("{} {name}", ty(member)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                match types.and_then(|types| types.decls.get(&id)) {
+                    Some(TypeDecl::Struct(fields)) if fields.is_empty() => {
+                        format!("type {} struct", def.name)
+                    }
+                    Some(TypeDecl::Struct(fields)) => {
+                        format!("type {} struct {{ {} }}", def.name, members(fields))
+                    }
+                    Some(TypeDecl::Union(variants)) => {
+                        format!("type {} union {{ {} }}", def.name, members(variants))
+                    }
+                    Some(TypeDecl::Aggregate(generics)) => format!(
+                        "type {} {}",
+                        def.name,
+                        generics
+                            .iter()
+                            .map(|generic| res.def(*generic).name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" + ")
+                    ),
+                    Some(TypeDecl::Alias(aliased)) => format!("type {} {}", def.name, ty(aliased)),
+                    Some(TypeDecl::Generic) | None => format!("type {}", def.name),
+                }
+            }
+            DefKind::Label => format!("@{}", def.name),
+        }
+    }
+
+    /// every token worth highlighting, by what it lexed as
+    fn semantic_tokens(&self) -> Vec<Json> {
+        let mut data = Vec::new();
+        let (mut line, mut start) = (0, 0);
+        for token in lexer::lex_lossless(&mut State::new(), &self.text) {
+            let ty = match &token.kind {
+                TokenKind::Comment => 6,
+                TokenKind::Lexeme(lexeme) => match lexeme {
+                    LexemeType::Fn
+                    | LexemeType::Mc
+                    | LexemeType::Return
+                    | LexemeType::State
+                    | LexemeType::If
+                    | LexemeType::Else
+                    | LexemeType::Goto
+                    | LexemeType::Loop
+                    | LexemeType::Struct
+                    | LexemeType::Union
+                    | LexemeType::Type
+                    | LexemeType::Let
+                    | LexemeType::Mod
+                    | LexemeType::BoolLit(_) => 0,
+                    LexemeType::Idn(idn)
+                        if crate::resolve::BUILTIN_TYPES.contains(&idn.as_str()) =>
+                    {
+                        1
+                    }
+                    LexemeType::Idn(_) => 2,
+                    LexemeType::Intlit(_) => 3,
+                    LexemeType::StrLit(_) => 4,
+                    LexemeType::Cross
+                    | LexemeType::Dash
+                    | LexemeType::Ast
+                    | LexemeType::FSlash
+                    | LexemeType::Perc
+                    | LexemeType::Bar
+                    | LexemeType::Amp
+                    | LexemeType::Tilde
+                    | LexemeType::Caret
+                    | LexemeType::RAng
+                    | LexemeType::LAng
+                    | LexemeType::Bang
+                    | LexemeType::Equals => 5,
+                    _ => continue,
+                },
+                TokenKind::Whitespace | TokenKind::Error => continue,
+            };
+
+            // (only a block comment spans lines, & it's highlighted a line at a time)
+            for (nth, text) in token.text.split('\n').enumerate() {
+                let token_line = token.span.sline as usize + nth;
+                let character = if nth == 0 {
+                    let at = self.position(token.span.sline, token.span.scol);
+                    at["character"].as_u64().unwrap_or_default() as usize
+                } else {
+                    0
+                };
+                let length: usize = text.chars().map(char::len_utf16).sum();
+                let delta_start = if token_line == line {
+                    character - start
+                } else {
+                    character
+                };
+                data.extend([
+                    (token_line - line).into(),
+                    delta_start.into(),
+                    length.into(),
+                    ty.into(),
+                    0.into(),
+                ]);
+                (line, start) = (token_line, character);
+            }
+        }
+        data
+    }
+}
@@ -1,24 +1,118 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use owo_colors::OwoColorize;
-use sdw::lexer;
+use sdw::bytecode::{self, Program};
+use sdw::driver::{self, Checked, Failure, Observer, Stage};
+use sdw::interp::{self, Value};
+use sdw::layout::{self, Target};
+use sdw::prelude::*;
+use sdw::pretty::Style;
+use sdw::repl::{self, Evaluated, Rejected, Session};
+use sdw::{compile, emit_asm, emit_c, emit_wat, fmt, lexer, lower, lsp, parser, passes, vm};
 use std::fs;
+use std::io::{self, Write};
 use std::process;
 use std::time::Instant;
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// filepath to read from
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// filepath to check
+    input: Option<String>,
 
     /// print extra information
     #[arg(short, long)]
     verbose: bool,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// run the file's `main` function; an `int` it returns becomes the exit code
+    Run {
+        /// source, or bytecode written by `compile` (which is always run by the vm)
+        input: String,
+        /// compile to bytecode & run that, rather than walking the syntax tree
+        #[arg(long)]
+        vm: bool,
+    },
+    /// compile the file to bytecode, which `run` can load without checking the source again
+    Compile {
+        input: String,
+        /// defaults to the input's name, with an `.sdwb` extension
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// print the bytecode compiled from a file (or previously written by `compile`)
+    Disasm { input: String },
+    /// translate the file to a single C99 source file
+    #[command(name = "emit-c")]
+    EmitC {
+        input: String,
+        /// defaults to the input's name, with a `.c` extension
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// translate the file to x86-64 assembly (GNU syntax) for Linux, with a `_start` calling
+    /// `main`
+    #[command(name = "emit-asm")]
+    EmitAsm {
+        input: String,
+        /// defaults to the input's name, with an `.s` extension
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// translate the file to the WebAssembly text format, exporting `main` & importing
+    /// `sdw.fail` to report runtime errors
+    #[command(name = "emit-wat")]
+    EmitWat {
+        input: String,
+        /// defaults to the input's name, with a `.wat` extension
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// print the module lowered to SSA form, after optimising it
+    #[command(name = "emit-ir")]
+    EmitIr {
+        input: String,
+        /// print it as lowered, without optimising
+        #[arg(long)]
+        raw: bool,
+    },
+    /// print the size, alignment & member offsets of every declared type
+    Layout {
+        input: String,
+        /// x86_64, aarch64, wasm32 or powerpc64
+        #[arg(short, long, default_value = "x86_64")]
+        target: String,
+    },
+    /// lay files out canonically (in place), keeping their comments
+    Fmt {
+        inputs: Vec<String>,
+        /// change nothing, but fail if any file isn't already formatted
+        #[arg(long)]
+        check: bool,
+        /// spaces per level of nesting
+        #[arg(long, default_value_t = 4)]
+        indent: usize,
+        /// how wide a line may be, before lists on it are split one item per line
+        #[arg(long, default_value_t = 100)]
+        width: usize,
+    },
+    /// read statements & expressions from the terminal, printing each expression's value &
+    /// type. `:ast`, `:tokens` & `:type` show what some input parses to, lexes to, or has
+    Repl,
+    /// serve the language server protocol over stdin & stdout, for editors
+    Lsp,
+}
+
 mod print {
     use std::time::Instant;
 
     use owo_colors::OwoColorize;
+    use sdw::driver::{self, Checked};
+    use sdw::layout::{Endianness, Layouts};
     use sdw::prelude::*;
 
     macro_rules! print_idn {
@@ -28,12 +122,113 @@ mod print {
         }};
     }
 
-    fn ste(_ident: usize, _expr: &Expr) {
-        todo!()
+    fn ste(ident: usize, expr: &Expr) {
+        match expr {
+            Expr::IntLiteral(int) => print_idn!(ident, "int literal -> {}", int),
+            Expr::BoolLiteral(bool) => print_idn!(ident, "bool literal -> {}", bool),
+            Expr::StrLiteral(text) => print_idn!(ident, "string literal -> {:?}", text),
+            Expr::Variable(name) => print_idn!(ident, "variable -> {}", name),
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => {
+                let what = match expr {
+                    Expr::UnaryNot(_) => "logical not",
+                    Expr::UnaryNeg(_) => "negation",
+                    Expr::UnaryPos(_) => "unary plus",
+                    Expr::SubExpr(_) => "parenthesised",
+                    Expr::Referal(_) => "reference",
+                    _ => "dereference",
+                };
+                print_idn!(ident, "{}:", what);
+                ste(ident + 1, &inner.spanned);
+            }
+            Expr::FnCall(name, args) => {
+                print_idn!(ident, "function call:");
+                print_idn!(ident + 1, "name -> {}", name);
+                print_idn!(ident + 1, "arguments:");
+                for arg in args {
+                    ste(ident + 2, &arg.spanned);
+                }
+                if args.is_empty() {
+                    print_idn!(ident + 2, "[ none ]");
+                }
+            }
+            Expr::BiOp(left, op, right) => {
+                print_idn!(ident, "binary operation -> {}", op.symbol());
+                ste(ident + 1, &left.spanned);
+                ste(ident + 1, &right.spanned);
+            }
+            Expr::ObjMember(object, member) => {
+                print_idn!(ident, "member access:");
+                print_idn!(ident + 1, "object -> {}", object.spanned);
+                print_idn!(ident + 1, "member -> {}", member.spanned);
+            }
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                print_idn!(ident, "conditional:");
+                print_idn!(ident + 1, "condition:");
+                ste(ident + 2, &condition.spanned);
+                print_idn!(ident + 1, "then:");
+                syntax_tree_ident(ident + 2, &then.spanned);
+                for (condition, block) in elifs {
+                    print_idn!(ident + 1, "else if condition:");
+                    ste(ident + 2, &condition.spanned);
+                    print_idn!(ident + 1, "then:");
+                    syntax_tree_ident(ident + 2, &block.spanned);
+                }
+                if let Some(r#else) = r#else {
+                    print_idn!(ident + 1, "else:");
+                    syntax_tree_ident(ident + 2, &r#else.spanned);
+                }
+            }
+            Expr::Block(block) => {
+                print_idn!(ident, "block:");
+                syntax_tree_ident(ident + 1, block);
+            }
+            Expr::StructLit { ty, fields } => {
+                print_idn!(ident, "struct literal:");
+                print_idn!(ident + 1, "type -> {}", ty.spanned);
+                match fields {
+                    StructLitFields::Positional(values) => {
+                        for value in values {
+                            print_idn!(ident + 1, "field:");
+                            ste(ident + 2, &value.spanned);
+                        }
+                    }
+                    StructLitFields::Named(values) => {
+                        for (name, value) in values {
+                            print_idn!(ident + 1, "field -> {}:", name.spanned);
+                            ste(ident + 2, &value.spanned);
+                        }
+                    }
+                }
+            }
+            Expr::Attributed { attr, expr } => {
+                print_idn!(ident, "attributed expression:");
+                attributes(ident + 1, std::slice::from_ref(attr));
+                ste(ident + 1, &expr.spanned);
+            }
+        }
     }
 
     fn stb(ident: usize, bound: &Bound) {
         match bound {
+            Bound::Generic => {
+                print_idn!(ident, "generic type");
+            }
+            Bound::Aggregate(generics) => {
+                print_idn!(ident, "generic aggregate:");
+                for generic in generics {
+                    print_idn!(ident + 1, "generic -> {}", generic.spanned);
+                }
+            }
             Bound::Struct(members) => {
                 print_idn!(ident, "struct declaration:");
                 if let Some(members) = members {
@@ -87,15 +282,30 @@ mod print {
         }
     }
 
+    fn attributes(ident: usize, attrs: &[Spanned<Attribute>]) {
+        for attr in attrs {
+            print_idn!(ident, "attribute -> {}", attr.spanned.name.spanned);
+            for arg in &attr.spanned.args {
+                match &arg.spanned {
+                    AttrArg::Int(int) => print_idn!(ident + 1, "arg -> {}", int),
+                    AttrArg::Bool(bool) => print_idn!(ident + 1, "arg -> {}", bool),
+                    AttrArg::Idn(idn) => print_idn!(ident + 1, "arg -> {}", idn),
+                }
+            }
+        }
+    }
+
     fn sts(ident: usize, stmt: &Stmt) {
         match stmt {
             Stmt::Fn {
+                attrs,
                 return_type,
                 name,
                 parameters,
                 body,
             } => {
                 print_idn!(ident, "function:");
+                attributes(ident + 1, attrs);
                 print_idn!(ident + 1, "name -> {}", name.spanned);
                 print_idn!(ident + 1, "return type -> {}", return_type.spanned);
                 print_idn!(ident + 1, "parameters:");
@@ -113,14 +323,16 @@ mod print {
                 }
 
                 print_idn!(ident + 1, "body:");
-                syntax_tree_ident(ident + 2, &body);
+                syntax_tree_ident(ident + 2, body);
             }
             Stmt::Stub {
+                attrs,
                 return_type,
                 name,
                 parameters,
             } => {
                 print_idn!(ident, "function stub:");
+                attributes(ident + 1, attrs);
                 print_idn!(ident + 1, "name -> {}", name.spanned);
                 print_idn!(ident + 1, "return type -> {}", return_type.spanned);
                 print_idn!(ident + 1, "parameters:");
@@ -162,12 +374,16 @@ mod print {
                 print_idn!(ident, "name -> {}", name.spanned);
                 ste(ident + 1, &updated.spanned);
             }
-            Stmt::Type { name, bound } => {
+            Stmt::Type { attrs, name, bound } => {
                 print_idn!(ident, "type declaration");
+                attributes(ident + 1, attrs);
                 print_idn!(ident + 1, "name -> {}", name.spanned);
                 stb(ident + 1, &bound.spanned);
             }
-            Stmt::Discard { .. } => todo!()
+            Stmt::Discard { expr } => {
+                print_idn!(ident, "discarded expression:");
+                ste(ident + 1, &expr.spanned);
+            }
         }
     }
 
@@ -187,7 +403,7 @@ mod print {
         syntax_tree_ident(0, root);
     }
 
-    pub fn lexemes(lexemes: &Vec<Lexeme>) {
+    pub fn lexemes(lexemes: &[Lexeme]) {
         println!(
             "lexemes:\n{}",
             lexemes
@@ -198,6 +414,60 @@ mod print {
         );
     }
 
+    pub fn layouts(checked: &Checked, layouts: &Layouts) {
+        let target = &layouts.target;
+        println!(
+            "target: {} ({}-bit pointers, {} endian)",
+            target.name.bright_green(),
+            target.pointer_width * 8,
+            match target.endianness {
+                Endianness::Little => "little",
+                Endianness::Big => "big",
+            }
+        );
+
+        // (`core`'s types are there too, but weren't declared by the file)
+        let mut decls = checked
+            .types
+            .decls
+            .iter()
+            .filter(|(id, _)| !driver::in_core(checked.res.def(**id).span))
+            .collect::<Vec<_>>();
+        decls.sort_by_key(|(id, _)| id.0);
+        for (id, decl) in decls {
+            let name = &checked.res.def(*id).name;
+            let kind = match decl {
+                TypeDecl::Struct(_) => "struct".to_owned(),
+                TypeDecl::Union(_) => "union".to_owned(),
+                TypeDecl::Alias(ty) => format!("alias of {}", ty.display(&checked.res)),
+                TypeDecl::Generic | TypeDecl::Aggregate(_) => "generic".to_owned(),
+            };
+
+            println!();
+            let Some(layout) = layouts.decl(*id) else {
+                println!(
+                    "{} ({}) - laid out as whatever stands in for it",
+                    name.bright_green(),
+                    kind
+                );
+                continue;
+            };
+            println!(
+                "{} ({}) - size {}, align {}",
+                name.bright_green(),
+                kind,
+                layout.size,
+                layout.align
+            );
+            for field in layout.discriminant.iter().chain(&layout.fields) {
+                println!(
+                    "  offset {:<4} size {:<4} {}",
+                    field.offset, field.size, field.name
+                );
+            }
+        }
+    }
+
     pub fn done(before: &Instant) {
         println!(
             "{}, in {} μs",
@@ -207,63 +477,388 @@ mod print {
     }
 }
 
-fn main() {
-    let args = Args::parse();
-    let contents = fs::read_to_string(&args.input).unwrap_or_else(|_| {
+/// prints each stage as it runs, as well as any warnings & errors it raises
+struct Progress {
+    contents: String,
+    verbose: bool,
+    before: Instant,
+}
+
+impl Observer for Progress {
+    fn start(&mut self, stage: Stage) {
+        self.before = Instant::now();
+        println!("{}..", stage.title().bright_green());
+    }
+
+    fn finish(&mut self, stage: Stage, state: &mut State) {
+        print::done(&self.before);
+        state.print_warns(&self.contents);
+        if !state.errors.is_empty() {
+            state.print_errs(&self.contents, stage.process());
+        }
+    }
+
+    fn lexed(&mut self, lexemes: &[Lexeme]) {
+        println!("produced {} lexemes", lexemes.len().bright_green());
+        println!();
+        if self.verbose {
+            print::lexemes(lexemes);
+            println!();
+        }
+    }
+
+    fn parsed(&mut self, ast: &Block) {
+        println!();
+        if self.verbose {
+            print::syntax_tree(ast);
+            println!();
+        }
+    }
+}
+
+/// like `Progress`, but only reporting warnings & errors - for modes which print something else
+struct Quiet {
+    contents: String,
+}
+
+impl Observer for Quiet {
+    fn finish(&mut self, stage: Stage, state: &mut State) {
+        state.print_warns(&self.contents);
+        if !state.errors.is_empty() {
+            state.print_errs(&self.contents, stage.process());
+        }
+    }
+}
+
+fn read(input: &str) -> String {
+    fs::read_to_string(input).unwrap_or_else(|_| {
         eprintln!(
             "{}: could not read from input file '{}' - does it exist?",
             "error".red(),
-            args.input
+            input
         );
         process::exit(1);
-    });
+    })
+}
 
-    let mut state = sdw::common::State::new();
+/// writes to `output`, or else a file named after the input, with the given extension
+fn write(input: &str, output: Option<String>, extension: &str, contents: &[u8]) {
+    let output = output.unwrap_or_else(|| {
+        std::path::Path::new(input)
+            .with_extension(extension)
+            .to_string_lossy()
+            .into_owned()
+    });
+    if let Err(err) = fs::write(&output, contents) {
+        eprintln!(
+            "{}: could not write to '{}': {}",
+            "error".red(),
+            output,
+            err
+        );
+        process::exit(1);
+    }
+}
 
-    let before = Instant::now();
-    println!("{}..", "lexing file".bright_green());
-    let lexemes = lexer::lex(&mut state, &contents);
+/// the file, checked without reporting progress, along with its contents
+fn checked(input: &str) -> (Checked, String) {
+    let contents = read(input);
+    let checked = check(
+        &contents,
+        &mut Quiet {
+            contents: contents.clone(),
+        },
+    );
+    (checked, contents)
+}
 
+/// translates the checked file with `emitter`, writing it as `write` does - exiting with the
+/// errors raised whilst `doing` it, if any
+fn emit(
+    input: &str,
+    output: Option<String>,
+    extension: &str,
+    doing: &str,
+    emitter: fn(&mut State, &Checked) -> String,
+) {
+    let (checked, contents) = checked(input);
+    let mut state = State::new();
+    let emitted = emitter(&mut state, &checked);
     if !state.errors.is_empty() {
-        state.print_errs(&contents, "lexing");
+        state.print_errs(&contents, doing);
         process::exit(1);
     }
+    write(input, output, extension, emitted.as_bytes());
+}
 
-    print::done(&before);
-    println!("produced {} lexemes", lexemes.len().bright_green());
-
-    println!();
-    if args.verbose {
-        print::lexemes(&lexemes);
-        println!();
+/// a compiled program - from a bytecode file, or else compiled from source, which is also
+/// returned (for printing runtime errors)
+fn load(input: &str) -> (Program, Option<String>) {
+    let bytes = fs::read(input).unwrap_or_else(|_| {
+        eprintln!(
+            "{}: could not read from input file '{}' - does it exist?",
+            "error".red(),
+            input
+        );
+        process::exit(1);
+    });
+    if bytecode::is_bytecode(&bytes) {
+        match Program::decode(&bytes) {
+            Ok(program) => return (program, None),
+            Err(err) => {
+                eprintln!("{}: couldn't load '{}': {}", "error".red(), input, err);
+                process::exit(1);
+            }
+        }
     }
 
-    let before = Instant::now();
-    println!("{}..", "parsing file".bright_green());
-    let st = sdw::parser::parse(&mut state, lexemes).unwrap_or_else(|err| {
-        #[rustfmt::skip]
-        let err_text = format!( // i don't know how better to write this. deal with it. it lines up
-            r"
+    let (checked, contents) = checked(input);
+    (compile::compile(&checked), Some(contents))
+}
+
+/// runs the file through every stage, exiting if any fail
+fn check(contents: &str, observer: &mut impl Observer) -> Checked {
+    let mut state = State::new();
+    match driver::check(&mut state, contents, observer) {
+        Ok(checked) => checked,
+        Err(Failure::Errors(_)) => process::exit(1),
+        Err(Failure::Unrecoverable(err)) => {
+            #[rustfmt::skip]
+            let err_text = format!( // i don't know how better to write this. deal with it. it lines up
+                r"
               an {} was raised: 
             ======================================
             ",
-            "unrecoverable error".red()
-        );
+                "unrecoverable error".red()
+            );
 
-        eprintln!("{}", err_text);
-        err.print(&contents);
-        process::exit(1);
-    });
+            eprintln!("{}", err_text);
+            err.print(contents);
+            process::exit(1);
+        }
+    }
+}
 
-    print::done(&before);
-    if !state.errors.is_empty() {
-        state.print_errs(&contents, "parsing");
-        process::exit(1);
+/// handles one `:command`; `false` once the session should end
+fn meta(session: &Session, line: &str) -> bool {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut state = State::new();
+    match command {
+        ":quit" | ":q" => return false,
+        ":tokens" => {
+            let lexemes = lexer::lex(&mut state, rest);
+            if state.errors.is_empty() {
+                print::lexemes(&lexemes);
+            } else {
+                state.print_errs(rest, Stage::Lex.process());
+            }
+        }
+        ":ast" => {
+            let lexemes = lexer::lex(&mut state, rest);
+            if !state.errors.is_empty() {
+                state.print_errs(rest, Stage::Lex.process());
+                return true;
+            }
+            match parser::parse(&mut state, lexemes) {
+                Ok(block) if state.errors.is_empty() => print::syntax_tree(&block),
+                Ok(_) => state.print_errs(rest, Stage::Parse.process()),
+                Err(err) => {
+                    state.errors.push(err);
+                    state.print_errs(rest, Stage::Parse.process());
+                }
+            }
+        }
+        ":type" => match session.type_of(&mut state, rest) {
+            Ok(Some(ty)) => println!("{ty}"),
+            Ok(None) => println!("(not an expression)"),
+            Err(rejected) => reject(&mut state, &session.source(rest), rejected),
+        },
+        _ => eprintln!(
+            "{}: unknown command '{}' (expected :ast, :tokens, :type or :quit)",
+            "error".red(),
+            command
+        ),
+    }
+    true
+}
+
+/// shows why some input wasn't accepted
+fn reject(state: &mut State, source: &str, rejected: Rejected) {
+    state.print_warns(source);
+    match rejected {
+        Rejected::Errors(stage) => state.print_errs(source, stage.process()),
+        Rejected::Unrecoverable(err) => {
+            eprintln!("an {} was raised:", "unrecoverable error".red());
+            err.print(source);
+        }
+        Rejected::Failed(err) => {
+            eprintln!("{} was raised whilst running:\n", "a runtime error".red());
+            err.print(source);
+        }
     }
+}
 
+fn interactive() {
+    let mut session = Session::new();
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "| " });
+        let _ = io::stdout().flush();
+        // stdin is only locked while reading a line, as what's evaluated may read it too
+        let mut line = String::new();
+        if !matches!(io::stdin().read_line(&mut line), Ok(1..)) {
+            break;
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            if !meta(&session, line.trim()) {
+                break;
+            }
+            continue;
+        }
+        if !input.is_empty() {
+            input.push('\n');
+        }
+        input.push_str(line);
+        if !repl::complete(&input) {
+            continue;
+        }
+
+        let input = std::mem::take(&mut input);
+        if input.trim().is_empty() {
+            continue;
+        }
+        let mut state = State::new();
+        match session.eval(&mut state, &input) {
+            Ok(evaluated) => {
+                state.print_warns(&session.source(""));
+                match evaluated {
+                    Evaluated::Value(Value::Void, _) | Evaluated::Kept => {}
+                    Evaluated::Value(value, ty) => println!("{value}: {ty}"),
+                    Evaluated::Exited(code) => process::exit(code as i32),
+                }
+            }
+            Err(rejected) => reject(&mut state, &session.source(&input), rejected),
+        }
+    }
     println!();
-    if args.verbose {
-        print::syntax_tree(&st);
-        println!();
+}
+
+fn main() {
+    let args = Args::parse();
+    match args.command {
+        None => {
+            let Some(input) = args.input else {
+                eprintln!("{}: no input file given", "error".red());
+                process::exit(1);
+            };
+            let contents = read(&input);
+            let mut progress = Progress {
+                contents: contents.clone(),
+                verbose: args.verbose,
+                before: Instant::now(),
+            };
+            check(&contents, &mut progress);
+        }
+        Some(Command::Run { input, vm }) => {
+            let compiled = fs::read(&input).is_ok_and(|bytes| bytecode::is_bytecode(&bytes));
+            let (result, contents) = if vm || compiled {
+                let (program, contents) = load(&input);
+                // without the source, errors are only given by line
+                (vm::run(&program), contents.unwrap_or_default())
+            } else {
+                let (checked, contents) = checked(&input);
+                (interp::run(&checked), contents)
+            };
+            match result {
+                Ok(Value::Int(code)) => process::exit(code as i32),
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("{} was raised whilst running:\n", "a runtime error".red());
+                    err.print(&contents);
+                    process::exit(101);
+                }
+            }
+        }
+        Some(Command::Compile { input, output }) => {
+            let (program, _) = load(&input);
+            write(&input, output, "sdwb", &program.encode());
+        }
+        Some(Command::EmitC { input, output }) => {
+            emit(&input, output, "c", "emitting C", emit_c::emit)
+        }
+        Some(Command::EmitAsm { input, output }) => {
+            emit(&input, output, "s", "emitting assembly", emit_asm::emit)
+        }
+        Some(Command::EmitWat { input, output }) => emit(
+            &input,
+            output,
+            "wat",
+            "emitting WebAssembly",
+            emit_wat::emit,
+        ),
+        Some(Command::EmitIr { input, raw }) => {
+            let (checked, _) = checked(&input);
+            let mut module = lower::lower(&checked);
+            if !raw {
+                passes::optimise(&mut module);
+            }
+            print!("{module}");
+        }
+        Some(Command::Disasm { input }) => {
+            let (program, _) = load(&input);
+            print!("{program}");
+        }
+        Some(Command::Layout { input, target }) => {
+            let Some(target) = Target::named(&target) else {
+                eprintln!(
+                    "{}: unknown target '{}' (expected one of: {})",
+                    "error".red(),
+                    target,
+                    Target::ALL.map(|target| target.name).join(", ")
+                );
+                process::exit(1);
+            };
+            let (checked, _) = checked(&input);
+            let layouts = layout::compute(&checked.types, &target);
+            print::layouts(&checked, &layouts);
+        }
+        Some(Command::Fmt {
+            inputs,
+            check,
+            indent,
+            width,
+        }) => {
+            let style = Style { indent, width };
+            let mut unformatted = false;
+            for input in inputs {
+                let contents = read(&input);
+                let mut state = State::new();
+                let Some(formatted) = fmt::format(&mut state, &contents, &style) else {
+                    state.print_errs(&contents, "formatting");
+                    process::exit(1);
+                };
+                if formatted == contents {
+                    continue;
+                }
+                if check {
+                    println!("'{}' isn't formatted", input);
+                    unformatted = true;
+                } else {
+                    write(&input, Some(input.clone()), "sdw", formatted.as_bytes());
+                }
+            }
+            if unformatted {
+                process::exit(1);
+            }
+        }
+        Some(Command::Repl) => interactive(),
+        Some(Command::Lsp) => {
+            let code = lsp::serve(&mut io::stdin().lock(), &mut io::stdout().lock());
+            process::exit(code.unwrap_or_else(|err| {
+                eprintln!("{}: {}", "error".red(), err);
+                1
+            }));
+        }
     }
 }
@@ -0,0 +1,757 @@
+use crate::driver::Checked;
+use crate::intrinsics::Intrinsic;
+use crate::ir::{Function, Inst, Module, Terminator, Value};
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+fn is_param(types: &Types, id: &DefId) -> bool {
+    matches!(
+        types.decls.get(id),
+        Some(TypeDecl::Generic | TypeDecl::Aggregate(_))
+    )
+}
+
+/// whether `ty` mentions a generic, so has no one representation
+pub fn is_generic(types: &Types, ty: &Ty) -> bool {
+    match ty {
+        Ty::Named(id) => is_param(types, id),
+        Ty::Pointer(to) => is_generic(types, to),
+        Ty::FnPtr { args, ret } => args.iter().chain([&**ret]).any(|ty| is_generic(types, ty)),
+        _ => false,
+    }
+}
+
+/// whether a function's parameters or what it returns are generic
+fn is_generic_fn(types: &Types, params: &[Ty], ret: &Ty) -> bool {
+    params.iter().chain([ret]).any(|ty| is_generic(types, ty))
+}
+
+/// whether `ty` says what a value is - a value which never arrives says nothing
+fn is_known(types: &Types, ty: &Ty) -> bool {
+    !matches!(ty, Ty::Never | Ty::Error) && !is_generic(types, ty)
+}
+
+/// the types of the parameters of the copy of a function taking `params`, called with
+/// arguments of the types `args`. a generic parameter is whatever its argument is, so two of
+/// the same generic may be different types, as they may be in the interpreter
+pub fn params(types: &Types, params: &[Ty], args: &[Ty]) -> Vec<Ty> {
+    params
+        .iter()
+        .zip(args)
+        .map(
+            |(param, arg)| match is_generic(types, param) && is_known(types, arg) {
+                true => arg.clone(),
+                false => param.clone(),
+            },
+        )
+        .collect()
+}
+
+/// the implementation of an interface stub for arguments of the types `args` - picked as the
+/// interpreter does at runtime: an exact implementation, over a default one
+pub fn implementation(res: &Resolutions, types: &Types, stub: DefId, args: &[Ty]) -> Option<DefId> {
+    let name = &res.def(stub).name;
+    let accepts = |param: &Ty, arg: &Ty| -> Option<usize> {
+        match param {
+            _ if param == arg => Some(1),
+            Ty::Named(id) if is_param(types, id) => Some(0),
+            _ if types.coerces(arg, param) => Some(0),
+            _ => None,
+        }
+    };
+    res.defs
+        .iter()
+        .enumerate()
+        .filter(|(_, def)| def.kind == DefKind::Fn && def.name == *name)
+        .filter_map(|(id, _)| {
+            let sig = types.sigs.get(&DefId(id))?;
+            if sig.params.len() != args.len() {
+                return None;
+            }
+            let score = sig
+                .params
+                .iter()
+                .zip(args)
+                .map(|(param, arg)| accepts(param, arg))
+                .sum::<Option<usize>>()?;
+            Some((DefId(id), score))
+        })
+        .max_by_key(|(id, score)| (*score, std::cmp::Reverse(id.0)))
+        .map(|(id, _)| id)
+}
+
+/// part of a function's mangled name, identifying the type of one of its parameters
+pub fn mangle(res: &Resolutions, ty: &Ty, out: &mut String) {
+    match ty {
+        Ty::Prim(PrimType::Int) => out.push('i'),
+        Ty::Prim(PrimType::Unt) => out.push('u'),
+        Ty::Prim(PrimType::Bool) => out.push('b'),
+        Ty::Prim(PrimType::Float) => out.push('f'),
+        Ty::Prim(PrimType::String) => out.push('s'),
+        Ty::Void | Ty::Never | Ty::Error => out.push('v'),
+        Ty::Named(id) => {
+            let name = &res.def(*id).name;
+            let _ = write!(out, "{}{name}", name.len());
+        }
+        Ty::Pointer(to) => {
+            out.push('P');
+            mangle(res, to, out);
+        }
+        Ty::FnPtr { args, ret } => {
+            out.push('F');
+            for arg in args {
+                mangle(res, arg, out);
+            }
+            out.push('_');
+            mangle(res, ret, out);
+            out.push('E');
+        }
+    }
+}
+
+/// a value which would have to be two different types at once, as the backends describe it
+pub fn conflict(res: &Resolutions, a: &Ty, b: &Ty) -> String {
+    format!(
+        "a generic value which is both `{}` and `{}`",
+        a.display(res),
+        b.display(res)
+    )
+}
+
+/*
+ * copies of the AST's functions
+ */
+
+/// what's known of the types in one copy of a function (or the top level), for the types of
+/// the arguments it's given
+#[derive(Debug, Clone, Default)]
+pub struct Inferred {
+    /// the types of the generic expressions & variables which are known
+    pub exprs: HashMap<Span, Ty>,
+    pub locals: HashMap<DefId, Ty>,
+    /// what it returns, if that's known
+    pub ret: Option<Ty>,
+    /// generic values which would have to be two types at once, & both of them
+    pub conflicts: Vec<(Span, Ty, Ty)>,
+}
+
+/// works out what each generic value is in the copies of generic functions, for backends
+/// lowering the AST itself
+pub struct Infer<'a> {
+    res: &'a Resolutions,
+    types: &'a Types,
+    /// every function with a body
+    pub bodies: HashMap<DefId, &'a Stmt>,
+    copies: HashMap<(DefId, Vec<Ty>), Inferred>,
+    /// the function being worked through, & what it's declared to return
+    current: Inferred,
+    ret: Ty,
+}
+
+impl<'a> Infer<'a> {
+    pub fn new(res: &'a Resolutions, types: &'a Types, bodies: HashMap<DefId, &'a Stmt>) -> Self {
+        Infer {
+            res,
+            types,
+            bodies,
+            copies: HashMap::new(),
+            current: Inferred::default(),
+            ret: Ty::Void,
+        }
+    }
+
+    pub fn is_generic(&self, function: DefId) -> bool {
+        let sig = &self.types.sigs[&function];
+        is_generic_fn(self.types, &sig.params, &sig.ret)
+    }
+
+    /// the function a call of `function` with arguments of the types `args` goes to (for a
+    /// stub, its implementation), & the types of its copy's parameters. `None` if no
+    /// implementation fits
+    pub fn target(&self, function: DefId, args: &[Ty]) -> Option<(DefId, Vec<Ty>)> {
+        let function = match self.res.def(function).kind {
+            DefKind::Stub => implementation(self.res, self.types, function, args)?,
+            _ => function,
+        };
+        let params = params(self.types, &self.types.sigs[&function].params, args);
+        Some((function, params))
+    }
+
+    /// what's known of the copy of `function` whose parameters are of the types `params`
+    pub fn copy(&mut self, function: DefId, params: &[Ty]) -> &Inferred {
+        let key = (function, params.to_vec());
+        if !self.copies.contains_key(&key) {
+            // a recursive call sees what's been worked out so far, so it's worked out again
+            // until that stops changing
+            self.copies.insert(key.clone(), Inferred::default());
+            loop {
+                let inferred = self.function(function, params);
+                let done = inferred.ret == self.copies[&key].ret;
+                self.copies.insert(key.clone(), inferred);
+                if done {
+                    break;
+                }
+            }
+        }
+        &self.copies[&key]
+    }
+
+    /// what's known of the top level, which is never generic but may call generic functions
+    pub fn top_level(&mut self, root: &Block) -> Inferred {
+        self.ret = Ty::Void;
+        self.block(root);
+        std::mem::take(&mut self.current)
+    }
+
+    fn function(&mut self, function: DefId, params: &[Ty]) -> Inferred {
+        let Stmt::Fn {
+            parameters, body, ..
+        } = self.bodies[&function]
+        else {
+            unreachable!("mono: collected a non-function");
+        };
+        let outer = std::mem::take(&mut self.current);
+        let outer_ret = std::mem::replace(&mut self.ret, self.types.sigs[&function].ret.clone());
+
+        for ((_, param), ty) in parameters.iter().zip(params) {
+            let id = self
+                .res
+                .def_at(param.span)
+                .expect("mono: unresolved parameter");
+            if is_known(self.types, ty) && is_generic(self.types, &self.types.locals[&id]) {
+                self.current.locals.insert(id, ty.clone());
+            }
+        }
+        let tail = self.block(body);
+        if is_generic(self.types, &self.ret) {
+            let span = body
+                .tail
+                .as_ref()
+                .map_or(self.res.def(function).span, |tail| tail.span);
+            let ret = self.current.ret.take();
+            self.current.ret = self.merge(ret, tail, span);
+        } else {
+            self.current.ret = Some(self.ret.clone());
+        }
+
+        self.ret = outer_ret;
+        std::mem::replace(&mut self.current, outer)
+    }
+
+    /// what's known of a value which may be either of two, reporting a conflict at `span`
+    /// where they're known to differ
+    fn merge(&mut self, a: Option<Ty>, b: Ty, span: Span) -> Option<Ty> {
+        match a {
+            _ if !is_known(self.types, &b) => a,
+            Some(a) if a != b => {
+                self.current.conflicts.push((span, a.clone(), b));
+                Some(a)
+            }
+            _ => Some(b),
+        }
+    }
+
+    /// the type of the block's value
+    fn block(&mut self, block: &Block) -> Ty {
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+        }
+        match &block.tail {
+            Some(tail) => self.expr(tail),
+            None => Ty::Void,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { .. }
+            | Stmt::Stub { .. }
+            | Stmt::Type { .. }
+            | Stmt::Label { .. }
+            | Stmt::Goto { .. }
+            | Stmt::Return { expr: None } => {}
+            Stmt::Loop { block } => {
+                self.block(block);
+            }
+            Stmt::Return { expr: Some(expr) } => {
+                let ty = self.expr(expr);
+                if is_generic(self.types, &self.ret) {
+                    let ret = self.current.ret.take();
+                    self.current.ret = self.merge(ret, ty, expr.span);
+                }
+            }
+            Stmt::VarDec {
+                name,
+                initialiser: value,
+            }
+            | Stmt::VarRes {
+                name,
+                updated: value,
+            } => {
+                let ty = self.expr(value);
+                let id = self
+                    .res
+                    .def_at(name.span)
+                    .expect("mono: unresolved variable");
+                if self
+                    .types
+                    .locals
+                    .get(&id)
+                    .is_some_and(|local| is_generic(self.types, local))
+                {
+                    let known = self.current.locals.remove(&id);
+                    if let Some(ty) = self.merge(known, ty, value.span) {
+                        self.current.locals.insert(id, ty);
+                    }
+                }
+            }
+            Stmt::Discard { expr } => {
+                self.expr(expr);
+            }
+        }
+    }
+
+    /// the type of `expr` - what it is here, where it's generic & that's known
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Ty {
+        let declared = self.types.expr(expr.span).cloned().unwrap_or(Ty::Error);
+        let generic = is_generic(self.types, &declared);
+        let found = match &expr.spanned {
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::ObjMember(..) => None,
+            Expr::Variable(_) => match self.res.get(expr.span) {
+                Some(Res::Def(id)) => self.current.locals.get(id).cloned(),
+                _ => None,
+            },
+            Expr::UnaryNot(inner) | Expr::UnaryNeg(inner) | Expr::UnaryPos(inner) => {
+                self.expr(inner);
+                None
+            }
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => Some(self.expr(inner)),
+            Expr::Referal(inner) => Some(Ty::Pointer(Box::new(self.expr(inner)))),
+            Expr::Indir(inner) => match self.expr(inner) {
+                Ty::Pointer(to) => Some(*to),
+                _ => None,
+            },
+            Expr::BiOp(left, _, right) => {
+                self.expr(left);
+                self.expr(right);
+                None
+            }
+            Expr::FnCall(_, args) => {
+                let args = args.iter().map(|arg| self.expr(arg)).collect::<Vec<_>>();
+                self.call(expr.span, &args)
+            }
+            Expr::Block(block) => Some(self.block(block)),
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.expr(condition);
+                let ty = self.block(&then.spanned);
+                let mut known = None;
+                if generic {
+                    known = self.merge(known, ty, then.span);
+                }
+                for (condition, block) in elifs {
+                    self.expr(condition);
+                    let ty = self.block(&block.spanned);
+                    if generic {
+                        known = self.merge(known, ty, block.span);
+                    }
+                }
+                if let Some(r#else) = r#else {
+                    let ty = self.block(&r#else.spanned);
+                    if generic {
+                        known = self.merge(known, ty, r#else.span);
+                    }
+                }
+                known
+            }
+            Expr::StructLit { fields, .. } => {
+                match fields {
+                    StructLitFields::Positional(values) => {
+                        for value in values {
+                            self.expr(value);
+                        }
+                    }
+                    StructLitFields::Named(values) => {
+                        for (_, value) in values {
+                            self.expr(value);
+                        }
+                    }
+                }
+                None
+            }
+        };
+        match found {
+            Some(found) if generic && is_known(self.types, &found) => {
+                self.current.exprs.insert(expr.span, found.clone());
+                found
+            }
+            _ => declared,
+        }
+    }
+
+    /// what a call returns, if it's known
+    fn call(&mut self, span: Span, args: &[Ty]) -> Option<Ty> {
+        if self.types.dispatch(span).is_some() {
+            return None;
+        }
+        let function = self.types.call(span)?;
+        if Intrinsic::of(self.res, self.types, function).is_some() {
+            return None;
+        }
+        let (function, params) = self.target(function, args)?;
+        match self.is_generic(function) {
+            true => self.copy(function, &params).ret.clone(),
+            false => Some(self.types.sigs[&function].ret.clone()),
+        }
+    }
+}
+
+/*
+ * copies of the IR's functions
+ */
+
+/// the copies made of a lowered module's functions, by what they're copies of & the types of
+/// their parameters
+struct Copies<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    target: &'a str,
+    originals: Vec<Function>,
+    by_id: HashMap<DefId, u32>,
+    copies: Vec<(u32, Vec<Ty>)>,
+    index: HashMap<(u32, Vec<Ty>), u32>,
+    /// what each copy of a generic function returns, as far as it's known
+    rets: HashMap<(u32, Vec<Ty>), Option<Ty>>,
+    names: HashSet<String>,
+}
+
+/// the types of a copy's values, what it returns, & the values which would have to be two
+/// types at once (with both of them)
+type Types_ = (Vec<Ty>, Option<Ty>, Vec<(Ty, Ty)>);
+
+impl Copies<'_> {
+    fn unsupported(&mut self, what: String, function: &Function) {
+        let err = EmitErrors::Unsupported {
+            what,
+            target: self.target.to_owned(),
+        };
+        let span = function
+            .id
+            .map(|id| self.res.def(id).span)
+            .unwrap_or_default();
+        self.state.errors.push(SdwErr::from_pos(err, span));
+    }
+
+    fn is_generic(&self, original: u32) -> bool {
+        let function = &self.originals[original as usize];
+        is_generic_fn(self.types, &function.params, &function.ret)
+    }
+
+    /// the index of the copy of `original` taking `params`, which is made if it hasn't been
+    fn copy(&mut self, original: u32, params: Vec<Ty>) -> u32 {
+        let key = (original, params);
+        if let Some(index) = self.index.get(&key) {
+            return *index;
+        }
+        let index = self.copies.len() as u32;
+        self.index.insert(key.clone(), index);
+        self.copies.push(key);
+        index
+    }
+
+    /// what the call `value` calls - the original, & the types of its copy's parameters.
+    /// `None` if it isn't a call, or no implementation of the stub fits
+    fn callee(&self, function: &Function, tys: &[Ty], value: Value) -> Option<(u32, Vec<Ty>)> {
+        let (original, args) = match function.inst(value) {
+            Inst::Call { function, args } => (*function, args),
+            Inst::CallInterface { stub, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| tys[arg.0 as usize].clone())
+                    .collect::<Vec<_>>();
+                let id = implementation(self.res, self.types, *stub, &args)?;
+                let original = self.by_id[&id];
+                let params = params(self.types, &self.originals[original as usize].params, &args);
+                return Some((original, params));
+            }
+            _ => return None,
+        };
+        let args = args
+            .iter()
+            .map(|arg| tys[arg.0 as usize].clone())
+            .collect::<Vec<_>>();
+        let params = params(self.types, &self.originals[original as usize].params, &args);
+        Some((original, params))
+    }
+
+    /// what the copy of `original` taking `params` returns, if it's known
+    fn ret(&mut self, original: u32, params: Vec<Ty>) -> Option<Ty> {
+        if !self.is_generic(original) {
+            return Some(self.originals[original as usize].ret.clone());
+        }
+        let key = (original, params);
+        if let Some(ret) = self.rets.get(&key) {
+            return ret.clone();
+        }
+        // a recursive call sees what's been worked out so far, so it's worked out again until
+        // that stops changing
+        self.rets.insert(key.clone(), None);
+        loop {
+            let (_, ret, _) = self.infer(original, &key.1);
+            if self.rets[&key] == ret {
+                return ret;
+            }
+            self.rets.insert(key.clone(), ret);
+        }
+    }
+
+    /// works out what each generic value is, in the copy of `original` taking `params`: from
+    /// the parameters & what calls return, through phis & variables kept in memory
+    fn infer(&mut self, original: u32, params: &[Ty]) -> Types_ {
+        let function = self.originals[original as usize].clone();
+        let mut tys = function.tys.clone();
+        let mut conflicts = Vec::new();
+        let mut values = function
+            .blocks
+            .iter()
+            .flat_map(|block| block.insts.iter().copied())
+            .collect::<Vec<_>>();
+        values.sort();
+
+        loop {
+            let mut changed = false;
+            for value in &values {
+                let index = value.0 as usize;
+                let found = match function.inst(*value) {
+                    _ if !is_generic(self.types, &function.tys[index]) => continue,
+                    Inst::Param(param) => vec![params[*param as usize].clone()],
+                    Inst::Call { .. } | Inst::CallInterface { .. } => {
+                        let ret = self
+                            .callee(&function, &tys, *value)
+                            .and_then(|(original, params)| self.ret(original, params));
+                        ret.into_iter().collect()
+                    }
+                    Inst::Phi(incoming) => incoming
+                        .iter()
+                        .map(|(_, value)| tys[value.0 as usize].clone())
+                        .collect(),
+                    Inst::Load(pointer) => match &tys[pointer.0 as usize] {
+                        Ty::Pointer(to) => vec![(**to).clone()],
+                        _ => vec![],
+                    },
+                    // a slot is whatever's stored in it
+                    Inst::Alloca => values
+                        .iter()
+                        .filter_map(|store| match function.inst(*store) {
+                            Inst::Store {
+                                pointer,
+                                value: stored,
+                            } if pointer == value => {
+                                Some(Ty::Pointer(Box::new(tys[stored.0 as usize].clone())))
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                for found in found {
+                    if !is_known(self.types, &found) {
+                        continue;
+                    }
+                    if !is_known(self.types, &tys[index]) {
+                        tys[index] = found;
+                        changed = true;
+                    } else if tys[index] != found {
+                        let both = (tys[index].clone(), found);
+                        if !conflicts.contains(&both) {
+                            conflicts.push(both);
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let ret = if is_generic(self.types, &function.ret) {
+            let mut ret: Option<Ty> = None;
+            for block in &function.blocks {
+                let Terminator::Return(value) = block.term else {
+                    continue;
+                };
+                let ty = &tys[value.0 as usize];
+                match &ret {
+                    _ if !is_known(self.types, ty) => {}
+                    Some(known) if known != ty => conflicts.push((known.clone(), ty.clone())),
+                    _ => ret = Some(ty.clone()),
+                }
+            }
+            ret
+        } else {
+            Some(function.ret.clone())
+        };
+        (tys, ret, conflicts)
+    }
+
+    /// the copy of `original` taking `params`, with calls & pointers referring to copies
+    fn instantiate(&mut self, original: u32, params: Vec<Ty>) -> Function {
+        let (tys, ret, conflicts) = self.infer(original, &params);
+        let mut function = self.originals[original as usize].clone();
+        for (a, b) in conflicts {
+            let what = conflict(self.res, &a, &b);
+            self.unsupported(what, &function);
+        }
+        function.tys = tys;
+        function.params = params;
+        if let Some(ret) = ret {
+            function.ret = ret;
+        }
+
+        let mut blocks = std::mem::take(&mut function.blocks);
+        for block in &mut blocks {
+            let mut insts = Vec::new();
+            for value in std::mem::take(&mut block.insts) {
+                let inst = match function.inst(value).clone() {
+                    Inst::Fn(original) if self.is_generic(original) => {
+                        let what = "a pointer to a generic function".to_owned();
+                        self.unsupported(what, &function);
+                        Inst::Undef
+                    }
+                    Inst::Fn(original) => {
+                        let params = self.originals[original as usize].params.clone();
+                        Inst::Fn(self.copy(original, params))
+                    }
+                    Inst::Call { args, .. } | Inst::CallInterface { args, .. } => {
+                        match self.callee(&function, &function.tys, value) {
+                            Some((original, params)) => {
+                                // an implementation may take a union its argument coerces to
+                                let args = args
+                                    .into_iter()
+                                    .zip(&params)
+                                    .map(|(arg, param)| {
+                                        coerce(&mut function, &mut insts, arg, param, self.types)
+                                    })
+                                    .collect();
+                                Inst::Call {
+                                    function: self.copy(original, params),
+                                    args,
+                                }
+                            }
+                            // left to fail at runtime, as the interpreter does
+                            None => function.inst(value).clone(),
+                        }
+                    }
+                    inst => inst,
+                };
+                function.insts[value.0 as usize] = inst;
+                insts.push(value);
+            }
+            block.insts = insts;
+        }
+        function.blocks = blocks;
+        function
+    }
+
+    /// a name for a copy of a generic function, from the types of its parameters
+    fn name(&mut self, function: &Function) -> String {
+        let mut name = format!("{}__", function.name);
+        for param in &function.params {
+            mangle(self.res, param, &mut name);
+        }
+        let mut unique = name.clone();
+        let mut n = 1;
+        while !self.names.insert(unique.clone()) {
+            unique = format!("{name}.{n}");
+            n += 1;
+        }
+        unique
+    }
+}
+
+/// `arg`, as a member of `to` if that's a union it isn't already - added to `insts`
+fn coerce(
+    function: &mut Function,
+    insts: &mut Vec<Value>,
+    arg: Value,
+    to: &Ty,
+    types: &Types,
+) -> Value {
+    let Ty::Named(union) = to else {
+        return arg;
+    };
+    let from = function.ty(arg);
+    match types.discriminant(*union, from).filter(|_| from != to) {
+        Some(tag) => {
+            function.insts.push(Inst::Union {
+                tag: tag as u32,
+                value: arg,
+            });
+            function.tys.push(to.clone());
+            let value = Value(function.insts.len() as u32 - 1);
+            insts.push(value);
+            value
+        }
+        None => arg,
+    }
+}
+
+/// replaces each generic function in a lowered module with a copy for each set of types it's
+/// called with, & each call of an interface stub with a call of the implementation which fits
+/// its arguments. calls which no implementation fits are left to fail at runtime, & what
+/// can't be copied (pointers to generic functions, values which would be two types at once)
+/// raises errors in `state`, as being unsupported by `target`.
+pub fn monomorphise(state: &mut State, module: &mut Module, checked: &Checked, target: &str) {
+    let originals = std::mem::take(&mut module.functions);
+    let names = originals
+        .iter()
+        .map(|function| function.name.clone())
+        .collect();
+    let by_id = originals
+        .iter()
+        .enumerate()
+        .filter_map(|(index, function)| Some((function.id?, index as u32)))
+        .collect();
+    let mut copies = Copies {
+        state,
+        res: &checked.res,
+        types: &checked.types,
+        target,
+        originals,
+        by_id,
+        copies: Vec::new(),
+        index: HashMap::new(),
+        rets: HashMap::new(),
+        names,
+    };
+    for original in 0..copies.originals.len() as u32 {
+        if !copies.is_generic(original) {
+            let params = copies.originals[original as usize].params.clone();
+            copies.copy(original, params);
+        }
+    }
+    // copying one function may call for more
+    let mut next = 0;
+    while let Some((original, params)) = copies.copies.get(next).cloned() {
+        let generic = copies.is_generic(original);
+        let mut function = copies.instantiate(original, params);
+        if generic {
+            function.name = copies.name(&function);
+        }
+        module.functions.push(function);
+        next += 1;
+    }
+    module.main = module.main.map(|main| {
+        let params = copies.originals[main as usize].params.clone();
+        copies.index[&(main, params)]
+    });
+}
@@ -0,0 +1,245 @@
+use crate::driver;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// how closely a parameter's type fits an argument, from least to most specific
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Fit {
+    /// the parameter is a generic, which anything may stand in for
+    Generic,
+    /// the parameter is an aggregate of generics (`type DoType Debug + Print;`)
+    Aggregate,
+    /// the argument is a variant of the parameter's union
+    Union,
+    Exact,
+}
+
+fn fit(types: &Types, arg: &Ty, param: &Ty) -> Option<Fit> {
+    if arg == param || matches!(arg, Ty::Never | Ty::Error) {
+        return Some(Fit::Exact);
+    }
+    if !types.coerces(arg, param) {
+        return None;
+    }
+
+    Some(match param {
+        Ty::Named(id) => match types.decls.get(id) {
+            Some(TypeDecl::Generic) => Fit::Generic,
+            Some(TypeDecl::Aggregate(_)) => Fit::Aggregate,
+            Some(TypeDecl::Union(_)) => Fit::Union,
+            _ => Fit::Exact,
+        },
+        _ => Fit::Exact,
+    })
+}
+
+pub enum Pick {
+    Found(DefId),
+    /// several candidates fit, and none is more specific than the rest
+    Ambiguous(Vec<DefId>),
+    NoMatch,
+}
+
+/// picks the most specific of `candidates` for arguments of the types `args`.
+///
+/// a candidate is more specific than another if each of its parameters fits its argument at
+/// least as closely, and one fits strictly more closely. when a `fn` and a stub share a
+/// signature, the `fn` is the stub's implementation, and so is picked - & the file's
+/// declarations are picked over `core`'s which fit as closely.
+pub fn pick(types: &Types, res: &Resolutions, args: &[Ty], candidates: &[DefId]) -> Pick {
+    let viable = candidates
+        .iter()
+        .filter_map(|id| {
+            let sig = types.sigs.get(id)?;
+            if sig.params.len() != args.len() {
+                return None;
+            }
+            let fits = args
+                .iter()
+                .zip(&sig.params)
+                .map(|(arg, param)| fit(types, arg, param))
+                .collect::<Option<Vec<_>>>()?;
+            Some((*id, fits))
+        })
+        .collect::<Vec<_>>();
+
+    let dominates = |a: &[Fit], b: &[Fit]| {
+        a.iter().zip(b).all(|(a, b)| a >= b) && a.iter().zip(b).any(|(a, b)| a > b)
+    };
+    let mut best = viable
+        .iter()
+        .filter(|(_, fits)| !viable.iter().any(|(_, other)| dominates(other, fits)))
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    // the file's declarations shadow `core`'s, where they fit as well
+    if best.iter().any(|id| !driver::in_core(res.def(*id).span)) {
+        best.retain(|id| !driver::in_core(res.def(*id).span));
+    }
+
+    match best.as_slice() {
+        [] => Pick::NoMatch,
+        [only] => Pick::Found(*only),
+        _ => {
+            let params = |id: &DefId| &types.sigs[id].params;
+            let same_sig = best.iter().all(|id| params(id) == params(&best[0]));
+            let fns = best
+                .iter()
+                .filter(|id| res.def(**id).kind == DefKind::Fn)
+                .collect::<Vec<_>>();
+            match fns.as_slice() {
+                [implementation] if same_sig => Pick::Found(**implementation),
+                _ => Pick::Ambiguous(best),
+            }
+        }
+    }
+}
+
+/// a call with a union argument, but no overload taking the union itself. it's lowered to a
+/// match on the argument's discriminant, calling the overload for whichever variant it holds -
+/// or matching on another union argument, if no overload takes that variant as it is.
+#[derive(Debug, Clone)]
+pub struct Dispatch {
+    /// the index of the argument matched on
+    pub arg: usize,
+    pub union: DefId,
+    /// what's done for each of the union's variants, by discriminant
+    pub arms: Vec<Arm>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Arm {
+    Call(DefId),
+    /// a nested match, on a later argument
+    Dispatch(Dispatch),
+}
+
+impl Dispatch {
+    /// every overload which may be called
+    pub fn targets(&self) -> Vec<DefId> {
+        self.arms
+            .iter()
+            .flat_map(|arm| match arm {
+                Arm::Call(target) => vec![*target],
+                Arm::Dispatch(dispatch) => dispatch.targets(),
+            })
+            .collect()
+    }
+}
+
+pub enum Dispatched {
+    Found(Dispatch),
+    /// the argument types - with a variant in place of each union matched on - no overload
+    /// takes, for each combination of variants missing one
+    Missing(Vec<Vec<Ty>>),
+}
+
+/// tries dispatching on each union argument in turn, for a call `pick` found no match for. a
+/// variant no overload takes as it is may be dispatched on again, by another union argument.
+pub fn dispatch(
+    types: &Types,
+    res: &Resolutions,
+    args: &[Ty],
+    candidates: &[DefId],
+) -> Option<Dispatched> {
+    let mut first_missing = None;
+    for (arg, ty) in args.iter().enumerate() {
+        let Ty::Named(union) = ty else {
+            continue;
+        };
+        let Some(TypeDecl::Union(variants)) = types.decls.get(union) else {
+            continue;
+        };
+
+        let mut arms = Vec::new();
+        let mut missing = Vec::new();
+        for (_, variant) in variants {
+            let mut args = args.to_vec();
+            args[arg] = variant.clone();
+            match pick(types, res, &args, candidates) {
+                Pick::Found(target) => arms.push(Arm::Call(target)),
+                Pick::Ambiguous(_) => missing.push(args),
+                Pick::NoMatch => match dispatch(types, res, &args, candidates) {
+                    Some(Dispatched::Found(dispatch)) => arms.push(Arm::Dispatch(dispatch)),
+                    Some(Dispatched::Missing(combinations)) => missing.extend(combinations),
+                    None => missing.push(args),
+                },
+            }
+        }
+
+        if missing.is_empty() {
+            return Some(Dispatched::Found(Dispatch {
+                arg,
+                union: *union,
+                arms,
+            }));
+        }
+        first_missing.get_or_insert(Dispatched::Missing(missing));
+    }
+    first_missing
+}
+
+/// `fn void print(Test)`, for listing candidates in diagnostics
+pub fn describe(types: &Types, res: &Resolutions, id: DefId) -> String {
+    let def = res.def(id);
+    match types.sigs.get(&id) {
+        Some(sig) => format!(
+            "fn {} {}({})",
+            sig.ret.display(res),
+            def.name,
+            sig.params
+                .iter()
+                .map(|param| param.display(res))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => format!("fn {}", def.name),
+    }
+}
+
+/// reports overloads which can never be told apart: two `fn`s (or two stubs) of the same
+/// name, declared in the same block, taking the same parameter types.
+pub fn check(state: &mut State, root: &Block, res: &Resolutions, types: &Types) {
+    let mut by_name: HashMap<&str, Vec<DefId>> = HashMap::new();
+    for stmt in &root.stmts {
+        let name = match &stmt.spanned {
+            Stmt::Fn { name, body, .. } => {
+                check(state, body, res, types);
+                name
+            }
+            Stmt::Stub { name, .. } => name,
+            _ => continue,
+        };
+        if let Some(id) = res.def_at(name.span) {
+            by_name.entry(&name.spanned).or_default().push(id);
+        }
+    }
+
+    let mut names = by_name.into_iter().collect::<Vec<_>>();
+    names.sort_by_key(|(_, ids)| ids[0].0);
+    for (_, overloads) in names {
+        for (idx, id) in overloads.iter().enumerate() {
+            let Some(sig) = types.sigs.get(id) else {
+                continue;
+            };
+            let kind = res.def(*id).kind;
+            let in_core = driver::in_core(res.def(*id).span);
+            let conflict = overloads[..idx].iter().find(|prev| {
+                res.def(**prev).kind == kind
+                    && driver::in_core(res.def(**prev).span) == in_core
+                    && types
+                        .sigs
+                        .get(prev)
+                        .is_some_and(|prev| prev.params == sig.params)
+            });
+
+            if let Some(prev) = conflict {
+                let err = SdwErr::from_pos(
+                    TypeErrors::ConflictingOverloads(describe(types, res, *id)),
+                    res.def(*id).span,
+                )
+                .with_note("conflicts with this declaration", res.def(*prev).span);
+                state.errors.push(err);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::consteval;
+use crate::cst::{Event, NodeKind};
 use crate::prelude::*;
 
 macro_rules! attempt {
@@ -34,7 +36,7 @@ type Idn = String;
 type GlobIdn = Vec<String>;
 type Type = String;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PrimType {
     Int,
     Unt,
@@ -43,9 +45,27 @@ pub enum PrimType {
     String,
 }
 
+impl PrimType {
+    /// the primitive `name` names, if it names one
+    pub fn named(name: &str) -> Option<PrimType> {
+        Some(match name {
+            "int" => PrimType::Int,
+            "unt" => PrimType::Unt,
+            "float" => PrimType::Float,
+            "bool" => PrimType::Bool,
+            "string" => PrimType::String,
+            _ => return None,
+        })
+    }
+}
+
 // TODO: unspan these
 #[derive(Debug)]
 pub enum Bound {
+    /// `type Print;` - declares a generic, which any type may stand in for
+    Generic,
+    /// `type DoType Debug + Print;` - compatible with each of the listed generics
+    Aggregate(Vec<Spanned<Idn>>),
     Prim(Spanned<PrimType>),
     Struct(Option<Vec<(Spanned<Bound>, Spanned<Idn>)>>),
     Union(Option<Vec<(Spanned<Bound>, Spanned<Idn>)>>),
@@ -62,6 +82,7 @@ type ExprSelf = Box<Spanned<Expr>>;
 pub enum Expr {
     IntLiteral(i64),
     BoolLiteral(bool),
+    StrLiteral(String),
     Variable(String),
     UnaryNot(ExprSelf),
     UnaryNeg(ExprSelf),
@@ -78,6 +99,44 @@ pub enum Expr {
         elifs: Vec<(ExprSelf, Spanned<Block>)>,
         r#else: Option<Spanned<Block>>,
     },
+    Block(Box<Block>),
+    StructLit {
+        ty: Spanned<Type>,
+        fields: StructLitFields,
+    },
+    /// `#[ num_in_range 0 100 ] expr` - binds looser than any operator
+    Attributed {
+        attr: Spanned<Attribute>,
+        expr: ExprSelf,
+    },
+}
+
+/// `Some { 10 }` is positional, `Col { .r = 1, .g = 2, .b = 3 }` is named.
+/// an empty literal (`Done {}`) is positional with no fields.
+#[derive(Debug)]
+pub enum StructLitFields {
+    Positional(Vec<ExprSelf>),
+    Named(Vec<(Spanned<Idn>, ExprSelf)>),
+}
+
+/// an ineffectual directive - `#[ must_be_read ]`, `#[ num_in_range 0 100 ]`.
+/// these never change what a program means, but checks may act upon them.
+#[derive(Debug)]
+pub struct Attribute {
+    pub name: Spanned<Idn>,
+    pub args: Vec<Spanned<AttrArg>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrArg {
+    Int(i64),
+    Bool(bool),
+    Idn(Idn),
+}
+
+/// whether any of `attrs` is named `name`
+pub fn has_attr(attrs: &[Spanned<Attribute>], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.spanned.name.spanned == name)
 }
 
 #[derive(Debug)]
@@ -98,7 +157,7 @@ impl From<Vec<Spanned<Stmt>>> for Block {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BiOps {
     Add,
     Sub,
@@ -125,12 +184,14 @@ pub enum BiOps {
 #[derive(Debug)]
 pub enum Stmt {
     Fn {
+        attrs: Vec<Spanned<Attribute>>,
         return_type: Spanned<Type>,
         name: Spanned<Idn>,
         parameters: Vec<(Spanned<Type>, Spanned<Idn>)>,
         body: Box<Block>,
     },
     Stub {
+        attrs: Vec<Spanned<Attribute>>,
         return_type: Spanned<Type>,
         name: Spanned<Idn>,
         parameters: Vec<Spanned<Type>>,
@@ -156,11 +217,12 @@ pub enum Stmt {
         updated: Spanned<Expr>,
     },
     Type {
+        attrs: Vec<Spanned<Attribute>>,
         name: Spanned<Idn>,
         bound: Spanned<Bound>,
     },
     Discard {
-        expr: Expr,
+        expr: Spanned<Expr>,
     },
 }
 
@@ -177,6 +239,11 @@ struct Parser<'a> {
     lexemes: Vec<Lexeme>,
     state: &'a mut State,
     last_span: Span,
+    /// set whilst parsing an `if` condition, where `cond {` must open the body
+    /// rather than a struct literal (same restriction rust uses)
+    no_struct_lit: bool,
+    /// in lossless mode, the structure of what's been parsed, for `cst` to build a tree of
+    events: Option<Vec<Event>>,
 }
 
 impl<'a> Parser<'a> {
@@ -185,6 +252,8 @@ impl<'a> Parser<'a> {
             lexemes,
             state,
             last_span: Span::default(),
+            no_struct_lit: false,
+            events: None,
         }
     }
 
@@ -210,9 +279,43 @@ impl<'a> Parser<'a> {
 
         let next = self.lexemes.remove(0);
         self.last_span = next.span;
+        if let Some(events) = &mut self.events {
+            events.push(Event::Token);
+        }
         Ok(next)
     }
 
+    /// where a node would start, were it to be closed
+    fn mark(&self) -> usize {
+        self.events.as_ref().map_or(0, Vec::len)
+    }
+
+    /// wraps everything consumed since `at` in a node. nodes are only ever closed once they
+    /// parse, so bailing out early never leaves one half open.
+    fn close(&mut self, at: usize, kind: NodeKind) {
+        if let Some(events) = &mut self.events {
+            events.insert(at, Event::Start(kind));
+            events.push(Event::Finish);
+        }
+    }
+
+    /// runs `parse`, wrapping what it consumes in a node of the kind `kind` gives its result
+    /// (or an `Error` node, if it failed)
+    fn node<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Return<T>,
+        kind: impl FnOnce(&T) -> NodeKind,
+    ) -> Return<T> {
+        let at = self.mark();
+        let result = parse(self)?;
+        let kind = match &result {
+            Success(node) => kind(&node.spanned),
+            Fail => NodeKind::Error,
+        };
+        self.close(at, kind);
+        Ok(result)
+    }
+
     /// never consumes
     fn peek(&self) -> Result<Lexeme> {
         self._tk_empty()?;
@@ -244,23 +347,298 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// like `peek`, but `n` lexemes ahead & without erroring
+    fn peek_nth(&self, n: usize) -> Option<&LexemeType> {
+        self.lexemes.get(n).map(|lexeme| &lexeme.spanned)
+    }
+
+    /// run `f` with struct literals allowed (or not), restoring the previous setting after
+    fn struct_lits<T>(&mut self, allowed: bool, f: impl FnOnce(&mut Self) -> T) -> T {
+        let before = std::mem::replace(&mut self.no_struct_lit, !allowed);
+        let result = f(self);
+        self.no_struct_lit = before;
+        result
+    }
+
     fn parse(&mut self) -> Result<Block> {
         let mut stmts = Vec::new();
+        let mut tail = None;
         loop {
             // escaping this way feels camp, but i think it's reasonable
             // * as it stands * `{}` aren't overloaded beyond block scope delimters, so
             // this is a reasonable assumption??
+            // (struct literals use braces too, but they are consumed whole by `parse_expr`)
             if self.done() || self.peek()?.spanned == LexemeType::RBrace {
                 break;
             }
 
-            match self.parse_stmt()? {
-                Success(leaf) => stmts.push(leaf),
+            if self.peek_nth(0) == Some(&LexemeType::Hash)
+                && self.peek_nth(1) == Some(&LexemeType::If)
+            {
+                let conditional = self.node(Self::parse_conditional, |_| NodeKind::Conditional)?;
+                let Success(chosen) = conditional else {
+                    continue;
+                };
+                stmts.extend(chosen.spanned.stmts);
+                if let Some(value) = chosen.spanned.tail {
+                    // the chosen group's value is only the block's if nothing follows it
+                    if self.done() || self.peek()?.spanned == LexemeType::RBrace {
+                        tail = Some(value);
+                        break;
+                    }
+                    self.state
+                        .errors
+                        .push(SdwErr::from_pos(ParseErrors::StmtsEndWithSemi, value.span));
+                }
+                continue;
+            }
+
+            if self.peek_nth(0) == Some(&LexemeType::Hash)
+                && self.peek_nth(1) == Some(&LexemeType::Else)
+            {
+                // the group is parsed (so any errors within are still found) & thrown away
+                let at = self.mark();
+                let start = self.next_span()?;
+                self.next()?;
+                self.next()?;
+                let span = Span::from_to(start, self.last_span);
+                self.state
+                    .errors
+                    .push(SdwErr::from_pos(ParseErrors::ElseWithoutIf, span));
+                if let Success(_) = self.expect(LexemeType::If)? {
+                    self.struct_lits(false, |parser| parser.parse_expr())?;
+                }
+                self.parse_cond_block()?;
+                self.close(at, NodeKind::Error);
+                continue;
+            }
+
+            // attributes on an expression are parsed with it, below
+            if self.peek_nth(0) == Some(&LexemeType::Hash)
+                && self.peek_nth(1) == Some(&LexemeType::LBrack)
+                && !self.attributes_expr()
+            {
+                match self.node(Self::parse_attributed, NodeKind::stmt)? {
+                    Success(leaf) => stmts.push(leaf),
+                    Fail => continue,
+                }
+                continue;
+            }
+
+            if self.starts_stmt() {
+                match self.node(Self::parse_stmt, NodeKind::stmt)? {
+                    Success(leaf) => stmts.push(leaf),
+                    Fail => continue,
+                }
+                continue;
+            }
+
+            // an expression is either discarded (`expr;`) or is the block's tail value
+            let at = self.mark();
+            let expr = match self.struct_lits(true, |parser| parser.parse_expr())? {
+                Success(expr) => expr,
                 Fail => continue,
+            };
+
+            if self.done() || self.peek()?.spanned == LexemeType::RBrace {
+                tail = Some(Box::new(expr));
+                break;
+            }
+
+            let end = self.next_span()?;
+            if let Fail = self.expect(LexemeType::Semi)? {
+                self.state
+                    .errors
+                    .push(SdwErr::from_pos(ParseErrors::StmtsEndWithSemi, expr.span));
+                continue;
+            }
+            let span = Span::from_to(expr.span, end);
+            self.close(at, NodeKind::Discard);
+            stmts.push(Spanned::new(Stmt::Discard { expr }, span));
+        }
+
+        Ok(Block { stmts, tail })
+    }
+
+    /// the whole file, which has no braces around it - so a `}` in it closes nothing
+    fn parse_root(&mut self) -> Result<Block> {
+        let mut block = self.parse()?;
+        while !self.done() {
+            let at = self.mark();
+            let brace = self.next()?;
+            self.close(at, NodeKind::Error);
+            self.state
+                .errors
+                .push(SdwErr::from_pos(ParseErrors::UnmatchedBrace, brace.span));
+            // (the rest is still parsed, for its errors)
+            let rest = self.parse()?;
+            block.stmts.extend(rest.stmts);
+            block.tail = rest.tail;
+        }
+        Ok(block)
+    }
+
+    /// `#[ name arg* ]`
+    fn parse_attribute(&mut self) -> Return<Attribute> {
+        let start = self.next_span()?;
+        attempt!(
+            self,
+            self.expect(LexemeType::Hash)?,
+            ParseErrors::AttributeNotOpened
+        );
+        attempt!(
+            self,
+            self.expect(LexemeType::LBrack)?,
+            ParseErrors::AttributeNotOpened
+        );
+        let name = attempt!(self, self.consume_idn()?, ParseErrors::AttributeName);
+
+        let mut args = Vec::new();
+        while let Fail = self.expect(LexemeType::RBrack)? {
+            let next = self.next()?;
+            let arg = match next.spanned {
+                LexemeType::Intlit(int) => Spanned::new(AttrArg::Int(int), next.span),
+                LexemeType::Dash => match self.next()?.spanned {
+                    LexemeType::Intlit(int) => {
+                        Spanned::new(AttrArg::Int(-int), Span::from_to(next.span, self.last_span))
+                    }
+                    _ => attempt!(self, Fail, ParseErrors::AttributeArg),
+                },
+                LexemeType::BoolLit(bool) => Spanned::new(AttrArg::Bool(bool), next.span),
+                LexemeType::Idn(idn) => Spanned::new(AttrArg::Idn(idn), next.span),
+                _ => {
+                    // skip the rest of the attribute, so the declaration after can still parse
+                    self.state
+                        .errors
+                        .push(SdwErr::from_pos(ParseErrors::AttributeArg, next.span));
+                    while !self.done() && self.next()?.spanned != LexemeType::RBrack {}
+                    return Ok(Fail);
+                }
+            };
+            args.push(arg);
+        }
+
+        let span = Span::from_to(start, self.last_span);
+        Ok(Success(Spanned::new(Attribute { name, args }, span)))
+    }
+
+    /// #if [cond] { [..] } [#else if [cond] { [..] }]?* [#else { [..] }]?
+    ///
+    /// conditional compilation: gives the contents of the first group whose (constant)
+    /// condition holds, or an empty block. every group must still parse, but the rest are
+    /// thrown away before names are ever resolved.
+    fn parse_conditional(&mut self) -> Return<Block> {
+        let start = self.next_span()?;
+        let mut chosen = None;
+        let mut first = true;
+        loop {
+            // `#if`, or `#else`
+            self.next()?;
+            self.next()?;
+            let conditional = first || matches!(self.expect(LexemeType::If)?, Success(_));
+            let holds = if conditional {
+                let condition = attempt!(
+                    self,
+                    self.struct_lits(false, |parser| parser.parse_expr())?,
+                    ParseErrors::ExpectedCondition
+                );
+                consteval::condition(self.state, &condition)
+            } else {
+                true
+            };
+            let group = attempt!(self.parse_cond_block()?);
+            if holds && chosen.is_none() {
+                chosen = Some(group.spanned);
+            }
+
+            let more = self.peek_nth(0) == Some(&LexemeType::Hash)
+                && self.peek_nth(1) == Some(&LexemeType::Else);
+            if !conditional || !more {
+                break;
+            }
+            first = false;
+        }
+
+        let span = Span::from_to(start, self.last_span);
+        let chosen = chosen.unwrap_or_else(|| Vec::new().into());
+        Ok(Success(Spanned::new(chosen, span)))
+    }
+
+    /// one or more attributes, followed by the declaration they apply to
+    fn parse_attributed(&mut self) -> Return<Stmt> {
+        let mut attributes = Vec::new();
+        while self.peek_nth(0) == Some(&LexemeType::Hash) {
+            let attribute = self.node(Self::parse_attribute, |_| NodeKind::Attribute)?;
+            attributes.push(attempt!(attribute));
+        }
+
+        if !self.starts_stmt() {
+            // (leaving what follows to be parsed on its own)
+            let span = Span::from_to(attributes[0].span, attributes[attributes.len() - 1].span);
+            self.state
+                .errors
+                .push(SdwErr::from_pos(ParseErrors::MisplacedAttribute, span));
+            return Ok(Fail);
+        }
+        let mut stmt = attempt!(self.parse_stmt()?);
+        match &mut stmt.spanned {
+            Stmt::Fn { attrs, .. } | Stmt::Stub { attrs, .. } | Stmt::Type { attrs, .. } => {
+                *attrs = attributes;
+            }
+            _ => {
+                let span = Span::from_to(attributes[0].span, attributes[attributes.len() - 1].span);
+                self.state
+                    .errors
+                    .push(SdwErr::from_pos(ParseErrors::MisplacedAttribute, span));
             }
         }
+        Ok(Success(stmt))
+    }
 
-        Ok(stmts.into())
+    /// whether the upcoming attributes are followed by an expression, which they're then
+    /// part of, rather than by a statement (or nothing)
+    fn attributes_expr(&self) -> bool {
+        let mut at = 0;
+        while self.peek_nth(at) == Some(&LexemeType::Hash)
+            && self.peek_nth(at + 1) == Some(&LexemeType::LBrack)
+        {
+            at += 2;
+            while self
+                .peek_nth(at)
+                .is_some_and(|lexeme| *lexeme != LexemeType::RBrack)
+            {
+                at += 1;
+            }
+            at += 1;
+        }
+        !matches!(self.peek_nth(at), None | Some(LexemeType::RBrace)) && !self.starts_stmt_at(at)
+    }
+
+    /// whether the upcoming lexemes are a statement, rather than an expression
+    fn starts_stmt(&self) -> bool {
+        self.starts_stmt_at(0)
+    }
+
+    /// like `starts_stmt`, but `n` lexemes ahead
+    fn starts_stmt_at(&self, n: usize) -> bool {
+        match self.peek_nth(n) {
+            Some(
+                LexemeType::Fn
+                | LexemeType::Mc
+                | LexemeType::Loop
+                | LexemeType::At
+                | LexemeType::Goto
+                | LexemeType::Return
+                | LexemeType::Let
+                | LexemeType::Type,
+            ) => true,
+            // `foo = ..` is a reassignment, `foo == ..` is an expression
+            Some(LexemeType::Idn(_)) => {
+                self.peek_nth(n + 1) == Some(&LexemeType::Equals)
+                    && self.peek_nth(n + 2) != Some(&LexemeType::Equals)
+            }
+            _ => false,
+        }
     }
 
     fn parse_type(&mut self) -> Return<Type> {
@@ -275,8 +653,13 @@ impl<'a> Parser<'a> {
             LexemeType::Fn => {
                 // fn int addTwo(int arg1, int arg2) { [body] };
                 // ^^ ^^^ ^^^^^^^
-                let return_type =
-                    attempt!(self, self.parse_type()?, ParseErrors::MissingFnReturnType);
+                // interface stubs may leave out the return type (`fn print(Print);`),
+                // in which case it is `void` (spanning the name, so `pretty` can leave it out)
+                let return_type = if self.peek_nth(1) == Some(&LexemeType::LParen) {
+                    Spanned::new("void".to_owned(), self.next_span()?)
+                } else {
+                    attempt!(self, self.parse_type()?, ParseErrors::MissingFnReturnType)
+                };
                 let name = attempt!(self, self.consume_idn()?, ParseErrors::MissingFnIdn);
                 attempt!(
                     self,
@@ -364,6 +747,7 @@ impl<'a> Parser<'a> {
                 //                                   ^ [^^^^] ^
                 let mut body = None;
                 if !stub {
+                    let at = self.mark();
                     attempt!(
                         self,
                         self.expect(LexemeType::LBrace)?,
@@ -375,6 +759,7 @@ impl<'a> Parser<'a> {
                         self.expect(LexemeType::RBrace)?,
                         ParseErrors::BlockNotClosed
                     );
+                    self.close(at, NodeKind::Block);
                 }
 
                 // fn int addTwo(int arg1, int arg2) { [body] };
@@ -390,6 +775,7 @@ impl<'a> Parser<'a> {
                 Success(if stub {
                     Spanned::new(
                         Stmt::Stub {
+                            attrs: Vec::new(),
                             return_type,
                             name,
                             parameters: stub_parameters,
@@ -399,6 +785,7 @@ impl<'a> Parser<'a> {
                 } else {
                     Spanned::new(
                         Stmt::Fn {
+                            attrs: Vec::new(),
                             return_type,
                             name,
                             parameters: body_parameters,
@@ -411,6 +798,7 @@ impl<'a> Parser<'a> {
             }
             LexemeType::Mc => unimplemented!("macros aren't implemented, and won't be for a while"),
             LexemeType::Loop => {
+                let at = self.mark();
                 attempt!(
                     self,
                     self.expect(LexemeType::LBrace)?,
@@ -422,6 +810,7 @@ impl<'a> Parser<'a> {
                     self.expect(LexemeType::RBrace)?,
                     ParseErrors::BlockNotClosed
                 );
+                self.close(at, NodeKind::Block);
                 let end = self.next_span()?;
                 attempt!(
                     self,
@@ -521,7 +910,7 @@ impl<'a> Parser<'a> {
             }
             LexemeType::Type => {
                 let name = attempt!(self, self.consume_idn()?, ParseErrors::NoTypeDecName);
-                let bound = attempt!(self.parse_bound()?);
+                let bound = attempt!(self.parse_decl_bound(name.span)?);
 
                 let end = self.next_span()?;
                 let span = Span::from_to(start, end);
@@ -531,25 +920,57 @@ impl<'a> Parser<'a> {
                     ParseErrors::StmtsEndWithSemi
                 );
 
-                Success(Spanned::new(Stmt::Type { name, bound }, span))
+                Success(Spanned::new(
+                    Stmt::Type {
+                        attrs: Vec::new(),
+                        name,
+                        bound,
+                    },
+                    span,
+                ))
             }
             // TODO: verify correct behaviour.
             _ => Fail,
         })
     }
 
+    /// the bound of a `type` declaration, which (unlike a member's bound) may be absent
+    /// (`type Print;`) or an aggregate of generics (`type DoType Debug + Print;`)
+    fn parse_decl_bound(&mut self, name: Span) -> Return<Bound> {
+        if self.peek()?.spanned == LexemeType::Semi {
+            return Ok(Success(Spanned::new(Bound::Generic, name)));
+        }
+
+        let at = self.mark();
+        let bound = attempt!(self.node(Self::parse_bound, NodeKind::bound)?);
+        if self.peek()?.spanned != LexemeType::Cross {
+            return Ok(Success(bound));
+        }
+
+        let start = bound.span;
+        let first = match bound.spanned {
+            Bound::Alias(first) => first,
+            _ => attempt!(self, Fail, ParseErrors::AggregateNotAlias),
+        };
+        let mut generics = vec![first];
+        while let Success(_) = self.expect(LexemeType::Cross)? {
+            let generic = attempt!(self, self.consume_idn()?, ParseErrors::AggregateNotAlias);
+            generics.push(generic);
+        }
+
+        let span = Span::from_to(start, self.last_span);
+        self.close(at, NodeKind::AggregateBound);
+        Ok(Success(Spanned::new(Bound::Aggregate(generics), span)))
+    }
+
     fn parse_bound(&mut self) -> Return<Bound> {
         let next = self.next()?;
         let start = next.span;
 
         Ok(Success(match next.spanned {
-            LexemeType::Idn(potential) => match potential.as_str() {
-                "int" => Spanned::new(Bound::Prim(Spanned::new(PrimType::Int, start)), start),
-                "unt" => Spanned::new(Bound::Prim(Spanned::new(PrimType::Unt, start)), start),
-                "float" => Spanned::new(Bound::Prim(Spanned::new(PrimType::Float, start)), start),
-                "bool" => Spanned::new(Bound::Prim(Spanned::new(PrimType::Bool, start)), start),
-                "string" => Spanned::new(Bound::Prim(Spanned::new(PrimType::String, start)), start),
-                _ => Spanned::new(Bound::Alias(Spanned::new(potential, start)), start),
+            LexemeType::Idn(potential) => match PrimType::named(&potential) {
+                Some(prim) => Spanned::new(Bound::Prim(Spanned::new(prim, start)), start),
+                None => Spanned::new(Bound::Alias(Spanned::new(potential, start)), start),
             },
             LexemeType::Struct | LexemeType::Union => {
                 let members = if self.peek()?.spanned != LexemeType::LBrace {
@@ -563,7 +984,7 @@ impl<'a> Parser<'a> {
                     let mut members = Vec::new();
                     while let Fail = self.expect(LexemeType::RBrace)? {
                         // TODO: error?
-                        let bound = attempt!(self.parse_bound()?);
+                        let bound = attempt!(self.node(Self::parse_bound, NodeKind::bound)?);
                         let name = attempt!(self, self.consume_idn()?, ParseErrors::NoMemberName);
                         members.push((bound, name));
                         let _ = self.expect(LexemeType::Comma);
@@ -583,7 +1004,7 @@ impl<'a> Parser<'a> {
                 )
             }
             LexemeType::Amp => {
-                let bound = attempt!(self.parse_bound()?);
+                let bound = attempt!(self.node(Self::parse_bound, NodeKind::bound)?);
                 let span = Span::from_to(start, self.last_span);
                 Spanned::new(Bound::Pointer(Box::new(bound)), span)
             }
@@ -635,40 +1056,85 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_expr_rbp(&mut self, rbp: usize) -> Return<Expr> {
-        let mut left = attempt!(self.nud()?);
-        while self.peek()?.spanned.prec() > rbp {
-            left = attempt!(self.led(left)?);
+        let at = self.mark();
+        let mut left = attempt!(self.node(Self::nud, NodeKind::expr)?);
+        while let Some((op, width)) = self.peek_biop() {
+            if op.prec() <= rbp {
+                break;
+            }
+            for _ in 0..width {
+                self.next()?;
+            }
+            left = attempt!(self.led(left, op)?);
+            self.close(at, NodeKind::Binary);
         }
 
         Ok(Success(left))
     }
 
+    /// the operator coming up (see `biop`)
+    fn peek_biop(&self) -> Option<(BiOps, usize)> {
+        biop(self.peek_nth(0)?, self.peek_nth(1))
+    }
+
     /*
     _EXPR           ->
-                        if _EXPR BLOCK [else if _EXPR BLOCK]?* [else BLOCK]? |\
                         GLOBIDN |\
                         [#\[IDN [a-z | A-Z | 0-9]?*\] _EXPR]
     */
 
     fn nud(&mut self) -> Return<Expr> {
+        if self.peek_nth(0) == Some(&LexemeType::Hash)
+            && self.peek_nth(1) == Some(&LexemeType::LBrack)
+        {
+            let attr = self.node(Self::parse_attribute, |_| NodeKind::Attribute)?;
+            let attr = attempt!(attr);
+            let expr = attempt!(self.parse_expr()?);
+            let span = Span::from_to(attr.span, expr.span);
+            return Ok(Success(Spanned::new(
+                Expr::Attributed {
+                    attr,
+                    expr: Box::new(expr),
+                },
+                span,
+            )));
+        }
+
         let start = self.next_span()?;
         Ok(Success(match self.next()?.spanned {
             #[rustfmt::skip]
             LexemeType::Intlit(il) => Spanned::new(Expr::IntLiteral(il), start),
             #[rustfmt::skip]
             LexemeType::BoolLit(bl) => Spanned::new(Expr::BoolLiteral(bl), start),
+            #[rustfmt::skip]
+            LexemeType::StrLit(sl) => Spanned::new(Expr::StrLiteral(sl), start),
             LexemeType::Cross => {
-                let expr = attempt!(self.parse_expr()?);
+                let expr = attempt!(self.parse_expr_rbp(UNARY_PREC)?);
                 let span = Span::from_to(start, expr.span);
                 Spanned::new(Expr::UnaryPos(Box::new(expr)), span)
             }
             LexemeType::Dash => {
-                let expr = attempt!(self.parse_expr()?);
+                let expr = attempt!(self.parse_expr_rbp(UNARY_PREC)?);
                 let span = Span::from_to(start, expr.span);
                 Spanned::new(Expr::UnaryNeg(Box::new(expr)), span)
             }
+            LexemeType::Bang => {
+                let expr = attempt!(self.parse_expr_rbp(UNARY_PREC)?);
+                let span = Span::from_to(start, expr.span);
+                Spanned::new(Expr::UnaryNot(Box::new(expr)), span)
+            }
+            LexemeType::Amp => {
+                let expr = attempt!(self.parse_expr_rbp(UNARY_PREC)?);
+                let span = Span::from_to(start, expr.span);
+                Spanned::new(Expr::Referal(Box::new(expr)), span)
+            }
+            LexemeType::Ast => {
+                let expr = attempt!(self.parse_expr_rbp(UNARY_PREC)?);
+                let span = Span::from_to(start, expr.span);
+                Spanned::new(Expr::Indir(Box::new(expr)), span)
+            }
             LexemeType::LParen => {
-                let expr = attempt!(self.parse_expr()?);
+                let expr = attempt!(self.struct_lits(true, |parser| parser.parse_expr())?);
                 let span = Span::from_to(start, self.next_span()?);
                 attempt!(
                     self,
@@ -677,20 +1143,24 @@ impl<'a> Parser<'a> {
                 );
                 Spanned::new(Expr::SubExpr(Box::new(expr)), span)
             }
-            LexemeType::Idn(name) => match self.peek()?.spanned {
-                LexemeType::LParen => {
+            LexemeType::LBrace => {
+                let block = attempt!(self.parse_block_rest(start)?);
+                Spanned::new(Expr::Block(Box::new(block.spanned)), block.span)
+            }
+            LexemeType::If => attempt!(self.parse_cond(start)?),
+            LexemeType::Idn(name) => match self.peek_nth(0) {
+                Some(LexemeType::LParen) => {
+                    self.next()?;
                     let mut args = Vec::new();
-                    if self.peek()?.spanned != LexemeType::RParen {
-                        while self.peek()?.spanned != LexemeType::RParen {
-                            let arg = attempt!(self.parse_expr()?);
-                            args.push(Box::new(arg));
+                    while self.peek()?.spanned != LexemeType::RParen {
+                        let arg = attempt!(self.struct_lits(true, |parser| parser.parse_expr())?);
+                        args.push(Box::new(arg));
 
-                            if let Fail = self.expect(LexemeType::Comma)? {
-                                if self.peek()?.spanned == LexemeType::RParen {
-                                    break;
-                                }
-                                attempt!(self, Fail, ParseErrors::StubNoArgDel);
+                        if let Fail = self.expect(LexemeType::Comma)? {
+                            if self.peek()?.spanned == LexemeType::RParen {
+                                break;
                             }
+                            attempt!(self, Fail, ParseErrors::StubNoArgDel);
                         }
                     }
 
@@ -703,27 +1173,246 @@ impl<'a> Parser<'a> {
                     );
                     Spanned::new(Expr::FnCall(name, args), span)
                 }
+                Some(LexemeType::Period) => {
+                    self.next()?;
+                    let member = attempt!(self, self.consume_idn()?, ParseErrors::ExpectedIdn);
+                    let span = Span::from_to(start, member.span);
+                    Spanned::new(Expr::ObjMember(Spanned::new(name, start), member), span)
+                }
+                Some(LexemeType::LBrace) if !self.no_struct_lit => {
+                    attempt!(self.parse_struct_lit(Spanned::new(name, start))?)
+                }
                 _ => Spanned::new(Expr::Variable(name), start),
             },
-            _ => todo!(),
+            _ => attempt!(self, Fail, ParseErrors::ExpectedExpr),
         }))
     }
 
-    fn led(&mut self, _left: Spanned<Expr>) -> Return<Expr> {
-        todo!()
+    fn led(&mut self, left: Spanned<Expr>, op: BiOps) -> Return<Expr> {
+        let right = attempt!(
+            self,
+            self.parse_expr_rbp(op.prec())?,
+            ParseErrors::ExpectedExpr
+        );
+        let span = Span::from_to(left.span, right.span);
+        Ok(Success(Spanned::new(
+            Expr::BiOp(Box::new(left), op, Box::new(right)),
+            span,
+        )))
+    }
+
+    /// parses the remainder of a block, after the opening `{` (which started at `start`)
+    fn parse_block_rest(&mut self, start: Span) -> Return<Block> {
+        let block = self.struct_lits(true, |parser| parser.parse())?;
+        let end = self.next_span()?;
+        attempt!(
+            self,
+            self.expect(LexemeType::RBrace)?,
+            ParseErrors::BlockNotClosed
+        );
+        Ok(Success(Spanned::new(block, Span::from_to(start, end))))
+    }
+
+    fn parse_cond_block(&mut self) -> Return<Block> {
+        let at = self.mark();
+        let start = self.next_span()?;
+        attempt!(
+            self,
+            self.expect(LexemeType::LBrace)?,
+            ParseErrors::BlockNotOpened
+        );
+        let block = attempt!(self.parse_block_rest(start)?);
+        self.close(at, NodeKind::Block);
+        Ok(Success(block))
+    }
+
+    /// if [cond] { [then] } [else if [cond] { [elif] }]?* [else { [else] }]?
+    ///    ^ (`start` is the span of the `if`)
+    fn parse_cond(&mut self, start: Span) -> Return<Expr> {
+        // `if done {}` - the brace here is the body, not a `done` struct literal
+        let condition = attempt!(
+            self,
+            self.struct_lits(false, |parser| parser.parse_expr())?,
+            ParseErrors::ExpectedCondition
+        );
+        let then = attempt!(self.parse_cond_block()?);
+
+        let mut elifs = Vec::new();
+        let mut r#else = None;
+        while let Some(LexemeType::Else) = self.peek_nth(0) {
+            self.next()?;
+            if let Success(_) = self.expect(LexemeType::If)? {
+                let condition = attempt!(
+                    self,
+                    self.struct_lits(false, |parser| parser.parse_expr())?,
+                    ParseErrors::ExpectedCondition
+                );
+                let block = attempt!(self.parse_cond_block()?);
+                elifs.push((Box::new(condition), block));
+            } else {
+                r#else = Some(attempt!(self.parse_cond_block()?));
+                break;
+            }
+        }
+
+        let span = Span::from_to(start, self.last_span);
+        Ok(Success(Spanned::new(
+            Expr::Cond {
+                condition: Box::new(condition),
+                then,
+                elifs,
+                r#else,
+            },
+            span,
+        )))
+    }
+
+    /// Some { 10 }  /  Col { .r = 1, .g = 2, .b = 3 }  /  Done {}
+    ///      ^ (the type name has already been consumed)
+    fn parse_struct_lit(&mut self, ty: Spanned<Type>) -> Return<Expr> {
+        attempt!(
+            self,
+            self.expect(LexemeType::LBrace)?,
+            ParseErrors::BlockNotOpened
+        );
+
+        let named = self.peek()?.spanned == LexemeType::Period;
+        let mut positional = Vec::new();
+        let mut members = Vec::new();
+        while self.peek()?.spanned != LexemeType::RBrace {
+            if let Success(_) = self.expect(LexemeType::Period)? {
+                if !named {
+                    attempt!(self, Fail, ParseErrors::MixedStructLitFields);
+                }
+                let name = attempt!(self, self.consume_idn()?, ParseErrors::NoMemberName);
+                attempt!(
+                    self,
+                    self.expect(LexemeType::Equals)?,
+                    ParseErrors::ExpectedEquals
+                );
+                let value = attempt!(self.struct_lits(true, |parser| parser.parse_expr())?);
+                members.push((name, Box::new(value)));
+            } else {
+                if named {
+                    attempt!(self, Fail, ParseErrors::MixedStructLitFields);
+                }
+                let value = attempt!(self.struct_lits(true, |parser| parser.parse_expr())?);
+                positional.push(Box::new(value));
+            }
+
+            if let Fail = self.expect(LexemeType::Comma)? {
+                if self.peek()?.spanned == LexemeType::RBrace {
+                    break;
+                }
+                attempt!(self, Fail, ParseErrors::StructLitNoDel);
+            }
+        }
+
+        let end = self.next_span()?;
+        attempt!(
+            self,
+            self.expect(LexemeType::RBrace)?,
+            ParseErrors::StructLitNotClosed
+        );
+
+        let span = Span::from_to(ty.span, end);
+        let fields = if named {
+            StructLitFields::Named(members)
+        } else {
+            StructLitFields::Positional(positional)
+        };
+        Ok(Success(Spanned::new(Expr::StructLit { ty, fields }, span)))
     }
 }
 
-impl LexemeType {
-    fn prec(&self) -> usize {
+/// the lexer only produces single-character lexemes, so operators such as `<<` and `==` are
+/// stitched together from `first` & the lexeme after it. gives the operator & how many lexemes
+/// it spans.
+pub(crate) fn biop(first: &LexemeType, second: Option<&LexemeType>) -> Option<(BiOps, usize)> {
+    use LexemeType as LT;
+    Some(match (first, second) {
+        (LT::Cross, _) => (BiOps::Add, 1),
+        (LT::Dash, _) => (BiOps::Sub, 1),
+        (LT::Ast, _) => (BiOps::Mul, 1),
+        (LT::FSlash, _) => (BiOps::Div, 1),
+        (LT::Perc, _) => (BiOps::Mod, 1),
+        (LT::Bar, Some(LT::Bar)) => (BiOps::LogOr, 2),
+        (LT::Bar, _) => (BiOps::BitOr, 1),
+        (LT::Amp, Some(LT::Amp)) => (BiOps::LogAnd, 2),
+        (LT::Amp, _) => (BiOps::BitAnd, 1),
+        (LT::Tilde, _) => (BiOps::BitNot, 1),
+        (LT::Caret, _) => (BiOps::BitXor, 1),
+        (LT::RAng, Some(LT::RAng)) => (BiOps::BitRshift, 2),
+        (LT::RAng, Some(LT::Equals)) => (BiOps::GrEq, 2),
+        (LT::RAng, _) => (BiOps::Gr, 1),
+        (LT::LAng, Some(LT::LAng)) => (BiOps::BitLShift, 2),
+        (LT::LAng, Some(LT::Equals)) => (BiOps::LsEq, 2),
+        (LT::LAng, _) => (BiOps::Ls, 1),
+        (LT::Equals, Some(LT::Equals)) => (BiOps::Eq, 2),
+        (LT::Bang, Some(LT::Equals)) => (BiOps::NEq, 2),
+        _ => return None,
+    })
+}
+
+/// binding power of prefix operators - tighter than any binary operator
+pub(crate) const UNARY_PREC: usize = 11;
+
+impl BiOps {
+    /// how tightly the operator binds - the higher, the tighter
+    pub fn prec(&self) -> usize {
         match self {
-            LexemeType::Cross | LexemeType::Dash => 5,
-            _ => 0,
+            BiOps::LogOr => 1,
+            BiOps::LogAnd => 2,
+            BiOps::BitOr => 3,
+            BiOps::BitXor | BiOps::BitNot => 4,
+            BiOps::BitAnd => 5,
+            BiOps::Eq | BiOps::NEq => 6,
+            BiOps::Gr | BiOps::Ls | BiOps::GrEq | BiOps::LsEq => 7,
+            BiOps::BitRshift | BiOps::BitLShift => 8,
+            BiOps::Add | BiOps::Sub => 9,
+            BiOps::Mul | BiOps::Div | BiOps::Mod => 10,
+            // `!` is only ever unary
+            BiOps::LogNot => 0,
+        }
+    }
+
+    /// as written in source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BiOps::Add => "+",
+            BiOps::Sub => "-",
+            BiOps::Mul => "*",
+            BiOps::Div => "/",
+            BiOps::Mod => "%",
+            BiOps::BitOr => "|",
+            BiOps::BitAnd => "&",
+            BiOps::BitNot => "~",
+            BiOps::BitXor => "^",
+            BiOps::BitRshift => ">>",
+            BiOps::BitLShift => "<<",
+            BiOps::LogOr => "||",
+            BiOps::LogAnd => "&&",
+            BiOps::LogNot => "!",
+            BiOps::Eq => "==",
+            BiOps::NEq => "!=",
+            BiOps::Gr => ">",
+            BiOps::Ls => "<",
+            BiOps::GrEq => ">=",
+            BiOps::LsEq => "<=",
         }
     }
 }
 
 pub fn parse(state: &mut State, lexemes: Vec<Lexeme>) -> Result<Block> {
     let mut parser = Parser::new(lexemes, state);
-    parser.parse()
+    parser.parse_root()
+}
+
+/// like `parse`, but also giving the structure of what was parsed - which lexemes each
+/// statement, expression & bound spans - for `cst`
+pub(crate) fn parse_events(state: &mut State, lexemes: Vec<Lexeme>) -> (Result<Block>, Vec<Event>) {
+    let mut parser = Parser::new(lexemes, state);
+    parser.events = Some(Vec::new());
+    let block = parser.parse_root();
+    (block, parser.events.unwrap_or_default())
 }
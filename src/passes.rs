@@ -0,0 +1,411 @@
+use crate::interp::{self, Value as Constant};
+use crate::ir::{BlockId, Function, Inst, Module, Terminator, Value};
+use std::collections::{HashMap, HashSet};
+
+/// how many times the passes are run over a function, at most, while they still find things
+/// to change
+const MAX_ROUNDS: usize = 16;
+
+/// a transformation of a function, which must leave it meaning the same thing
+pub trait Pass {
+    fn name(&self) -> &'static str;
+    /// whether anything changed
+    fn run(&mut self, function: &mut Function) -> bool;
+}
+
+/// runs passes over every function of a module, in order, until none change anything
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, pass: impl Pass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// the passes `emit-ir` runs
+    pub fn standard() -> Self {
+        Self::new().with(ConstProp).with(Dce).with(SimplifyCfg)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    pub fn run(&mut self, module: &mut Module) {
+        for function in &mut module.functions {
+            for _ in 0..MAX_ROUNDS {
+                let mut changed = false;
+                for pass in &mut self.passes {
+                    changed |= pass.run(function);
+                    debug_assert!(
+                        function.verify().is_ok(),
+                        "`{}` broke `{}`: {:?}",
+                        pass.name(),
+                        function.name,
+                        function.verify()
+                    );
+                }
+                if !changed {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// the constant an instruction produces, if it's a literal
+fn constant(inst: &Inst) -> Option<Constant> {
+    match inst {
+        Inst::Int(int) => Some(Constant::Int(*int)),
+        Inst::Unt(unt) => Some(Constant::Unt(*unt)),
+        Inst::Bool(bool) => Some(Constant::Bool(*bool)),
+        Inst::Void => Some(Constant::Void),
+        _ => None,
+    }
+}
+
+fn literal(constant: Constant) -> Option<Inst> {
+    match constant {
+        Constant::Int(int) => Some(Inst::Int(int)),
+        Constant::Unt(unt) => Some(Inst::Unt(unt)),
+        Constant::Bool(bool) => Some(Inst::Bool(bool)),
+        Constant::Void => Some(Inst::Void),
+        _ => None,
+    }
+}
+
+/// removes `from`'s entries from the phis of `block`
+fn forget_pred(function: &mut Function, block: BlockId, from: BlockId) {
+    for value in function.blocks[block.0 as usize].insts.clone() {
+        if let Inst::Phi(incoming) = &mut function.insts[value.0 as usize] {
+            incoming.retain(|(pred, _)| *pred != from);
+        }
+    }
+}
+
+/// folds operations on constants (as far as they'd succeed at runtime - those that would raise
+/// an error are left to), members of values built in the same function, & branches on
+/// constants
+pub struct ConstProp;
+
+impl Pass for ConstProp {
+    fn name(&self) -> &'static str {
+        "const-prop"
+    }
+
+    fn run(&mut self, function: &mut Function) -> bool {
+        let mut changed = false;
+        let mut replacements = HashMap::new();
+        for block in function.reverse_postorder() {
+            let mut moved = Vec::new();
+            for value in function.blocks[block.0 as usize].insts.clone() {
+                let get = |value: &Value| &function.insts[value.0 as usize];
+                let folded = match get(&value) {
+                    Inst::Neg(operand) => constant(get(operand))
+                        .and_then(|operand| interp::negate(operand).ok())
+                        .and_then(literal),
+                    Inst::Not(operand) => match get(operand) {
+                        Inst::Bool(bool) => Some(Inst::Bool(!bool)),
+                        _ => None,
+                    },
+                    Inst::BiOp(op, left, right) => {
+                        match (constant(get(left)), constant(get(right))) {
+                            (Some(left), Some(right)) => {
+                                interp::biop(left, *op, right).ok().and_then(literal)
+                            }
+                            _ => None,
+                        }
+                    }
+                    Inst::Tag(union) => match get(union) {
+                        Inst::Union { tag, .. } => Some(Inst::Unt(*tag as u64)),
+                        _ => None,
+                    },
+                    Inst::Member(object, member) => {
+                        let from = match get(object) {
+                            Inst::Struct(fields) => Some(fields[*member as usize]),
+                            Inst::Union { tag, value } if tag == member => Some(*value),
+                            _ => None,
+                        };
+                        if let Some(from) = from {
+                            replacements.insert(value, from);
+                        }
+                        None
+                    }
+                    Inst::Phi(incoming) => {
+                        let operands = incoming
+                            .iter()
+                            .map(|(_, operand)| *operand)
+                            .filter(|operand| *operand != value)
+                            .collect::<HashSet<_>>();
+                        let constants = operands
+                            .iter()
+                            .map(|operand| constant(get(operand)))
+                            .collect::<Option<Vec<_>>>()
+                            .unwrap_or_default();
+                        if operands.len() == 1 {
+                            replacements.insert(value, *operands.iter().next().unwrap());
+                            None
+                        } else {
+                            let same = constants.iter().all(|other| *other == constants[0]);
+                            let folded = constants.first().cloned().filter(|_| same);
+                            let folded = folded.and_then(literal);
+                            // phis must lead their block, so this is moved after them
+                            if folded.is_some() {
+                                moved.push(value);
+                            }
+                            folded
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(folded) = folded {
+                    function.insts[value.0 as usize] = folded;
+                    changed = true;
+                }
+            }
+
+            let insts = &mut function.blocks[block.0 as usize].insts;
+            insts.retain(|value| !moved.contains(value));
+            let at = insts
+                .iter()
+                .take_while(|value| matches!(function.insts[value.0 as usize], Inst::Phi(_)))
+                .count();
+            insts.splice(at..at, moved);
+
+            let term = &function.blocks[block.0 as usize].term;
+            let (taken, dropped) = match term {
+                Terminator::Branch {
+                    condition,
+                    then,
+                    r#else,
+                } => match function.inst(*condition) {
+                    Inst::Bool(true) => (*then, vec![*r#else]),
+                    Inst::Bool(false) => (*r#else, vec![*then]),
+                    _ => continue,
+                },
+                Terminator::Switch { tag, targets } => match function.inst(*tag) {
+                    Inst::Unt(tag) => {
+                        let mut others = targets.clone();
+                        let taken = others.remove(*tag as usize);
+                        (taken, others)
+                    }
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            for dropped in dropped {
+                if dropped != taken {
+                    forget_pred(function, dropped, block);
+                }
+            }
+            function.blocks[block.0 as usize].term = Terminator::Jump(taken);
+            changed = true;
+        }
+
+        for block in &mut function.blocks {
+            block
+                .insts
+                .retain(|value| !replacements.contains_key(value));
+        }
+        function.replace_uses(&replacements);
+        changed || !replacements.is_empty()
+    }
+}
+
+/// removes instructions whose results are never used (unless they have effects), & slots
+/// which are only ever written to
+pub struct Dce;
+
+impl Pass for Dce {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, function: &mut Function) -> bool {
+        // slots only stored to needn't be
+        let mut unread = HashSet::new();
+        for block in &function.blocks {
+            for value in &block.insts {
+                if let Inst::Alloca = function.inst(*value) {
+                    unread.insert(*value);
+                }
+            }
+        }
+        for block in &function.blocks {
+            for value in &block.insts {
+                match function.inst(*value) {
+                    Inst::Store { value: stored, .. } => {
+                        unread.remove(stored);
+                    }
+                    inst => {
+                        for operand in inst.operands() {
+                            unread.remove(&operand);
+                        }
+                    }
+                }
+            }
+            for operand in block.term.operands() {
+                unread.remove(&operand);
+            }
+        }
+
+        let mut live = HashSet::new();
+        let mut work = Vec::new();
+        for block in &function.blocks {
+            for value in &block.insts {
+                let inst = function.inst(*value);
+                let dead_store =
+                    matches!(inst, Inst::Store { pointer, .. } if unread.contains(pointer));
+                if inst.has_effects() && !dead_store {
+                    work.push(*value);
+                }
+            }
+            work.extend(block.term.operands());
+        }
+        while let Some(value) = work.pop() {
+            if live.insert(value) {
+                work.extend(function.inst(value).operands());
+            }
+        }
+
+        let mut changed = false;
+        for block in &mut function.blocks {
+            let before = block.insts.len();
+            block.insts.retain(|value| live.contains(value));
+            changed |= block.insts.len() != before;
+        }
+        changed
+    }
+}
+
+/// removes unreachable blocks, merges a block into its only predecessor where that can only
+/// go to it, & skips over blocks which do nothing but jump elsewhere
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &'static str {
+        "simplify-cfg"
+    }
+
+    fn run(&mut self, function: &mut Function) -> bool {
+        let mut changed = false;
+        for block in &mut function.blocks {
+            if let Terminator::Branch { then, r#else, .. } = block.term {
+                if then == r#else {
+                    block.term = Terminator::Jump(then);
+                    changed = true;
+                }
+            }
+        }
+
+        // empty blocks which just jump on are skipped, unless that would give the block
+        // jumped to two phi entries from the same predecessor
+        let mut preds = function.preds();
+        for index in 1..function.blocks.len() {
+            let block = BlockId(index as u32);
+            let Terminator::Jump(to) = function.blocks[index].term else {
+                continue;
+            };
+            if to == block || !function.blocks[index].insts.is_empty() {
+                continue;
+            }
+            for pred in preds[index].clone() {
+                if preds[to.0 as usize].contains(&pred) {
+                    continue;
+                }
+                for succ in function.blocks[pred.0 as usize].term.succs_mut() {
+                    if *succ == block {
+                        *succ = to;
+                    }
+                }
+                for value in function.blocks[to.0 as usize].insts.clone() {
+                    if let Inst::Phi(incoming) = &mut function.insts[value.0 as usize] {
+                        let from = incoming.iter().find(|(from, _)| *from == block).copied();
+                        if let Some((_, value)) = from {
+                            incoming.push((pred, value));
+                        }
+                    }
+                }
+                preds[index].retain(|other| *other != pred);
+                preds[to.0 as usize].push(pred);
+                changed = true;
+            }
+        }
+
+        // a block which is its successor's only predecessor (& which only goes there) is
+        // merged with it
+        let mut merged = HashSet::new();
+        let mut replacements = HashMap::new();
+        for index in 0..function.blocks.len() {
+            let block = BlockId(index as u32);
+            if merged.contains(&block) {
+                continue;
+            }
+            while let Terminator::Jump(to) = function.blocks[index].term {
+                if to == block || to.0 == 0 || preds[to.0 as usize] != [block] {
+                    break;
+                }
+                let next = std::mem::replace(
+                    &mut function.blocks[to.0 as usize],
+                    crate::ir::Block {
+                        insts: Vec::new(),
+                        term: Terminator::Unreachable,
+                    },
+                );
+                for value in next.insts {
+                    match &function.insts[value.0 as usize] {
+                        Inst::Phi(incoming) => {
+                            replacements.insert(value, incoming[0].1);
+                        }
+                        _ => function.blocks[index].insts.push(value),
+                    }
+                }
+                for succ in next.term.succs() {
+                    let succ_preds = &mut preds[succ.0 as usize];
+                    for pred in succ_preds.iter_mut() {
+                        if *pred == to {
+                            *pred = block;
+                        }
+                    }
+                    for value in &function.blocks[succ.0 as usize].insts {
+                        if let Inst::Phi(incoming) = &mut function.insts[value.0 as usize] {
+                            for (pred, _) in incoming {
+                                if *pred == to {
+                                    *pred = block;
+                                }
+                            }
+                        }
+                    }
+                }
+                function.blocks[index].term = next.term;
+                preds[to.0 as usize].clear();
+                merged.insert(to);
+                changed = true;
+            }
+        }
+        function.replace_uses(&replacements);
+
+        let reachable = function
+            .reverse_postorder()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        if reachable.len() != function.blocks.len() {
+            function.retain_blocks(|block| reachable.contains(&block));
+            changed = true;
+        }
+        changed |= function.remove_trivial_phis();
+        changed
+    }
+}
+
+/// optimises a module with the standard passes
+pub fn optimise(module: &mut Module) {
+    PassManager::standard().run(module);
+}
@@ -0,0 +1,711 @@
+use crate::fmt::{Comment, Comments, Position};
+use crate::parser::UNARY_PREC;
+use crate::prelude::*;
+use std::cell::RefCell;
+
+/// how source is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    /// spaces per level of nesting
+    pub indent: usize,
+    /// how wide a line may be. calls, struct literals, parameter lists & struct members which
+    /// would make one any wider (or have comments among them) are put one item per line.
+    pub width: usize,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            indent: 4,
+            width: 100,
+        }
+    }
+}
+
+/// source for a module (or any block's contents): one statement per line, ending in the tail.
+/// parsing it again gives back the same tree, but for spans.
+pub fn block(block: &Block) -> String {
+    Printer::new(&Style::default(), None).module(block)
+}
+
+/// `block` laid out `depth` levels deep in `style`, with the comments of the source it was
+/// parsed from put back
+pub(crate) fn layout(
+    block: &Block,
+    style: &Style,
+    comments: &RefCell<Comments>,
+    depth: usize,
+) -> String {
+    Printer {
+        depth,
+        ..Printer::new(style, Some(comments))
+    }
+    .module(block)
+}
+
+/// an `#if`'s condition, `depth` levels deep & from column `at`
+pub(crate) fn condition(expr: &Expr, style: &Style, depth: usize, at: usize) -> String {
+    Printer {
+        depth,
+        ..Printer::new(style, None)
+    }
+    .expr(expr, Span::default(), Place::Condition, at)
+}
+
+pub fn stmt(stmt: &Stmt) -> String {
+    Printer::new(&Style::default(), None).stmt(stmt, Span::default())
+}
+
+pub fn expr(expr: &Expr) -> String {
+    Printer::new(&Style::default(), None).expr(expr, Span::default(), Place::Free, 0)
+}
+
+/// a type declaration's bound, as written after its name (a generic's is empty)
+pub fn bound(bound: &Bound) -> String {
+    Printer::new(&Style::default(), None).bound(bound, Span::default(), 0)
+}
+
+/// `#[ name args ]`
+fn attribute(attr: &Attribute) -> String {
+    let mut source = format!("#[ {}", attr.name.spanned);
+    for arg in &attr.args {
+        match &arg.spanned {
+            AttrArg::Int(int) => source += &format!(" {int}"),
+            AttrArg::Bool(bool) => source += &format!(" {bool}"),
+            AttrArg::Idn(idn) => source += &format!(" {idn}"),
+        }
+    }
+    source + " ]"
+}
+
+fn literal(text: &str) -> String {
+    let mut source = String::from('"');
+    for ch in text.chars() {
+        match ch {
+            '\n' => source.push_str("\\n"),
+            '\t' => source.push_str("\\t"),
+            '\0' => source.push_str("\\0"),
+            '\\' | '"' => {
+                source.push('\\');
+                source.push(ch);
+            }
+            ch => source.push(ch),
+        }
+    }
+    source.push('"');
+    source
+}
+
+/// the column after `text`, written from column `at`
+fn end(at: usize, text: &str) -> usize {
+    match text.rsplit_once('\n') {
+        Some((_, last)) => last.chars().count(),
+        None => at + text.chars().count(),
+    }
+}
+
+/// where `span` starts
+fn starts(span: Span) -> Position {
+    (span.sline, span.scol)
+}
+
+/// just after where `span` ends
+fn ends(span: Span) -> Position {
+    (span.eline.saturating_sub(1), span.ecol)
+}
+
+/// where an expression is written, which decides whether it needs parentheses
+#[derive(Clone, Copy)]
+enum Place {
+    /// anywhere a whole expression is parsed, up to a `,`, `;` or closing bracket
+    Free,
+    /// an operand, which must bind at least this tightly (the right operand of a left
+    /// associative operator must bind tighter than it)
+    Operand { prec: usize, after_amp: bool },
+    /// within an `if` condition, where `name {` begins the body, not a struct literal
+    Condition,
+    /// an operand within an `if` condition
+    CondOperand { prec: usize, after_amp: bool },
+}
+
+impl Place {
+    fn operand(self, prec: usize, after_amp: bool) -> Place {
+        match self {
+            Place::Condition | Place::CondOperand { .. } => Place::CondOperand { prec, after_amp },
+            _ => Place::Operand { prec, after_amp },
+        }
+    }
+}
+
+/// a function's return type & name - leaving out a `void` which the source left out too
+fn signature(return_type: &Spanned<String>, name: &Spanned<String>) -> String {
+    match return_type.spanned == "void" && return_type.span == name.span {
+        true => name.spanned.clone(),
+        false => format!("{} {}", return_type.spanned, name.spanned),
+    }
+}
+
+/// how tightly an expression binds, as an operand: `#[ .. ]` takes everything after it
+fn binding(expr: &Expr) -> usize {
+    match expr {
+        Expr::BiOp(_, op, _) => op.prec(),
+        Expr::Attributed { .. } => 0,
+        _ => usize::MAX,
+    }
+}
+
+/// whether `expr` wouldn't parse back the same in `place`, without parentheses
+fn parenthesised(expr: &Expr, place: Place) -> bool {
+    match place {
+        Place::Free => false,
+        Place::Condition => matches!(expr, Expr::StructLit { .. }),
+        Place::Operand { prec, after_amp } | Place::CondOperand { prec, after_amp } => {
+            binding(expr) < prec
+                // `a & &b` would be lexed as `a && b`
+                || (after_amp && starts_with_amp(expr, place))
+                || matches!(
+                    (expr, place),
+                    (Expr::StructLit { .. }, Place::CondOperand { .. })
+                )
+        }
+    }
+}
+
+fn starts_with_amp(expr: &Expr, place: Place) -> bool {
+    match expr {
+        Expr::Referal(_) => true,
+        Expr::BiOp(left, op, _) => {
+            let place = place.operand(op.prec(), false);
+            !parenthesised(&left.spanned, place) && starts_with_amp(&left.spanned, place)
+        }
+        _ => false,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Printer<'a> {
+    style: &'a Style,
+    depth: usize,
+    /// put back before (& after) the code they were written by
+    comments: Option<&'a RefCell<Comments>>,
+    /// where what's being laid out ends in the source (its block's closing brace, or the end
+    /// of its statement) - the comments after which aren't its
+    end: Position,
+    /// never splitting anything across lines, to see how wide it'd be
+    flat: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(style: &'a Style, comments: Option<&'a RefCell<Comments>>) -> Printer<'a> {
+        Printer {
+            style,
+            depth: 0,
+            comments,
+            end: (SpanInt::MAX, SpanInt::MAX),
+            flat: false,
+        }
+    }
+
+    fn nested(&self) -> Printer<'a> {
+        Printer {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.depth * self.style.indent
+    }
+
+    fn indent(&self) -> String {
+        " ".repeat(self.column())
+    }
+
+    /// whether `render` (from column `at`) fits on the line, when nothing is split
+    fn fits(&self, at: usize, render: impl FnOnce(&Printer) -> String) -> bool {
+        if self.flat {
+            return true;
+        }
+        let measure = Printer {
+            comments: None,
+            flat: true,
+            ..*self
+        };
+        let text = render(&measure);
+        let first = text.split('\n').next().unwrap_or_default();
+        at + first.chars().count() <= self.style.width
+    }
+
+    /// items (spanning `spans`) separated by commas on one line - or if they won't fit, or
+    /// there are comments among them (within `span`), each on its own line between the
+    /// brackets, with the comments before & after each. `trailing` is whether a comma can
+    /// follow the last.
+    fn list(
+        &self,
+        at: usize,
+        (open, close): (&str, &str),
+        trailing: bool,
+        spans: &[Span],
+        span: Span,
+        item: impl Fn(&Printer, usize, usize) -> String,
+    ) -> String {
+        let count = spans.len();
+        let flat = |printer: &Printer| {
+            let mut source = open.to_owned();
+            let mut column = at + open.chars().count();
+            for index in 0..count {
+                if index > 0 {
+                    source += ", ";
+                    column += 2;
+                }
+                let text = item(printer, index, column);
+                column = end(column, &text);
+                source += &text;
+            }
+            source + close
+        };
+        let end = self.end.min(ends(span));
+        let commented = self
+            .comments
+            .is_some_and(|comments| comments.borrow().within(starts(span), end));
+        if count == 0 || !commented && self.fits(at, flat) {
+            return flat(self);
+        }
+
+        let nested = Printer {
+            end,
+            ..self.nested()
+        };
+        let mut source = open.trim_end().to_owned();
+        for index in 0..count {
+            let mut line = nested.indent();
+            for comment in nested.take(|comments| comments.before(starts(spans[index]))) {
+                if comment.leading && comment.line == spans[index].sline {
+                    line += &format!("{} ", comment.text);
+                } else {
+                    source += &format!("\n{}{}", nested.indent(), comment.text);
+                }
+            }
+            let at = line.chars().count();
+            source += &format!("\n{line}{}", item(&nested, index, at));
+            if trailing || index + 1 < count {
+                source.push(',');
+            }
+
+            // (those before the next item on its line are its, but the last item's are all
+            // its own - only the closing bracket follows them)
+            let line_end = (spans[index].eline, 0).min(end);
+            let after = match spans.get(index + 1) {
+                Some(next) => {
+                    nested.take(|comments| comments.trailing(line_end.min(starts(*next))))
+                }
+                None => nested.take(|comments| comments.before(line_end)),
+            };
+            for (index, comment) in after.into_iter().enumerate() {
+                match index {
+                    0 => source += &format!(" {}", comment.text),
+                    _ => source += &format!("\n{}{}", nested.indent(), comment.text),
+                }
+            }
+        }
+        for comment in nested.take(|comments| comments.before(end)) {
+            source += &format!("\n{}{}", nested.indent(), comment.text);
+        }
+        source + &format!("\n{}{}", self.indent(), close.trim_start())
+    }
+
+    /// the comments `take` takes - if they're being put back
+    fn take(&self, take: impl FnOnce(&mut Comments) -> Vec<Comment>) -> Vec<Comment> {
+        self.comments
+            .map_or_else(Vec::new, |comments| take(&mut comments.borrow_mut()))
+    }
+
+    fn module(&self, block: &Block) -> String {
+        let lines = self.lines(block);
+        if lines.is_empty() {
+            lines
+        } else {
+            lines + "\n"
+        }
+    }
+
+    /// a block's statements & tail, one per line (after any comments before each), indented.
+    /// then any comments before the block's end.
+    fn lines(&self, block: &Block) -> String {
+        let mut lines = Vec::new();
+        for stmt in &block.stmts {
+            let start = match &stmt.spanned {
+                Stmt::Fn { attrs, .. } | Stmt::Stub { attrs, .. } | Stmt::Type { attrs, .. } => {
+                    attrs.first().map_or(stmt.span, |attr| attr.span)
+                }
+                _ => stmt.span,
+            };
+            self.item(&mut lines, starts(start), stmt.span, |printer| {
+                printer.stmt(&stmt.spanned, stmt.span)
+            });
+        }
+        if let Some(tail) = &block.tail {
+            self.item(&mut lines, starts(tail.span), tail.span, |printer| {
+                printer.expr(&tail.spanned, tail.span, Place::Free, printer.column())
+            });
+        }
+        self.comments_before(&mut lines, self.end);
+        lines.join("\n")
+    }
+
+    /// a statement (or tail) with the comments before it (or before it on its first line), &
+    /// any after it on its last line
+    fn item(
+        &self,
+        lines: &mut Vec<String>,
+        start: Position,
+        span: Span,
+        render: impl FnOnce(&Printer) -> String,
+    ) {
+        self.comments_before(lines, (start.0, 0));
+        self.gap(lines, start.0);
+        let printer = Printer {
+            end: self.end.min(ends(span)),
+            ..*self
+        };
+        let mut text = self.indent();
+        for comment in self.take(|comments| comments.before(start)) {
+            text += &format!("{} ", comment.text);
+        }
+        text += &render(&printer);
+
+        let line_end = (span.eline, 0);
+        let mut after = self
+            .take(|comments| comments.trailing(line_end.min(self.end)))
+            .into_iter();
+        if let Some(comment) = after.next() {
+            text += &format!(" {}", comment.text);
+        }
+        lines.push(text);
+        // (only from within a statement split across lines, as a comment ends its line)
+        for comment in after {
+            lines.push(self.indent() + &comment.text);
+        }
+    }
+
+    fn comments_before(&self, lines: &mut Vec<String>, at: Position) {
+        for comment in self.take(|comments| comments.before(at)) {
+            self.gap(lines, comment.line);
+            lines.push(self.indent() + &comment.text);
+        }
+    }
+
+    /// a blank line, if there was one before `line` (& this isn't the start of a block)
+    fn gap(&self, lines: &mut Vec<String>, line: SpanInt) {
+        let Some(comments) = self.comments else {
+            return;
+        };
+        if !lines.is_empty() && comments.borrow().blank_before(line) {
+            lines.push(String::new());
+        }
+    }
+
+    /// `block` in braces, which end at `end` in the source
+    fn braced(&self, block: &Block, end: Position) -> String {
+        let lines = if self.flat {
+            // (only the first line matters)
+            if block.stmts.is_empty() && block.tail.is_none() {
+                String::new()
+            } else {
+                "\n".to_owned()
+            }
+        } else {
+            Printer {
+                end: self.end.min(end),
+                ..self.nested()
+            }
+            .lines(block)
+        };
+        if lines.is_empty() {
+            "{}".to_owned()
+        } else {
+            format!("{{\n{lines}\n{}}}", self.indent())
+        }
+    }
+
+    /// `#[ .. ]`s, each on its own line before the declaration
+    fn attributed(&self, attrs: &[Spanned<Attribute>], decl: String) -> String {
+        let mut source = String::new();
+        for attr in attrs {
+            source += &format!("{}\n{}", attribute(&attr.spanned), self.indent());
+        }
+        source + &decl
+    }
+
+    fn stmt(&self, stmt: &Stmt, span: Span) -> String {
+        let at = self.column();
+        // where a block within ends, along with the statement
+        let end = ends(span);
+        match stmt {
+            Stmt::Fn {
+                attrs,
+                return_type,
+                name,
+                parameters,
+                body,
+            } => {
+                let open = format!("fn {}(", signature(return_type, name));
+                let spans: Vec<_> = parameters
+                    .iter()
+                    .map(|(ty, name)| Span::from_to(ty.span, name.span))
+                    .collect();
+                let span = Span::from_to(name.span, *spans.last().unwrap_or(&name.span));
+                let parameters = self.list(at, (&open, ") "), true, &spans, span, |_, index, _| {
+                    let (ty, name) = &parameters[index];
+                    format!("{} {}", ty.spanned, name.spanned)
+                });
+                let decl = format!("{parameters}{};", self.braced(body, end));
+                self.attributed(attrs, decl)
+            }
+            Stmt::Stub {
+                attrs,
+                return_type,
+                name,
+                parameters,
+            } => {
+                let open = format!("fn {}(", signature(return_type, name));
+                let spans: Vec<_> = parameters.iter().map(|parameter| parameter.span).collect();
+                // (a stub's parameters can't end in a comma)
+                let span = Span::from_to(name.span, span);
+                let parameters =
+                    self.list(at, (&open, ");"), false, &spans, span, |_, index, _| {
+                        parameters[index].spanned.clone()
+                    });
+                self.attributed(attrs, parameters)
+            }
+            Stmt::Loop { block } => format!("loop {};", self.braced(block, end)),
+            Stmt::Label { name } => format!("@{};", name.spanned),
+            Stmt::Goto { name } => format!("goto @{};", name.spanned),
+            Stmt::Return { expr: None } => "return;".to_owned(),
+            Stmt::Return { expr: Some(expr) } => {
+                let expr = self.expr(&expr.spanned, expr.span, Place::Free, at + 7);
+                format!("return {expr};")
+            }
+            Stmt::VarDec { name, initialiser } => {
+                let prefix = format!("let {} = ", name.spanned);
+                let at = at + prefix.chars().count();
+                let initialiser =
+                    self.expr(&initialiser.spanned, initialiser.span, Place::Free, at);
+                format!("{prefix}{initialiser};")
+            }
+            Stmt::VarRes { name, updated } => {
+                let prefix = format!("{} = ", name.spanned);
+                let at = at + prefix.chars().count();
+                let updated = self.expr(&updated.spanned, updated.span, Place::Free, at);
+                format!("{prefix}{updated};")
+            }
+            Stmt::Type { attrs, name, bound } => {
+                let bound_span = bound.span;
+                let decl = match &bound.spanned {
+                    Bound::Generic => format!("type {};", name.spanned),
+                    bound => {
+                        let prefix = format!("type {} ", name.spanned);
+                        let at = at + prefix.chars().count();
+                        let bound = self.bound(bound, bound_span, at);
+                        format!("{prefix}{bound};")
+                    }
+                };
+                self.attributed(attrs, decl)
+            }
+            Stmt::Discard { expr } => {
+                format!("{};", self.expr(&expr.spanned, expr.span, Place::Free, at))
+            }
+        }
+    }
+
+    /// `bound` (spanning `span`) from column `at`
+    fn bound(&self, bound: &Bound, span: Span, at: usize) -> String {
+        match bound {
+            Bound::Generic => String::new(),
+            Bound::Aggregate(generics) => generics
+                .iter()
+                .map(|generic| generic.spanned.as_str())
+                .collect::<Vec<_>>()
+                .join(" + "),
+            Bound::Prim(prim) => match prim.spanned {
+                PrimType::Int => "int",
+                PrimType::Unt => "unt",
+                PrimType::Float => "float",
+                PrimType::Bool => "bool",
+                PrimType::String => "string",
+            }
+            .to_owned(),
+            Bound::Struct(members) => self.members("struct", members, span, at),
+            Bound::Union(members) => self.members("union", members, span, at),
+            Bound::Alias(name) => name.spanned.clone(),
+            Bound::Pointer(bound) => format!("&{}", self.bound(&bound.spanned, bound.span, at + 1)),
+            Bound::FnPtr { args, return_type } => format!(
+                "({}) -> {}",
+                args.iter()
+                    .map(|arg| arg.spanned.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                return_type.spanned
+            ),
+        }
+    }
+
+    fn members(
+        &self,
+        keyword: &str,
+        members: &Option<Vec<(Spanned<Bound>, Spanned<String>)>>,
+        span: Span,
+        at: usize,
+    ) -> String {
+        match members {
+            None => keyword.to_owned(),
+            Some(members) if members.is_empty() => format!("{keyword} {{}}"),
+            Some(members) => {
+                let open = format!("{keyword} {{ ");
+                let spans: Vec<_> = members
+                    .iter()
+                    .map(|(bound, name)| Span::from_to(bound.span, name.span))
+                    .collect();
+                self.list(
+                    at,
+                    (&open, " }"),
+                    true,
+                    &spans,
+                    span,
+                    |printer, index, at| {
+                        let (bound, name) = &members[index];
+                        let member = printer.bound(&bound.spanned, bound.span, at);
+                        format!("{member} {}", name.spanned)
+                    },
+                )
+            }
+        }
+    }
+
+    /// `expr` (spanning `span`) from column `at`, parenthesised if it wouldn't otherwise parse
+    /// back the same in `place`
+    fn expr(&self, expr: &Expr, span: Span, place: Place, at: usize) -> String {
+        if parenthesised(expr, place) {
+            format!("({})", self.unparenthesised(expr, span, place, at + 1))
+        } else {
+            self.unparenthesised(expr, span, place, at)
+        }
+    }
+
+    fn spanned(&self, expr: &Spanned<Expr>, place: Place, at: usize) -> String {
+        self.expr(&expr.spanned, expr.span, place, at)
+    }
+
+    fn unparenthesised(&self, expr: &Expr, span: Span, place: Place, at: usize) -> String {
+        match expr {
+            Expr::IntLiteral(int) => int.to_string(),
+            Expr::BoolLiteral(bool) => bool.to_string(),
+            Expr::StrLiteral(text) => literal(text),
+            Expr::Variable(name) => name.clone(),
+            Expr::UnaryNot(inner) => format!("!{}", self.unary(inner, place, at)),
+            Expr::UnaryNeg(inner) => format!("-{}", self.unary(inner, place, at)),
+            Expr::UnaryPos(inner) => format!("+{}", self.unary(inner, place, at)),
+            Expr::Referal(inner) => format!("&{}", self.unary(inner, place, at)),
+            Expr::Indir(inner) => format!("*{}", self.unary(inner, place, at)),
+            Expr::SubExpr(inner) => format!("({})", self.spanned(inner, Place::Free, at + 1)),
+            Expr::FnCall(name, args) => {
+                let open = format!("{name}(");
+                let spans: Vec<_> = args.iter().map(|arg| arg.span).collect();
+                self.list(
+                    at,
+                    (&open, ")"),
+                    true,
+                    &spans,
+                    span,
+                    |printer, index, at| printer.spanned(&args[index], Place::Free, at),
+                )
+            }
+            Expr::BiOp(left, op, right) => {
+                // every operator is left associative
+                let left = self.spanned(left, place.operand(op.prec(), false), at);
+                let at = end(at, &left) + op.symbol().len() + 2;
+                let after_amp = *op == BiOps::BitAnd;
+                let right = self.spanned(right, place.operand(op.prec() + 1, after_amp), at);
+                format!("{left} {} {right}", op.symbol())
+            }
+            Expr::ObjMember(object, member) => format!("{}.{}", object.spanned, member.spanned),
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                let condition = self.spanned(condition, Place::Condition, at + 3);
+                let block = self.braced(&then.spanned, ends(then.span));
+                let mut source = format!("if {condition} {block}");
+                for (condition, block) in elifs {
+                    let at = end(at, &source) + 9;
+                    let condition = self.spanned(condition, Place::Condition, at);
+                    let block = self.braced(&block.spanned, ends(block.span));
+                    source += &format!(" else if {condition} {block}");
+                }
+                if let Some(r#else) = r#else {
+                    let block = self.braced(&r#else.spanned, ends(r#else.span));
+                    source += &format!(" else {block}");
+                }
+                source
+            }
+            Expr::Block(block) => self.braced(block, ends(span)),
+            Expr::StructLit { ty, fields } => {
+                let open = format!("{} {{ ", ty.spanned);
+                match fields {
+                    StructLitFields::Positional(values) if values.is_empty() => {
+                        format!("{} {{}}", ty.spanned)
+                    }
+                    StructLitFields::Named(values) if values.is_empty() => {
+                        format!("{} {{}}", ty.spanned)
+                    }
+                    StructLitFields::Positional(values) => {
+                        let spans: Vec<_> = values.iter().map(|value| value.span).collect();
+                        self.list(
+                            at,
+                            (&open, " }"),
+                            true,
+                            &spans,
+                            span,
+                            |printer, index, at| printer.spanned(&values[index], Place::Free, at),
+                        )
+                    }
+                    StructLitFields::Named(values) => {
+                        let spans: Vec<_> = values
+                            .iter()
+                            .map(|(name, value)| Span::from_to(name.span, value.span))
+                            .collect();
+                        self.list(
+                            at,
+                            (&open, " }"),
+                            true,
+                            &spans,
+                            span,
+                            |printer, index, at| {
+                                let (name, value) = &values[index];
+                                let prefix = format!(".{} = ", name.spanned);
+                                let at = at + prefix.chars().count();
+                                prefix + &printer.spanned(value, Place::Free, at)
+                            },
+                        )
+                    }
+                }
+            }
+            Expr::Attributed { attr, expr } => {
+                let place = match place {
+                    Place::Condition | Place::CondOperand { .. } => Place::Condition,
+                    _ => Place::Free,
+                };
+                let attr = attribute(&attr.spanned);
+                let at = at + attr.chars().count() + 1;
+                format!("{attr} {}", self.spanned(expr, place, at))
+            }
+        }
+    }
+
+    /// a prefix operator's operand, which binds tighter than any binary operator
+    fn unary(&self, inner: &Spanned<Expr>, place: Place, at: usize) -> String {
+        self.spanned(inner, place.operand(UNARY_PREC, false), at + 1)
+    }
+}
@@ -0,0 +1,213 @@
+use crate::ir::{BlockId, Function};
+use std::collections::BTreeSet;
+
+/// a function's blocks arranged into structured control flow, by the relooper algorithm (as
+/// in emscripten). a shape may be entered at any of its entries; where there's more than one,
+/// a `label` variable set before each branch says which.
+///
+/// every branch then either goes to an entry of the `next` shape of one it's within (leaving
+/// that shape), or to an entry of a loop it's within (continuing it).
+#[derive(Debug, Clone)]
+pub enum Shape {
+    /// a single block, followed by whatever it branches to
+    Simple {
+        block: BlockId,
+        next: Option<Box<Shape>>,
+    },
+    /// a body which branches back to its entries, followed by whatever it leaves to
+    Loop {
+        entries: Vec<BlockId>,
+        inner: Box<Shape>,
+        next: Option<Box<Shape>>,
+    },
+    /// a shape for each entry which can be told apart from the others, chosen by `label`.
+    /// entries without one fall through to `next`
+    Multiple {
+        entries: Vec<BlockId>,
+        handled: Vec<(BlockId, Shape)>,
+        next: Option<Box<Shape>>,
+    },
+}
+
+impl Shape {
+    pub fn entries(&self) -> Vec<BlockId> {
+        match self {
+            Shape::Simple { block, .. } => vec![*block],
+            Shape::Loop { entries, .. } | Shape::Multiple { entries, .. } => entries.clone(),
+        }
+    }
+}
+
+type Blocks = BTreeSet<BlockId>;
+
+struct Relooper {
+    succs: Vec<Vec<BlockId>>,
+    preds: Vec<Vec<BlockId>>,
+}
+
+impl Relooper {
+    /// the successors of `block` still to be arranged - those within `blocks`, other than
+    /// the `heads` of loops (as branches to those just continue the loop)
+    fn succs<'a>(
+        &'a self,
+        block: BlockId,
+        blocks: &'a Blocks,
+        heads: &'a Blocks,
+    ) -> impl Iterator<Item = BlockId> + 'a {
+        self.succs[block.0 as usize]
+            .iter()
+            .copied()
+            .filter(|succ| blocks.contains(succ) && !heads.contains(succ))
+    }
+
+    /// every block reachable from `from` by following at least one branch
+    fn reachable(&self, from: &[BlockId], blocks: &Blocks, heads: &Blocks) -> Blocks {
+        let mut seen = Blocks::new();
+        let mut work = from
+            .iter()
+            .flat_map(|block| self.succs(*block, blocks, heads))
+            .collect::<Vec<_>>();
+        while let Some(block) = work.pop() {
+            if seen.insert(block) {
+                work.extend(self.succs(block, blocks, heads));
+            }
+        }
+        seen
+    }
+
+    /// the blocks outside of `within` (but still to be arranged) which it branches to
+    fn exits(&self, within: &Blocks, blocks: &Blocks, heads: &Blocks) -> Vec<BlockId> {
+        let mut exits = Vec::new();
+        for block in within {
+            for succ in self.succs(*block, blocks, heads) {
+                if !within.contains(&succ) && !exits.contains(&succ) {
+                    exits.push(succ);
+                }
+            }
+        }
+        exits
+    }
+
+    fn shape(&self, entries: Vec<BlockId>, blocks: Blocks, heads: &Blocks) -> Option<Shape> {
+        if entries.is_empty() {
+            return None;
+        }
+
+        // an entry which can't come back to itself is simply run, then whatever follows it
+        if let [entry] = entries[..] {
+            if !self.reachable(&[entry], &blocks, heads).contains(&entry) {
+                let mut rest = blocks;
+                rest.remove(&entry);
+                let mut next = Vec::new();
+                for succ in self.succs(entry, &rest, heads) {
+                    if !next.contains(&succ) {
+                        next.push(succ);
+                    }
+                }
+                return Some(Shape::Simple {
+                    block: entry,
+                    next: self.shape(next, rest, heads).map(Box::new),
+                });
+            }
+        } else if let Some(shape) = self.multiple(&entries, &blocks, heads) {
+            return Some(shape);
+        }
+        Some(self.r#loop(entries, blocks, heads))
+    }
+
+    /// splits off the blocks only reachable from one entry, for each entry that has them
+    fn multiple(&self, entries: &[BlockId], blocks: &Blocks, heads: &Blocks) -> Option<Shape> {
+        let reaches = entries
+            .iter()
+            .map(|entry| {
+                let mut reached = self.reachable(&[*entry], blocks, heads);
+                reached.insert(*entry);
+                reached
+            })
+            .collect::<Vec<_>>();
+        let mut handled = Vec::new();
+        let mut taken = Blocks::new();
+        let mut groups = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let group = reaches[index]
+                .iter()
+                .copied()
+                .filter(|block| {
+                    reaches
+                        .iter()
+                        .enumerate()
+                        .all(|(other, reached)| other == index || !reached.contains(block))
+                })
+                .collect::<Blocks>();
+            if group.contains(entry) {
+                taken.extend(&group);
+                groups.push((*entry, group));
+            }
+        }
+        if groups.is_empty() {
+            return None;
+        }
+
+        let mut next = entries
+            .iter()
+            .copied()
+            .filter(|entry| !taken.contains(entry))
+            .collect::<Vec<_>>();
+        for exit in self.exits(&taken, blocks, heads) {
+            if !next.contains(&exit) {
+                next.push(exit);
+            }
+        }
+        for (entry, group) in groups {
+            let inner = self.shape(vec![entry], group, heads);
+            handled.push((entry, inner.expect("relooper: an empty group")));
+        }
+        let rest = blocks.difference(&taken).copied().collect();
+        Some(Shape::Multiple {
+            entries: entries.to_vec(),
+            handled,
+            next: self.shape(next, rest, heads).map(Box::new),
+        })
+    }
+
+    /// loops over every block which can come back to an entry
+    fn r#loop(&self, entries: Vec<BlockId>, blocks: Blocks, heads: &Blocks) -> Shape {
+        let mut inner = entries.iter().copied().collect::<Blocks>();
+        let mut work = entries.clone();
+        while let Some(block) = work.pop() {
+            for pred in &self.preds[block.0 as usize] {
+                if blocks.contains(pred) && !heads.contains(&block) && inner.insert(*pred) {
+                    work.push(*pred);
+                }
+            }
+        }
+
+        let next = self.exits(&inner, &blocks, heads);
+        let rest = blocks.difference(&inner).copied().collect();
+        let mut inner_heads = heads.clone();
+        inner_heads.extend(&entries);
+        Shape::Loop {
+            entries: entries.clone(),
+            inner: Box::new(
+                self.shape(entries, inner, &inner_heads)
+                    .expect("relooper: an empty loop"),
+            ),
+            next: self.shape(next, rest, heads).map(Box::new),
+        }
+    }
+}
+
+/// arranges the blocks reachable from the entry of a function
+pub fn reloop(function: &Function) -> Shape {
+    let succs = function
+        .blocks
+        .iter()
+        .map(|block| block.term.succs())
+        .collect();
+    let preds = function.preds();
+    let relooper = Relooper { succs, preds };
+    let blocks = function.reverse_postorder().into_iter().collect();
+    relooper
+        .shape(vec![BlockId(0)], blocks, &Blocks::new())
+        .expect("relooper: a function without an entry")
+}
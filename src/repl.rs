@@ -0,0 +1,135 @@
+use crate::driver::{self, Checked, Failure, Stage};
+use crate::interp::{self, Ended, Globals, Value};
+use crate::lexer;
+use crate::prelude::*;
+
+/// the input so far in an interactive session. everything which has been accepted is kept as
+/// the top level of a module, which is checked again along with each new input - so `let`s,
+/// `fn`s & `type`s carry over. only the new input is run, with the variables left by what
+/// ran before.
+#[derive(Debug, Default)]
+pub struct Session {
+    history: String,
+    globals: Globals,
+}
+
+/// what evaluating some input did
+#[derive(Debug)]
+pub enum Evaluated {
+    /// the input ended in an expression, with this value & type
+    Value(Value, String),
+    /// only statements & declarations, which later input can use
+    Kept,
+    /// the program asked to exit, with this code
+    Exited(i64),
+}
+
+/// why some input wasn't accepted - it's forgotten, though whatever it did before a runtime
+/// error stays done
+#[derive(Debug)]
+pub enum Rejected {
+    /// the stage raised errors, which are left in the `State`
+    Errors(Stage),
+    Unrecoverable(SdwErr),
+    /// a runtime error
+    Failed(SdwErr),
+}
+
+impl From<Failure> for Rejected {
+    fn from(failure: Failure) -> Rejected {
+        match failure {
+            Failure::Errors(stage) => Rejected::Errors(stage),
+            Failure::Unrecoverable(err) => Rejected::Unrecoverable(err),
+        }
+    }
+}
+
+/// whether `input` is finished, or has unclosed braces (so more lines are wanted)
+pub fn complete(input: &str) -> bool {
+    let mut state = State::new();
+    let lexemes = lexer::lex(&mut state, input);
+    let depth = lexemes
+        .iter()
+        .fold(0, |depth, lexeme| match lexeme.spanned {
+            LexemeType::LBrace => depth + 1,
+            LexemeType::RBrace => depth - 1,
+            _ => depth,
+        });
+    // whatever doesn't lex is complete, so the error is shown
+    !state.errors.is_empty() || depth <= 0
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    /// the text errors for `input` point into
+    pub fn source(&self, input: &str) -> String {
+        format!("{}{input}", self.history)
+    }
+
+    /// the line `input` starts on, within `source`
+    fn start(&self) -> SpanInt {
+        self.history.matches('\n').count() as SpanInt
+    }
+
+    fn check(&self, state: &mut State, input: &str) -> std::result::Result<Checked, Rejected> {
+        let checked = driver::check_shown(state, &self.source(input), &mut ())?;
+        // the rest were shown when they were entered
+        let start = self.start();
+        state.warnings.retain(|warning| warning.span.sline >= start);
+        Ok(checked)
+    }
+
+    /// the type of the expression `input` ends in, without running it
+    pub fn type_of(
+        &self,
+        state: &mut State,
+        input: &str,
+    ) -> std::result::Result<Option<String>, Rejected> {
+        let checked = self.check(state, input)?;
+        Ok(self.tail(&checked).map(|tail| {
+            let ty = checked.types.expr(tail.span).unwrap_or(&Ty::Error);
+            ty.display(&checked.res)
+        }))
+    }
+
+    fn tail<'c>(&self, checked: &'c Checked) -> Option<&'c Spanned<Expr>> {
+        checked
+            .ast
+            .tail
+            .as_deref()
+            .filter(|tail| tail.span.sline >= self.start())
+    }
+
+    /// checks & runs `input` after everything accepted before, keeping it if it succeeds.
+    /// only what `input` itself writes is shown.
+    pub fn eval(
+        &mut self,
+        state: &mut State,
+        input: &str,
+    ) -> std::result::Result<Evaluated, Rejected> {
+        let checked = self.check(state, input)?;
+        let start = self.start();
+        let ended = interp::evaluate(&checked, &mut self.globals, start);
+        let evaluated = match ended.map_err(Rejected::Failed)? {
+            Ended::Exited(code) => return Ok(Evaluated::Exited(code)),
+            Ended::Value(value) => match self.tail(&checked) {
+                Some(tail) => {
+                    let ty = checked.types.expr(tail.span).unwrap_or(&Ty::Error);
+                    Evaluated::Value(value, ty.display(&checked.res))
+                }
+                None => Evaluated::Kept,
+            },
+        };
+
+        self.history.push_str(input);
+        self.history.push('\n');
+        // an expression is kept as a statement, for whatever it does
+        if let Evaluated::Value(..) = evaluated {
+            self.history.push_str(";\n");
+        }
+        Ok(evaluated)
+    }
+}
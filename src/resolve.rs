@@ -0,0 +1,536 @@
+use crate::driver;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// type names which are always in scope, and so are never declared
+pub const BUILTIN_TYPES: [&str; 6] = ["int", "unt", "float", "bool", "string", "void"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DefId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Fn,
+    Stub,
+    Type,
+    Var,
+    Param,
+    Label,
+}
+
+impl DefKind {
+    fn describe(&self) -> &'static str {
+        match self {
+            DefKind::Fn | DefKind::Stub => "function",
+            DefKind::Type => "type",
+            DefKind::Var => "variable",
+            DefKind::Param => "parameter",
+            DefKind::Label => "label",
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        matches!(self, DefKind::Var | DefKind::Param)
+    }
+}
+
+#[derive(Debug)]
+pub struct Def {
+    pub name: String,
+    pub kind: DefKind,
+    /// the span of the declaring identifier
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Res {
+    Def(DefId),
+    /// a function name; every overload (`fn` or stub) in scope is a candidate,
+    /// and which one is meant can only be decided by type
+    Overloads(Vec<DefId>),
+}
+
+/// the side-table produced by name resolution.
+///
+/// the AST has no node ids, so identifiers are keyed by their span. declarations map to their
+/// own `DefId`, and uses map to whatever they refer to. a function call is keyed by the span of
+/// the whole call expression (`Expr::FnCall` does not span its name separately).
+#[derive(Debug, Default)]
+pub struct Resolutions {
+    pub defs: Vec<Def>,
+    table: HashMap<Span, Res>,
+}
+
+impl Resolutions {
+    pub fn def(&self, id: DefId) -> &Def {
+        &self.defs[id.0]
+    }
+
+    pub fn get(&self, span: Span) -> Option<&Res> {
+        self.table.get(&span)
+    }
+
+    /// every recorded identifier (declarations included) & what it resolved to
+    pub fn iter(&self) -> impl Iterator<Item = (&Span, &Res)> {
+        self.table.iter()
+    }
+
+    /// the single declaration an identifier refers to (`None` for overloaded function names)
+    pub fn def_at(&self, span: Span) -> Option<DefId> {
+        match self.table.get(&span)? {
+            Res::Def(id) => Some(*id),
+            Res::Overloads(_) => None,
+        }
+    }
+
+    fn add(&mut self, name: &Spanned<String>, kind: DefKind) -> DefId {
+        let id = DefId(self.defs.len());
+        self.defs.push(Def {
+            name: name.spanned.clone(),
+            kind,
+            span: name.span,
+        });
+        self.table.insert(name.span, Res::Def(id));
+        id
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum ScopeKind {
+    Module,
+    Function,
+    Block,
+}
+
+struct Scope {
+    kind: ScopeKind,
+    types: HashMap<String, DefId>,
+    /// functions may be overloaded, so a name can have several values
+    values: HashMap<String, Vec<DefId>>,
+}
+
+impl Scope {
+    fn new(kind: ScopeKind) -> Self {
+        Self {
+            kind,
+            types: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+}
+
+/// labels are function-wide & may be jumped to before they appear,
+/// so `goto`s are only resolved once the whole function has been seen
+#[derive(Default)]
+struct LabelScope {
+    labels: HashMap<String, DefId>,
+    gotos: Vec<Spanned<String>>,
+}
+
+struct Resolver<'a> {
+    state: &'a mut State,
+    res: Resolutions,
+    scopes: Vec<Scope>,
+    labels: Vec<LabelScope>,
+}
+
+impl<'a> Resolver<'a> {
+    fn error(&mut self, err: SdwErr) {
+        self.state.errors.push(err);
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("resolver: no scope")
+    }
+
+    fn declare_type(&mut self, name: &Spanned<String>) {
+        if let Some(&prev) = self.scope().types.get(&name.spanned) {
+            let err = SdwErr::from_pos(
+                ResolveErrors::Duplicate {
+                    kind: "type",
+                    name: name.spanned.clone(),
+                },
+                name.span,
+            )
+            .with_note("first declared here", self.res.def(prev).span);
+            self.error(err);
+            return;
+        }
+
+        let id = self.res.add(name, DefKind::Type);
+        self.scope().types.insert(name.spanned.clone(), id);
+    }
+
+    fn declare_value(&mut self, name: &Spanned<String>, kind: DefKind) {
+        let existing = self.scope().values.get(&name.spanned).cloned();
+        if let Some(prev) = existing {
+            // overloading is only allowed between functions
+            let overload =
+                !kind.is_local() && prev.iter().all(|id| !self.res.def(*id).kind.is_local());
+            if !overload {
+                let err = SdwErr::from_pos(
+                    ResolveErrors::Duplicate {
+                        kind: kind.describe(),
+                        name: name.spanned.clone(),
+                    },
+                    name.span,
+                )
+                .with_note("first declared here", self.res.def(prev[0]).span);
+                self.error(err);
+                return;
+            }
+        } else if kind.is_local() {
+            if let Some(prev) = self.lookup_local(&name.spanned) {
+                let prev = self.res.def(prev);
+                let warning = SdwErr::from_pos(
+                    ResolveErrors::Shadowed {
+                        kind: kind.describe(),
+                        name: name.spanned.clone(),
+                    },
+                    name.span,
+                )
+                .with_note(format!("shadows this {}", prev.kind.describe()), prev.span);
+                self.state.warnings.push(warning);
+            }
+        }
+
+        let id = self.res.add(name, kind);
+        self.scope()
+            .values
+            .entry(name.spanned.clone())
+            .or_default()
+            .push(id);
+    }
+
+    /// a variable or parameter of the enclosing function, outside the current scope
+    fn lookup_local(&self, name: &str) -> Option<DefId> {
+        for scope in self.scopes.iter().rev().skip(1) {
+            if scope.kind == ScopeKind::Module {
+                break;
+            }
+            if let Some(ids) = scope.values.get(name) {
+                return ids
+                    .iter()
+                    .copied()
+                    .find(|id| self.res.def(*id).kind.is_local());
+            }
+            if scope.kind == ScopeKind::Function {
+                break;
+            }
+        }
+        None
+    }
+
+    fn lookup_value(&self, name: &str) -> Option<Res> {
+        // locals of an enclosing function aren't visible from a nested one
+        let mut crossed_fn = false;
+        for (depth, scope) in self.scopes.iter().enumerate().rev() {
+            if let Some(ids) = scope.values.get(name) {
+                let mut ids = ids
+                    .iter()
+                    .copied()
+                    .filter(|id| !crossed_fn || !self.res.def(*id).kind.is_local())
+                    .collect::<Vec<_>>();
+
+                match ids.as_slice() {
+                    [] => {}
+                    [id] if self.res.def(*id).kind.is_local() => return Some(Res::Def(*id)),
+                    _ if scope.kind == ScopeKind::Module => {
+                        // a module's functions are overloads of those of the modules around
+                        // it (`core`'s) - which `overload::pick` only falls back to
+                        for outer in self.scopes[..depth].iter().rev() {
+                            ids.extend(outer.values.get(name).into_iter().flatten());
+                        }
+                        return Some(Res::Overloads(ids));
+                    }
+                    _ => return Some(Res::Overloads(ids)),
+                }
+            }
+            if scope.kind == ScopeKind::Function {
+                crossed_fn = true;
+            }
+        }
+        None
+    }
+
+    fn use_type(&mut self, name: &Spanned<String>) {
+        if !self.find_type(name) {
+            self.error(SdwErr::from_pos(
+                ResolveErrors::Undeclared {
+                    kind: "type",
+                    name: name.spanned.clone(),
+                },
+                name.span,
+            ));
+        }
+    }
+
+    /// records what a type name refers to, returning whether it's declared
+    fn find_type(&mut self, name: &Spanned<String>) -> bool {
+        if BUILTIN_TYPES.contains(&name.spanned.as_str()) {
+            return true;
+        }
+
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.types.get(&name.spanned).copied());
+        if let Some(id) = found {
+            self.res.table.insert(name.span, Res::Def(id));
+        }
+        found.is_some()
+    }
+
+    /// `span` is where the use is recorded, which isn't always the name's own span
+    fn use_value(&mut self, name: &str, span: Span, kind: &'static str) {
+        match self.lookup_value(name) {
+            Some(res) => {
+                self.res.table.insert(span, res);
+            }
+            None => self.error(SdwErr::from_pos(
+                ResolveErrors::Undeclared {
+                    kind,
+                    name: name.to_owned(),
+                },
+                span,
+            )),
+        }
+    }
+
+    /// functions & types may be used before they are declared, so they are
+    /// brought into scope before anything in the block is resolved
+    fn declare_items(&mut self, stmts: &[Spanned<Stmt>]) {
+        for stmt in stmts {
+            match &stmt.spanned {
+                Stmt::Fn { name, .. } => self.declare_value(name, DefKind::Fn),
+                Stmt::Stub { name, .. } => self.declare_value(name, DefKind::Stub),
+                Stmt::Type { name, .. } => self.declare_type(name),
+                _ => {}
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block, kind: ScopeKind) {
+        self.stmts(&block.stmts, block.tail.as_deref(), kind);
+    }
+
+    fn stmts(&mut self, stmts: &[Spanned<Stmt>], tail: Option<&Spanned<Expr>>, kind: ScopeKind) {
+        self.scopes.push(Scope::new(kind));
+        self.declare_items(stmts);
+        for stmt in stmts {
+            self.stmt(&stmt.spanned);
+        }
+        if let Some(tail) = tail {
+            self.expr(tail);
+        }
+        self.scopes.pop();
+    }
+
+    fn enter_labels(&mut self) {
+        self.labels.push(LabelScope::default());
+    }
+
+    fn exit_labels(&mut self) {
+        let scope = self.labels.pop().expect("resolver: no label scope");
+        for goto in scope.gotos {
+            // unknown labels are reported by `labels::check`, which can suggest alternatives
+            if let Some(id) = scope.labels.get(&goto.spanned) {
+                self.res.table.insert(goto.span, Res::Def(*id));
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn {
+                return_type,
+                parameters,
+                body,
+                ..
+            } => {
+                self.use_type(return_type);
+                for (r#type, _) in parameters {
+                    self.use_type(r#type);
+                }
+
+                self.scopes.push(Scope::new(ScopeKind::Function));
+                for (_, name) in parameters {
+                    self.declare_value(name, DefKind::Param);
+                }
+                self.enter_labels();
+                self.block(body, ScopeKind::Block);
+                self.exit_labels();
+                self.scopes.pop();
+            }
+            Stmt::Stub {
+                return_type,
+                parameters,
+                ..
+            } => {
+                self.use_type(return_type);
+                for r#type in parameters {
+                    self.use_type(r#type);
+                }
+            }
+            Stmt::Loop { block } => self.block(block, ScopeKind::Block),
+            Stmt::Label { name } => {
+                // duplicates are reported by `labels::check`; the first declaration wins
+                let labels = &self.labels.last().expect("resolver: no label scope").labels;
+                if labels.contains_key(&name.spanned) {
+                    return;
+                }
+
+                let id = self.res.add(name, DefKind::Label);
+                self.labels
+                    .last_mut()
+                    .expect("resolver: no label scope")
+                    .labels
+                    .insert(name.spanned.clone(), id);
+            }
+            Stmt::Goto { name } => self
+                .labels
+                .last_mut()
+                .expect("resolver: no label scope")
+                .gotos
+                .push(name.clone()),
+            Stmt::Return { expr } => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+            }
+            Stmt::VarDec { name, initialiser } => {
+                // the initialiser can't see the variable it initialises
+                self.expr(initialiser);
+                self.declare_value(name, DefKind::Var);
+            }
+            Stmt::VarRes { name, updated } => {
+                self.use_value(&name.spanned, name.span, "variable");
+                self.expr(updated);
+            }
+            // declared by `declare_items`
+            Stmt::Type { bound, .. } => match &bound.spanned {
+                // a dangling alias is reported by `cycles::check`, along with its own aliases
+                Bound::Alias(to) => {
+                    self.find_type(to);
+                }
+                bound => self.bound(bound),
+            },
+            Stmt::Discard { expr } => self.expr(expr),
+        }
+    }
+
+    fn bound(&mut self, bound: &Bound) {
+        match bound {
+            Bound::Generic | Bound::Prim(_) => {}
+            Bound::Aggregate(generics) => {
+                for generic in generics {
+                    self.use_type(generic);
+                }
+            }
+            Bound::Struct(members) | Bound::Union(members) => {
+                for (bound, _) in members.iter().flatten() {
+                    self.bound(&bound.spanned);
+                }
+            }
+            Bound::Alias(to) => self.use_type(to),
+            Bound::Pointer(to) => self.bound(&to.spanned),
+            Bound::FnPtr { args, return_type } => {
+                for arg in args {
+                    self.use_type(arg);
+                }
+                self.use_type(return_type);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::IntLiteral(_) | Expr::BoolLiteral(_) | Expr::StrLiteral(_) => {}
+            Expr::Variable(name) => self.use_value(name, expr.span, "variable"),
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Attributed { expr: inner, .. }
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => self.expr(inner),
+            Expr::FnCall(name, args) => {
+                self.use_value(name, expr.span, "function");
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::BiOp(left, _, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            // members can only be checked once the object's type is known
+            Expr::ObjMember(object, _) => self.use_value(&object.spanned, object.span, "variable"),
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.expr(condition);
+                self.block(&then.spanned, ScopeKind::Block);
+                for (condition, block) in elifs {
+                    self.expr(condition);
+                    self.block(&block.spanned, ScopeKind::Block);
+                }
+                if let Some(r#else) = r#else {
+                    self.block(&r#else.spanned, ScopeKind::Block);
+                }
+            }
+            Expr::Block(block) => self.block(block, ScopeKind::Block),
+            Expr::StructLit { ty, fields } => {
+                self.use_type(ty);
+                match fields {
+                    StructLitFields::Positional(values) => {
+                        for value in values {
+                            self.expr(value);
+                        }
+                    }
+                    StructLitFields::Named(members) => {
+                        for (_, value) in members {
+                            self.expr(value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// binds every identifier in the module to its declaration, reporting undeclared &
+/// duplicate names as errors and shadowed locals as warnings.
+/// (labels are bound, but their errors are left to `labels::check`)
+///
+/// `core`'s statements (which come first) are a module of their own around the file's, so
+/// the file may declare the same names - which `core` never sees.
+pub fn resolve(state: &mut State, root: &Block) -> Resolutions {
+    let mut resolver = Resolver {
+        state,
+        res: Resolutions::default(),
+        scopes: Vec::new(),
+        labels: Vec::new(),
+    };
+
+    let core = root
+        .stmts
+        .iter()
+        .take_while(|stmt| driver::in_core(stmt.span))
+        .count();
+    let (core, file) = root.stmts.split_at(core);
+    resolver.enter_labels();
+    resolver.scopes.push(Scope::new(ScopeKind::Module));
+    resolver.declare_items(core);
+    for stmt in core {
+        resolver.stmt(&stmt.spanned);
+    }
+    resolver.stmts(file, root.tail.as_deref(), ScopeKind::Module);
+    resolver.scopes.pop();
+    resolver.exit_labels();
+    resolver.res
+}
@@ -0,0 +1,1215 @@
+use crate::overload;
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Prim(PrimType),
+    Void,
+    /// a declared struct, union or generic type. aliases are transparent, and never appear here
+    Named(DefId),
+    Pointer(Box<Ty>),
+    FnPtr {
+        args: Vec<Ty>,
+        ret: Box<Ty>,
+    },
+    /// the "type" of an expression which never produces a value (eg. a block ending in `return`)
+    Never,
+    /// stands in for anything that failed to type check, so one mistake isn't reported repeatedly
+    Error,
+}
+
+impl Ty {
+    pub const INT: Ty = Ty::Prim(PrimType::Int);
+    pub const UNT: Ty = Ty::Prim(PrimType::Unt);
+    pub const BOOL: Ty = Ty::Prim(PrimType::Bool);
+    pub const STRING: Ty = Ty::Prim(PrimType::String);
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Ty::Prim(PrimType::Int | PrimType::Unt))
+    }
+
+    /// types which are compatible with everything, as their expression never
+    /// produces a value (or was already reported as an error)
+    pub fn is_wildcard(&self) -> bool {
+        matches!(self, Ty::Never | Ty::Error)
+    }
+
+    pub fn display(&self, res: &Resolutions) -> String {
+        match self {
+            Ty::Prim(prim) => match prim {
+                PrimType::Int => "int",
+                PrimType::Unt => "unt",
+                PrimType::Float => "float",
+                PrimType::Bool => "bool",
+                PrimType::String => "string",
+            }
+            .to_owned(),
+            Ty::Void => "void".to_owned(),
+            Ty::Named(id) => res.def(*id).name.clone(),
+            Ty::Pointer(to) => format!("&{}", to.display(res)),
+            Ty::FnPtr { args, ret } => format!(
+                "({}) -> {}",
+                args.iter()
+                    .map(|arg| arg.display(res))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ret.display(res)
+            ),
+            Ty::Never => "!".to_owned(),
+            Ty::Error => "{unknown}".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeDecl {
+    /// members in declaration order. a tag struct (`type Done struct;`) has none
+    Struct(Vec<(String, Ty)>),
+    Union(Vec<(String, Ty)>),
+    Generic,
+    /// the generics a `type DoType Debug + Print;` is compatible with
+    Aggregate(Vec<DefId>),
+    /// any other bound - `type Ser S;`, `type SerPtr &Ser;`, `type Count int;` ..
+    Alias(Ty),
+}
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    /// declared `#[ no_return ]` - calls never come back
+    pub no_return: bool,
+}
+
+impl Signature {
+    /// the type of a call's result
+    pub fn result(&self) -> Ty {
+        if self.no_return {
+            Ty::Never
+        } else {
+            self.ret.clone()
+        }
+    }
+}
+
+/// the side-tables produced by type checking.
+/// like `Resolutions`, expressions are keyed by their span.
+#[derive(Debug, Default)]
+pub struct Types {
+    pub decls: HashMap<DefId, TypeDecl>,
+    /// for every `fn` & stub
+    pub sigs: HashMap<DefId, Signature>,
+    /// for every variable & parameter
+    pub locals: HashMap<DefId, Ty>,
+    exprs: HashMap<Span, Ty>,
+    /// the function each `Expr::FnCall` (keyed by its span) calls,
+    /// unless it calls through a function pointer
+    calls: HashMap<Span, DefId>,
+    /// calls matching on a union argument, in place of a single function in `calls`
+    dispatches: HashMap<Span, overload::Dispatch>,
+}
+
+impl Types {
+    pub fn expr(&self, span: Span) -> Option<&Ty> {
+        self.exprs.get(&span)
+    }
+
+    pub fn call(&self, span: Span) -> Option<DefId> {
+        self.calls.get(&span).copied()
+    }
+
+    pub fn dispatch(&self, span: Span) -> Option<&overload::Dispatch> {
+        self.dispatches.get(&span)
+    }
+
+    /// the tag a union value holding a `variant` carries - its index among the union's members
+    pub fn discriminant(&self, union: DefId, variant: &Ty) -> Option<usize> {
+        match self.decls.get(&union)? {
+            TypeDecl::Union(members) => members.iter().position(|(_, member)| member == variant),
+            _ => None,
+        }
+    }
+
+    /// the members of a struct or union, looking through pointers
+    pub fn members(&self, ty: &Ty) -> Option<&[(String, Ty)]> {
+        match ty {
+            Ty::Named(id) => match self.decls.get(id)? {
+                TypeDecl::Struct(members) | TypeDecl::Union(members) => Some(members),
+                _ => None,
+            },
+            Ty::Pointer(to) => self.members(to),
+            _ => None,
+        }
+    }
+
+    /// whether a value of type `from` can be used where `to` is expected
+    pub fn coerces(&self, from: &Ty, to: &Ty) -> bool {
+        if from == to || from.is_wildcard() || to.is_wildcard() {
+            return true;
+        }
+
+        let Ty::Named(to_id) = to else {
+            return false;
+        };
+        match self.decls.get(to_id) {
+            // any type may stand in for a generic; whether it actually
+            // implements the generic's interface is checked separately
+            Some(TypeDecl::Generic) => true,
+            Some(TypeDecl::Aggregate(generics)) => match from {
+                Ty::Named(from_id) => match self.decls.get(from_id) {
+                    Some(TypeDecl::Aggregate(from_generics)) => generics
+                        .iter()
+                        .all(|generic| from_generics.contains(generic)),
+                    Some(TypeDecl::Generic) => false,
+                    _ => true,
+                },
+                _ => true,
+            },
+            // a variant is implicitly converted to its union
+            Some(TypeDecl::Union(members)) => members.iter().any(|(_, member)| member == from),
+            _ => false,
+        }
+    }
+}
+
+/// how the type of a declared name is to be found - it may be declared later in the file
+enum PendingDecl<'a> {
+    Bound(&'a Bound),
+    Done(TypeDecl),
+}
+
+struct Checker<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    types: Types,
+    /// the return type of each function currently being checked (innermost last)
+    returns: Vec<(Ty, Span)>,
+    /// aliases part-way through being lowered; used to avoid looping on `type A B; type B A;`
+    lowering: HashSet<DefId>,
+}
+
+macro_rules! type_err {
+    ($checker:expr, $err:expr, $span:expr) => {{
+        $checker.state.errors.push(SdwErr::from_pos($err, $span));
+    }};
+}
+
+impl<'a> Checker<'a> {
+    fn show(&self, ty: &Ty) -> String {
+        ty.display(self.res)
+    }
+
+    fn record(&mut self, span: Span, ty: Ty) -> Ty {
+        self.types.exprs.insert(span, ty.clone());
+        ty
+    }
+
+    /*
+     * declarations
+     */
+
+    fn collect_decls<'b>(&mut self, block: &'b Block, pending: &mut HashMap<DefId, &'b Bound>) {
+        for stmt in &block.stmts {
+            match &stmt.spanned {
+                Stmt::Type { name, bound, .. } => {
+                    if let Some(id) = self.res.def_at(name.span) {
+                        pending.insert(id, &bound.spanned);
+                    }
+                }
+                Stmt::Fn { body, .. } => self.collect_decls(body, pending),
+                _ => {}
+            }
+        }
+    }
+
+    /// lowers the type name `name` (spanned at `span`)
+    fn lower_name(&mut self, name: &str, span: Span, decls: &HashMap<DefId, PendingDecl>) -> Ty {
+        if let Some(builtin) = builtin(name) {
+            return builtin;
+        }
+
+        // undeclared names have already been reported by the resolver
+        let Some(id) = self.res.def_at(span) else {
+            return Ty::Error;
+        };
+        self.lower_def(id, decls)
+    }
+
+    fn lower_def(&mut self, id: DefId, decls: &HashMap<DefId, PendingDecl>) -> Ty {
+        match decls.get(&id) {
+            Some(PendingDecl::Bound(
+                Bound::Struct(_) | Bound::Union(_) | Bound::Generic | Bound::Aggregate(_),
+            ))
+            | Some(PendingDecl::Done(
+                TypeDecl::Struct(_)
+                | TypeDecl::Union(_)
+                | TypeDecl::Generic
+                | TypeDecl::Aggregate(_),
+            )) => Ty::Named(id),
+            Some(PendingDecl::Done(TypeDecl::Alias(ty))) => ty.clone(),
+            Some(PendingDecl::Bound(bound)) => {
+                // a cyclic alias (`type A B; type B A;`) has no type to lower to
+                if !self.lowering.insert(id) {
+                    return Ty::Error;
+                }
+                let ty = self.lower_bound(bound, decls);
+                self.lowering.remove(&id);
+                ty
+            }
+            None => Ty::Error,
+        }
+    }
+
+    fn lower_bound(&mut self, bound: &Bound, decls: &HashMap<DefId, PendingDecl>) -> Ty {
+        match bound {
+            Bound::Prim(prim) => Ty::Prim(prim.spanned),
+            Bound::Alias(name) => self.lower_name(&name.spanned, name.span, decls),
+            Bound::Pointer(to) => Ty::Pointer(Box::new(self.lower_bound(&to.spanned, decls))),
+            Bound::FnPtr { args, return_type } => Ty::FnPtr {
+                args: args
+                    .iter()
+                    .map(|arg| self.lower_name(&arg.spanned, arg.span, decls))
+                    .collect(),
+                ret: Box::new(self.lower_name(&return_type.spanned, return_type.span, decls)),
+            },
+            // only valid as the bound of a declaration, which `declare` handles
+            Bound::Struct(_) | Bound::Union(_) | Bound::Generic | Bound::Aggregate(_) => Ty::Error,
+        }
+    }
+
+    fn lower_members(
+        &mut self,
+        members: &Option<Vec<(Spanned<Bound>, Spanned<String>)>>,
+        decls: &HashMap<DefId, PendingDecl>,
+    ) -> Vec<(String, Ty)> {
+        let mut lowered: Vec<(String, Ty)> = Vec::new();
+        for (bound, name) in members.iter().flatten() {
+            if lowered.iter().any(|(prev, _)| *prev == name.spanned) {
+                type_err!(
+                    self,
+                    TypeErrors::DuplicateMember(name.spanned.clone()),
+                    name.span
+                );
+                continue;
+            }
+            if let Bound::Struct(_) | Bound::Union(_) = bound.spanned {
+                type_err!(self, TypeErrors::AnonymousAggregate, bound.span);
+            }
+            let ty = self.lower_bound(&bound.spanned, decls);
+            lowered.push((name.spanned.clone(), ty));
+        }
+        lowered
+    }
+
+    fn declare(&mut self, root: &Block) {
+        let mut bounds = HashMap::new();
+        self.collect_decls(root, &mut bounds);
+
+        let mut decls = bounds
+            .iter()
+            .map(|(id, bound)| (*id, PendingDecl::Bound(bound)))
+            .collect::<HashMap<_, _>>();
+
+        let mut ids = bounds.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+        for id in ids {
+            let decl = match bounds[&id] {
+                Bound::Struct(members) => TypeDecl::Struct(self.lower_members(members, &decls)),
+                Bound::Union(members) => TypeDecl::Union(self.lower_members(members, &decls)),
+                Bound::Generic => TypeDecl::Generic,
+                Bound::Aggregate(generics) => TypeDecl::Aggregate(
+                    generics
+                        .iter()
+                        .filter_map(|generic| {
+                            let id = self.res.def_at(generic.span)?;
+                            if !matches!(bounds.get(&id), Some(Bound::Generic)) {
+                                type_err!(
+                                    self,
+                                    TypeErrors::AggregateOfConcrete(generic.spanned.clone()),
+                                    generic.span
+                                );
+                            }
+                            Some(id)
+                        })
+                        .collect(),
+                ),
+                _ => TypeDecl::Alias(self.lower_def(id, &decls)),
+            };
+            decls.insert(id, PendingDecl::Done(decl.clone()));
+            self.types.decls.insert(id, decl);
+        }
+    }
+
+    /// lowers a type name once every declaration has been lowered
+    fn lower_type(&self, name: &Spanned<String>) -> Ty {
+        if let Some(builtin) = builtin(name.spanned.as_str()) {
+            return builtin;
+        }
+
+        let Some(id) = self.res.def_at(name.span) else {
+            return Ty::Error;
+        };
+        match self.types.decls.get(&id) {
+            Some(TypeDecl::Alias(ty)) => ty.clone(),
+            Some(_) => Ty::Named(id),
+            None => Ty::Error,
+        }
+    }
+
+    fn collect_sigs(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            match &stmt.spanned {
+                Stmt::Fn {
+                    attrs,
+                    return_type,
+                    name,
+                    parameters,
+                    body,
+                    ..
+                } => {
+                    let params = parameters
+                        .iter()
+                        .map(|(r#type, _)| self.lower_type(r#type))
+                        .collect::<Vec<_>>();
+                    for ((_, name), ty) in parameters.iter().zip(&params) {
+                        if let Some(id) = self.res.def_at(name.span) {
+                            self.types.locals.insert(id, ty.clone());
+                        }
+                    }
+                    let ret = self.lower_type(return_type);
+                    if let Some(id) = self.res.def_at(name.span) {
+                        let no_return = has_attr(attrs, "no_return");
+                        self.types.sigs.insert(
+                            id,
+                            Signature {
+                                params,
+                                ret,
+                                no_return,
+                            },
+                        );
+                    }
+                    self.collect_sigs(body);
+                }
+                Stmt::Stub {
+                    attrs,
+                    return_type,
+                    name,
+                    parameters,
+                    ..
+                } => {
+                    let params = parameters
+                        .iter()
+                        .map(|r#type| self.lower_type(r#type))
+                        .collect();
+                    let ret = self.lower_type(return_type);
+                    if let Some(id) = self.res.def_at(name.span) {
+                        let no_return = has_attr(attrs, "no_return");
+                        self.types.sigs.insert(
+                            id,
+                            Signature {
+                                params,
+                                ret,
+                                no_return,
+                            },
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /*
+     * statements
+     */
+
+    /// checks that `found` (the type of the expression at `span`) can be used as `expected`
+    fn expect(&mut self, found: &Ty, expected: &Ty, span: Span, because: Option<Span>) {
+        if self.types.coerces(found, expected) {
+            return;
+        }
+
+        let mut err = SdwErr::from_pos(
+            TypeErrors::Mismatch {
+                expected: self.show(expected),
+                found: self.show(found),
+            },
+            span,
+        );
+        if let Some(because) = because {
+            err = err.with_note(
+                format!("`{}` expected because of this", self.show(expected)),
+                because,
+            );
+        }
+        self.state.errors.push(err);
+    }
+
+    /// returns whether the statement diverges (control never continues past it)
+    fn stmt(&mut self, stmt: &Spanned<Stmt>) -> bool {
+        match &stmt.spanned {
+            Stmt::Fn {
+                return_type,
+                name,
+                body,
+                ..
+            } => {
+                let ret = self
+                    .res
+                    .def_at(name.span)
+                    .and_then(|id| self.types.sigs.get(&id))
+                    .map(|sig| sig.ret.clone())
+                    .unwrap_or(Ty::Error);
+
+                self.returns.push((ret.clone(), return_type.span));
+                let found = self.block(body, Some(&ret));
+                self.returns.pop();
+
+                // without a tail, the body must `return` on every path - `cfg::check` verifies that
+                if let Some(tail) = &body.tail {
+                    self.expect(&found, &ret, tail.span, Some(return_type.span));
+                }
+                false
+            }
+            Stmt::Stub { .. } | Stmt::Type { .. } | Stmt::Label { .. } => false,
+            Stmt::Goto { .. } => true,
+            Stmt::Loop { block } => {
+                self.block(block, None);
+                // the only ways out of a loop are `goto` & `return`
+                !contains_goto(block)
+            }
+            Stmt::Return { expr } => {
+                let Some((ret, ret_span)) = self.returns.last().cloned() else {
+                    type_err!(self, TypeErrors::ReturnOutsideFn, stmt.span);
+                    if let Some(expr) = expr {
+                        self.expr(expr, None);
+                    }
+                    return true;
+                };
+
+                match expr {
+                    Some(expr) => {
+                        let found = self.expr(expr, Some(&ret));
+                        self.expect(&found, &ret, expr.span, Some(ret_span));
+                    }
+                    None => self.expect(&Ty::Void, &ret, stmt.span, Some(ret_span)),
+                }
+                true
+            }
+            Stmt::VarDec { name, initialiser } => {
+                let ty = self.expr(initialiser, None);
+                if ty == Ty::Void {
+                    type_err!(
+                        self,
+                        TypeErrors::VoidBinding(name.spanned.clone()),
+                        initialiser.span
+                    );
+                }
+                if let Some(id) = self.res.def_at(name.span) {
+                    self.types.locals.insert(id, ty.clone());
+                }
+                ty == Ty::Never
+            }
+            Stmt::VarRes { name, updated } => {
+                let target = match self.res.get(name.span) {
+                    Some(Res::Def(id)) => self.types.locals.get(id).cloned(),
+                    _ => None,
+                };
+                let Some(target) = target else {
+                    if self.res.get(name.span).is_some() {
+                        type_err!(
+                            self,
+                            TypeErrors::NotAssignable(name.spanned.clone()),
+                            name.span
+                        );
+                    }
+                    return self.expr(updated, None) == Ty::Never;
+                };
+
+                let found = self.expr(updated, Some(&target));
+                let declared = self.res.def_at(name.span).map(|id| self.res.def(id).span);
+                self.expect(&found, &target, updated.span, declared);
+                found == Ty::Never
+            }
+            Stmt::Discard { expr } => self.expr(expr, None) == Ty::Never,
+        }
+    }
+
+    fn block(&mut self, block: &Block, expected: Option<&Ty>) -> Ty {
+        let mut diverges = false;
+        for stmt in &block.stmts {
+            // a label can be jumped to, so whatever follows is reachable again
+            if let Stmt::Label { .. } = stmt.spanned {
+                diverges = false;
+            }
+            diverges |= self.stmt(stmt);
+        }
+
+        let tail = block.tail.as_ref().map(|tail| self.expr(tail, expected));
+        if diverges {
+            Ty::Never
+        } else {
+            tail.unwrap_or(Ty::Void)
+        }
+    }
+
+    /*
+     * expressions
+     */
+
+    /// `expected` is only a hint (eg. so `1` can be an `unt`) - the caller must still check
+    fn expr(&mut self, expr: &Spanned<Expr>, expected: Option<&Ty>) -> Ty {
+        let ty = self.infer(expr, expected);
+        self.record(expr.span, ty)
+    }
+
+    fn infer(&mut self, expr: &Spanned<Expr>, expected: Option<&Ty>) -> Ty {
+        match &expr.spanned {
+            Expr::IntLiteral(_) => match expected {
+                Some(Ty::Prim(PrimType::Unt)) => Ty::UNT,
+                _ => Ty::INT,
+            },
+            Expr::BoolLiteral(_) => Ty::BOOL,
+            Expr::StrLiteral(_) => Ty::STRING,
+            Expr::Variable(name) => match self.res.get(expr.span).cloned() {
+                Some(Res::Def(id)) => self.types.locals.get(&id).cloned().unwrap_or(Ty::Error),
+                Some(Res::Overloads(ids)) => match ids.as_slice() {
+                    [id] => {
+                        let sig = self.types.sigs.get(id);
+                        sig.map_or(Ty::Error, |sig| Ty::FnPtr {
+                            args: sig.params.clone(),
+                            ret: Box::new(sig.ret.clone()),
+                        })
+                    }
+                    _ => {
+                        type_err!(self, TypeErrors::OverloadedValue(name.clone()), expr.span);
+                        Ty::Error
+                    }
+                },
+                None => Ty::Error,
+            },
+            Expr::SubExpr(inner) | Expr::Attributed { expr: inner, .. } => {
+                self.expr(inner, expected)
+            }
+            Expr::UnaryNeg(inner) | Expr::UnaryPos(inner) => {
+                let ty = self.expr(inner, expected);
+                self.expect_integer(&ty, inner.span);
+                ty
+            }
+            Expr::UnaryNot(inner) => {
+                let ty = self.expr(inner, Some(&Ty::BOOL));
+                self.expect(&ty, &Ty::BOOL, inner.span, None);
+                Ty::BOOL
+            }
+            Expr::Referal(inner) => {
+                if !is_place(&inner.spanned) {
+                    type_err!(self, TypeErrors::NotAPlace, inner.span);
+                }
+                let pointee = match expected {
+                    Some(Ty::Pointer(to)) => Some(to.as_ref()),
+                    _ => None,
+                };
+                let ty = self.expr(inner, pointee);
+                Ty::Pointer(Box::new(ty))
+            }
+            Expr::Indir(inner) => match self.expr(inner, None) {
+                Ty::Pointer(to) => *to,
+                Ty::Error => Ty::Error,
+                other => {
+                    type_err!(
+                        self,
+                        TypeErrors::ExpectedPointer(self.show(&other)),
+                        inner.span
+                    );
+                    Ty::Error
+                }
+            },
+            Expr::BiOp(left, op, right) => self.biop(left, *op, right, expected),
+            Expr::ObjMember(object, member) => {
+                let object_ty = match self.res.def_at(object.span) {
+                    Some(id) => self.types.locals.get(&id).cloned().unwrap_or(Ty::Error),
+                    None => Ty::Error,
+                };
+                if object_ty == Ty::Error {
+                    return Ty::Error;
+                }
+
+                let Some(members) = self.types.members(&object_ty) else {
+                    type_err!(
+                        self,
+                        TypeErrors::NoMembers(self.show(&object_ty)),
+                        object.span
+                    );
+                    return Ty::Error;
+                };
+                match members.iter().find(|(name, _)| *name == member.spanned) {
+                    Some((_, ty)) => ty.clone(),
+                    None => {
+                        type_err!(
+                            self,
+                            TypeErrors::NoMember {
+                                ty: self.show(&object_ty),
+                                member: member.spanned.clone(),
+                            },
+                            member.span
+                        );
+                        Ty::Error
+                    }
+                }
+            }
+            Expr::FnCall(name, args) => self.call(expr.span, name, args),
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                let check_condition = |checker: &mut Self, condition: &Spanned<Expr>| {
+                    let ty = checker.expr(condition, Some(&Ty::BOOL));
+                    checker.expect(&ty, &Ty::BOOL, condition.span, None);
+                };
+                check_condition(self, condition);
+                for (condition, _) in elifs {
+                    check_condition(self, condition);
+                }
+
+                let Some(r#else) = r#else else {
+                    // without an `else`, there's no value to give when every condition fails
+                    for block in std::iter::once(then).chain(elifs.iter().map(|(_, block)| block)) {
+                        let ty = self.block(&block.spanned, Some(&Ty::Void));
+                        let span = block.spanned.tail.as_ref().map_or(block.span, |t| t.span);
+                        self.expect(&ty, &Ty::Void, span, None);
+                    }
+                    return Ty::Void;
+                };
+
+                // the first branch with a value decides the type of the others
+                let branches = std::iter::once(then)
+                    .chain(elifs.iter().map(|(_, block)| block))
+                    .chain(std::iter::once(r#else));
+                let mut result: Option<(Ty, Span)> = None;
+                let mut diverges = true;
+                for block in branches {
+                    let hint = result.as_ref().map(|(ty, _)| ty).or(expected).cloned();
+                    let ty = self.block(&block.spanned, hint.as_ref());
+                    let span = block.spanned.tail.as_ref().map_or(block.span, |t| t.span);
+                    diverges &= ty == Ty::Never;
+                    match &result {
+                        _ if ty == Ty::Never => {}
+                        Some((first, first_span)) => {
+                            let (first, first_span) = (first.clone(), *first_span);
+                            self.expect(&ty, &first, span, Some(first_span));
+                        }
+                        // eg. `if err { None {} } else { Some { 10 } }` where an `Option` is
+                        // expected - the branches are each converted to the union
+                        None => match expected {
+                            Some(hint) if !hint.is_wildcard() && self.types.coerces(&ty, hint) => {
+                                result = Some((hint.clone(), span))
+                            }
+                            _ => result = Some((ty, span)),
+                        },
+                    }
+                }
+
+                if diverges {
+                    Ty::Never
+                } else {
+                    result.map_or(Ty::Error, |(ty, _)| ty)
+                }
+            }
+            Expr::Block(block) => self.block(block, expected),
+            Expr::StructLit { ty, fields } => self.struct_lit(ty, fields),
+        }
+    }
+
+    fn expect_integer(&mut self, ty: &Ty, span: Span) {
+        if !ty.is_integer() && !ty.is_wildcard() {
+            type_err!(self, TypeErrors::ExpectedInteger(self.show(ty)), span);
+        }
+    }
+
+    fn biop(
+        &mut self,
+        left: &Spanned<Expr>,
+        op: BiOps,
+        right: &Spanned<Expr>,
+        expected: Option<&Ty>,
+    ) -> Ty {
+        match op {
+            BiOps::LogOr | BiOps::LogAnd | BiOps::LogNot => {
+                let left_ty = self.expr(left, Some(&Ty::BOOL));
+                let right_ty = self.expr(right, Some(&Ty::BOOL));
+                self.expect(&left_ty, &Ty::BOOL, left.span, None);
+                self.expect(&right_ty, &Ty::BOOL, right.span, None);
+                Ty::BOOL
+            }
+            BiOps::Eq | BiOps::NEq => {
+                let left_ty = self.expr(left, None);
+                let right_ty = self.expr(right, Some(&left_ty));
+                if !matches!(left_ty, Ty::Prim(_) | Ty::Pointer(_)) && !left_ty.is_wildcard() {
+                    type_err!(
+                        self,
+                        TypeErrors::NotComparable(self.show(&left_ty)),
+                        left.span
+                    );
+                } else {
+                    self.operands_match(&left_ty, left.span, &right_ty, right.span);
+                }
+                Ty::BOOL
+            }
+            BiOps::Gr | BiOps::Ls | BiOps::GrEq | BiOps::LsEq => {
+                let left_ty = self.expr(left, None);
+                let right_ty = self.expr(right, Some(&left_ty));
+                self.integer_operands(&left_ty, left.span, &right_ty, right.span);
+                Ty::BOOL
+            }
+            // arithmetic & bitwise
+            _ => {
+                let hint = expected.filter(|ty| ty.is_integer());
+                let left_ty = self.expr(left, hint);
+                let right_ty = self.expr(right, Some(&left_ty));
+                self.integer_operands(&left_ty, left.span, &right_ty, right.span);
+                if left_ty.is_wildcard() {
+                    right_ty
+                } else {
+                    left_ty
+                }
+            }
+        }
+    }
+
+    fn integer_operands(&mut self, left: &Ty, left_span: Span, right: &Ty, right_span: Span) {
+        let sides = [
+            (left, left_span, right, right_span),
+            (right, right_span, left, left_span),
+        ];
+        for (ty, span, other, other_span) in sides {
+            if ty.is_integer() || ty.is_wildcard() {
+                continue;
+            }
+            let mut err = SdwErr::from_pos(TypeErrors::ExpectedInteger(self.show(ty)), span);
+            if !other.is_wildcard() {
+                err = err.with_note(
+                    format!("the other operand is `{}`", self.show(other)),
+                    other_span,
+                );
+            }
+            self.state.errors.push(err);
+        }
+        if left.is_integer() && right.is_integer() {
+            self.operands_match(left, left_span, right, right_span);
+        }
+    }
+
+    fn operands_match(&mut self, left: &Ty, left_span: Span, right: &Ty, right_span: Span) {
+        if left == right || left.is_wildcard() || right.is_wildcard() {
+            return;
+        }
+        let err = SdwErr::from_pos(
+            TypeErrors::OperandMismatch {
+                left: self.show(left),
+                right: self.show(right),
+            },
+            right_span,
+        )
+        .with_note(format!("this is `{}`", self.show(left)), left_span);
+        self.state.errors.push(err);
+    }
+
+    fn call(&mut self, span: Span, name: &str, args: &[Box<Spanned<Expr>>]) -> Ty {
+        match self.res.get(span).cloned() {
+            // calling through a function pointer
+            Some(Res::Def(id)) => {
+                let callee = self.types.locals.get(&id).cloned().unwrap_or(Ty::Error);
+                let Ty::FnPtr { args: params, ret } = callee else {
+                    if callee != Ty::Error {
+                        type_err!(self, TypeErrors::NotCallable(self.show(&callee)), span);
+                    }
+                    self.args(args, None);
+                    return Ty::Error;
+                };
+                let found = self.args(args, Some(&params));
+                self.check_args(span, args, &params, found);
+                *ret
+            }
+            Some(Res::Overloads(candidates)) => self
+                .pick_overload(span, name, args, &candidates)
+                .unwrap_or(Ty::Error),
+            None => {
+                self.args(args, None);
+                Ty::Error
+            }
+        }
+    }
+
+    fn args(&mut self, args: &[Box<Spanned<Expr>>], params: Option<&[Ty]>) -> Vec<Ty> {
+        args.iter()
+            .enumerate()
+            .map(|(idx, arg)| self.expr(arg, params.and_then(|params| params.get(idx))))
+            .collect()
+    }
+
+    fn check_args(
+        &mut self,
+        span: Span,
+        args: &[Box<Spanned<Expr>>],
+        params: &[Ty],
+        found: Vec<Ty>,
+    ) {
+        if found.len() != params.len() {
+            type_err!(
+                self,
+                TypeErrors::ArgCount {
+                    expected: params.len(),
+                    found: found.len(),
+                },
+                span
+            );
+            return;
+        }
+        for ((ty, param), arg) in found.iter().zip(params).zip(args) {
+            self.expect(ty, param, arg.span, None);
+        }
+    }
+
+    /// picks which of a function's overloads a call refers to (see `overload::pick`), or which
+    /// it dispatches between at runtime, returning the type of its result
+    fn pick_overload(
+        &mut self,
+        span: Span,
+        name: &str,
+        args: &[Box<Spanned<Expr>>],
+        candidates: &[DefId],
+    ) -> Option<Ty> {
+        // with only one candidate, its parameters can guide the arguments (eg. `1` as an `unt`)
+        let found = if let [only] = candidates {
+            let params = self.types.sigs.get(only)?.params.clone();
+            let found = self.args(args, Some(&params));
+            let fits = found.len() == params.len()
+                && found
+                    .iter()
+                    .zip(&params)
+                    .all(|(ty, param)| self.types.coerces(ty, param));
+            if fits || !self.has_union(&found) {
+                self.check_args(span, args, &params, found);
+                self.types.calls.insert(span, *only);
+                return Some(self.types.sigs[only].result());
+            }
+            found
+        } else {
+            self.args(args, None)
+        };
+
+        let (err, listed) = match overload::pick(&self.types, self.res, &found, candidates) {
+            overload::Pick::Found(target) => {
+                self.types.calls.insert(span, target);
+                return Some(self.types.sigs[&target].result());
+            }
+            overload::Pick::Ambiguous(best) => (
+                TypeErrors::AmbiguousCall {
+                    name: name.to_owned(),
+                    args: self.show_all(&found),
+                },
+                best,
+            ),
+            overload::Pick::NoMatch => {
+                match overload::dispatch(&self.types, self.res, &found, candidates) {
+                    Some(overload::Dispatched::Found(dispatch)) => {
+                        return Some(self.dispatch(span, name, dispatch));
+                    }
+                    Some(overload::Dispatched::Missing(combinations)) => {
+                        self.missing_combinations(span, name, &found, args, &combinations);
+                        return None;
+                    }
+                    None => (
+                        TypeErrors::NoMatchingOverload {
+                            name: name.to_owned(),
+                            args: self.show_all(&found),
+                        },
+                        candidates.to_vec(),
+                    ),
+                }
+            }
+        };
+
+        let mut err = SdwErr::from_pos(err, span);
+        for candidate in listed {
+            err = err.with_note(
+                format!(
+                    "candidate: `{}`",
+                    overload::describe(&self.types, self.res, candidate)
+                ),
+                self.res.def(candidate).span,
+            );
+        }
+        self.state.errors.push(err);
+        None
+    }
+
+    fn has_union(&self, tys: &[Ty]) -> bool {
+        tys.iter().any(|ty| {
+            matches!(ty, Ty::Named(id) if matches!(self.types.decls.get(id), Some(TypeDecl::Union(_))))
+        })
+    }
+
+    /// records a call dispatched on a union; each arm must return the same type
+    fn dispatch(&mut self, span: Span, name: &str, dispatch: overload::Dispatch) -> Ty {
+        let targets = dispatch.targets();
+        let rets = targets
+            .iter()
+            .map(|target| self.types.sigs[target].result())
+            .collect::<Vec<_>>();
+        let ret = rets
+            .iter()
+            .find(|ret| !ret.is_wildcard())
+            .cloned()
+            .unwrap_or(Ty::Never);
+
+        for (arm, arm_ret) in targets.iter().zip(&rets) {
+            if self.types.coerces(arm_ret, &ret) {
+                continue;
+            }
+            let err = SdwErr::from_pos(
+                TypeErrors::Mismatch {
+                    expected: self.show(&ret),
+                    found: self.show(arm_ret),
+                },
+                span,
+            )
+            .with_note(
+                format!(
+                    "every overload `{name}` dispatches to must return the same type, but `{}` doesn't",
+                    overload::describe(&self.types, self.res, *arm)
+                ),
+                self.res.def(*arm).span,
+            );
+            self.state.errors.push(err);
+        }
+
+        self.types.dispatches.insert(span, dispatch);
+        ret
+    }
+
+    /// reports each combination of variants (in place of the union arguments, `found`) which
+    /// no overload takes
+    fn missing_combinations(
+        &mut self,
+        span: Span,
+        name: &str,
+        found: &[Ty],
+        args: &[Box<Spanned<Expr>>],
+        combinations: &[Vec<Ty>],
+    ) {
+        for combination in combinations {
+            let mut err = SdwErr::from_pos(
+                TypeErrors::MissingVariant {
+                    name: name.to_owned(),
+                    args: self.show_all(combination),
+                },
+                span,
+            );
+            for ((union, variant), arg) in found.iter().zip(combination).zip(args) {
+                if union != variant {
+                    err = err.with_note(
+                        format!(
+                            "this is a `{}`, which may hold a `{}`",
+                            self.show(union),
+                            self.show(variant)
+                        ),
+                        arg.span,
+                    );
+                }
+            }
+            self.state.errors.push(err);
+        }
+    }
+
+    fn show_all(&self, tys: &[Ty]) -> String {
+        tys.iter()
+            .map(|ty| self.show(ty))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn struct_lit(&mut self, ty: &Spanned<String>, fields: &StructLitFields) -> Ty {
+        let lit_ty = self.lower_type(ty);
+        if lit_ty == Ty::Error {
+            return Ty::Error;
+        }
+
+        let (members, union) = match &lit_ty {
+            Ty::Named(id) => match self.types.decls.get(id) {
+                Some(TypeDecl::Struct(members)) => (members.clone(), false),
+                Some(TypeDecl::Union(members)) => (members.clone(), true),
+                _ => (Vec::new(), false),
+            },
+            _ => (Vec::new(), false),
+        };
+        let is_aggregate = matches!(&lit_ty, Ty::Named(id)
+            if matches!(self.types.decls.get(id), Some(TypeDecl::Struct(_) | TypeDecl::Union(_))));
+        if !is_aggregate {
+            type_err!(self, TypeErrors::NotAStruct(self.show(&lit_ty)), ty.span);
+            return Ty::Error;
+        }
+
+        match fields {
+            StructLitFields::Positional(values) => {
+                // a union holds one member at a time, so can only be built by name
+                if union && !values.is_empty() {
+                    type_err!(
+                        self,
+                        TypeErrors::PositionalUnion(self.show(&lit_ty)),
+                        ty.span
+                    );
+                    return lit_ty;
+                }
+                if values.len() != members.len() {
+                    type_err!(
+                        self,
+                        TypeErrors::FieldCount {
+                            ty: self.show(&lit_ty),
+                            expected: members.len(),
+                            found: values.len(),
+                        },
+                        ty.span
+                    );
+                }
+                for (value, (_, member)) in values.iter().zip(&members) {
+                    let found = self.expr(value, Some(member));
+                    self.expect(&found, member, value.span, None);
+                }
+            }
+            StructLitFields::Named(values) => {
+                let mut given: Vec<&str> = Vec::new();
+                for (name, value) in values {
+                    let Some((_, member)) = members.iter().find(|(m, _)| *m == name.spanned) else {
+                        type_err!(
+                            self,
+                            TypeErrors::NoMember {
+                                ty: self.show(&lit_ty),
+                                member: name.spanned.clone(),
+                            },
+                            name.span
+                        );
+                        self.expr(value, None);
+                        continue;
+                    };
+                    if given.contains(&name.spanned.as_str()) {
+                        type_err!(
+                            self,
+                            TypeErrors::DuplicateMember(name.spanned.clone()),
+                            name.span
+                        );
+                    }
+                    given.push(&name.spanned);
+                    let found = self.expr(value, Some(member));
+                    self.expect(&found, member, value.span, None);
+                }
+
+                if union && given.len() > 1 {
+                    type_err!(
+                        self,
+                        TypeErrors::UnionFieldCount(self.show(&lit_ty)),
+                        ty.span
+                    );
+                }
+                if !union {
+                    for (member, _) in &members {
+                        if !given.contains(&member.as_str()) {
+                            type_err!(
+                                self,
+                                TypeErrors::MissingMember {
+                                    ty: self.show(&lit_ty),
+                                    member: member.clone(),
+                                },
+                                ty.span
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        lit_ty
+    }
+}
+
+/// the types of `resolve::BUILTIN_TYPES`
+fn builtin(name: &str) -> Option<Ty> {
+    Some(match name {
+        "int" => Ty::INT,
+        "unt" => Ty::UNT,
+        "float" => Ty::Prim(PrimType::Float),
+        "bool" => Ty::BOOL,
+        "string" => Ty::Prim(PrimType::String),
+        "void" => Ty::Void,
+        _ => return None,
+    })
+}
+
+/// whether `&` can take the address of the expression
+fn is_place(expr: &Expr) -> bool {
+    match expr {
+        Expr::Variable(_) | Expr::ObjMember(..) | Expr::Indir(_) => true,
+        Expr::SubExpr(inner) => is_place(&inner.spanned),
+        _ => false,
+    }
+}
+
+fn contains_goto(block: &Block) -> bool {
+    fn in_expr(expr: &Expr) -> bool {
+        match expr {
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                in_expr(&condition.spanned)
+                    || contains_goto(&then.spanned)
+                    || elifs.iter().any(|(condition, block)| {
+                        in_expr(&condition.spanned) || contains_goto(&block.spanned)
+                    })
+                    || r#else
+                        .as_ref()
+                        .is_some_and(|block| contains_goto(&block.spanned))
+            }
+            Expr::Block(block) => contains_goto(block),
+            _ => false,
+        }
+    }
+
+    block.stmts.iter().any(|stmt| match &stmt.spanned {
+        Stmt::Goto { .. } => true,
+        Stmt::Loop { block } => contains_goto(block),
+        Stmt::Discard { expr: inner }
+        | Stmt::VarDec {
+            initialiser: inner, ..
+        }
+        | Stmt::VarRes { updated: inner, .. }
+        | Stmt::Return { expr: Some(inner) } => in_expr(&inner.spanned),
+        _ => false,
+    }) || block
+        .tail
+        .as_ref()
+        .is_some_and(|tail| in_expr(&tail.spanned))
+}
+
+/// assigns a type to every expression, and checks that operators, conditions, calls,
+/// assignments & returns are given values of the right type.
+pub fn check(state: &mut State, root: &Block, res: &Resolutions) -> Types {
+    let mut checker = Checker {
+        state,
+        res,
+        types: Types::default(),
+        returns: Vec::new(),
+        lowering: HashSet::new(),
+    };
+
+    checker.declare(root);
+    checker.collect_sigs(root);
+    overload::check(checker.state, root, res, &checker.types);
+    checker.block(root, None);
+    checker.types
+}
@@ -0,0 +1,160 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+
+struct Checker<'a> {
+    state: &'a mut State,
+    res: &'a Resolutions,
+    types: &'a Types,
+    /// types declared `#[ must_be_read ]`, along with the attribute
+    must_be_read: HashMap<DefId, Span>,
+}
+
+impl<'a> Checker<'a> {
+    /// `consumed` is whether whatever contains the block uses its value
+    fn block(&mut self, block: &Block, consumed: bool) {
+        for stmt in &block.stmts {
+            self.stmt(&stmt.spanned);
+        }
+        let Some(tail) = &block.tail else {
+            return;
+        };
+
+        self.expr(tail);
+        if consumed {
+            return;
+        }
+        match self.types.expr(tail.span) {
+            Some(Ty::Void | Ty::Never | Ty::Error) | None => {}
+            Some(ty) => {
+                let err =
+                    SdwErr::from_pos(TypeErrors::UnusedValue(ty.display(self.res)), tail.span);
+                self.state.errors.push(err);
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Fn { body, .. } => self.block(body, true),
+            // a loop only ends by jumping out of it, so its body's value goes nowhere
+            Stmt::Loop { block } => self.block(block, false),
+            Stmt::Discard { expr } => {
+                self.expr(expr);
+                self.discarded(expr.span);
+            }
+            Stmt::Return { expr: Some(expr) }
+            | Stmt::VarDec {
+                initialiser: expr, ..
+            }
+            | Stmt::VarRes { updated: expr, .. } => self.expr(expr),
+            Stmt::Stub { .. }
+            | Stmt::Type { .. }
+            | Stmt::Label { .. }
+            | Stmt::Goto { .. }
+            | Stmt::Return { expr: None } => {}
+        }
+    }
+
+    /// an explicit discard is fine, unless its type is `#[ must_be_read ]`
+    fn discarded(&mut self, span: Span) {
+        let Some(Ty::Named(id)) = self.types.expr(span) else {
+            return;
+        };
+        let Some(attr) = self.must_be_read.get(id) else {
+            return;
+        };
+        let name = self.res.def(*id).name.clone();
+        let warning = SdwErr::from_pos(TypeErrors::MustBeRead(name.clone()), span)
+            .with_note(format!("`{name}` is marked as `must_be_read` here"), *attr);
+        self.state.warnings.push(warning);
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) {
+        match &expr.spanned {
+            Expr::IntLiteral(_)
+            | Expr::BoolLiteral(_)
+            | Expr::StrLiteral(_)
+            | Expr::Variable(_)
+            | Expr::ObjMember(..) => {}
+            Expr::UnaryNot(inner)
+            | Expr::UnaryNeg(inner)
+            | Expr::UnaryPos(inner)
+            | Expr::SubExpr(inner)
+            | Expr::Attributed { expr: inner, .. }
+            | Expr::Referal(inner)
+            | Expr::Indir(inner) => self.expr(inner),
+            Expr::FnCall(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::BiOp(left, _, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Cond {
+                condition,
+                then,
+                elifs,
+                r#else,
+            } => {
+                self.expr(condition);
+                self.block(&then.spanned, true);
+                for (condition, block) in elifs {
+                    self.expr(condition);
+                    self.block(&block.spanned, true);
+                }
+                if let Some(r#else) = r#else {
+                    self.block(&r#else.spanned, true);
+                }
+            }
+            Expr::Block(block) => self.block(block, true),
+            Expr::StructLit { fields, .. } => match fields {
+                StructLitFields::Positional(values) => {
+                    for value in values {
+                        self.expr(value);
+                    }
+                }
+                StructLitFields::Named(members) => {
+                    for (_, value) in members {
+                        self.expr(value);
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn collect_must_be_read(block: &Block, res: &Resolutions, found: &mut HashMap<DefId, Span>) {
+    for stmt in &block.stmts {
+        match &stmt.spanned {
+            Stmt::Type { attrs, name, .. } => {
+                let attr = attrs
+                    .iter()
+                    .find(|attr| attr.spanned.name.spanned == "must_be_read");
+                if let (Some(attr), Some(id)) = (attr, res.def_at(name.span)) {
+                    found.insert(id, attr.span);
+                }
+            }
+            Stmt::Fn { body, .. } => collect_must_be_read(body, res, found),
+            _ => {}
+        }
+    }
+}
+
+/// reports values which are dropped without being explicitly discarded (`expr;`) - a block's
+/// tail, where nothing uses the block's value. explicitly discarding a value of a
+/// `#[ must_be_read ]` type raises a warning. `shown` is whether the root's own value is used
+/// (printed by a repl).
+pub fn check(state: &mut State, root: &Block, res: &Resolutions, types: &Types, shown: bool) {
+    let mut must_be_read = HashMap::new();
+    collect_must_be_read(root, res, &mut must_be_read);
+
+    let mut checker = Checker {
+        state,
+        res,
+        types,
+        must_be_read,
+    };
+    checker.block(root, shown);
+}
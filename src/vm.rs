@@ -0,0 +1,497 @@
+use crate::bytecode::{Arm, Callee, Interface, Op, Program, RtTy, Trap, Union};
+use crate::interp::{self, Pointer, Value, MAX_DEPTH};
+use crate::intrinsics::{Called, Host};
+use crate::prelude::*;
+use std::collections::HashMap;
+
+struct Slot {
+    value: Value,
+    generation: u64,
+}
+
+/// a function call in progress (or the module's top level)
+struct Frame {
+    function: u32,
+    ip: usize,
+    /// where the function's locals start in memory; everything from here is freed on return
+    base: usize,
+    /// how much of the stack was in use before the call
+    stack: usize,
+    call: Option<Span>,
+}
+
+type Run<T> = std::result::Result<T, SdwErr>;
+
+struct Vm<'p> {
+    program: &'p Program,
+    /// for calls through function pointers
+    functions: HashMap<DefId, Callee>,
+    unions: HashMap<DefId, usize>,
+    stack: Vec<Value>,
+    memory: Vec<Slot>,
+    generation: u64,
+    frames: Vec<Frame>,
+    /// the top level's frame is gone by the time `main` runs, but still counts towards
+    /// `MAX_DEPTH` - so the vm overflows at the same depth as the interpreter
+    floor: usize,
+    host: Host,
+    /// the code the program asked to exit with, which ends it at once
+    exited: Option<i64>,
+}
+
+impl<'p> Vm<'p> {
+    fn allocate(&mut self, value: Value) -> usize {
+        self.generation += 1;
+        self.memory.push(Slot {
+            value,
+            generation: self.generation,
+        });
+        self.memory.len() - 1
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("vm: no frame")
+    }
+
+    fn pop(&mut self) -> Run<Value> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(self.malformed("an instruction pops an empty stack")),
+        }
+    }
+
+    /// where the top `count` values on the stack start
+    fn top(&self, count: usize) -> Run<usize> {
+        match self.stack.len().checked_sub(count) {
+            Some(at) => Ok(at),
+            None => Err(self.malformed("an instruction pops an empty stack")),
+        }
+    }
+
+    /// takes the top `count` values from the stack
+    fn split(&mut self, count: usize) -> Run<Vec<Value>> {
+        let at = self.top(count)?;
+        Ok(self.stack.split_off(at))
+    }
+
+    fn slot(&self, local: u32) -> usize {
+        self.frame().base + local as usize
+    }
+
+    /// a runtime error, with the call stack (innermost first) as its notes
+    fn fail(&self, err: RuntimeErrors) -> SdwErr {
+        let frame = self.frame();
+        let span = self.program.functions[frame.function as usize].spans[frame.ip - 1];
+        self.trace(SdwErr::from_pos(err, span))
+    }
+
+    /// bytecode which was loaded (rather than just compiled) may be anything at all - so what
+    /// can't happen in a program from `compile` is an error, rather than a panic
+    fn malformed(&self, what: &'static str) -> SdwErr {
+        self.fail(RuntimeErrors::Malformed(what))
+    }
+
+    fn union(&self, union: &DefId) -> Run<&'p Union> {
+        match self.unions.get(union) {
+            Some(index) => Ok(&self.program.unions[*index]),
+            None => Err(self.malformed("a union's type doesn't exist")),
+        }
+    }
+
+    fn trace(&self, mut err: SdwErr) -> SdwErr {
+        for frame in self.frames.iter().rev() {
+            if let Some(span) = frame.call {
+                let name = &self.program.functions[frame.function as usize].name;
+                err = err.with_note(format!("in `{name}`, called here"), span);
+            }
+        }
+        err
+    }
+
+    fn load(&self, pointer: &Pointer) -> Run<Value> {
+        let mut value = match self.memory.get(pointer.slot) {
+            Some(slot) if slot.generation == pointer.generation => &slot.value,
+            _ => return Err(self.fail(RuntimeErrors::DanglingPointer)),
+        };
+        for member in &pointer.path {
+            value = self.member(value, *member)?;
+        }
+        Ok(value.clone())
+    }
+
+    fn member<'v>(&self, value: &'v Value, member: usize) -> Run<&'v Value> {
+        let missing = || self.malformed("a member which doesn't exist");
+        match value {
+            Value::Struct { fields, .. } => fields.get(member).ok_or_else(missing),
+            Value::Union {
+                ty,
+                discriminant,
+                value,
+            } => {
+                if *discriminant == member {
+                    return Ok(value);
+                }
+                let members = &self.union(ty)?.members;
+                let (Some(held), Some(member)) = (members.get(*discriminant), members.get(member))
+                else {
+                    return Err(missing());
+                };
+                Err(self.fail(RuntimeErrors::WrongMember {
+                    held: held.0.clone(),
+                    member: member.0.clone(),
+                }))
+            }
+            _ => Err(self.malformed("a member of a value without members")),
+        }
+    }
+
+    fn coerce(&self, value: Value, union: DefId) -> Run<Value> {
+        if matches!(&value, Value::Union { ty, .. } if *ty == union)
+            || matches!(value, Value::Pointer(_) | Value::Fn(_))
+        {
+            return Ok(value);
+        }
+        let members = &self.union(&union)?.members;
+        let ty = RtTy::of(&value);
+        Ok(match members.iter().position(|(_, member)| *member == ty) {
+            Some(discriminant) => Value::Union {
+                ty: union,
+                discriminant,
+                value: Box::new(value),
+            },
+            None => value,
+        })
+    }
+
+    /// the implementation of an interface stub, for the arguments it's actually given. the
+    /// closest match wins - an exact implementation, over a default one.
+    fn implementation(&self, interface: &Interface, args: &[Value]) -> Run<u32> {
+        let accepts = |param: &RtTy, arg: &Value| -> Option<usize> {
+            match (param, RtTy::of(arg)) {
+                (RtTy::Pointer, RtTy::Pointer) | (RtTy::Fn, RtTy::Fn) | (RtTy::Any, _) => Some(0),
+                (param, arg) if *param == arg => Some(1),
+                (RtTy::Named(union), arg) => {
+                    let union = &self.program.unions[*self.unions.get(union)?];
+                    union
+                        .members
+                        .iter()
+                        .any(|(_, member)| *member == arg)
+                        .then_some(0)
+                }
+                _ => None,
+            }
+        };
+
+        // candidates are in declaration order, & the earliest wins a tie
+        let mut best: Option<(u32, usize)> = None;
+        for candidate in &interface.candidates {
+            let score = candidate
+                .params
+                .iter()
+                .zip(args)
+                .map(|(param, arg)| accepts(param, arg))
+                .sum::<Option<usize>>();
+            if let Some(score) = score {
+                if best.is_none_or(|(_, best)| score > best) {
+                    best = Some((candidate.function, score));
+                }
+            }
+        }
+        match best {
+            Some((function, _)) => Ok(function),
+            None => Err(self.fail(RuntimeErrors::NoImplementation(interface.name.clone()))),
+        }
+    }
+
+    /// calls `callee` with the top `args` values on the stack
+    fn call(&mut self, callee: Callee, args: u32) -> Run<()> {
+        let function = match callee {
+            Callee::Function(function) => function,
+            Callee::Interface(interface) => {
+                let interface = &self.program.interfaces[interface as usize];
+                let args = &self.stack[self.top(args as usize)?..];
+                self.implementation(interface, args)?
+            }
+        };
+        let frame = self.frame();
+        let span = self.program.functions[frame.function as usize].spans[frame.ip - 1];
+        self.enter(function, args, Some(span))
+    }
+
+    fn enter(&mut self, function: u32, args: u32, call: Option<Span>) -> Run<()> {
+        let depth = self.frames.len() + self.floor;
+        if depth >= MAX_DEPTH {
+            return Err(self.fail(RuntimeErrors::StackOverflow(depth)));
+        }
+
+        let locals = self.program.functions[function as usize].locals as usize;
+        let stack = self.top(args as usize)?;
+        let base = self.memory.len();
+        for at in stack..self.stack.len() {
+            let arg = std::mem::replace(&mut self.stack[at], Value::Void);
+            self.allocate(arg);
+        }
+        self.stack.truncate(stack);
+        for _ in args as usize..locals {
+            self.allocate(Value::Void);
+        }
+        self.frames.push(Frame {
+            function,
+            ip: 0,
+            base,
+            stack,
+            call,
+        });
+        Ok(())
+    }
+
+    /// runs until the frame at the bottom of the stack returns
+    fn execute(&mut self) -> Run<Value> {
+        let program = self.program;
+        loop {
+            let frame = self.frames.last_mut().expect("vm: no frame");
+            let op = &program.functions[frame.function as usize].code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Int(int) => self.stack.push(Value::Int(*int)),
+                Op::Unt(unt) => self.stack.push(Value::Unt(*unt)),
+                Op::Bool(bool) => self.stack.push(Value::Bool(*bool)),
+                Op::Str(text) => self.stack.push(Value::Str(text.clone())),
+                Op::Void => self.stack.push(Value::Void),
+                Op::Fn(id) => self.stack.push(Value::Fn(*id)),
+                Op::Load(local) => {
+                    let value = self.memory[self.slot(*local)].value.clone();
+                    self.stack.push(value);
+                }
+                Op::Declare(local) => {
+                    let value = self.pop()?;
+                    let slot = self.slot(*local);
+                    self.generation += 1;
+                    self.memory[slot] = Slot {
+                        value,
+                        generation: self.generation,
+                    };
+                }
+                Op::Store(local) => {
+                    let value = self.pop()?;
+                    let slot = self.slot(*local);
+                    self.memory[slot].value = value;
+                }
+                Op::Ref(local) => {
+                    let slot = self.slot(*local);
+                    self.stack.push(Value::Pointer(Pointer {
+                        slot,
+                        generation: self.memory[slot].generation,
+                        path: Vec::new(),
+                    }));
+                }
+                Op::Temp => {
+                    let value = self.pop()?;
+                    let slot = self.allocate(value);
+                    self.stack.push(Value::Pointer(Pointer {
+                        slot,
+                        generation: self.memory[slot].generation,
+                        path: Vec::new(),
+                    }));
+                }
+                Op::RefMember(member) => match self.stack.last_mut() {
+                    Some(Value::Pointer(pointer)) => pointer.path.push(*member as usize),
+                    _ => return Err(self.malformed("a member of a non-pointer")),
+                },
+                Op::Deref => match self.pop()? {
+                    Value::Pointer(pointer) => {
+                        let value = self.load(&pointer)?;
+                        self.stack.push(value);
+                    }
+                    _ => return Err(self.malformed("a non-pointer is dereferenced")),
+                },
+                Op::Member(member) => {
+                    let value = self.pop()?;
+                    let member = self.member(&value, *member as usize)?.clone();
+                    self.stack.push(member);
+                }
+                Op::Pop => {
+                    self.pop()?;
+                }
+                Op::Drop(count) => {
+                    self.split(*count as usize)?;
+                }
+                Op::Neg => {
+                    let value = self.pop()?;
+                    let value = interp::negate(value).map_err(|err| self.fail(err))?;
+                    self.stack.push(value);
+                }
+                Op::Not => match self.pop()? {
+                    Value::Bool(bool) => self.stack.push(Value::Bool(!bool)),
+                    _ => return Err(self.malformed("`!` of a non-bool")),
+                },
+                Op::BiOp(op) => {
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    let value = interp::biop(left, *op, right).map_err(|err| self.fail(err))?;
+                    self.stack.push(value);
+                }
+                Op::Struct { ty, order } => {
+                    let values = self.split(order.len())?;
+                    let mut fields = vec![Value::Void; order.len()];
+                    for (value, index) in values.into_iter().zip(order) {
+                        let Some(field) = fields.get_mut(*index as usize) else {
+                            return Err(self.malformed("a member which doesn't exist"));
+                        };
+                        *field = value;
+                    }
+                    self.stack.push(Value::Struct { ty: *ty, fields });
+                }
+                Op::Union { ty, discriminant } => {
+                    let value = self.pop()?;
+                    self.stack.push(Value::Union {
+                        ty: *ty,
+                        discriminant: *discriminant as usize,
+                        value: Box::new(value),
+                    });
+                }
+                Op::Coerce(union) => {
+                    let value = self.pop()?;
+                    let value = self.coerce(value, *union)?;
+                    self.stack.push(value);
+                }
+                Op::Jump(to) => self.frames.last_mut().expect("vm: no frame").ip = *to as usize,
+                Op::JumpUnless(to) => {
+                    if self.pop()? == Value::Bool(false) {
+                        self.frames.last_mut().expect("vm: no frame").ip = *to as usize;
+                    }
+                }
+                Op::JumpKeep { when, to } => {
+                    if self.stack.last() == Some(&Value::Bool(*when)) {
+                        self.frames.last_mut().expect("vm: no frame").ip = *to as usize;
+                    }
+                }
+                Op::Call { function, args } => self.call(Callee::Function(*function), *args)?,
+                Op::CallInterface { interface, args } => {
+                    self.call(Callee::Interface(*interface), *args)?
+                }
+                Op::Intrinsic {
+                    intrinsic,
+                    args,
+                    ret,
+                } => {
+                    let args = self.split(*args as usize)?;
+                    match self.host.call(*intrinsic, &args, *ret) {
+                        Ok(Called::Returned(value)) => self.stack.push(value),
+                        Ok(Called::Exited(code)) => {
+                            self.exited = Some(code);
+                            self.frames.clear();
+                            return Ok(Value::Int(code));
+                        }
+                        Err(err) => return Err(self.fail(err)),
+                    }
+                }
+                Op::CallPtr { args } => match self.pop()? {
+                    Value::Fn(id) => {
+                        let Some(callee) = self.functions.get(&id).copied() else {
+                            return Err(self.malformed("a function pointer to nothing"));
+                        };
+                        self.call(callee, *args)?;
+                    }
+                    _ => return Err(self.malformed("a non-function is called")),
+                },
+                Op::Dispatch { table, arg, args } => {
+                    let (mut table, mut arg) = (*table, *arg);
+                    let callee = loop {
+                        let at = self.top((*args - arg) as usize)?;
+                        let Value::Union {
+                            discriminant,
+                            value,
+                            ..
+                        } = std::mem::replace(&mut self.stack[at], Value::Void)
+                        else {
+                            return Err(self.malformed("a dispatch on a non-union"));
+                        };
+                        self.stack[at] = *value;
+                        let Some(arm) = program.dispatches[table as usize].get(discriminant) else {
+                            return Err(self.malformed("a dispatch without an arm for the member"));
+                        };
+                        match *arm {
+                            Arm::Call(callee) => break callee,
+                            Arm::Dispatch {
+                                table: next,
+                                arg: on,
+                            } => (table, arg) = (next, on),
+                        }
+                    };
+                    self.call(callee, *args)?;
+                }
+                Op::Return => {
+                    let value = self.pop()?;
+                    let frame = self.frames.pop().expect("vm: no frame");
+                    self.memory.truncate(frame.base);
+                    self.stack.truncate(frame.stack);
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+                Op::Trap(Trap::JumpIntoExpr) => return Err(self.fail(RuntimeErrors::JumpIntoExpr)),
+                Op::Trap(Trap::EmptyUnion) => {
+                    return Err(self.fail(RuntimeErrors::Unsupported(
+                        "a union literal without a member".to_owned(),
+                    )))
+                }
+            }
+        }
+    }
+}
+
+/// runs a compiled module: its top level first, then `main`, whose result is returned.
+/// errors are exactly those `interp::run` would raise.
+pub fn run(program: &Program) -> std::result::Result<Value, SdwErr> {
+    let Some(main) = program.main else {
+        return Err(SdwErr::from_pos(RuntimeErrors::NoMain, Span::default()));
+    };
+    let main_fn = &program.functions[main as usize];
+    if main_fn.params != 0 {
+        return Err(SdwErr::from_pos(
+            RuntimeErrors::MainTakesParams,
+            main_fn.span,
+        ));
+    }
+
+    let mut functions = HashMap::new();
+    for (index, function) in program.functions.iter().enumerate() {
+        if let Some(id) = function.id {
+            functions.insert(id, Callee::Function(index as u32));
+        }
+    }
+    for (index, interface) in program.interfaces.iter().enumerate() {
+        functions.insert(interface.stub, Callee::Interface(index as u32));
+    }
+    let mut vm = Vm {
+        program,
+        functions,
+        unions: program
+            .unions
+            .iter()
+            .enumerate()
+            .map(|(index, union)| (union.id, index))
+            .collect(),
+        stack: Vec::new(),
+        memory: Vec::new(),
+        generation: 0,
+        frames: Vec::new(),
+        floor: 0,
+        host: Host::default(),
+        exited: None,
+    };
+
+    let result = vm.enter(program.init, 0, None).and_then(|()| {
+        vm.execute()?;
+        if let Some(code) = vm.exited {
+            return Ok(Value::Int(code));
+        }
+        vm.floor = 1;
+        vm.enter(main, 0, None)?;
+        vm.execute()
+    });
+    result.map_err(|err| interp::collapse(err.with_note("in `main`", main_fn.span)))
+}
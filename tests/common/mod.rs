@@ -0,0 +1,100 @@
+// each test crate uses only some of these
+#![allow(dead_code)]
+
+use sdw::driver::{self, Checked};
+use sdw::interp::{self, Value};
+use sdw::prelude::*;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub fn checked(source: &str) -> Checked {
+    let mut state = State::new();
+    let Ok(checked) = driver::check(&mut state, source, &mut ()) else {
+        panic!("failed to check: {:#?}", state.errors);
+    };
+    checked
+}
+
+/// what `main` returns in the interpreter, as an integer - `None` if it returns nothing
+pub fn interpreted(source: &str) -> Option<i64> {
+    match interp::run(&checked(source)) {
+        Ok(Value::Int(int)) => Some(int),
+        Ok(Value::Unt(unt)) => Some(unt as i64),
+        Ok(Value::Bool(bool)) => Some(bool.into()),
+        Ok(_) => None,
+        Err(err) => panic!("failed to interpret: {err:#?}"),
+    }
+}
+
+/// the interpreter must fail with `message`
+pub fn interpreter_fails(source: &str, message: &str) {
+    let Err(err) = interp::run(&checked(source)) else {
+        panic!("the interpreter didn't fail");
+    };
+    assert_eq!(err.ty.to_string(), message);
+}
+
+/// builds `code` (an `ext` file) into a binary with the system's `cc` & runs it - `None` if
+/// there's no `cc`
+pub fn build_and_run(code: &str, ext: &str, args: &[&str]) -> Option<Output> {
+    static COUNT: AtomicU32 = AtomicU32::new(0);
+
+    let name = format!(
+        "sdw-{ext}-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let dir = std::env::temp_dir();
+    let file = dir.join(format!("{name}.{ext}"));
+    let binary: PathBuf = dir.join(name);
+    std::fs::write(&file, code).expect("failed to write the code");
+
+    let Ok(cc) = Command::new("cc")
+        .args(args)
+        .arg("-o")
+        .arg(&binary)
+        .arg(&file)
+        .output()
+    else {
+        eprintln!("no `cc` to build with - skipping");
+        return None;
+    };
+    assert!(
+        cc.status.success(),
+        "cc failed:\n{}\n{code}",
+        String::from_utf8_lossy(&cc.stderr)
+    );
+    let output = Command::new(&binary).output().expect("failed to run");
+    let _ = std::fs::remove_file(file);
+    let _ = std::fs::remove_file(binary);
+    Some(output)
+}
+
+/// the program built by `run` must exit with what `main` returns in the interpreter
+pub fn matches_interpreter(source: &str, run: fn(&str) -> Option<Output>) {
+    let expected = interpreted(source).unwrap_or(0) as u8;
+    let Some(output) = run(source) else {
+        return;
+    };
+    assert_eq!(
+        output.status.code(),
+        Some(expected as i32),
+        "for:\n{source}\nstderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// the program built by `run` must fail as the interpreter does, with the same message
+pub fn fails(source: &str, message: &str, run: fn(&str) -> Option<Output>) {
+    interpreter_fails(source, message);
+    let Some(output) = run(source) else {
+        return;
+    };
+    assert_eq!(output.status.code(), Some(101));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr,
+        format!("a runtime error was raised whilst running: {message}\n")
+    );
+}
@@ -0,0 +1,144 @@
+use sdw::consteval::Value;
+use sdw::driver::{self, Failure, Stage};
+use sdw::prelude::*;
+
+/// the folded value of `let x = [..];`
+fn folded(source: &str) -> Option<Value> {
+    let mut state = State::new();
+    let Ok(checked) = driver::check(&mut state, source, &mut ()) else {
+        panic!("failed to check: {:#?}", state.errors);
+    };
+    let Stmt::VarDec { initialiser, .. } = &checked.ast.stmts.last().unwrap().spanned else {
+        panic!("expected a `let`");
+    };
+    checked.consts.get(initialiser.span)
+}
+
+/// the errors raised whilst checking `source`, and the stage raising them
+fn errors(source: &str) -> (Stage, Vec<String>) {
+    let mut state = State::new();
+    match driver::check(&mut state, source, &mut ()) {
+        Err(Failure::Errors(stage)) => (
+            stage,
+            state.errors.iter().map(|err| err.ty.to_string()).collect(),
+        ),
+        _ => panic!("expected `{source}` to fail"),
+    }
+}
+
+#[test]
+fn folds() {
+    assert_eq!(folded("let x = 1 << 4 | 2;"), Some(Value::Int(18)));
+    assert_eq!(folded("let x = -(7 / 2) % 3;"), Some(Value::Int(0)));
+    assert_eq!(folded("let x = 3 > 2 && !false;"), Some(Value::Bool(true)));
+    assert_eq!(folded("let y = 1; let x = y + 1;"), None);
+    assert_eq!(
+        folded("fn unt f(unt u) { u }; let x = f(1 << 63);"),
+        None,
+        "only the largest constant expressions are folded"
+    );
+}
+
+#[test]
+fn unt_literals() {
+    let source = "fn unt f(unt u) { u }; let x = f(9223372036854775807 + 1);";
+    let mut state = State::new();
+    assert!(driver::check(&mut state, source, &mut ()).is_ok());
+    assert_eq!(
+        errors("fn unt f(unt u) { u }; let x = f(0 - 1);"),
+        (Stage::Constants, vec!["`0 - 1` overflows `unt`".to_owned()])
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    let (stage, errors) = errors(
+        "let a = 9223372036854775807 + 1; let b = 10 / (5 - 5); let c = 1 << 64; \
+         let d = false && 1 / 0 == 0;",
+    );
+    assert_eq!(stage, Stage::Constants);
+    assert_eq!(
+        errors,
+        [
+            "`9223372036854775807 + 1` overflows `int`",
+            "`10 / 0` divides by zero",
+            "`1 << 64` shifts by more than the 64 bits of an integer",
+        ]
+    );
+}
+
+#[test]
+fn ranges() {
+    let (_, errors) = errors(
+        "#[ num_in_range 0 100 ] fn int percent() { 50 + 70 };
+         fn int f() { 1 };
+         let a = (#[ num_in_range 0 10 ] f()) - 1;
+         let b = (#[ num_in_range 0 10 ] 5 * 3) - 1;
+         let c = #[ num_in_range 0 ] 1;",
+    );
+    assert_eq!(
+        errors,
+        [
+            "`120` is outside of the range `0..=100`",
+            "`15` is outside of the range `0..=10`",
+            "`num_in_range` takes two integers - the smallest & largest values allowed",
+        ]
+    );
+}
+
+#[test]
+fn conditional_compilation() {
+    let source = "
+        #if 1 << 2 == 4 { let x = 1; } #else { let x = true; }
+        #if false { let y = 1; } #else if 2 > 1 { let y = x; }
+        let z = { #if true { y + 1 } #else { y } };
+    ";
+    assert_eq!(folded(source), None);
+
+    let (stage, errors) = errors("let y = 1; #if y == 1 { } #if 3 { } #else { }");
+    assert_eq!(stage, Stage::Parse);
+    assert_eq!(
+        errors,
+        [
+            "`#if` conditions must be constant",
+            "`#if` conditions must be a `bool`, found `int`",
+        ]
+    );
+}
+
+#[test]
+fn attributed_statements() {
+    // attributes starting a statement apply to the expression after them, not only to
+    // declarations
+    let mut state = State::new();
+    let source = "fn int f() { 1 }; fn int main() { #[ num_in_range 0 10 ] f(); 3 };";
+    let Ok(checked) = driver::check(&mut state, source, &mut ()) else {
+        panic!("failed to check: {:#?}", state.errors);
+    };
+    let Stmt::Fn { body, .. } = &checked.ast.stmts.last().unwrap().spanned else {
+        panic!("expected `main`");
+    };
+    assert!(matches!(
+        &body.stmts[..],
+        [Spanned { spanned: Stmt::Discard { expr }, .. }]
+            if matches!(expr.spanned, Expr::Attributed { .. })
+    ));
+
+    assert_eq!(
+        errors("fn int main() { #[ num_in_range 0 100 ] 101 };"),
+        (
+            Stage::Constants,
+            vec!["`101` is outside of the range `0..=100`".to_owned()]
+        )
+    );
+    assert_eq!(
+        errors("fn int main() { let x = 1; #[ num_in_range 0 10 ] x = 2; x };"),
+        (
+            Stage::Parse,
+            vec![
+                "attributes can only be applied to `fn` & `type` declarations, or to expressions"
+                    .to_owned()
+            ]
+        )
+    );
+}
@@ -0,0 +1,174 @@
+use sdw::driver;
+use sdw::prelude::*;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// runs the program with `sdw run`, then again on the vm - which must behave the same
+fn run(source: &str) -> Output {
+    static COUNT: AtomicU32 = AtomicU32::new(0);
+
+    let file = std::env::temp_dir().join(format!(
+        "sdw-core-{}-{}.sdw",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&file, source).expect("failed to write the source");
+    let run = |vm: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_sdw"));
+        command.arg("run").arg(&file);
+        if vm {
+            command.arg("--vm");
+        }
+        command.output().expect("failed to run `sdw`")
+    };
+    let (interpreted, vm) = (run(false), run(true));
+    let _ = std::fs::remove_file(file);
+
+    assert_eq!(interpreted.status.code(), vm.status.code());
+    assert_eq!(interpreted.stdout, vm.stdout);
+    interpreted
+}
+
+fn lex_error(source: &str) -> String {
+    let mut state = State::new();
+    assert!(driver::check(&mut state, source, &mut ()).is_err());
+    state.errors[0].ty.to_string()
+}
+
+#[test]
+fn printing() {
+    let output = run("
+        // everything `core` can print
+        fn void main() {
+            printLn(\"hello, \\\"world\\\"\\t!\");
+            printLn(0);
+            printLn(1234);
+            printLn(-56);
+            printLn(-9223372036854775807 - 1);
+            printLn(9223372036854775807);
+            printLn(true);
+            print(false);
+            print(\"\");
+            printLn();
+        };
+    ");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hello, \"world\"\t!\n0\n1234\n-56\n-9223372036854775808\n9223372036854775807\ntrue\nfalse\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn formatting() {
+    let output = run("
+        type Point struct { int x, int y };
+        fn void print(Point point) { print(\"({}, {})\", point.x, point.y); };
+        fn void main() {
+            printLn(\"{} + {} = {}\", 1, 2, 3);
+            printLn(\"{} is {}!\", \"this\", true);
+            printLn(\"at {}, naïvely {}\", Point { 1, -2 }, \"{}\");
+            // missing values & placeholders
+            printLn(\"{} {}\", 4);
+            printLn(\"{\", 5);
+            print(\"{}{}\", 6, 7);
+        };
+    ");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1 + 2 = 3\nthis is true!\nat (1, -2), naïvely {}\n4 {}\n{\n67"
+    );
+}
+
+#[test]
+fn syscalls_and_memory() {
+    // a string's bytes, & those `alloc` gives (which are zeroed, until stored to)
+    let output = run("
+        fn int main() {
+            let text = \"copied\";
+            let buffer = alloc(length(text));
+            store(buffer + 1, 353);
+            syscall(1, 1, address(\"hi\\n\"), 3);
+            write(1, address(text), length(text));
+            printLn(write(1, buffer, 4));
+            exit(7);
+            printLn(\"unreachable\");
+            0
+        };
+    ");
+    assert_eq!(output.stdout, b"hi\ncopied\0a\0\x004\n");
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn panics() {
+    let output = run("
+        fn int check(int n) { if n > 2 { panic(\"too big\"); }; n };
+        fn int main() { printLn(check(1)); check(3) };
+    ");
+    assert_eq!(output.stdout, b"1\n");
+    assert_eq!(output.status.code(), Some(101));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("panicked: too big"));
+
+    // raised within `core`, but shown from there
+    let output = run("fn int main() { write(1, 5, 3) };");
+    assert_eq!(output.status.code(), Some(101));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the 3 bytes at 5 aren't all in memory"));
+    assert!(stderr.contains("of `core`"));
+}
+
+#[test]
+fn strings_and_comments() {
+    assert_eq!(
+        lex_error("fn void main() { printLn(\"unclosed); };"),
+        "this string is never closed (expected a `\"`)"
+    );
+    assert_eq!(
+        lex_error("fn void main() { printLn(\"\\q\"); };"),
+        "`\\q` isn't an escape sequence"
+    );
+    let output = run("
+        // a comment // with another in it
+        fn int main() { // after code
+            printLn(\"// not a comment\");
+            printLn(\"naïve \\\"ünïcode\\\"\");
+            0 // at the end
+        };
+    ");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "// not a comment\nnaïve \"ünïcode\"\n"
+    );
+}
+
+#[test]
+fn shadowing() {
+    // the file's declarations shadow `core`'s, which `core` itself still uses
+    let output = run("
+        type Printable struct { int x };
+        fn int exit(int code) { code + 1 };
+        fn int length(string text) { 7 };
+        fn int main() {
+            printLn(\"{}\", length(\"abc\"));
+            exit(4)
+        };
+    ");
+    assert_eq!(output.stdout, b"7\n");
+    assert_eq!(output.status.code(), Some(5));
+
+    // a generic of the file's own, named as `core`'s is
+    let output = run("
+        type Print;
+        fn print(Print);
+        type Debug;
+        fn debug(Debug);
+        type Test struct;
+        fn print(Test t) { printLn(\"test print\"); };
+        fn debug(Test t) { printLn(\"test debug\"); };
+        type DoType Debug + Print;
+        fn do(DoType d) { print(d); debug(d); };
+        fn int main() { do(Test {}); print(3); 0 };
+    ");
+    assert_eq!(output.stdout, b"test print\ntest debug\n3");
+}
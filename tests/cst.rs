@@ -0,0 +1,193 @@
+use sdw::cst::{self, NodeKind, SyntaxElement, SyntaxNode};
+use sdw::prelude::*;
+use sdw::{driver, lexer, parser};
+use std::rc::Rc;
+
+const FEATURES: &str = "\
+// everything the parser knows
+#[ num_in_range -5 5 ] #[ must_be_read ]
+type Small int;
+type Point struct { int x, &Point next };
+type Either union { int left, bool right };
+type Callback (int, bool) -> int;
+type Print;
+type Show Print + Small;
+fn print(Print);
+fn int add(int a, /* the other */ int b,) { a + b * -a };
+/* across
+   lines */
+fn void main() {
+    let p = Point { .x = 1, .next = &p };
+    let q = Point { 2, p.next, };
+    @top;
+    loop { goto @top; };
+    p = #[ must_be_read ] !(*q.next == p) && true || { 1 } >= 2;
+    if x { 1 } else if { y } { 2 } else { \"three\" };
+    #if 1 > 2 { let a = 1; } #else if true { let b = 2; } #else { let c = 3; }
+    return add(1, 2 << 3);
+};
+";
+
+fn tree(source: &str) -> SyntaxNode {
+    cst::parse(&mut State::new(), source)
+}
+
+/// the ast, without spans (which `lower` takes from the tree's tokens, not the parser's rules)
+fn shape(block: &Block) -> String {
+    let spans = regex::Regex::new(r", span: Span \{[^}]*\}").unwrap();
+    spans.replace_all(&format!("{block:?}"), "").into_owned()
+}
+
+/// every node's children sit end to end, covering exactly its text
+fn check_ranges(node: &SyntaxNode) {
+    let mut at = node.text_range().start;
+    for child in node.children_with_tokens() {
+        let range = match &child {
+            SyntaxElement::Node(child) => {
+                assert_eq!(child.parent(), Some(node));
+                check_ranges(child);
+                child.text_range()
+            }
+            SyntaxElement::Token(token) => token.text_range(),
+        };
+        assert_eq!(range.start, at);
+        at = range.end;
+    }
+    assert_eq!(at, node.text_range().end);
+}
+
+#[test]
+fn text_is_exact() {
+    let mut sources = vec![
+        driver::CORE.to_owned(),
+        FEATURES.to_owned(),
+        "fn void main() {\r\n\tprintLn(\"ünïcode\"); // é\n}\n\n".to_owned(),
+        "  // nothing but trivia  ".to_owned(),
+        String::new(),
+        // lex & parse errors, & a stray brace the parser stops at
+        "let x = $ 1; \"unclosed\nlet y = \"\\q\";".to_owned(),
+        "fn int main( { let = ; }}} let z = 1;".to_owned(),
+        "#else { 1 }; #[ 1 ] let x = (1 + ; fn f(int) -> ;".to_owned(),
+    ];
+    // (& every prefix of the features, most of which don't parse)
+    for end in (0..FEATURES.len()).filter(|&end| FEATURES.is_char_boundary(end)) {
+        sources.push(FEATURES[..end].to_owned());
+    }
+
+    for source in sources {
+        let root = tree(&source);
+        assert_eq!(root.text(), source);
+        assert_eq!(root.green().text_len(), source.len());
+        assert_eq!(root.kind(), NodeKind::Root);
+        check_ranges(&root);
+    }
+}
+
+#[test]
+fn the_ast_can_be_derived() {
+    for source in [driver::CORE, FEATURES] {
+        let mut state = State::new();
+        let root = cst::parse(&mut state, source);
+        assert!(state.errors.is_empty(), "{:?}", state.errors);
+        let lowered = cst::lower(&mut state, &root);
+        assert!(state.errors.is_empty(), "{:?}", state.errors);
+
+        let lexemes = lexer::lex(&mut state, source);
+        let parsed = parser::parse(&mut state, lexemes).unwrap();
+        assert_eq!(shape(&lowered), shape(&parsed));
+    }
+
+    // spans come from where the tokens are
+    let root = tree("fn void main() {\n    let a = \"ünï\"; let b = 1;\n};");
+    let block = cst::lower(&mut State::new(), &root);
+    let Stmt::Fn { body, .. } = &block.stmts[0].spanned else {
+        panic!("{block:?}");
+    };
+    let Stmt::VarDec { name, .. } = &body.stmts[1].spanned else {
+        panic!("{body:?}");
+    };
+    let span = name.span;
+    assert_eq!(
+        (span.sline, span.eline, span.scol, span.ecol),
+        (1, 2, 24, 25)
+    );
+}
+
+#[test]
+fn structure() {
+    let root = tree("fn int one() { 1 }; // one\n");
+    assert_eq!(
+        format!("{root:?}"),
+        r#"Root@0..27
+  Fn@0..19
+    Fn@0..2 "fn"
+    Whitespace@2..3 " "
+    Idn("int")@3..6 "int"
+    Whitespace@6..7 " "
+    Idn("one")@7..10 "one"
+    LParen@10..11 "("
+    RParen@11..12 ")"
+    Whitespace@12..13 " "
+    Block@13..18
+      LBrace@13..14 "{"
+      Whitespace@14..15 " "
+      Literal@15..16
+        Intlit(1)@15..16 "1"
+      Whitespace@16..17 " "
+      RBrace@17..18 "}"
+    Semi@18..19 ";"
+  Whitespace@19..20 " "
+  Comment@20..26 "// one"
+  Whitespace@26..27 "\n"
+"#
+    );
+
+    let root = tree("fn void main() { let x = a - -b * c; };");
+    let minus = root.token_at(29).unwrap();
+    assert_eq!(minus.text(), "-");
+    let kinds: Vec<_> = minus.parent().ancestors().map(|node| node.kind()).collect();
+    assert_eq!(
+        kinds,
+        [
+            NodeKind::Prefix,
+            NodeKind::Binary,
+            NodeKind::Binary,
+            NodeKind::Let,
+            NodeKind::Block,
+            NodeKind::Fn,
+            NodeKind::Root,
+        ]
+    );
+    let binary = minus.parent().parent().unwrap().parent().unwrap();
+    assert_eq!(binary.text(), "a - -b * c");
+    assert_eq!(binary.text_range(), 25..35);
+
+    // identical tokens are shared
+    let tokens = root.descendant_tokens();
+    let semis: Vec<_> = tokens.iter().filter(|token| token.text() == ";").collect();
+    assert_eq!(semis.len(), 2);
+    assert!(Rc::ptr_eq(semis[0].green(), semis[1].green()));
+    assert_ne!(semis[0], semis[1]);
+}
+
+#[test]
+fn errors_are_kept() {
+    let mut state = State::new();
+    let root = cst::parse(&mut state, "fn void main() { let x = ; f(2); };");
+    assert!(!state.errors.is_empty());
+    let kinds: Vec<_> = root.descendants().iter().map(SyntaxNode::kind).collect();
+    assert!(kinds.contains(&NodeKind::Error), "{root:?}");
+
+    // what did parse still lowers
+    let block = cst::lower(&mut State::new(), &root);
+    let Stmt::Fn { body, .. } = &block.stmts[0].spanned else {
+        panic!("{block:?}");
+    };
+    assert!(matches!(
+        &body.stmts[..],
+        [Spanned {
+            spanned: Stmt::Discard { .. },
+            ..
+        }]
+    ));
+}
@@ -0,0 +1,549 @@
+use sdw::driver::{self, Failure, Stage};
+use sdw::prelude::*;
+
+/// `line:col-line:col`, counting both from 1 (& with the end exclusive)
+fn at(span: Span) -> String {
+    format!(
+        "{}:{}-{}:{}",
+        span.sline + 1,
+        span.scol,
+        span.eline,
+        span.ecol
+    )
+}
+
+/// a diagnostic, where it was raised & each of its notes - one per line
+fn render(err: &SdwErr) -> String {
+    let mut rendered = format!("{} {}", at(err.span), err.ty);
+    for note in &err.notes {
+        match note.span {
+            Some(span) => rendered += &format!("\n  {} {}", at(span), note.message),
+            None => rendered += &format!("\n  {}", note.message),
+        }
+    }
+    rendered
+}
+
+/// the errors raised whilst checking `source`, and the stage raising them
+fn errors(source: &str) -> (Stage, Vec<String>) {
+    let mut state = State::new();
+    match driver::check(&mut state, source, &mut ()) {
+        Err(Failure::Errors(stage)) => (stage, state.errors.iter().map(render).collect()),
+        _ => panic!("expected `{source}` to fail"),
+    }
+}
+
+/// the warnings raised whilst checking `source`, which must check
+fn warnings(source: &str) -> Vec<String> {
+    let mut state = State::new();
+    if driver::check(&mut state, source, &mut ()).is_err() {
+        panic!("failed to check: {:#?}", state.errors);
+    }
+    state.warnings.iter().map(render).collect()
+}
+
+#[test]
+fn duplicate_names() {
+    let (stage, errors) = errors(
+        "type T int;
+type T bool;
+fn void f() {
+    let x = 1;
+    let x = 2;
+};
+fn int g(int a, int a) { a };",
+    );
+    assert_eq!(stage, Stage::Resolve);
+    assert_eq!(
+        errors,
+        [
+            "2:6-2:7 the type `T` is declared more than once in this scope
+  1:6-1:7 first declared here",
+            "5:9-5:10 the variable `x` is declared more than once in this scope
+  4:9-4:10 first declared here",
+            "7:21-7:22 the parameter `a` is declared more than once in this scope
+  7:14-7:15 first declared here",
+        ]
+    );
+}
+
+#[test]
+fn shadowed_names() {
+    let warnings = warnings(
+        "fn void f(int x) {
+    if true { let x = 2; };
+    let y = 1;
+    loop { let y = y; goto @out; };
+    @out;
+};",
+    );
+    assert_eq!(
+        warnings,
+        [
+            "2:19-2:20 the variable `x` shadows an earlier declaration
+  1:15-1:16 shadows this parameter",
+            "4:16-4:17 the variable `y` shadows an earlier declaration
+  3:9-3:10 shadows this variable",
+        ]
+    );
+}
+
+#[test]
+fn undeclared_names() {
+    let (stage, errors) = errors(
+        "fn Missing f() { g(1) + y };
+type Alias Nowhere;
+let z = { let inner = 1; inner };
+let w = inner;",
+    );
+    assert_eq!(stage, Stage::Resolve);
+    // (a dangling alias is left to `cycles`, which can say what refers to it)
+    assert_eq!(
+        errors,
+        [
+            "1:4-1:11 use of undeclared type `Missing`",
+            "1:18-1:22 use of undeclared function `g`",
+            "1:25-1:26 use of undeclared variable `y`",
+            "4:9-4:14 use of undeclared variable `inner`",
+        ]
+    );
+}
+
+#[test]
+fn labels() {
+    let (stage, errors) = errors(
+        "fn void f() {
+    @top;
+    @top;
+    goto @tpo;
+    goto @elsewhere;
+};
+fn void g() { goto @top; };",
+    );
+    assert_eq!(stage, Stage::Labels);
+    assert_eq!(
+        errors,
+        [
+            "3:6-3:9 the label `@top` is declared more than once in this function
+  2:6-2:9 first declared here",
+            "4:11-4:14 no label `@tpo` in this function
+  2:6-2:9 did you mean `@top`?",
+            // nothing is close enough to suggest
+            "5:11-5:20 no label `@elsewhere` in this function",
+            // labels belong to their function
+            "7:21-7:24 no label `@top` in this function",
+        ]
+    );
+}
+
+#[test]
+fn gotos_skipping_lets() {
+    let (stage, errors) = errors(
+        "fn void f() {
+    goto @after;
+    let x = 1;
+    let unused = 2;
+    @after;
+    printLn(x);
+};",
+    );
+    // (`unused` is skipped too, but never read)
+    assert_eq!(stage, Stage::Labels);
+    assert_eq!(
+        errors,
+        [
+            "2:11-2:16 this `goto` skips the declaration of `x`, which is used after the label
+  3:9-3:10 `x` is declared here
+  6:13-6:14 and used here, after the label"
+        ]
+    );
+
+    // jumping backwards runs the `let` again, so skips nothing
+    let warnings = warnings(
+        "fn void f() {
+    let x = 1;
+    @again;
+    let y = x;
+    if y == 1 { goto @again; };
+};",
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn operands() {
+    let (stage, errors) = errors(
+        "fn void f(int x, bool c, unt u) {
+    let a = x + c;
+    let b = c << true;
+    let d = x * u;
+};",
+    );
+    // both operands are pointed to, whichever is wrong
+    assert_eq!(stage, Stage::Types);
+    assert_eq!(
+        errors,
+        [
+            "2:17-2:18 expected an integer (`int` or `unt`), found `bool`
+  2:13-2:14 the other operand is `int`",
+            "3:13-3:14 expected an integer (`int` or `unt`), found `bool`
+  3:18-3:22 the other operand is `bool`",
+            "3:18-3:22 expected an integer (`int` or `unt`), found `bool`
+  3:13-3:14 the other operand is `bool`",
+            "4:17-4:18 mismatched operands - `int` and `unt`
+  4:13-4:14 this is `int`",
+        ]
+    );
+}
+
+#[test]
+fn mismatches() {
+    let (stage, errors) = errors(
+        "fn int f(bool b) {
+    let x = if b { 1 } else { true };
+    return b;
+};
+let y = f(1);
+let z = f(true, 2);
+let v = y();
+let w = &1;
+let u = *y;
+let t = y == f;",
+    );
+    assert_eq!(stage, Stage::Types);
+    assert_eq!(
+        errors,
+        [
+            "2:31-2:35 mismatched types - expected `int`, found `bool`
+  2:20-2:21 `int` expected because of this",
+            "3:12-3:13 mismatched types - expected `int`, found `bool`
+  1:4-1:7 `int` expected because of this",
+            "5:11-5:12 mismatched types - expected `bool`, found `int`",
+            "6:9-6:19 expected 1 argument(s), found 2",
+            "7:9-7:12 `int` is not a function",
+            "8:10-8:11 only variables, members & dereferences can be referenced with `&`",
+            "9:10-9:11 cannot dereference `int`, which is not a pointer",
+            "10:14-10:15 mismatched operands - `int` and `(bool) -> int`
+  10:9-10:10 this is `int`",
+        ]
+    );
+}
+
+#[test]
+fn struct_literals() {
+    let (stage, errors) = errors(
+        "type P struct { int x, bool y };
+type E union { int i, bool b };
+let a = P { 1 };
+let b = P { .x = 1, .x = 2 };
+let c = P { .x = 1 };
+let d = E { 1 };
+let e = E { .i = 1, .b = true };
+let f = int { 1 };
+let g = P { .z = 1 };
+let h = a.z;
+let one = 1;
+let i = one.x;",
+    );
+    assert_eq!(stage, Stage::Types);
+    assert_eq!(
+        errors,
+        [
+            "3:9-3:10 `P` has 2 member(s), but 1 were given",
+            "4:22-4:23 the member `x` is given more than once",
+            "4:9-4:10 `P` is missing a value for its member `y`",
+            "5:9-5:10 `P` is missing a value for its member `y`",
+            "6:9-6:10 the union `E` must be built by naming a member (`E { .name = [..] }`)",
+            "7:9-7:10 a `E` holds only one of its members at a time",
+            "8:9-8:12 `int` is not a struct or union, so can't be built with a literal",
+            "9:14-9:15 `P` has no member `z`",
+            "9:9-9:10 `P` is missing a value for its member `x`",
+            "9:9-9:10 `P` is missing a value for its member `y`",
+            "10:11-10:12 `P` has no member `z`",
+            "12:9-12:12 `int` has no members",
+        ]
+    );
+}
+
+#[test]
+fn bindings() {
+    let (stage, errors) = errors(
+        "fn void nothing() {};
+fn int f() { 1 };
+let a = nothing();
+f = 2;
+return 1;",
+    );
+    assert_eq!(stage, Stage::Types);
+    assert_eq!(
+        errors,
+        [
+            "3:9-3:18 `a` can't be bound to a `void` value",
+            "4:1-4:2 cannot assign to `f`, which is not a variable",
+            "5:1-5:10 `return` outside of a function",
+        ]
+    );
+}
+
+#[test]
+fn overloads() {
+    let (stage, errors) = errors(
+        "type Print;
+fn show(Print);
+type Text struct { int len };
+fn void show(Text t) {};
+fn void pair(int a, Print b) {};
+fn void pair(Print a, int b) {};
+fn void once(int a) {};
+fn void once(bool b) {};
+let a = pair(1, 2);
+let b = once(Text { 1 });
+let c = once;",
+    );
+    assert_eq!(stage, Stage::Types);
+    // (`int` implements `Print`, so either `pair` will do)
+    assert_eq!(
+        errors,
+        [
+            "9:9-9:19 call to `pair` with (int, int) is ambiguous
+  5:9-5:13 candidate: `fn void pair(int, Print)`
+  6:9-6:13 candidate: `fn void pair(Print, int)`",
+            "10:9-10:25 no overload of `once` takes (Text)
+  7:9-7:13 candidate: `fn void once(int)`
+  8:9-8:13 candidate: `fn void once(bool)`",
+            "11:9-11:13 `once` has several overloads, so can't be used as a value",
+        ]
+    );
+}
+
+#[test]
+fn conflicting_overloads() {
+    let (stage, errors) = errors("fn int twice(int a) { a };\nfn int twice(int b) { b };");
+    assert_eq!(stage, Stage::Types);
+    assert_eq!(
+        errors,
+        [
+            "2:8-2:13 `fn int twice(int)` is declared more than once with the same parameter types
+  1:8-1:13 conflicts with this declaration"
+        ]
+    );
+}
+
+#[test]
+fn missing_implementations() {
+    let (stage, errors) = errors(
+        "type Say;
+type Inspect;
+type Show Say + Inspect;
+fn say(Say);
+fn inspect(Inspect);
+fn void say(int i) {};
+fn void show(Show s) { say(s); inspect(s); };
+fn void main() {
+    show(1);
+    say(true);
+};",
+    );
+    assert_eq!(stage, Stage::Interfaces);
+    assert_eq!(
+        errors,
+        [
+            "9:10-9:11 `int` is used as a generic, but has no implementation `fn void inspect(int)`
+  5:4-5:11 required by `fn void inspect(Inspect)`, as `int` is used as a `Show`",
+            "10:9-10:13 `bool` is used as a generic, but has no implementation `fn void say(bool)`
+  4:4-4:7 required by `fn void say(Say)`, as `bool` is used as a `Say`",
+        ]
+    );
+
+    // an implementation generic over the interface covers everything
+    let warnings = warnings(
+        "type Say;
+fn say(Say);
+fn void say(Say p) {};
+fn void main() { say(true); };",
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn implemented_intrinsics() {
+    let (stage, errors) = errors(
+        "fn int syscall(bool, int);
+fn int syscall(bool b, int i) { 0 };
+fn int length(string s) { 1 };
+fn int main() { length(\"hello\") };",
+    );
+    assert_eq!(stage, Stage::Interfaces);
+    // `core`'s own stubs are shadowed by the file's `fn`s, rather than implemented
+    assert_eq!(
+        errors,
+        ["2:8-2:15 `syscall` is an intrinsic, which each backend provides - it can't be implemented
+  1:8-1:15 the intrinsic is declared here"]
+    );
+
+    // an overload taking something else is a function of its own
+    let warnings = warnings("fn int length(int n) { n }; fn int main() { length(3) };");
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn missing_combinations() {
+    let (stage, errors) = errors(
+        "type Some struct { int some };
+type None struct { };
+type Option union { Some some, None none };
+fn int both(Some a, Some b) { a.some * b.some };
+fn int both(None a, Option b) { 100 };
+fn void main(Option x, Option y) { let z = both(x, y); };",
+    );
+    // `both(Some, Some)` is there, but not with a `None` second
+    assert_eq!(stage, Stage::Types);
+    assert_eq!(
+        errors,
+        [
+            "6:44-6:54 no overload of `both` takes (Some, None), so it can't be dispatched to
+  6:49-6:50 this is a `Option`, which may hold a `Some`
+  6:52-6:53 this is a `Option`, which may hold a `None`"
+        ]
+    );
+}
+
+#[test]
+fn unused_values() {
+    let (stage, errors) = errors(
+        "fn int f() { 1 };
+fn void g() { loop { f() }; };
+let x = { f() };
+x + 1",
+    );
+    // (`{ f() }` is used by the `let`)
+    assert_eq!(stage, Stage::Unused);
+    assert_eq!(
+        errors,
+        [
+            "2:22-2:25 this `int` value is never used - discard it explicitly with a `;`",
+            "4:1-4:6 this `int` value is never used - discard it explicitly with a `;`",
+        ]
+    );
+}
+
+#[test]
+fn must_be_read() {
+    let warnings = warnings(
+        "#[ must_be_read ]
+type Status struct { int code };
+fn Status status() { Status { 1 } };
+fn void main() {
+    status();
+    let s = status();
+    (status());
+};",
+    );
+    assert_eq!(
+        warnings,
+        [
+            "5:5-5:13 a `Status` is discarded without being read
+  1:1-1:18 `Status` is marked as `must_be_read` here",
+            "7:5-7:15 a `Status` is discarded without being read
+  1:1-1:18 `Status` is marked as `must_be_read` here",
+        ]
+    );
+}
+
+#[test]
+fn return_paths() {
+    let (stage, errors) = errors(
+        "fn int f(bool b) {
+    if b { return 1; };
+};
+fn int g() {};
+#[ no_return ]
+fn void stop(bool b) {
+    if b { return; };
+    panic(\"stopped\");
+};
+#[ no_return ]
+fn void fall() {};",
+    );
+    assert_eq!(stage, Stage::ReturnPaths);
+    assert_eq!(
+        errors,
+        [
+            "2:5-2:24 `f` can reach the end of its body without returning a value
+  1:4-1:7 `int` must be returned",
+            "4:8-4:9 `g` can reach the end of its body without returning a value
+  4:4-4:7 `int` must be returned",
+            "7:12-7:19 `stop` is declared `no_return`, but can return here
+  5:1-5:15 declared as never returning here",
+            "11:9-11:13 `fall` is declared `no_return`, but can return here
+  10:1-10:15 declared as never returning here",
+        ]
+    );
+
+    // a call to a `no_return` function doesn't need to be followed by a value
+    let warnings = warnings("fn int f() { panic(\"never\"); };");
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn unreachable_code() {
+    let warnings = warnings(
+        "fn int f() {
+    return 1;
+    let x = 2;
+    x
+};
+fn void g() {
+    panic(\"no\");
+    printLn(1);
+};
+fn void h() {
+    loop {};
+    printLn(2);
+};",
+    );
+    // only the first unreachable statement of a run is pointed to
+    assert_eq!(
+        warnings,
+        [
+            "3:5-3:15 unreachable code
+  2:5-2:14 control never continues past this",
+            "8:5-8:16 unreachable code
+  7:5-7:17 control never continues past this",
+            "12:5-12:16 unreachable code
+  11:5-11:13 control never continues past this",
+        ]
+    );
+}
+
+#[test]
+fn cycles() {
+    let (stage, errors) = errors(
+        "type A B;
+type B C;
+type C A;
+type List struct { int head, Link tail };
+type Link List;
+type Node struct { int value, &Node next };
+type X Y;
+type Y Z;
+type Z Nowhere;",
+    );
+    // `Node` only stores a pointer to itself, which is fine
+    assert_eq!(stage, Stage::TypeDecls);
+    assert_eq!(
+        errors,
+        [
+            "1:6-1:7 alias cycle - A -> B -> C -> A
+  1:8-1:9 `A` is an alias of `B`
+  2:8-2:9 `B` is an alias of `C`
+  3:8-3:9 `C` is an alias of `A`",
+            "4:6-4:10 `List` contains itself, so would be infinitely large - List -> Link -> List
+  4:30-4:34 `List` stores a `Link` (as `tail`) - use `&Link` to store a pointer instead
+  5:11-5:15 `Link` is an alias of `List`",
+            "9:8-9:15 `Nowhere` is never declared - X -> Y -> Z -> Nowhere
+  8:6-8:7 `Y` is an alias of `Z`
+  7:6-7:7 `X` is an alias of `Y`",
+        ]
+    );
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use common::{build_and_run, checked, fails, matches_interpreter};
+use sdw::emit_asm;
+use sdw::prelude::*;
+use std::process::Output;
+
+/// assembles & links the emitted assembly with the system's `cc`, then runs it - `None` if
+/// there's no `cc`, or it isn't for x86-64 linux
+fn assemble_and_run(source: &str) -> Option<Output> {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        eprintln!("not on x86-64 linux - skipping");
+        return None;
+    }
+    let mut state = State::new();
+    let asm = emit_asm::emit(&mut state, &checked(source));
+    assert!(
+        state.errors.is_empty(),
+        "failed to emit: {:#?}",
+        state.errors
+    );
+    build_and_run(&asm, "s", &["-nostdlib", "-static"])
+}
+
+#[test]
+fn programs() {
+    let sources = [
+        "fn int fib(int n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+         fn int main() { fib(20) };",
+        "fn int main() {
+            let a = 2;
+            let b = { let c = a * 10; c + 1 };
+            a = a + b;
+            if a > 20 { a } else if a > 10 { 0 } else { 1 }
+         };",
+        "type Point struct { int x, int y };
+         type PointPtr &Point;
+         type Line struct { Point from, Point to };
+         fn int length(PointPtr point) { point.x + point.y };
+         fn PointPtr pick(PointPtr a, PointPtr b, bool first) { if first { a } else { b } };
+         fn int main() {
+            let line = Line { .to = Point { 3, 4 }, .from = Point { 1, 2 } };
+            let to = line.to;
+            let from = line.from;
+            let chosen = pick(&from, &to, false);
+            let x = &to.x;
+            length(chosen) * 10 + *x
+         };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn int unwrap(Some some) { some.some };
+         fn int unwrap(None none) { 0 };
+         fn Option half(int n) { if n % 2 == 0 { Some { n / 2 } } else { None {} } };
+         fn int main() {
+            let held = half(8);
+            let some = held.some;
+            unwrap(half(10)) + unwrap(half(7)) + some.some
+         };",
+        "type Op (int) -> int;
+         fn int double(int n) { n * 2 };
+         fn int apply(Op op, int n) { op(n) };
+         fn int main() { apply(double, 21) };",
+        "fn bool both(bool a, bool b) { a && b || !a && !b };
+         fn unt big() { 1 << 63 };
+         fn unt main() {
+            let u = big() >> 60;
+            if both(true, true) && both(false, false) { u } else { 0 }
+         };",
+        "fn int main() { -100 };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn Option maybe(int n) { if n > 0 { Some { n } } else { None {} } };
+         fn int both(Some a, Some b) { a.some * b.some };
+         fn int both(Some a, None b) { a.some };
+         fn int both(None a, Option b) { 100 };
+         fn int main() { both(maybe(2), maybe(3)) + both(maybe(5), maybe(0)) * 10 + both(maybe(0), maybe(7)) };",
+        "fn bool main() { 3 > 2 };",
+    ];
+    for source in sources {
+        matches_interpreter(source, assemble_and_run);
+    }
+}
+
+#[test]
+fn calling_convention() {
+    // more arguments than registers, & structs passed & returned in registers & memory
+    matches_interpreter(
+        "type Pair struct { int a, bool b };
+         type Big struct { int a, int b, int c, bool d };
+         fn int many(int a, int b, int c, int d, int e, int f, int g, int h) {
+            a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h
+         };
+         fn Pair pair(int a) { Pair { a, a > 3 } };
+         fn Big big(Pair pair, int n) { Big { pair.a, n, pair.a * n, pair.b } };
+         fn int sum(int a, int b, int c, int d, int e, Pair pair, Big big) {
+            if pair.b && big.d { a + b + c + d + e + pair.a + big.a + big.b + big.c } else { 0 }
+         };
+         fn int main() {
+            let big = big(pair(5), 3);
+            many(1, 2, 3, 4, 5, 6, 7, 8) + sum(1, 2, 3, 4, 5, pair(4), big)
+         };",
+        assemble_and_run,
+    );
+}
+
+#[test]
+fn gotos() {
+    matches_interpreter(
+        "fn int main() {
+            let total = 0;
+            let n = 5;
+            goto @start;
+            loop {
+                total = total + n;
+                @start;
+                n = n + 1;
+                if n > 10 { goto @done; };
+            };
+            @done;
+            total
+        };",
+        assemble_and_run,
+    );
+    matches_interpreter(
+        "fn int main() {
+            let n = 0;
+            @again;
+            let sum = 1 + 2 * { n = n + 1; if n < 100 { goto @again; }; 3 };
+            sum + n
+        };",
+        assemble_and_run,
+    );
+    fails(
+        "fn int main() {
+            let n = 0;
+            let a = 1 + { @inside; n = n + 1; 2 };
+            if n < 2 { goto @inside; };
+            a
+        };",
+        "cannot jump into the middle of an expression",
+        assemble_and_run,
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    fails(
+        "fn int divide(int a, int b) { a / b };
+         fn int main() { divide(10, 0) };",
+        "`10 / 0` divides by zero",
+        assemble_and_run,
+    );
+    fails(
+        "fn int grow(int n) { n * 4611686018427387904 };
+         fn int main() { grow(4) };",
+        "`4 * 4611686018427387904` overflows `int`",
+        assemble_and_run,
+    );
+    fails(
+        "fn int low() { -9223372036854775807 - 1 };
+         fn int main() { low() % -1 };",
+        "`-9223372036854775808 % -1` overflows `int`",
+        assemble_and_run,
+    );
+    fails(
+        "fn unt take(unt a, unt b) { a - b };
+         fn unt main() { take(2, 3) };",
+        "`2 - 3` overflows `unt`",
+        assemble_and_run,
+    );
+    fails(
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn Option nothing() { None {} };
+         fn int main() { let held = nothing(); let some = held.some; some.some };",
+        "the union holds its `none` member, not `some`",
+        assemble_and_run,
+    );
+}
+
+#[test]
+fn syscalls() {
+    let Some(output) = assemble_and_run(
+        "type Bytes &int;
+         fn int syscall(int, int, Bytes, int);
+         fn int main() {
+            let text = 104 + 256 * 105 + 65536 * 10;
+            let written = syscall(1, 1, &text, 3);
+            syscall(60, written + 4);
+            0
+         };",
+    ) else {
+        return;
+    };
+    assert_eq!(output.stdout, b"hi\n");
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn core() {
+    // printing (& formatting), allocating & exiting, through `core`
+    let Some(output) = assemble_and_run(
+        "fn int main() {
+            printLn(\"hello, \\\"world\\\"\\t!\");
+            printLn(-9223372036854775807 - 1);
+            printLn(1234);
+            printLn(true);
+            printLn(\"{} & {}{}\", 1, true, \"!\");
+            print(false);
+            printLn();
+            let buffer = alloc(4);
+            store(buffer + 1, 353);
+            printLn(write(1, buffer, 4));
+            exit(7);
+            0
+         };",
+    ) else {
+        return;
+    };
+    assert_eq!(
+        output.stdout,
+        b"hello, \"world\"\t!\n-9223372036854775808\n1234\ntrue\n1 & true!\nfalse\n\0a\0\x004\n"
+    );
+    assert_eq!(output.status.code(), Some(7));
+
+    let Some(output) =
+        assemble_and_run("fn int main() { printLn(\"before\"); panic(\"oh no\"); };")
+    else {
+        return;
+    };
+    assert_eq!(output.stdout, b"before\n");
+    assert_eq!(output.status.code(), Some(101));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("panicked: oh no"));
+}
+
+#[test]
+fn generics() {
+    // a copy of each generic function for each set of types it's called with
+    for source in [
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         fn int area(Square square) { square.side * square.side };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { twice(Square { 3 }) };",
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         type Circle struct { int radius };
+         fn int area(Square square) { square.side * square.side };
+         fn int area(Circle circle) { 3 * circle.radius * circle.radius };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { twice(Square { 3 }) + twice(Circle { 2 }) };",
+        "type Area;
+         fn int area(Area);
+         fn int area(Area shape) { 1 };
+         type Square struct { int side };
+         type Line struct { int length };
+         fn int area(Square square) { square.side * square.side };
+         fn int main() { area(Square { 4 }) + area(Line { 9 }) };",
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         fn int area(Square square) { square.side * square.side };
+         fn Area same(Area shape) { shape };
+         fn Area twice(Area shape) { same(same(shape)) };
+         fn int main() { area(twice(Square { 5 })) };",
+        "type Shown;
+         fn int code(Shown);
+         fn int code(int i) { i };
+         fn int code(bool b) { if b { 10 } else { 20 } };
+         fn int two(Shown a, Shown b) { code(a) + code(b) };
+         fn Shown pick(Shown a, bool first) { if first { return a; }; a };
+         fn int main() { let x = pick(false, true); two(1, true) + two(x, 2) + code(x) };",
+    ] {
+        matches_interpreter(source, assemble_and_run);
+    }
+}
+
+#[test]
+fn no_main() {
+    let mut state = State::new();
+    emit_asm::emit(&mut state, &checked("fn int start() { 0 };"));
+    assert!(matches!(
+        state.errors[0].ty,
+        ErrType::Emit(EmitErrors::NoMain)
+    ));
+}
@@ -0,0 +1,388 @@
+mod common;
+
+use common::{checked, interpreted, interpreter_fails};
+use sdw::emit_wat;
+use sdw::prelude::*;
+use wasmi::{Caller, Engine, Error, Extern, Linker, Memory, Module, Store, Val};
+
+/// what running `main` did - what it returned, the message it failed with, or the code it
+/// exited with
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Returned(Option<i64>),
+    Failed(String),
+    Exited(i32),
+}
+
+/// what the module's imports have been given
+#[derive(Default)]
+struct Host {
+    failed: Option<String>,
+    stdout: Vec<u8>,
+}
+
+fn memory(caller: &Caller<Host>) -> Memory {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        panic!("no memory exported");
+    };
+    memory
+}
+
+fn run(source: &str) -> Outcome {
+    execute(source).0
+}
+
+/// assembles the emitted text, checking it's valid, then runs its `main` - giving what it
+/// wrote to stdout too. only writing & exiting are provided as syscalls.
+fn execute(source: &str) -> (Outcome, Vec<u8>) {
+    let mut state = State::new();
+    let wat = emit_wat::emit(&mut state, &checked(source));
+    assert!(
+        state.errors.is_empty(),
+        "failed to emit: {:#?}",
+        state.errors
+    );
+    let wasm = wat::parse_str(&wat).unwrap_or_else(|err| panic!("invalid: {err}\n{wat}"));
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).unwrap_or_else(|err| panic!("{err}\n{wat}"));
+    let mut store = Store::new(&engine, Host::default());
+    let mut linker = Linker::new(&engine);
+    linker
+        .func_wrap(
+            "sdw",
+            "fail",
+            |mut caller: Caller<Host>, ptr: i32, len: i32| {
+                let mut message = vec![0; len as usize];
+                memory(&caller)
+                    .read(&caller, ptr as usize, &mut message)
+                    .expect("the message is out of bounds");
+                caller.data_mut().failed = Some(String::from_utf8(message).expect("not utf-8"));
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "sdw",
+            "syscall",
+            |mut caller: Caller<Host>,
+             number: i64,
+             a: i64,
+             b: i64,
+             c: i64,
+             _: i64,
+             _: i64,
+             _: i64|
+             -> std::result::Result<i64, Error> {
+                match (number, a) {
+                    (1, 1) => {
+                        let mut bytes = vec![0; c as usize];
+                        memory(&caller)
+                            .read(&caller, b as usize, &mut bytes)
+                            .expect("the bytes are out of bounds");
+                        caller.data_mut().stdout.extend(bytes);
+                        Ok(c)
+                    }
+                    (60 | 231, code) => Err(Error::i32_exit(code as i32)),
+                    _ => panic!("syscall {number} isn't provided"),
+                }
+            },
+        )
+        .unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .unwrap_or_else(|err| panic!("failed to instantiate: {err}\n{wat}"));
+    let main = instance.get_func(&store, "main").expect("no `main`");
+    let mut results = vec![Val::I32(0); main.ty(&store).results().len()];
+    let outcome = match main.call(&mut store, &[], &mut results) {
+        Ok(()) => Outcome::Returned(results.pop().map(|val| match val {
+            Val::I32(int) => int.into(),
+            Val::I64(int) => int,
+            val => panic!("returned {val:?}"),
+        })),
+        Err(err) => match (store.data_mut().failed.take(), err.i32_exit_status()) {
+            (Some(message), _) => Outcome::Failed(message),
+            (None, Some(code)) => Outcome::Exited(code),
+            (None, None) => panic!("trapped without a runtime error: {err}\n{wat}"),
+        },
+    };
+    (outcome, std::mem::take(&mut store.data_mut().stdout))
+}
+
+/// `main` must return what it does in the interpreter
+fn matches_interpreter(source: &str) {
+    let expected = interpreted(source);
+    assert_eq!(run(source), Outcome::Returned(expected), "for:\n{source}");
+}
+
+/// the program must fail as the interpreter does, with the same message
+fn fails(source: &str, message: &str) {
+    interpreter_fails(source, message);
+    assert_eq!(run(source), Outcome::Failed(message.to_owned()));
+}
+
+#[test]
+fn programs() {
+    let sources = [
+        "fn int fib(int n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+         fn int main() { fib(20) };",
+        "fn int main() {
+            let a = 2;
+            let b = { let c = a * 10; c + 1 };
+            a = a + b;
+            if a > 20 { a } else if a > 10 { 0 } else { 1 }
+         };",
+        "type Point struct { int x, int y };
+         type PointPtr &Point;
+         type Line struct { Point from, Point to };
+         fn int length(PointPtr point) { point.x + point.y };
+         fn PointPtr pick(PointPtr a, PointPtr b, bool first) { if first { a } else { b } };
+         fn int main() {
+            let line = Line { .to = Point { 3, 4 }, .from = Point { 1, 2 } };
+            let to = line.to;
+            let from = line.from;
+            let chosen = pick(&from, &to, false);
+            let x = &to.x;
+            length(chosen) * 10 + *x
+         };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn int unwrap(Some some) { some.some };
+         fn int unwrap(None none) { 0 };
+         fn Option half(int n) { if n % 2 == 0 { Some { n / 2 } } else { None {} } };
+         fn int main() {
+            let held = half(8);
+            let some = held.some;
+            unwrap(half(10)) + unwrap(half(7)) + some.some
+         };",
+        "type Op (int) -> int;
+         fn int double(int n) { n * 2 };
+         fn int apply(Op op, int n) { op(n) };
+         fn int main() { apply(double, 21) };",
+        "fn bool both(bool a, bool b) { a && b || !a && !b };
+         fn unt big() { 1 << 63 };
+         fn unt main() {
+            let u = big() >> 60;
+            if both(true, true) && both(false, false) { u } else { 0 }
+         };",
+        "fn int main() { -100 };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn Option maybe(int n) { if n > 0 { Some { n } } else { None {} } };
+         fn int both(Some a, Some b) { a.some * b.some };
+         fn int both(Some a, None b) { a.some };
+         fn int both(None a, Option b) { 100 };
+         fn int main() { both(maybe(2), maybe(3)) + both(maybe(5), maybe(0)) * 10 + both(maybe(0), maybe(7)) };",
+        "fn bool main() { 3 > 2 };",
+        "fn void main() {};",
+    ];
+    for source in sources {
+        matches_interpreter(source);
+    }
+}
+
+#[test]
+fn aggregates() {
+    // structs passed & returned through linear memory, & held by loops
+    matches_interpreter(
+        "type Pair struct { int a, bool b };
+         type Big struct { int a, int b, int c, bool d };
+         fn Pair pair(int a) { Pair { a, a > 3 } };
+         fn Big big(Pair pair, int n) { Big { pair.a, n, pair.a * n, pair.b } };
+         fn int sum(int a, Pair pair, Big big) {
+            if pair.b && big.d { a + pair.a + big.a + big.b + big.c } else { 0 }
+         };
+         fn int main() {
+            let total = 0;
+            let n = 0;
+            loop {
+                let big = big(pair(n + 4), n);
+                total = total + sum(n, pair(4 + n), big);
+                n = n + 1;
+                if n == 5 { goto @out; };
+            };
+            @out;
+            total
+         };",
+    );
+}
+
+#[test]
+fn gotos() {
+    matches_interpreter(
+        "fn int main() {
+            let total = 0;
+            let n = 5;
+            goto @start;
+            loop {
+                total = total + n;
+                @start;
+                n = n + 1;
+                if n > 10 { goto @done; };
+            };
+            @done;
+            total
+        };",
+    );
+    matches_interpreter(
+        "fn int main() {
+            let n = 0;
+            @again;
+            let sum = 1 + 2 * { n = n + 1; if n < 100 { goto @again; }; 3 };
+            sum + n
+        };",
+    );
+    // two labels jumping into each other's loops, which can't be nested
+    matches_interpreter(
+        "fn int main() {
+            let n = 0;
+            let steps = 0;
+            @a;
+            steps = steps + 1;
+            n = n + 3;
+            if n > 40 { goto @done; };
+            if n % 2 == 0 { goto @b; };
+            goto @a;
+            @b;
+            steps = steps + 10;
+            n = n + 1;
+            if n % 5 == 0 { goto @a; };
+            goto @b;
+            @done;
+            steps * 100 + n
+        };",
+    );
+    fails(
+        "fn int main() {
+            let n = 0;
+            let a = 1 + { @inside; n = n + 1; 2 };
+            if n < 2 { goto @inside; };
+            a
+        };",
+        "cannot jump into the middle of an expression",
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    fails(
+        "fn int divide(int a, int b) { a / b };
+         fn int main() { divide(10, 0) };",
+        "`10 / 0` divides by zero",
+    );
+    fails(
+        "fn int grow(int n) { n * 4611686018427387904 };
+         fn int main() { grow(4) };",
+        "`4 * 4611686018427387904` overflows `int`",
+    );
+    fails(
+        "fn int low() { -9223372036854775807 - 1 };
+         fn int main() { low() % -1 };",
+        "`-9223372036854775808 % -1` overflows `int`",
+    );
+    fails(
+        "fn unt take(unt a, unt b) { a - b };
+         fn unt main() { take(2, 3) };",
+        "`2 - 3` overflows `unt`",
+    );
+    fails(
+        "fn int shift(int a, int b) { a << b };
+         fn int main() { shift(1, 64) };",
+        "`1 << 64` shifts by more than the 64 bits of an integer",
+    );
+    fails(
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn Option nothing() { None {} };
+         fn int main() { let held = nothing(); let some = held.some; some.some };",
+        "the union holds its `none` member, not `some`",
+    );
+}
+
+#[test]
+fn core() {
+    // printing (& formatting), allocating & exiting, through `core`
+    let (outcome, stdout) = execute(
+        "fn int main() {
+            printLn(\"hello, \\\"world\\\"\\t!\");
+            printLn(-9223372036854775807 - 1);
+            printLn(1234);
+            printLn(true);
+            printLn(\"{} & {}{}\", 1, true, \"!\");
+            print(false);
+            printLn();
+            let buffer = alloc(4);
+            store(buffer + 1, 353);
+            printLn(write(1, buffer, 4));
+            exit(7);
+            0
+         };",
+    );
+    assert_eq!(outcome, Outcome::Exited(7));
+    assert_eq!(
+        stdout,
+        b"hello, \"world\"\t!\n-9223372036854775808\n1234\ntrue\n1 & true!\nfalse\n\0a\0\x004\n"
+    );
+
+    let (outcome, stdout) = execute("fn int main() { printLn(\"before\"); panic(\"oh no\"); };");
+    assert_eq!(outcome, Outcome::Failed("panicked: oh no".to_owned()));
+    assert_eq!(stdout, b"before\n");
+}
+
+#[test]
+fn generics() {
+    // a copy of each generic function for each set of types it's called with
+    for source in [
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         fn int area(Square square) { square.side * square.side };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { twice(Square { 3 }) };",
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         type Circle struct { int radius };
+         fn int area(Square square) { square.side * square.side };
+         fn int area(Circle circle) { 3 * circle.radius * circle.radius };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { twice(Square { 3 }) + twice(Circle { 2 }) };",
+        "type Area;
+         fn int area(Area);
+         fn int area(Area shape) { 1 };
+         type Square struct { int side };
+         type Line struct { int length };
+         fn int area(Square square) { square.side * square.side };
+         fn int main() { area(Square { 4 }) + area(Line { 9 }) };",
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         fn int area(Square square) { square.side * square.side };
+         fn Area same(Area shape) { shape };
+         fn Area twice(Area shape) { same(same(shape)) };
+         fn int main() { area(twice(Square { 5 })) };",
+        "type Shown;
+         fn int code(Shown);
+         fn int code(int i) { i };
+         fn int code(bool b) { if b { 10 } else { 20 } };
+         fn int two(Shown a, Shown b) { code(a) + code(b) };
+         fn Shown pick(Shown a, bool first) { if first { return a; }; a };
+         fn int main() { let x = pick(false, true); two(1, true) + two(x, 2) + code(x) };",
+    ] {
+        matches_interpreter(source);
+    }
+}
+
+#[test]
+fn no_main() {
+    let mut state = State::new();
+    emit_wat::emit(&mut state, &checked("fn int start() { 0 };"));
+    assert!(matches!(
+        state.errors[0].ty,
+        ErrType::Emit(EmitErrors::NoMain)
+    ));
+}
@@ -0,0 +1,374 @@
+use sdw::prelude::*;
+use sdw::pretty::Style;
+use sdw::{driver, fmt, lexer, parser};
+use std::process::Command;
+
+fn format_with(source: &str, style: &Style) -> String {
+    let mut state = State::new();
+    let formatted = fmt::format(&mut state, source, style);
+    assert!(state.errors.is_empty(), "{:?}", state.errors);
+    let formatted = formatted.unwrap();
+    // formatting again changes nothing
+    assert_eq!(
+        fmt::format(&mut State::new(), &formatted, style).as_deref(),
+        Some(formatted.as_str()),
+        "\n{formatted}"
+    );
+    formatted
+}
+
+fn format(source: &str) -> String {
+    format_with(source, &Style::default())
+}
+
+fn error(source: &str) -> String {
+    let mut state = State::new();
+    assert!(fmt::format(&mut state, source, &Style::default()).is_none());
+    state.errors[0].ty.to_string()
+}
+
+#[test]
+fn comments_are_kept() {
+    let source = "\
+// a header
+
+
+#[ must_be_read ]
+type Point struct { int x, int y }; // after a type
+fn int add(int a,int b){ // after the opening brace
+  a+b
+};
+fn void main() {
+    let p = Point{1,2};   // after code
+    // on its own line
+    printLn(add(p.x,
+        p.y)); // within a statement
+    loop {
+        // all that's in the loop
+    };
+    // at the end of a block
+};
+// at the end of the file
+";
+    assert_eq!(
+        format(source),
+        "\
+// a header
+
+#[ must_be_read ]
+type Point struct { int x, int y }; // after a type
+fn int add(int a, int b) {
+    // after the opening brace
+    a + b
+};
+fn void main() {
+    let p = Point { 1, 2 }; // after code
+    // on its own line
+    printLn(add(p.x, p.y)); // within a statement
+    loop {
+        // all that's in the loop
+    };
+    // at the end of a block
+};
+// at the end of the file
+"
+    );
+}
+
+#[test]
+fn width_and_indentation() {
+    let source = "fn int f(int first, int second) { g(first, Pair { .a = second, .b = 2 }) };\n\
+                  type Pair struct { int a, int b };";
+    assert_eq!(
+        format(source),
+        "fn int f(int first, int second) {\n    g(first, Pair { .a = second, .b = 2 })\n};\n\
+         type Pair struct { int a, int b };\n"
+    );
+    let narrow = Style {
+        indent: 2,
+        width: 24,
+    };
+    assert_eq!(
+        format_with(source, &narrow),
+        "\
+fn int f(
+  int first,
+  int second,
+) {
+  g(
+    first,
+    Pair {
+      .a = second,
+      .b = 2,
+    },
+  )
+};
+type Pair struct {
+  int a,
+  int b,
+};
+"
+    );
+}
+
+#[test]
+fn meaning_is_kept() {
+    // `core` has comments, long lines & everything it can print
+    let shape = |source: &str| {
+        let mut state = State::new();
+        let lexemes = lexer::lex(&mut state, source);
+        let block = parser::parse(&mut state, lexemes).unwrap();
+        let spans = regex::Regex::new(r", span: Span \{[^}]*\}").unwrap();
+        spans.replace_all(&format!("{block:?}"), "").into_owned()
+    };
+    for width in [10, 40, 80, 100, 200] {
+        for indent in [0, 2, 4] {
+            let formatted = format_with(driver::CORE, &Style { indent, width });
+            assert_eq!(shape(&formatted), shape(driver::CORE));
+            let comments = |source: &str| {
+                source
+                    .lines()
+                    .filter_map(|line| line.find("//").map(|at| line[at..].trim().to_owned()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(comments(&formatted), comments(driver::CORE));
+        }
+    }
+}
+
+#[test]
+fn lossless_tokens() {
+    for source in [
+        driver::CORE,
+        "fn void main() {\r\n\tprintLn(\"ünïcode\"); // é\n}",
+        "let x = $ 1; \"unclosed\nlet y = \"\\q\";",
+        "a /* b\n * c */ d /* never closed",
+    ] {
+        let tokens = lexer::lex_lossless(&mut State::new(), source);
+        let text: String = tokens.iter().map(|token| token.text.as_str()).collect();
+        assert_eq!(text, source);
+    }
+    let mut state = State::new();
+    let tokens = lexer::lex_lossless(&mut state, "a // b\n  $");
+    let kinds: Vec<_> = tokens.iter().map(|token| &token.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            &lexer::TokenKind::Lexeme(LexemeType::Idn("a".to_owned())),
+            &lexer::TokenKind::Whitespace,
+            &lexer::TokenKind::Comment,
+            &lexer::TokenKind::Whitespace,
+            &lexer::TokenKind::Error,
+        ]
+    );
+    assert_eq!(state.errors.len(), 1);
+    assert_eq!((tokens[2].span.sline, tokens[2].span.scol), (0, 3));
+    assert_eq!((tokens[4].span.sline, tokens[4].span.scol), (1, 3));
+
+    // block comments may span lines, but don't nest
+    let mut state = State::new();
+    let tokens = lexer::lex_lossless(&mut state, "a /* b /* c\n */ d */");
+    let kinds: Vec<_> = tokens.iter().map(|token| &token.kind).collect();
+    assert_eq!(kinds[2], &lexer::TokenKind::Comment);
+    assert_eq!(tokens[2].text, "/* b /* c\n */");
+    let span = tokens[2].span;
+    assert_eq!((span.sline, span.scol, span.eline, span.ecol), (0, 3, 2, 4));
+    assert_eq!(
+        tokens[4].kind,
+        lexer::TokenKind::Lexeme(LexemeType::Idn("d".to_owned()))
+    );
+    assert!(state.errors.is_empty());
+}
+
+#[test]
+fn block_comments() {
+    assert_eq!(
+        format(
+            "/* a header,
+   over two lines */
+fn int add(int a, /* the other */ int b) {
+    a+b /* the sum */
+};
+/* before main */ fn int main() { /* inside */ add(1, 2) };"
+        ),
+        "/* a header,
+   over two lines */
+fn int add(
+    int a,
+    /* the other */ int b,
+) {
+    a + b /* the sum */
+};
+/* before main */ fn int main() {
+    /* inside */ add(1, 2)
+};
+"
+    );
+}
+
+#[test]
+fn nested_comments() {
+    // comments stay by the code they were written by, within blocks, lists & literals
+    assert_eq!(
+        format("fn int f(int x) { if x>3 {x} else { /* empty-ish */ 0 } };"),
+        "\
+fn int f(int x) {
+    if x > 3 {
+        x
+    } else {
+        /* empty-ish */ 0
+    }
+};
+"
+    );
+    assert_eq!(
+        format("fn void main() { g(a, // first\n 2); printLn(f(a, /* b */ b /* end */)); };"),
+        "\
+fn void main() {
+    g(
+        a, // first
+        2,
+    );
+    printLn(
+        f(
+            a,
+            /* b */ b, /* end */
+        ),
+    );
+};
+"
+    );
+    assert_eq!(
+        format(
+            "let p = P {\n // the x\n .x = 1, .y = 2 }; // after\n\
+             type P struct {\n    int x, // the x\n    int y\n    // the end\n};"
+        ),
+        "\
+let p = P {
+    // the x
+    .x = 1,
+    .y = 2,
+}; // after
+type P struct {
+    int x, // the x
+    int y,
+    // the end
+};
+"
+    );
+}
+
+#[test]
+fn unformattable() {
+    assert_eq!(
+        error("let x = \"unclosed;"),
+        "this string is never closed (expected a `\"`)"
+    );
+    assert_eq!(error("let x = ;"), "expected an expression");
+    assert_eq!(
+        error("let x = 1; /* never closed"),
+        "this comment is never closed (expected a `*/`)"
+    );
+    // a group which isn't chosen must still parse
+    assert_eq!(
+        error("#if true { let x = 1; } #else { let y = ; }"),
+        "expected an expression"
+    );
+}
+
+#[test]
+fn nothing_is_lost() {
+    // a `}` closing nothing used to end the file, throwing away everything after it
+    let source = "fn int main() { 1 };\n}\nthis is not valid at all ((( \n";
+    assert_eq!(
+        error(source),
+        "closing brace (`}`) without a block to close"
+    );
+    assert_eq!(
+        error("}\n#if true"),
+        "closing brace (`}`) without a block to close"
+    );
+    // & the parser still reads what's after it
+    let mut state = State::new();
+    let lexemes = lexer::lex(&mut state, "fn int main() { 1 };\n}\nlet x = 1;");
+    let block = parser::parse(&mut state, lexemes).unwrap();
+    assert_eq!(block.stmts.len(), 2);
+    assert_eq!(state.errors.len(), 1);
+
+    // stubs which leave out `void` still do
+    assert_eq!(
+        format("type Print; fn print(Print);"),
+        "type Print;\nfn print(Print);\n"
+    );
+}
+
+#[test]
+fn conditionals() {
+    // every group is kept, with the comments in & between them
+    assert_eq!(
+        format(
+            "#if 1>2 {let a=1;}   #else if   true   // why
+{ let b=2;
+  /* inner */ let c=3; } #else {}
+fn int main(){
+    #if true { fn void f(){ #if false {let z=1;} }; } #else { let y= 1; } // after
+  let z = { #if true { 1 } #else { 2 } };
+  z
+};"
+        ),
+        "#if 1 > 2 {
+    let a = 1;
+} #else if true {
+    // why
+    let b = 2;
+    /* inner */ let c = 3;
+} #else {}
+fn int main() {
+    #if true {
+        fn void f() {
+            #if false {
+                let z = 1;
+            }
+        };
+    } #else {
+        let y = 1;
+    } // after
+    let z = {
+        #if true {
+            1
+        } #else {
+            2
+        }
+    };
+    z
+};
+"
+    );
+}
+
+#[test]
+fn check_mode() {
+    let file = std::env::temp_dir().join(format!("sdw-fmt-{}.sdw", std::process::id()));
+    let sdw = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_sdw"))
+            .arg("fmt")
+            .arg(&file)
+            .args(args)
+            .output()
+            .expect("failed to run `sdw`")
+    };
+    std::fs::write(&file, "fn  int main(){0};").unwrap();
+    assert_eq!(sdw(&["--check"]).status.code(), Some(1));
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "fn  int main(){0};"
+    );
+    assert!(sdw(&[]).status.success());
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "fn int main() {\n    0\n};\n"
+    );
+    assert!(sdw(&["--check"]).status.success());
+    assert_eq!(sdw(&["--check", "--indent", "2"]).status.code(), Some(1));
+    let _ = std::fs::remove_file(file);
+}
@@ -0,0 +1,182 @@
+use sdw::driver;
+use sdw::interp::{self, Value};
+use sdw::prelude::*;
+
+fn run(source: &str) -> std::result::Result<Value, SdwErr> {
+    let mut state = State::new();
+    let Ok(checked) = driver::check(&mut state, source, &mut ()) else {
+        panic!("failed to check: {:#?}", state.errors);
+    };
+    interp::run(&checked)
+}
+
+fn int(source: &str) -> i64 {
+    match run(source) {
+        Ok(Value::Int(int)) => int,
+        other => panic!("expected an `int`, got {other:?}"),
+    }
+}
+
+/// the runtime error, & the functions on the call stack when it was raised
+fn failure(source: &str) -> (String, Vec<String>) {
+    let err = run(source).expect_err("expected a runtime error");
+    let trace = err.notes.iter().map(|note| note.message.clone()).collect();
+    (err.ty.to_string(), trace)
+}
+
+#[test]
+fn recursion() {
+    let source = "
+        fn int fib(int n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+        fn int main() { fib(20) };
+    ";
+    assert_eq!(int(source), 6765);
+}
+
+#[test]
+fn variables_and_blocks() {
+    let source = "
+        fn int main() {
+            let a = 2;
+            let b = { let c = a * 10; c + 1 };
+            a = a + b;
+            if a > 20 { a } else if a > 10 { 0 } else { 1 }
+        };
+    ";
+    assert_eq!(int(source), 23);
+}
+
+#[test]
+fn loops_and_gotos() {
+    let source = "
+        fn int main() {
+            let total = 0;
+            let n = 5;
+            goto @start;
+            loop {
+                total = total + n;
+                @start;
+                n = n + 1;
+                if n > 10 { goto @done; };
+            };
+            @done;
+            total
+        };
+    ";
+    // 6 + 7 + .. + 10, as the first pass jumps over adding 5
+    assert_eq!(int(source), 40);
+}
+
+#[test]
+fn structs_and_pointers() {
+    let source = "
+        type Point struct { int x, int y };
+        type PointPtr &Point;
+        type Line struct { Point from, Point to };
+
+        fn int length(PointPtr point) { point.x + point.y };
+        fn PointPtr pick(PointPtr a, PointPtr b, bool first) { if first { a } else { b } };
+
+        fn int main() {
+            let line = Line { .to = Point { 3, 4 }, .from = Point { 1, 2 } };
+            let to = line.to;
+            let from = line.from;
+            let chosen = pick(&from, &to, false);
+            let x = &to.x;
+            length(chosen) * 10 + *x
+        };
+    ";
+    assert_eq!(int(source), 73);
+}
+
+#[test]
+fn unions_and_interfaces() {
+    let source = "
+        type Some struct { int some };
+        type None struct;
+        type Option union { Some some, None none };
+
+        fn int unwrap(Some some) { some.some };
+        fn int unwrap(None none) { 0 };
+        fn Option half(int n) { if n % 2 == 0 { Some { n / 2 } } else { None {} } };
+
+        type Area;
+        fn int area(Area);
+        type Square struct { int side };
+        fn int area(Square square) { square.side * square.side };
+        fn int twice(Area shape) { area(shape) * 2 };
+
+        fn int main() { unwrap(half(10)) + unwrap(half(7)) + twice(Square { 3 }) };
+    ";
+    assert_eq!(int(source), 23);
+}
+
+#[test]
+fn dispatching_on_several_unions() {
+    let source = "
+        type Some struct { int some };
+        type None struct;
+        type Option union { Some some, None none };
+        fn Option maybe(int n) { if n > 0 { Some { n } } else { None {} } };
+
+        // the first `Option` is matched on, then - only if it's a `Some` - the second
+        fn int both(Some a, Some b) { a.some * b.some };
+        fn int both(Some a, None b) { a.some };
+        fn int both(None a, Option b) { 100 };
+
+        fn int main() { both(maybe(2), maybe(3)) + both(maybe(5), maybe(0)) * 10 + both(maybe(0), maybe(7)) };
+    ";
+    assert_eq!(int(source), 156);
+}
+
+#[test]
+fn function_pointers() {
+    let source = "
+        type Op (int) -> int;
+        fn int double(int n) { n * 2 };
+        fn int apply(Op op, int n) { op(n) };
+        fn int main() { apply(double, 21) };
+    ";
+    assert_eq!(int(source), 42);
+}
+
+#[test]
+fn runtime_errors() {
+    let source = "
+        fn int divide(int a, int b) { a / b };
+        fn int average(int total, int count) { divide(total, count) };
+        fn int main() { average(10, 0) };
+    ";
+    assert_eq!(
+        failure(source),
+        (
+            "`10 / 0` divides by zero".to_owned(),
+            vec![
+                "in `divide`, called here".to_owned(),
+                "in `average`, called here".to_owned(),
+                "in `main`".to_owned(),
+            ]
+        )
+    );
+
+    let source = "
+        type Some struct { int some };
+        type None struct;
+        type Option union { Some some, None none };
+        fn int main() { let op = Option { .none = None {} }; let some = op.some; some.some };
+    ";
+    assert_eq!(
+        failure(source).0,
+        "the union holds its `none` member, not `some`"
+    );
+
+    let source = "
+        fn int down(int n) { 1 + down(n + 1) };
+        fn int main() { down(0) };
+    ";
+    let (err, trace) = failure(source);
+    assert_eq!(err, "the call stack overflowed, at 10000 calls deep");
+    assert_eq!(trace[0], "in `down`, called here (9997 times)");
+
+    assert_eq!(failure("fn int start() { 0 };").0, "no `fn main()` to run");
+}
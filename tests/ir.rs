@@ -0,0 +1,299 @@
+mod common;
+
+use common::checked;
+use sdw::ir::{Inst, Module, Terminator};
+use sdw::passes::{self, ConstProp, Dce, PassManager};
+use sdw::prelude::*;
+use sdw::{lower, mono};
+
+/// lowers the source, checking the module is well formed before & after optimising it
+fn lowered(source: &str) -> (Module, Module) {
+    let raw = lower::lower(&checked(source));
+    let mut optimised = raw.clone();
+    passes::optimise(&mut optimised);
+    for module in [&raw, &optimised] {
+        for function in &module.functions {
+            if let Err(err) = function.verify() {
+                panic!("`{}` is malformed: {err}\n{module}", function.name);
+            }
+        }
+    }
+    (raw, optimised)
+}
+
+fn function<'a>(module: &'a Module, name: &str) -> &'a sdw::ir::Function {
+    module
+        .functions
+        .iter()
+        .find(|function| function.name == name)
+        .unwrap_or_else(|| panic!("no `{name}` in:\n{module}"))
+}
+
+/// the dump of one function of a module
+fn dump(module: &Module, name: &str) -> String {
+    let text = module.to_string();
+    let header = format!("fn @{name}(");
+    let start = text
+        .find(&header)
+        .unwrap_or_else(|| panic!("no `{name}` in:\n{module}"));
+    let end = text[start..].find("\n}\n").unwrap() + start;
+    format!("{}\n}}\n", &text[start..end])
+}
+
+/// every instruction of a function, in block order
+fn insts<'a>(module: &'a Module, name: &str) -> Vec<&'a Inst> {
+    let function = function(module, name);
+    function
+        .blocks
+        .iter()
+        .flat_map(|block| &block.insts)
+        .map(|value| function.inst(*value))
+        .collect()
+}
+
+#[test]
+fn folds_constants() {
+    let (_, optimised) = lowered(
+        "fn int main() {
+            let a = 2;
+            let b = a * 3;
+            if b > 5 { b } else { 0 }
+         };",
+    );
+    assert_eq!(
+        optimised.to_string(),
+        "\
+fn @.init() -> void {
+bb0:
+    %0: void = void
+    return %0
+}
+
+fn @main() -> int (entry point) {
+bb0:
+    %0: int = int 6
+    return %0
+}
+"
+    );
+}
+
+#[test]
+fn golden() {
+    let (raw, optimised) = lowered(
+        "fn int fib(int n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+         fn int main() { fib(2 * 5) };",
+    );
+    assert_eq!(
+        dump(&raw, "fib"),
+        "\
+fn @fib(int) -> int {
+bb0:
+    %0: int = param 0
+    %1: int = int 2
+    %2: bool = op < %0, %1
+    branch %2, bb2, bb3
+bb1:
+    %3: int = phi [bb2: %0], [bb3: %10]
+    return %3
+bb2:
+    jump bb1
+bb3:
+    %4: int = int 1
+    %5: int = op - %0, %4
+    %6: int = call @fib(%5)
+    %7: int = int 2
+    %8: int = op - %0, %7
+    %9: int = call @fib(%8)
+    %10: int = op + %6, %9
+    jump bb1
+}
+"
+    );
+    assert_eq!(
+        dump(&optimised, "main"),
+        "\
+fn @main() -> int (entry point) {
+bb0:
+    %0: int = int 10
+    %1: int = call @fib(%0)
+    return %1
+}
+"
+    );
+}
+
+#[test]
+fn control_flow() {
+    let (raw, optimised) = lowered(
+        "fn int main() {
+            let total = 0;
+            let n = 5;
+            goto @start;
+            loop {
+                total = total + n;
+                @start;
+                n = n + 1;
+                if n > 10 { goto @done; };
+            };
+            @done;
+            total
+         };",
+    );
+    let phis = |module| {
+        insts(module, "main")
+            .into_iter()
+            .filter(|inst| matches!(inst, Inst::Phi(_)))
+            .count()
+    };
+    // `total` & `n` meet at the loop's head
+    assert_eq!(phis(&raw), 2);
+    assert_eq!(phis(&optimised), 2);
+    assert!(function(&optimised, "main").blocks.len() < function(&raw, "main").blocks.len());
+
+    let (_, optimised) = lowered(
+        "fn int main() {
+            let n = 0;
+            let a = 1 + { @inside; n = n + 1; 2 };
+            if n < 2 { goto @inside; };
+            a
+         };",
+    );
+    assert!(function(&optimised, "main")
+        .blocks
+        .iter()
+        .any(|block| matches!(block.term, Terminator::Trap(_))));
+}
+
+#[test]
+fn well_formed() {
+    let sources = [
+        "type Point struct { int x, int y };
+         type PointPtr &Point;
+         type Line struct { Point from, Point to };
+         fn int length(PointPtr point) { point.x + point.y };
+         fn int main() {
+            let line = Line { .to = Point { 3, 4 }, .from = Point { 1, 2 } };
+            let to = line.to;
+            let x = &to.x;
+            length(&to) * 10 + *x
+         };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn int unwrap(Some some) { some.some };
+         fn int unwrap(None none) { 0 };
+         fn Option half(int n) { if n % 2 == 0 { Some { n / 2 } } else { None {} } };
+         fn int main() { unwrap(half(10)) + unwrap(half(7)) };",
+        "type Op (int) -> int;
+         fn int double(int n) { n * 2 };
+         fn int apply(Op op, int n) { op(n) };
+         fn int main() { apply(double, 21) };",
+        "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         fn int area(Square square) { square.side * square.side };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { twice(Square { 3 }) };",
+        "fn bool both(bool a, bool b) { a && b || !a && !b };
+         fn unt main() { if both(true, false) { 1 } else { 1 << 4 } };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn Option maybe(int n) { if n > 0 { Some { n } } else { None {} } };
+         fn int both(Some a, Some b) { a.some * b.some };
+         fn int both(Some a, None b) { a.some };
+         fn int both(None a, Option b) { 100 };
+         fn int main() { both(maybe(2), maybe(3)) + both(maybe(5), maybe(0)) * 10 + both(maybe(0), maybe(7)) };",
+    ];
+    for source in sources {
+        lowered(source);
+    }
+
+    let (raw, _) = lowered(sources[1]);
+    assert!(insts(&raw, "main")
+        .iter()
+        .any(|inst| matches!(inst, Inst::Tag(_))));
+    let (raw, _) = lowered(sources[2]);
+    assert!(insts(&raw, "apply")
+        .iter()
+        .any(|inst| matches!(inst, Inst::CallPtr { .. })));
+    let (raw, _) = lowered(sources[3]);
+    assert!(insts(&raw, "twice")
+        .iter()
+        .any(|inst| matches!(inst, Inst::CallInterface { .. })));
+}
+
+#[test]
+fn monomorphise() {
+    let source = "type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         type Circle struct { int radius };
+         fn int area(Square square) { square.side * square.side };
+         fn int area(Circle circle) { 3 * circle.radius * circle.radius };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { twice(Square { 3 }) + twice(Circle { 2 }) };";
+    let checked = checked(source);
+    let mut module = lower::lower(&checked);
+    let mut state = State::new();
+    mono::monomorphise(&mut state, &mut module, &checked, "a test");
+    assert!(state.errors.is_empty(), "{:#?}", state.errors);
+    for function in &module.functions {
+        if let Err(err) = function.verify() {
+            panic!("`{}` is malformed: {err}\n{module}", function.name);
+        }
+    }
+
+    // a copy for each shape, calling the implementation for it
+    assert!(module
+        .functions
+        .iter()
+        .all(|function| function.name != "twice"));
+    assert_eq!(
+        dump(&module, "twice__6Square"),
+        "fn @twice__6Square(Square) -> int {
+bb0:
+    %0: Square = param 0
+    %1: int = call @area.0(%0)
+    %2: int = int 2
+    %3: int = op * %1, %2
+    return %3
+}
+"
+    );
+    assert!(insts(&module, "twice__6Circle")
+        .iter()
+        .any(|inst| matches!(inst, Inst::Call { .. })));
+    assert_eq!(module.functions[module.main.unwrap() as usize].name, "main");
+
+    // a generic value can't be two types at once in one copy
+    let source = "type Shown;
+         fn Shown pick(Shown a, Shown b, bool first) { if first { return a; }; b };
+         fn int main() { pick(3, true, false); 0 };";
+    let conflicting = common::checked(source);
+    let mut module = lower::lower(&conflicting);
+    let mut state = State::new();
+    mono::monomorphise(&mut state, &mut module, &conflicting, "a test");
+    assert!(matches!(
+        &state.errors[..],
+        [SdwErr { ty: ErrType::Emit(EmitErrors::Unsupported { what, .. }), .. }]
+            if what == "a generic value which is both `bool` and `int`"
+    ));
+}
+
+#[test]
+fn pass_manager() {
+    assert_eq!(
+        PassManager::standard().names(),
+        ["const-prop", "dce", "simplify-cfg"]
+    );
+
+    // without dce, folded operands are left behind
+    let source = "fn int main() { 2 + 3 * 4 };";
+    let mut module = lower::lower(&checked(source));
+    PassManager::new().with(ConstProp).run(&mut module);
+    assert_eq!(insts(&module, "main").len(), 5);
+    PassManager::new().with(Dce).run(&mut module);
+    assert_eq!(insts(&module, "main"), [&Inst::Int(14)]);
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::checked;
+use sdw::driver::Checked;
+use sdw::layout::{self, Layouts, Target};
+use sdw::prelude::*;
+use std::path::PathBuf;
+use std::process::Command;
+
+const SOURCE: &str = "
+type Col struct { bool r, int g, bool b };
+type Packed struct { bool a, bool b, unt c };
+type Nested struct { bool flag, Col col, bool after };
+type Named struct { string name, &Col col, bool short };
+type Some struct { int some };
+type None struct { bool unused };
+type Option union { Some some, None none };
+type Flag union { bool on, Col col };
+type Holder struct { bool first, Option option };
+type Callback struct { bool set, (int) -> int call };
+type Colour Col;
+";
+
+/// the same declarations, following the layout rules documented in `layout`
+const C_SOURCE: &str = r#"
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+
+typedef struct { const char *ptr; size_t len; } string;
+
+typedef struct { bool r; int64_t g; bool b; } Col;
+typedef struct { bool a; bool b; uint64_t c; } Packed;
+typedef struct { bool flag; Col col; bool after; } Nested;
+typedef struct { string name; Col *col; bool short_; } Named;
+typedef struct { int64_t some; } Some;
+typedef struct { bool unused; } None;
+typedef struct { uint32_t discriminant; union { Some some; None none; } value; } Option;
+typedef struct { uint32_t discriminant; union { bool on; Col col; } value; } Flag;
+typedef struct { bool first; Option option; } Holder;
+typedef struct { bool set; int64_t (*call)(int64_t); } Callback;
+typedef Col Colour;
+
+#define TYPE(T) printf(#T " %zu %zu\n", sizeof(T), _Alignof(T))
+#define FIELD(T, f, name) printf(#T "." name " %zu\n", offsetof(T, f))
+#define MEMBER(T, f, name) printf(#T "." name " %zu\n", offsetof(T, value.f))
+
+int main(void) {
+    TYPE(Col); FIELD(Col, r, "r"); FIELD(Col, g, "g"); FIELD(Col, b, "b");
+    TYPE(Packed); FIELD(Packed, a, "a"); FIELD(Packed, b, "b"); FIELD(Packed, c, "c");
+    TYPE(Nested); FIELD(Nested, flag, "flag"); FIELD(Nested, col, "col");
+    FIELD(Nested, after, "after");
+    TYPE(Named); FIELD(Named, name, "name"); FIELD(Named, col, "col");
+    FIELD(Named, short_, "short");
+    TYPE(Some); FIELD(Some, some, "some");
+    TYPE(None); FIELD(None, unused, "unused");
+    TYPE(Option); MEMBER(Option, some, "some"); MEMBER(Option, none, "none");
+    TYPE(Flag); MEMBER(Flag, on, "on"); MEMBER(Flag, col, "col");
+    TYPE(Holder); FIELD(Holder, first, "first"); FIELD(Holder, option, "option");
+    TYPE(Callback); FIELD(Callback, set, "set"); FIELD(Callback, call, "call");
+    TYPE(Colour); FIELD(Colour, r, "r"); FIELD(Colour, g, "g"); FIELD(Colour, b, "b");
+    return 0;
+}
+"#;
+
+fn decl(checked: &Checked, name: &str) -> DefId {
+    checked
+        .res
+        .defs
+        .iter()
+        .position(|def| def.kind == DefKind::Type && def.name == name)
+        .map(DefId)
+        .unwrap_or_else(|| panic!("no type `{name}`"))
+}
+
+/// `sdw`'s layouts, in the format the C program prints them
+fn describe(checked: &Checked, layouts: &Layouts) -> Vec<String> {
+    let mut lines = Vec::new();
+    for name in [
+        "Col", "Packed", "Nested", "Named", "Some", "None", "Option", "Flag", "Holder", "Callback",
+        "Colour",
+    ] {
+        let layout = layouts.decl(decl(checked, name)).unwrap();
+        lines.push(format!("{name} {} {}", layout.size, layout.align));
+        for field in &layout.fields {
+            lines.push(format!("{name}.{} {}", field.name, field.offset));
+        }
+    }
+    lines
+}
+
+/// compiles & runs the C program, or `None` if there's no C compiler to hand
+fn c_layouts() -> Option<Vec<String>> {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("layout");
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("layout.c");
+    let binary = dir.join("layout");
+    std::fs::write(&source, C_SOURCE).unwrap();
+
+    let compiled = Command::new("cc")
+        .args(["-std=c11", "-o"])
+        .arg(&binary)
+        .arg(&source)
+        .status()
+        .ok()?;
+    assert!(compiled.success(), "failed to compile the C layouts");
+
+    let output = Command::new(&binary).output().unwrap();
+    assert!(output.status.success());
+    Some(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+#[test]
+fn matches_c() {
+    if !cfg!(all(target_arch = "x86_64", unix)) {
+        return;
+    }
+    let Some(expected) = c_layouts() else {
+        eprintln!("no C compiler found; skipping");
+        return;
+    };
+
+    let checked = checked(SOURCE);
+    let layouts = layout::compute(&checked.types, &Target::X86_64);
+    assert_eq!(describe(&checked, &layouts), expected);
+}
+
+#[test]
+fn pointer_width() {
+    let checked = checked("type Link struct { bool set, &Link next, string name };");
+    let link = decl(&checked, "Link");
+
+    let wide = layout::compute(&checked.types, &Target::X86_64);
+    let wide = wide.decl(link).unwrap();
+    assert_eq!((wide.size, wide.align), (32, 8));
+    assert_eq!(wide.fields[1].offset, 8);
+    assert_eq!(wide.fields[2].offset, 16);
+
+    let narrow = layout::compute(&checked.types, &Target::WASM32);
+    let narrow = narrow.decl(link).unwrap();
+    assert_eq!((narrow.size, narrow.align), (16, 4));
+    assert_eq!(narrow.fields[1].offset, 4);
+    assert_eq!(narrow.fields[2].offset, 8);
+}
+
+#[test]
+fn generics_have_no_layout() {
+    let checked = checked("type Print; type Held struct { Print held };");
+    let layouts = layout::compute(&checked.types, &Target::X86_64);
+    assert!(layouts.decl(decl(&checked, "Print")).is_none());
+    assert!(layouts.decl(decl(&checked, "Held")).is_none());
+}
+
+#[test]
+fn only_the_files_types() {
+    // `core`'s types (like `Printable`) aren't listed, even where the file shadows them
+    let file = std::env::temp_dir().join(format!("sdw-layout-{}.sdw", std::process::id()));
+    std::fs::write(&file, "type Printable struct { int x };").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sdw"))
+        .arg("layout")
+        .arg(&file)
+        .output()
+        .expect("failed to run `sdw`");
+    let _ = std::fs::remove_file(file);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("Printable").count(), 1, "{stdout}");
+    assert!(stdout.contains("size 8"), "{stdout}");
+}
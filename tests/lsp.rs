@@ -0,0 +1,379 @@
+use sdw::json::Json;
+use sdw::lsp;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+const URI: &str = "file:///tmp/main.sdw";
+
+const SOURCE: &str = "\
+type Pair struct { int left, int right };
+fn int add(int a, int b) { a + b };
+fn void main() {
+    let total = add(1, 2);
+    let pair = Pair { total, 3 };
+    printLn(pair.left + total);
+};
+";
+
+fn frame(message: &Json) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+fn request(id: usize, method: &str, params: Json) -> Json {
+    Json::obj([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::obj([
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ])
+}
+
+fn open(text: &str) -> Json {
+    notification(
+        "textDocument/didOpen",
+        Json::obj([(
+            "textDocument",
+            Json::obj([
+                ("uri", URI.into()),
+                ("languageId", "sdw".into()),
+                ("version", 1.into()),
+                ("text", text.into()),
+            ]),
+        )]),
+    )
+}
+
+fn at(id: usize, method: &str, line: usize, character: usize) -> Json {
+    request(
+        id,
+        method,
+        Json::obj([
+            ("textDocument", Json::obj([("uri", URI.into())])),
+            (
+                "position",
+                Json::obj([("line", line.into()), ("character", character.into())]),
+            ),
+        ]),
+    )
+}
+
+fn document(id: usize, method: &str) -> Json {
+    request(
+        id,
+        method,
+        Json::obj([("textDocument", Json::obj([("uri", URI.into())]))]),
+    )
+}
+
+fn split(mut output: &str) -> Vec<Json> {
+    let mut messages = Vec::new();
+    while let Some(rest) = output.strip_prefix("Content-Length: ") {
+        let (length, rest) = rest.split_once("\r\n\r\n").unwrap();
+        let length: usize = length.parse().unwrap();
+        messages.push(Json::parse(&rest[..length]).unwrap());
+        output = &rest[length..];
+    }
+    assert!(output.is_empty(), "{output:?}");
+    messages
+}
+
+/// runs a whole session, giving every message the server sent & the code it exited with
+fn session(messages: &[Json]) -> (Vec<Json>, i32) {
+    let input: String = messages.iter().map(frame).collect();
+    let mut output = Vec::new();
+    let code = lsp::serve(&mut input.as_bytes(), &mut output).unwrap();
+    (split(&String::from_utf8(output).unwrap()), code)
+}
+
+/// the response to the request with `id`
+fn response(messages: &[Json], id: usize) -> &Json {
+    messages
+        .iter()
+        .find(|message| message["id"].as_u64() == Some(id as u64))
+        .unwrap_or_else(|| panic!("no response to {id}"))
+}
+
+fn diagnostics(messages: &[Json]) -> Vec<&Json> {
+    messages
+        .iter()
+        .filter(|message| message["method"].as_str() == Some("textDocument/publishDiagnostics"))
+        .map(|message| &message["params"]["diagnostics"])
+        .collect()
+}
+
+fn range(json: &Json) -> [u64; 4] {
+    [
+        json["start"]["line"].as_u64().unwrap(),
+        json["start"]["character"].as_u64().unwrap(),
+        json["end"]["line"].as_u64().unwrap(),
+        json["end"]["character"].as_u64().unwrap(),
+    ]
+}
+
+#[test]
+fn lifecycle() {
+    let shutdown = request(2, "shutdown", Json::Null);
+    let exit = notification("exit", Json::Null);
+    let (messages, code) = session(&[
+        request(1, "initialize", Json::obj([])),
+        notification("initialized", Json::obj([])),
+        shutdown.clone(),
+        exit.clone(),
+    ]);
+    assert_eq!(code, 0);
+    let capabilities = &response(&messages, 1)["result"]["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], Json::Bool(true));
+    assert_eq!(capabilities["definitionProvider"], Json::Bool(true));
+    assert_eq!(capabilities["documentSymbolProvider"], Json::Bool(true));
+    assert_eq!(
+        capabilities["semanticTokensProvider"]["legend"]["tokenTypes"][0],
+        Json::from("keyword")
+    );
+    assert_eq!(response(&messages, 2)["result"], Json::Null);
+
+    // exiting without shutting down first is an error
+    assert_eq!(session(std::slice::from_ref(&exit)).1, 1);
+    // as is the client going away
+    assert_eq!(session(&[]).1, 1);
+
+    let (messages, _) = session(&[request(7, "textDocument/rename", Json::obj([])), exit]);
+    assert_eq!(response(&messages, 7)["error"]["code"], Json::Num(-32601.0));
+}
+
+#[test]
+fn diagnostics_follow_changes() {
+    let change = |text: &str| {
+        notification(
+            "textDocument/didChange",
+            Json::obj([
+                (
+                    "textDocument",
+                    Json::obj([("uri", URI.into()), ("version", 2.into())]),
+                ),
+                (
+                    "contentChanges",
+                    Json::Arr(vec![Json::obj([("text", text.into())])]),
+                ),
+            ]),
+        )
+    };
+    let (messages, _) = session(&[
+        open("fn void main() {\n    let s = \"𝄞\"; printLn(s + missing);\n};"),
+        change("fn void main() { let x = ; };"),
+        change(SOURCE),
+        change("fn void main() {\n    let x = 1;\n    if true { let x = 2; };\n};"),
+        notification(
+            "textDocument/didClose",
+            Json::obj([("textDocument", Json::obj([("uri", URI.into())]))]),
+        ),
+    ]);
+    let published = diagnostics(&messages);
+    assert_eq!(published.len(), 5);
+
+    // positions count utf-16 code units, which `𝄞` is two of
+    let undeclared = &published[0][0];
+    assert_eq!(
+        undeclared["message"],
+        Json::from("use of undeclared variable `missing`")
+    );
+    assert_eq!(undeclared["severity"], Json::Num(1.0));
+    assert_eq!(undeclared["code"], Json::from("SDW E/R"));
+    assert_eq!(range(&undeclared["range"]), [1, 30, 1, 37]);
+
+    assert_eq!(
+        published[1][0]["message"],
+        Json::from("expected an expression")
+    );
+    assert_eq!(published[2], &Json::Arr(Vec::new()));
+
+    let shadowed = &published[3][0];
+    assert_eq!(shadowed["severity"], Json::Num(2.0));
+    assert_eq!(range(&shadowed["range"]), [2, 18, 2, 19]);
+    let note = &shadowed["relatedInformation"][0];
+    assert_eq!(note["message"], Json::from("shadows this variable"));
+    assert_eq!(range(&note["location"]["range"]), [1, 8, 1, 9]);
+    assert_eq!(published[4], &Json::Arr(Vec::new()));
+}
+
+#[test]
+fn symbols() {
+    let source = "\
+type Print;
+type Shape union { int circle, bool square };
+fn print(Print);
+fn int outer() {
+    fn int inner() { 1 };
+    inner()
+};
+#if false { fn void hidden() {}; }
+// `mod` is a keyword, but there's no such declaration to list
+";
+    let (messages, _) = session(&[open(source), document(1, "textDocument/documentSymbol")]);
+    let symbols = response(&messages, 1)["result"].as_array().unwrap();
+    let names: Vec<_> = symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol["name"].as_str().unwrap(),
+                symbol["kind"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("Print", 26),
+            ("Shape", 10),
+            ("print", 12),
+            ("outer", 12),
+            ("hidden", 12)
+        ]
+    );
+    assert_eq!(symbols[2]["detail"], Json::from("stub"));
+    assert_eq!(range(&symbols[3]["range"]), [3, 0, 6, 2]);
+    assert_eq!(range(&symbols[3]["selectionRange"]), [3, 7, 3, 12]);
+    assert_eq!(symbols[3]["children"][0]["name"], Json::from("inner"));
+}
+
+#[test]
+fn definitions_and_hovers() {
+    let (messages, _) = session(&[
+        open(SOURCE),
+        // `total`, where it's added
+        at(1, "textDocument/definition", 5, 25),
+        at(2, "textDocument/hover", 5, 25),
+        // `add`, where it's called
+        at(3, "textDocument/definition", 3, 17),
+        at(4, "textDocument/hover", 3, 17),
+        // `Pair`, in the struct literal
+        at(5, "textDocument/hover", 4, 16),
+        // `printLn`, which is in `core`
+        at(6, "textDocument/definition", 5, 6),
+        at(7, "textDocument/hover", 5, 6),
+        // nothing at all
+        at(8, "textDocument/hover", 3, 0),
+    ]);
+
+    let definition = &response(&messages, 1)["result"];
+    assert_eq!(definition["uri"], Json::from(URI));
+    assert_eq!(range(&definition["range"]), [3, 8, 3, 13]);
+    let hover = &response(&messages, 2)["result"];
+    assert_eq!(hover["contents"]["value"], Json::from("total: int"));
+    assert_eq!(range(&hover["range"]), [5, 24, 5, 29]);
+
+    assert_eq!(
+        range(&response(&messages, 3)["result"]["range"]),
+        [1, 7, 1, 10]
+    );
+    assert_eq!(
+        response(&messages, 4)["result"]["contents"]["value"],
+        Json::from("fn int add(int, int)")
+    );
+    assert_eq!(
+        response(&messages, 5)["result"]["contents"]["value"],
+        Json::from("type Pair struct { int left, int right }")
+    );
+    assert_eq!(response(&messages, 6)["result"], Json::Null);
+    assert_eq!(
+        response(&messages, 7)["result"]["contents"]["value"],
+        Json::from("fn void printLn(int)")
+    );
+    assert_eq!(response(&messages, 8)["result"], Json::Null);
+}
+
+#[test]
+fn semantic_tokens() {
+    let (messages, _) = session(&[
+        open("fn int one() {\n  \"é\" + 1 // one\n} /* é\nx */;"),
+        document(1, "textDocument/semanticTokens/full"),
+    ]);
+    let data: Vec<_> = response(&messages, 1)["result"]["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|num| num.as_u64().unwrap())
+        .collect();
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        [
+            0, 0, 2, 0, 0, // fn
+            0, 3, 3, 1, 0, // int
+            0, 4, 3, 2, 0, // one
+            1, 2, 3, 4, 0, // "é"
+            0, 4, 1, 5, 0, // +
+            0, 2, 1, 3, 0, // 1
+            0, 2, 6, 6, 0, // // one
+            1, 2, 4, 6, 0, // /* é
+            1, 0, 4, 6, 0, // x */
+        ]
+    );
+}
+
+#[test]
+fn json() {
+    let text = r#"{"a": [1, -2.5e1, true, null], "b": {"c": "\"\u00e9\ud834\udd1e\n"}, "d": []}"#;
+    let json = Json::parse(text).unwrap();
+    assert_eq!(json["a"][1], Json::Num(-25.0));
+    assert_eq!(json["b"]["c"], Json::from("\"é𝄞\n"));
+    assert_eq!(json["missing"][3], Json::Null);
+    assert_eq!(
+        json.to_string(),
+        r#"{"a":[1,-25,true,null],"b":{"c":"\"é𝄞\n"},"d":[]}"#
+    );
+    assert_eq!(Json::parse(&json.to_string()), Some(json));
+    for invalid in ["", "{", "[1,]", "{\"a\" 1}", "nul", "1 2", "\"\\x\""] {
+        assert_eq!(Json::parse(invalid), None, "{invalid}");
+    }
+}
+
+#[test]
+fn over_stdio() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sdw"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run `sdw`");
+    let input: String = [
+        request(1, "initialize", Json::obj([])),
+        open("fn void main() { nope(); };"),
+        request(2, "shutdown", Json::Null),
+        notification("exit", Json::Null),
+    ]
+    .iter()
+    .map(frame)
+    .collect();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let mut output = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let messages = split(&output);
+    assert_eq!(messages.len(), 3);
+    assert!(response(&messages, 1)["result"]["capabilities"] != Json::Null);
+    let published = diagnostics(&messages);
+    assert_eq!(
+        published[0][0]["message"],
+        Json::from("use of undeclared function `nope`")
+    );
+}
@@ -0,0 +1,163 @@
+use sdw::parser::{BiOps, Block, Bound, Expr, Stmt, StructLitFields};
+use sdw::prelude::*;
+use sdw::{lexer, parser};
+
+fn parse(source: &str) -> Block {
+    let mut state = State::new();
+    let lexemes = lexer::lex(&mut state, source);
+    let block = parser::parse(&mut state, lexemes);
+    assert!(state.errors.is_empty(), "{source}\n{:?}", state.errors);
+    block.unwrap_or_else(|err| panic!("{source}\n{err:?}"))
+}
+
+/// the expression a source is, as the tail of the module
+fn expr(source: &str) -> Expr {
+    parse(source).tail.expect("no tail").spanned
+}
+
+/// the first error parsing `source` raises
+fn error(source: &str) -> String {
+    let mut state = State::new();
+    let lexemes = lexer::lex(&mut state, source);
+    if let Err(err) = parser::parse(&mut state, lexemes) {
+        state.errors.push(err);
+    }
+    state.errors.first().expect("no error").ty.to_string()
+}
+
+fn int(expr: &Expr) -> i64 {
+    match expr {
+        Expr::IntLiteral(int) => *int,
+        expr => panic!("not an int: {expr:?}"),
+    }
+}
+
+fn variable(expr: &Expr) -> &str {
+    match expr {
+        Expr::Variable(name) => name,
+        expr => panic!("not a variable: {expr:?}"),
+    }
+}
+
+#[test]
+fn struct_literals() {
+    let Expr::StructLit { ty, fields } = expr("Point { 1, 2 }") else {
+        panic!("not a struct literal");
+    };
+    assert_eq!(ty.spanned, "Point");
+    let StructLitFields::Positional(values) = fields else {
+        panic!("not positional");
+    };
+    assert_eq!(
+        values
+            .iter()
+            .map(|value| int(&value.spanned))
+            .collect::<Vec<_>>(),
+        [1, 2]
+    );
+
+    let Expr::StructLit { fields, .. } = expr("Point { .y = 2, .x = 1 }") else {
+        panic!("not a struct literal");
+    };
+    let StructLitFields::Named(members) = fields else {
+        panic!("not named");
+    };
+    let members = members
+        .iter()
+        .map(|(name, value)| (name.spanned.as_str(), int(&value.spanned)))
+        .collect::<Vec<_>>();
+    assert_eq!(members, [("y", 2), ("x", 1)]);
+
+    // an empty literal is positional, with no fields
+    let Expr::StructLit { fields, .. } = expr("Done {}") else {
+        panic!("not a struct literal");
+    };
+    assert!(matches!(fields, StructLitFields::Positional(values) if values.is_empty()));
+
+    for mixed in ["Point { 1, .y = 2 }", "Point { .x = 1, 2 }"] {
+        assert_eq!(
+            error(mixed),
+            "struct literals cannot mix named (`.name = expr`) and positional fields"
+        );
+    }
+}
+
+#[test]
+fn conditions_are_not_struct_literals() {
+    // the brace after `done` opens the body
+    let Expr::Cond {
+        condition,
+        then,
+        r#else,
+        ..
+    } = expr("if done { 1 } else { 2 }")
+    else {
+        panic!("not a condition");
+    };
+    assert_eq!(variable(&condition.spanned), "done");
+    assert_eq!(int(&then.spanned.tail.unwrap().spanned), 1);
+    assert_eq!(int(&r#else.unwrap().spanned.tail.unwrap().spanned), 2);
+
+    // unless it's in parentheses
+    let Expr::Cond { condition, .. } = expr("if (Done {}) == d { 1 }") else {
+        panic!("not a condition");
+    };
+    let Expr::BiOp(left, BiOps::Eq, _) = condition.spanned else {
+        panic!("not a comparison");
+    };
+    let Expr::SubExpr(inner) = left.spanned else {
+        panic!("not parenthesised");
+    };
+    assert!(matches!(inner.spanned, Expr::StructLit { .. }));
+}
+
+#[test]
+fn precedence() {
+    // shifts bind tighter than `|`
+    let Expr::BiOp(left, BiOps::BitOr, right) = expr("1 << 4 | 2") else {
+        panic!("not an or");
+    };
+    let Expr::BiOp(shifted, BiOps::BitLShift, by) = left.spanned else {
+        panic!("not a shift");
+    };
+    assert_eq!((int(&shifted.spanned), int(&by.spanned)), (1, 4));
+    assert_eq!(int(&right.spanned), 2);
+
+    // & comparisons tighter than `&&`
+    let Expr::BiOp(left, BiOps::LogAnd, right) = expr("a == b && c") else {
+        panic!("not an and");
+    };
+    let Expr::BiOp(a, BiOps::Eq, b) = left.spanned else {
+        panic!("not a comparison");
+    };
+    assert_eq!((variable(&a.spanned), variable(&b.spanned)), ("a", "b"));
+    assert_eq!(variable(&right.spanned), "c");
+}
+
+#[test]
+fn type_bounds() {
+    let bound = |source: &str| match parse(source).stmts.remove(0).spanned {
+        Stmt::Type { bound, .. } => bound.spanned,
+        stmt => panic!("not a type: {stmt:?}"),
+    };
+    assert!(matches!(bound("type T;"), Bound::Generic));
+    let Bound::Aggregate(generics) = bound("type D A + B;") else {
+        panic!("not an aggregate");
+    };
+    let generics = generics
+        .iter()
+        .map(|generic| generic.spanned.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(generics, ["A", "B"]);
+
+    // only generics' names can be combined
+    for source in ["type D A + &B;", "type D &A + B;"] {
+        assert_eq!(error(source), "only type names can be combined with `+`");
+    }
+
+    // a `fn` without a return type returns `void`
+    let Stmt::Stub { return_type, .. } = parse("fn print(Print);").stmts.remove(0).spanned else {
+        panic!("not a stub");
+    };
+    assert_eq!(return_type.spanned, "void");
+}
@@ -0,0 +1,473 @@
+use regex::Regex;
+use sdw::prelude::*;
+use sdw::pretty::Style;
+use sdw::{driver, fmt, lexer, parser, pretty};
+
+fn parse(source: &str) -> Block {
+    let mut state = State::new();
+    let lexemes = lexer::lex(&mut state, source);
+    let block = parser::parse(&mut state, lexemes);
+    assert!(state.errors.is_empty(), "{source}\n{:?}", state.errors);
+    block.unwrap_or_else(|err| panic!("{source}\n{err:?}"))
+}
+
+/// the tree, without any spans
+fn shape(block: &Block) -> String {
+    let spans = Regex::new(r", span: Span \{[^}]*\}").unwrap();
+    spans.replace_all(&format!("{block:?}"), "").into_owned()
+}
+
+/// a tiny xorshift generator, so every run checks the same programs
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    fn chance(&mut self, in_n: usize) -> bool {
+        self.below(in_n) == 0
+    }
+
+    fn pick<'a>(&mut self, from: &[&'a str]) -> &'a str {
+        from[self.below(from.len())]
+    }
+}
+
+fn spanned<T>(spanned: T) -> Spanned<T> {
+    Spanned::new(spanned, Span::default())
+}
+
+fn boxed(expr: Expr) -> Box<Spanned<Expr>> {
+    Box::new(spanned(expr))
+}
+
+const NAMES: &[&str] = &["a", "b", "count", "x1", "_tmp"];
+const TYPES: &[&str] = &["int", "bool", "Point", "string"];
+const OPS: &[BiOps] = &[
+    BiOps::Add,
+    BiOps::Sub,
+    BiOps::Mul,
+    BiOps::Div,
+    BiOps::Mod,
+    BiOps::BitOr,
+    BiOps::BitAnd,
+    BiOps::BitNot,
+    BiOps::BitXor,
+    BiOps::BitRshift,
+    BiOps::BitLShift,
+    BiOps::LogOr,
+    BiOps::LogAnd,
+    BiOps::Eq,
+    BiOps::NEq,
+    BiOps::Gr,
+    BiOps::Ls,
+    BiOps::GrEq,
+    BiOps::LsEq,
+];
+
+/// what the parser would need parenthesised, were it written where `within` says
+#[derive(Clone, Copy)]
+struct Within {
+    prec: usize,
+    after_amp: bool,
+    condition: bool,
+    stmt: bool,
+}
+
+const FREE: Within = Within {
+    prec: 0,
+    after_amp: false,
+    condition: false,
+    stmt: false,
+};
+
+fn starts_with_amp(expr: &Expr) -> bool {
+    match expr {
+        Expr::Referal(_) => true,
+        Expr::BiOp(left, ..) => starts_with_amp(&left.spanned),
+        _ => false,
+    }
+}
+
+/// only trees the parser could give back - so wherever parentheses are needed, there's a
+/// `SubExpr`
+fn expr(rng: &mut Rng, depth: usize, within: Within) -> Expr {
+    let leaf = depth == 0 || rng.chance(3);
+    let operand = |prec: usize, after_amp: bool| Within {
+        prec,
+        after_amp,
+        stmt: false,
+        ..within
+    };
+    let expr = match if leaf { rng.below(4) } else { rng.below(17) } {
+        0 => Expr::IntLiteral(rng.below(100_000) as i64),
+        1 => Expr::BoolLiteral(rng.chance(2)),
+        2 => Expr::Variable(rng.pick(NAMES).to_owned()),
+        3 => {
+            let text = (0..rng.below(6))
+                .map(|_| rng.pick(&["a", "\"", "\\", "\n", "\t", "\0", "#", "//", "é", " "]))
+                .collect();
+            Expr::StrLiteral(text)
+        }
+        kind @ 4..=8 => {
+            let inner = boxed(expr(rng, depth - 1, operand(usize::MAX, false)));
+            match kind {
+                4 => Expr::UnaryNot(inner),
+                5 => Expr::UnaryNeg(inner),
+                6 => Expr::UnaryPos(inner),
+                7 => Expr::Referal(inner),
+                _ => Expr::Indir(inner),
+            }
+        }
+        9 => Expr::SubExpr(boxed(expr(rng, depth - 1, FREE))),
+        10 => Expr::FnCall(
+            rng.pick(NAMES).to_owned(),
+            (0..rng.below(3))
+                .map(|_| boxed(expr(rng, depth - 1, FREE)))
+                .collect(),
+        ),
+        11 | 12 => {
+            let op = OPS[rng.below(OPS.len())];
+            let left = expr(rng, depth - 1, operand(op.prec(), false));
+            let right = expr(rng, depth - 1, operand(op.prec() + 1, op == BiOps::BitAnd));
+            Expr::BiOp(Box::new(spanned(left)), op, Box::new(spanned(right)))
+        }
+        13 => Expr::ObjMember(
+            spanned(rng.pick(NAMES).to_owned()),
+            spanned(rng.pick(NAMES).to_owned()),
+        ),
+        14 => {
+            let condition = Within {
+                condition: true,
+                ..FREE
+            };
+            Expr::Cond {
+                condition: boxed(expr(rng, depth - 1, condition)),
+                then: spanned(block(rng, depth - 1)),
+                elifs: (0..rng.below(3))
+                    .map(|_| {
+                        (
+                            boxed(expr(rng, depth - 1, condition)),
+                            spanned(block(rng, depth - 1)),
+                        )
+                    })
+                    .collect(),
+                r#else: rng.chance(2).then(|| spanned(block(rng, depth - 1))),
+            }
+        }
+        15 => {
+            let ty = spanned(rng.pick(&["Point", "Some", "Done"]).to_owned());
+            let count = rng.below(3);
+            let fields = if count > 0 && rng.chance(2) {
+                StructLitFields::Named(
+                    (0..count)
+                        .map(|_| {
+                            let name = spanned(rng.pick(NAMES).to_owned());
+                            (name, boxed(expr(rng, depth - 1, FREE)))
+                        })
+                        .collect(),
+                )
+            } else {
+                StructLitFields::Positional(
+                    (0..count)
+                        .map(|_| boxed(expr(rng, depth - 1, FREE)))
+                        .collect(),
+                )
+            };
+            Expr::StructLit { ty, fields }
+        }
+        _ => {
+            let inner = Within {
+                prec: 0,
+                stmt: false,
+                ..within
+            };
+            Expr::Attributed {
+                attr: spanned(attribute(rng)),
+                expr: boxed(expr(rng, depth - 1, inner)),
+            }
+        }
+    };
+
+    let binding = match &expr {
+        Expr::BiOp(_, op, _) => op.prec(),
+        Expr::Attributed { .. } => 0,
+        _ => usize::MAX,
+    };
+    let parenthesised = binding < within.prec
+        || (within.after_amp && starts_with_amp(&expr))
+        || (within.condition && matches!(expr, Expr::StructLit { .. }))
+        || (within.stmt && matches!(expr, Expr::Attributed { .. }));
+    if parenthesised {
+        Expr::SubExpr(boxed(expr))
+    } else {
+        expr
+    }
+}
+
+fn attribute(rng: &mut Rng) -> Attribute {
+    Attribute {
+        name: spanned(
+            rng.pick(&["must_be_read", "num_in_range", "inline"])
+                .to_owned(),
+        ),
+        args: (0..rng.below(3))
+            .map(|_| {
+                spanned(match rng.below(3) {
+                    0 => AttrArg::Int(rng.below(200) as i64 - 100),
+                    1 => AttrArg::Bool(rng.chance(2)),
+                    _ => AttrArg::Idn(rng.pick(NAMES).to_owned()),
+                })
+            })
+            .collect(),
+    }
+}
+
+fn bound(rng: &mut Rng, depth: usize) -> Bound {
+    match if depth == 0 {
+        rng.below(2)
+    } else {
+        rng.below(6)
+    } {
+        0 => Bound::Prim(spanned(
+            [
+                PrimType::Int,
+                PrimType::Unt,
+                PrimType::Float,
+                PrimType::Bool,
+                PrimType::String,
+            ][rng.below(5)],
+        )),
+        1 => Bound::Alias(spanned(rng.pick(&["Point", "Print"]).to_owned())),
+        kind @ (2 | 3) => {
+            let members = (!rng.chance(4)).then(|| {
+                (0..rng.below(3))
+                    .map(|_| {
+                        let name = spanned(rng.pick(NAMES).to_owned());
+                        (spanned(bound(rng, depth - 1)), name)
+                    })
+                    .collect()
+            });
+            if kind == 2 {
+                Bound::Struct(members)
+            } else {
+                Bound::Union(members)
+            }
+        }
+        4 => Bound::Pointer(Box::new(spanned(bound(rng, depth - 1)))),
+        _ => Bound::FnPtr {
+            args: (0..rng.below(3))
+                .map(|_| spanned(rng.pick(TYPES).to_owned()))
+                .collect(),
+            return_type: spanned(rng.pick(TYPES).to_owned()),
+        },
+    }
+}
+
+fn stmt(rng: &mut Rng, depth: usize) -> Stmt {
+    let name = spanned(rng.pick(NAMES).to_owned());
+    let attrs = |rng: &mut Rng| (0..rng.below(2)).map(|_| spanned(attribute(rng))).collect();
+    match rng.below(11) {
+        0 if depth > 0 => Stmt::Fn {
+            attrs: attrs(rng),
+            return_type: spanned(rng.pick(TYPES).to_owned()),
+            name,
+            parameters: (0..rng.below(3))
+                .map(|_| {
+                    let ty = spanned(rng.pick(TYPES).to_owned());
+                    (ty, spanned(rng.pick(NAMES).to_owned()))
+                })
+                .collect(),
+            body: Box::new(block(rng, depth - 1)),
+        },
+        1 => Stmt::Stub {
+            attrs: attrs(rng),
+            return_type: spanned(rng.pick(TYPES).to_owned()),
+            name,
+            parameters: (0..rng.below(3))
+                .map(|_| spanned(rng.pick(TYPES).to_owned()))
+                .collect(),
+        },
+        2 if depth > 0 => Stmt::Loop {
+            block: Box::new(block(rng, depth - 1)),
+        },
+        3 => Stmt::Label { name },
+        4 => Stmt::Goto { name },
+        5 => Stmt::Return {
+            expr: rng.chance(2).then(|| spanned(expr(rng, depth, FREE))),
+        },
+        6 => Stmt::VarDec {
+            name,
+            initialiser: spanned(expr(rng, depth, FREE)),
+        },
+        7 => Stmt::VarRes {
+            name,
+            updated: spanned(expr(rng, depth, FREE)),
+        },
+        8 => {
+            let bound = match rng.below(3) {
+                0 => Bound::Generic,
+                1 => Bound::Aggregate(
+                    (0..2 + rng.below(2))
+                        .map(|_| spanned(rng.pick(&["Print", "Debug", "Eq"]).to_owned()))
+                        .collect(),
+                ),
+                _ => bound(rng, 2),
+            };
+            Stmt::Type {
+                attrs: attrs(rng),
+                name: spanned(rng.pick(&["Point", "Pair", "Print"]).to_owned()),
+                bound: spanned(bound),
+            }
+        }
+        _ => Stmt::Discard {
+            expr: spanned(expr(rng, depth, Within { stmt: true, ..FREE })),
+        },
+    }
+}
+
+fn block(rng: &mut Rng, depth: usize) -> Block {
+    Block {
+        stmts: (0..rng.below(4))
+            .map(|_| spanned(stmt(rng, depth)))
+            .collect(),
+        tail: rng.chance(2).then(|| {
+            let within = Within { stmt: true, ..FREE };
+            boxed(expr(rng, depth, within))
+        }),
+    }
+}
+
+#[test]
+fn generated_programs_round_trip() {
+    let mut rng = Rng(0x5eed_cafe_f00d_d00d);
+    for _ in 0..500 {
+        let program = block(&mut rng, 4);
+        let source = pretty::block(&program);
+        assert_eq!(shape(&parse(&source)), shape(&program), "\n{source}");
+
+        // splitting lists across lines mustn't change anything either
+        let narrow = Style {
+            indent: 2,
+            width: 30,
+        };
+        let formatted = fmt::format(&mut State::new(), &source, &narrow).unwrap();
+        assert_eq!(shape(&parse(&formatted)), shape(&program), "\n{formatted}");
+    }
+}
+
+#[test]
+fn minimal_parentheses() {
+    let operators = |expr: Expr| pretty::expr(&expr);
+    let var = |name: &str| boxed(Expr::Variable(name.to_owned()));
+    let biop = |left, op, right| boxed(Expr::BiOp(left, op, right));
+
+    // needed, though the tree has no `SubExpr`s
+    let sum = biop(var("a"), BiOps::Add, var("b"));
+    assert_eq!(
+        operators(Expr::BiOp(sum, BiOps::Mul, var("c"))),
+        "(a + b) * c"
+    );
+    let difference = biop(var("b"), BiOps::Sub, var("c"));
+    assert_eq!(
+        operators(Expr::BiOp(var("a"), BiOps::Sub, difference)),
+        "a - (b - c)"
+    );
+    let reference = boxed(Expr::Referal(var("b")));
+    assert_eq!(
+        operators(Expr::BiOp(var("a"), BiOps::BitAnd, reference)),
+        "a & (&b)"
+    );
+    let attributed = Expr::Attributed {
+        attr: spanned(Attribute {
+            name: spanned("inline".to_owned()),
+            args: Vec::new(),
+        }),
+        expr: var("a"),
+    };
+    assert_eq!(
+        operators(Expr::UnaryNeg(boxed(attributed))),
+        "-(#[ inline ] a)"
+    );
+
+    // & not otherwise
+    let product = biop(var("b"), BiOps::Mul, var("c"));
+    assert_eq!(
+        operators(Expr::BiOp(var("a"), BiOps::Add, product)),
+        "a + b * c"
+    );
+    let sum = biop(var("a"), BiOps::Add, var("b"));
+    assert_eq!(
+        operators(Expr::BiOp(sum, BiOps::Sub, var("c"))),
+        "a + b - c"
+    );
+    let written = "fn int f(int a) {\n    -a + !b * (c)\n};\n";
+    assert_eq!(pretty::block(&parse(written)), written);
+}
+
+#[test]
+fn struct_literals_in_conditions() {
+    let source = "if (Point { 1, 2 }) == p {\n    f(Point { .x = 1 })\n} else {}\n";
+    let block = parse(source);
+    assert_eq!(pretty::block(&block), source);
+
+    let literal = boxed(Expr::StructLit {
+        ty: spanned("Done".to_owned()),
+        fields: StructLitFields::Positional(Vec::new()),
+    });
+    let cond = Expr::Cond {
+        condition: boxed(Expr::BiOp(
+            literal,
+            BiOps::Eq,
+            boxed(Expr::BoolLiteral(true)),
+        )),
+        then: spanned(Block {
+            stmts: Vec::new(),
+            tail: None,
+        }),
+        elifs: Vec::new(),
+        r#else: None,
+    };
+    assert_eq!(pretty::expr(&cond), "if (Done {}) == true {}");
+}
+
+#[test]
+fn declarations() {
+    let source = "\
+#[ must_be_read ]
+type Point struct { int x, &Point next };
+type Print;
+type Show Print + Debug;
+type Callback (int, bool) -> string;
+type Maybe union;
+fn int add(int, int);
+fn void main() {
+    @top;
+    loop {
+        goto @top;
+    };
+    return;
+};
+";
+    assert_eq!(pretty::block(&parse(source)), source);
+    let Stmt::Type { bound, .. } = &parse(source).stmts[0].spanned else {
+        unreachable!();
+    };
+    assert_eq!(
+        pretty::bound(&bound.spanned),
+        "struct { int x, &Point next }"
+    );
+}
+
+#[test]
+fn core_round_trips() {
+    let core = parse(driver::CORE);
+    let source = pretty::block(&core);
+    assert_eq!(shape(&parse(&source)), shape(&core));
+    // comments are dropped, but that's all
+    assert_eq!(pretty::block(&parse(&source)), source);
+}
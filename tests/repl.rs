@@ -0,0 +1,153 @@
+use sdw::interp::Value;
+use sdw::prelude::*;
+use sdw::repl::{self, Evaluated, Rejected, Session};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// the value & type `input` evaluates to, which must be accepted
+fn eval(session: &mut Session, input: &str) -> Evaluated {
+    let mut state = State::new();
+    match session.eval(&mut state, input) {
+        Ok(evaluated) => evaluated,
+        Err(rejected) => panic!("`{input}` was rejected: {rejected:?} ({:?})", state.errors),
+    }
+}
+
+fn shown(session: &mut Session, input: &str) -> String {
+    match eval(session, input) {
+        Evaluated::Value(value, ty) => format!("{value}: {ty}"),
+        evaluated => panic!("`{input}` gave no value: {evaluated:?}"),
+    }
+}
+
+/// runs `sdw repl` on the given lines, returning what it printed
+fn session(input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sdw"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run `sdw`");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn declarations_persist() {
+    let mut session = Session::new();
+    assert!(matches!(eval(&mut session, "let x = 4;"), Evaluated::Kept));
+    assert_eq!(shown(&mut session, "x * 2"), "8: int");
+    eval(&mut session, "fn int square(int n) {\n    n * n\n};");
+    assert_eq!(shown(&mut session, "square(x)"), "16: int");
+    eval(&mut session, "type Point struct { int x, int y };");
+    eval(&mut session, "let p = Point { 1, 2 };");
+    assert_eq!(shown(&mut session, "p.y == 2"), "true: bool");
+    assert_eq!(shown(&mut session, "\"text\""), "\"text\": string");
+    assert!(matches!(
+        eval(&mut session, "printLn(x)"),
+        Evaluated::Value(Value::Void, _)
+    ));
+}
+
+#[test]
+fn variables_are_kept() {
+    // what ran before isn't run again, but its variables are still there - even once later
+    // declarations have numbered everything differently
+    let mut session = Session::new();
+    eval(&mut session, "let count = 0;");
+    eval(&mut session, "type Point struct { int x, int y };");
+    eval(&mut session, "let p = Point { 1, 2 };");
+    eval(&mut session, "let at = &count;");
+    eval(&mut session, "count = count + 1;");
+    eval(&mut session, "fn int sum(Point p) { p.x + p.y };");
+    eval(&mut session, "type Line struct { Point from, Point to };");
+    assert_eq!(shown(&mut session, "*at + count"), "2: int");
+    assert_eq!(shown(&mut session, "sum(p)"), "3: int");
+    eval(&mut session, "let line = Line { p, Point { 3, 4 } };");
+    assert_eq!(
+        shown(&mut session, "sum(line.from) + sum(line.to)"),
+        "10: int"
+    );
+}
+
+#[test]
+fn rejected_input_is_forgotten() {
+    let mut session = Session::new();
+    eval(&mut session, "let x = 1;");
+    eval(&mut session, "fn int divide(int a, int b) { a / b };");
+
+    let mut state = State::new();
+    let rejected = session.eval(&mut state, "let y = nope;");
+    assert!(matches!(rejected, Err(Rejected::Errors(_))));
+    assert_eq!(state.errors.len(), 1);
+    // a runtime error throws away the input too
+    let mut state = State::new();
+    let rejected = session.eval(&mut state, "let z = divide(1, 0);");
+    assert!(matches!(rejected, Err(Rejected::Failed(_))));
+
+    let mut state = State::new();
+    assert!(session.eval(&mut state, "y").is_err());
+    assert!(session.eval(&mut State::new(), "z").is_err());
+    assert_eq!(shown(&mut session, "x"), "1: int");
+    assert!(matches!(
+        eval(&mut session, "exit(3)"),
+        Evaluated::Exited(3)
+    ));
+}
+
+#[test]
+fn types_without_running() {
+    let mut session = Session::new();
+    eval(&mut session, "fn bool positive(int n) { n > 0 };");
+    let mut state = State::new();
+    assert_eq!(
+        session.type_of(&mut state, "positive(-1)").unwrap(),
+        Some("bool".to_owned())
+    );
+    assert_eq!(session.type_of(&mut state, "let a = 1;").unwrap(), None);
+    // nothing is evaluated, so this doesn't exit
+    assert!(session.type_of(&mut state, "exit(1)").is_ok());
+}
+
+#[test]
+fn unbalanced_braces_continue() {
+    assert!(repl::complete("let x = 1;"));
+    assert!(!repl::complete("fn int f() {"));
+    assert!(!repl::complete("fn int f() {\n    if true { 1 }"));
+    assert!(repl::complete(
+        "fn int f() {\n    if true { 1 } else { 2 }\n};"
+    ));
+    // so that the error is shown straight away
+    assert!(repl::complete("{ \"unclosed"));
+}
+
+#[test]
+fn interactive() {
+    let output = session(
+        "let x = 2;\nprintLn(\"once\");\nfn int twice(int n) {\n    n * 2\n};\ntwice(x)\n\
+         :type twice(x) > 1\n:ast -x + 1\n:tokens let y\n:quit\nprintLn(\"never\");\n",
+    );
+    assert_eq!(output.matches("once").count(), 1);
+    assert!(output.contains("> | | > 4: int\n"));
+    assert!(output.contains("> bool\n"));
+    assert!(output.contains("binary operation -> +\n  negation:\n    variable -> x\n"));
+    assert!(output.contains("Let Idn(\"y\")"));
+    assert!(!output.contains("never"));
+}
+
+#[test]
+fn reading_stdin() {
+    // what's evaluated reads the lines after it
+    let output = session(
+        "let buffer = alloc(4);\nread(0, buffer, 4)\nabc\nwrite(1, buffer, 3);\nprintLn();\n",
+    );
+    assert!(output.contains("> 4: int\n> abc> \n> "));
+}
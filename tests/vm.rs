@@ -0,0 +1,262 @@
+mod common;
+
+use common::checked;
+use sdw::bytecode::{DecodeError, Op, Program, RtTy};
+use sdw::interp::{self, Value};
+use sdw::intrinsics::Intrinsic;
+use sdw::prelude::*;
+use sdw::{compile, vm};
+
+/// the result of the vm, which must match the interpreter's - errors by message & trace
+fn run(source: &str) -> std::result::Result<Value, (String, Vec<String>)> {
+    let checked = checked(source);
+    let describe = |err: SdwErr| {
+        let trace = err.notes.iter().map(|note| note.message.clone()).collect();
+        (err.ty.to_string(), trace)
+    };
+    let interpreted = interp::run(&checked).map_err(describe);
+    let compiled = vm::run(&compile::compile(&checked)).map_err(describe);
+    assert_eq!(compiled, interpreted, "the vm & interpreter disagree");
+    compiled
+}
+
+#[test]
+fn matches_interpreter() {
+    let sources = [
+        "fn int fib(int n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } };
+         fn int main() { fib(20) };",
+        "fn int main() {
+            let a = 2;
+            let b = { let c = a * 10; c + 1 };
+            a = a + b;
+            if a > 20 { a } else if a > 10 { 0 } else { 1 }
+         };",
+        "type Point struct { int x, int y };
+         type PointPtr &Point;
+         type Line struct { Point from, Point to };
+         fn int length(PointPtr point) { point.x + point.y };
+         fn PointPtr pick(PointPtr a, PointPtr b, bool first) { if first { a } else { b } };
+         fn int main() {
+            let line = Line { .to = Point { 3, 4 }, .from = Point { 1, 2 } };
+            let to = line.to;
+            let from = line.from;
+            let chosen = pick(&from, &to, false);
+            let x = &to.x;
+            length(chosen) * 10 + *x
+         };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn int unwrap(Some some) { some.some };
+         fn int unwrap(None none) { 0 };
+         fn Option half(int n) { if n % 2 == 0 { Some { n / 2 } } else { None {} } };
+         type Area;
+         fn int area(Area);
+         type Square struct { int side };
+         fn int area(Square square) { square.side * square.side };
+         fn int twice(Area shape) { area(shape) * 2 };
+         fn int main() { unwrap(half(10)) + unwrap(half(7)) + twice(Square { 3 }) };",
+        "type Op (int) -> int;
+         fn int double(int n) { n * 2 };
+         fn int apply(Op op, int n) { op(n) };
+         fn int main() { apply(double, 21) };",
+        "fn bool both(bool a, bool b) { a && b || !a && !b };
+         fn unt big() { 1 << 63 };
+         fn unt main() { let u = big(); if both(true, true) && both(false, false) { u } else { 0 } };",
+        "type Some struct { int some };
+         type None struct;
+         type Option union { Some some, None none };
+         fn Option maybe(int n) { if n > 0 { Some { n } } else { None {} } };
+         fn int both(Some a, Some b) { a.some * b.some };
+         fn int both(Some a, None b) { a.some };
+         fn int both(None a, Option b) { 100 };
+         fn int main() { both(maybe(2), maybe(3)) + both(maybe(5), maybe(0)) * 10 + both(maybe(0), maybe(7)) };",
+    ];
+    let expected = [
+        Value::Int(6765),
+        Value::Int(23),
+        Value::Int(73),
+        Value::Int(23),
+        Value::Int(42),
+        Value::Unt(1 << 63),
+        Value::Int(156),
+    ];
+    for (source, expected) in sources.into_iter().zip(expected) {
+        assert_eq!(run(source), Ok(expected));
+    }
+}
+
+#[test]
+fn gotos() {
+    let source = "
+        fn int main() {
+            let total = 0;
+            let n = 5;
+            goto @start;
+            loop {
+                total = total + n;
+                @start;
+                n = n + 1;
+                if n > 10 { goto @done; };
+            };
+            @done;
+            total
+        };
+    ";
+    assert_eq!(run(source), Ok(Value::Int(40)));
+
+    // values left on the stack by the expressions jumped out of are dropped
+    let source = "
+        fn int main() {
+            let n = 0;
+            @again;
+            let sum = 1 + 2 * { n = n + 1; if n < 100 { goto @again; }; 3 };
+            sum + n
+        };
+    ";
+    assert_eq!(run(source), Ok(Value::Int(107)));
+
+    let source = "
+        fn int main() {
+            let n = 0;
+            let a = 1 + { @inside; n = n + 1; 2 };
+            if n < 2 { goto @inside; };
+            a
+        };
+    ";
+    assert_eq!(
+        run(source),
+        Err((
+            "cannot jump into the middle of an expression".to_owned(),
+            vec!["in `main`".to_owned()]
+        ))
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    let source = "
+        fn int divide(int a, int b) { a / b };
+        fn int average(int total, int count) { divide(total, count) };
+        fn int main() { average(10, 0) };
+    ";
+    assert_eq!(
+        run(source),
+        Err((
+            "`10 / 0` divides by zero".to_owned(),
+            vec![
+                "in `divide`, called here".to_owned(),
+                "in `average`, called here".to_owned(),
+                "in `main`".to_owned(),
+            ]
+        ))
+    );
+
+    let source = "
+        fn int down(int n) { 1 + down(n + 1) };
+        fn int main() { down(0) };
+    ";
+    let (err, trace) = run(source).unwrap_err();
+    assert_eq!(err, "the call stack overflowed, at 10000 calls deep");
+    assert_eq!(trace[0], "in `down`, called here (9997 times)");
+
+    assert_eq!(
+        run("fn int start() { 0 };").unwrap_err().0,
+        "no `fn main()` to run"
+    );
+}
+
+#[test]
+fn serialisation() {
+    let source = "
+        type Some struct { int some };
+        type None struct;
+        type Option union { Some some, None none };
+        fn int unwrap(Some some) { some.some };
+        fn int unwrap(None none) { -1 };
+        fn Option find(int n) { if n > 0 { Some { n } } else { None {} } };
+        fn int main() { unwrap(find(7)) * unwrap(find(0)) };
+    ";
+    let program = compile::compile(&checked(source));
+    let bytes = program.encode();
+    let decoded = Program::decode(&bytes).expect("failed to decode");
+    assert_eq!(decoded, program);
+    assert!(matches!(vm::run(&decoded), Ok(Value::Int(-7))));
+    assert_eq!(decoded.to_string(), program.to_string());
+
+    assert_eq!(Program::decode(b"fn int"), Err(DecodeError::NotBytecode));
+    assert_eq!(
+        Program::decode(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::Truncated)
+    );
+    let mut stale = bytes.clone();
+    stale[4] = 0;
+    assert_eq!(Program::decode(&stale), Err(DecodeError::Version(0)));
+}
+
+#[test]
+fn disassembly() {
+    let program = compile::compile(&checked(
+        "fn int square(int n) { n * n }; fn int main() { square(3) };",
+    ));
+    let listing = program.to_string();
+    assert!(listing.contains("fn 1 `square` - 1 params, 1 locals"));
+    assert!(listing.contains("fn 2 `main` (entry point)"));
+    assert!(listing.contains("op *"));
+    assert!(listing.contains("call fn 1 (1)"));
+}
+
+#[test]
+fn tampered_bytecode() {
+    // bytecode which was changed after `compile` wrote it is an error, never a crash
+    let program = compile::compile(&checked("fn int main() { let x = 1; length(\"ab\") + x };"));
+    let main = program.main.unwrap() as usize;
+    let tampered = |at: usize, op: Op| {
+        let mut program = program.clone();
+        program.functions[main].code[at] = op;
+        Program::decode(&program.encode()).map(|program| match vm::run(&program) {
+            Ok(value) => panic!("ran to {value:?}"),
+            Err(err) => err.ty.to_string(),
+        })
+    };
+    let malformed = |what: &str| Ok(format!("the bytecode is malformed: {what}"));
+
+    assert_eq!(
+        tampered(2, Op::Int(5)),
+        malformed("a string intrinsic given a non-string")
+    );
+    assert_eq!(
+        tampered(4, Op::Bool(true)),
+        malformed("mismatched operands")
+    );
+    assert_eq!(
+        tampered(4, Op::Deref),
+        malformed("a non-pointer is dereferenced")
+    );
+    assert_eq!(
+        tampered(4, Op::Member(0)),
+        malformed("a member of a value without members")
+    );
+    assert_eq!(
+        tampered(5, Op::Drop(3)),
+        malformed("an instruction pops an empty stack")
+    );
+    assert_eq!(
+        tampered(5, Op::CallPtr { args: 0 }),
+        malformed("a non-function is called")
+    );
+    // what can be checked before running is, when it's loaded
+    assert_eq!(
+        tampered(
+            3,
+            Op::Intrinsic {
+                intrinsic: Intrinsic::Length,
+                args: 2,
+                ret: RtTy::Int,
+            }
+        ),
+        Err(DecodeError::Malformed(
+            "an intrinsic is given the wrong number of arguments"
+        ))
+    );
+}